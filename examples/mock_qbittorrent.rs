@@ -0,0 +1,63 @@
+//! Serves a fake qBittorrent with a few torrents moving along, to run bit-tower against:
+//!
+//! ```bash
+//! cargo run --example mock_qbittorrent --features mock -- 127.0.0.1:9090
+//! BITTOWER_QBT_URL=http://127.0.0.1:9090/api/v2 cargo leptos watch
+//! ```
+//!
+//! Log in with `admin`/`adminadmin`.
+
+use std::time::Duration;
+
+use bittower::qbittorrent::mock::{MockQbittorrent, MockTorrent};
+
+const MIB: u64 = 1024 * 1024;
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        .init();
+
+    let addr = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "127.0.0.1:9090".to_owned());
+
+    let qbt = MockQbittorrent::default();
+    qbt.add_torrent(
+        MockTorrent::default()
+            .name("debian-12.7.0-amd64-netinst.iso")
+            .size(631 * MIB)
+            .download_rate(4 * MIB)
+            .upload_rate(MIB / 2),
+    );
+    qbt.add_torrent(
+        MockTorrent::default()
+            .name("archlinux-2024.09.01-x86_64.iso")
+            .size(1180 * MIB)
+            .progress(0.35)
+            .download_rate(MIB)
+            .category("linux"),
+    );
+    qbt.add_torrent(
+        MockTorrent::default()
+            .name("big-buck-bunny-1080p.mkv")
+            .size(885 * MIB)
+            .progress(1.0)
+            .upload_rate(MIB / 4)
+            .tags("movies,cc"),
+    );
+    qbt.add_torrent(
+        MockTorrent::default()
+            .name("ubuntu-24.04.1-desktop-amd64.iso")
+            .size(6 * 1024 * MIB)
+            .progress(0.8)
+            .download_rate(2 * MIB)
+            .paused(true),
+    );
+    let _simulation = qbt.simulate(Duration::from_secs(1));
+
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    tracing::info!("Mock qBittorrent listening on http://{addr}/api/v2");
+    axum::serve(listener, qbt.router()).await
+}
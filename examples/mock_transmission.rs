@@ -0,0 +1,47 @@
+//! Serves a fake Transmission with a few torrents moving along, to run bit-tower against:
+//!
+//! ```bash
+//! cargo run --example mock_transmission --features mock -- 127.0.0.1:9091
+//! BITTOWER_QBT_URL=transmission+http://127.0.0.1:9091/transmission/rpc cargo leptos watch
+//! ```
+//!
+//! Log in with `transmission`/`transmission`.
+
+use std::time::Duration;
+
+use bittower::transmission::mock::MockTransmission;
+
+const MIB: u64 = 1024 * 1024;
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        .init();
+
+    let addr = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "127.0.0.1:9091".to_owned());
+
+    let transmission = MockTransmission::default();
+    let debian = transmission.add_torrent("debian-12.7.0-amd64-netinst.iso", 631 * MIB);
+    transmission.set_download_rate(&debian, 4 * MIB);
+    let arch = transmission.add_torrent("archlinux-2024.09.01-x86_64.iso", 1180 * MIB);
+    transmission.set_download_rate(&arch, MIB);
+    transmission.add_torrent("ubuntu-24.04.1-desktop-amd64.iso", 6 * 1024 * MIB);
+
+    tokio::spawn({
+        let transmission = transmission.clone();
+        async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(1));
+            loop {
+                interval.tick().await;
+                transmission.tick(Duration::from_secs(1));
+            }
+        }
+    });
+
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    tracing::info!("Mock Transmission listening on http://{addr}/transmission/rpc");
+    axum::serve(listener, transmission.router()).await
+}
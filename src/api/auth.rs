@@ -0,0 +1,84 @@
+use http::{header, HeaderMap};
+use sha2::{Digest, Sha256};
+
+use crate::app::auth::ssr::{TokenStore, Users};
+use crate::app::auth::Role;
+
+/// Who may call the REST API: whoever sends the token of `BITTOWER_API_TOKEN`, or one of the
+/// personal tokens of bit-tower's users. Cheap to clone.
+#[derive(Clone, Debug, Default)]
+pub struct ApiAuth {
+    /// Digest of the shared token, `None` when there is none.
+    token: Option<[u8; 32]>,
+    tokens: TokenStore,
+    /// bit-tower's own users, without whom there are no personal tokens.
+    users: Option<Users>,
+}
+
+/// Who is calling the REST API.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ApiCaller {
+    /// Owner of the personal token, `None` for the shared token.
+    pub username: Option<String>,
+    /// What the token may do, the shared one may do anything.
+    pub role: Role,
+    /// The instances the caller may use, every one when `None`.
+    pub instances: Option<Vec<String>>,
+}
+
+impl ApiCaller {
+    pub fn may_use(&self, instance: &str) -> bool {
+        self.instances.as_ref().map_or(true, |instances| {
+            instances.iter().any(|name| name == instance)
+        })
+    }
+}
+
+impl ApiAuth {
+    pub fn new(token: Option<&str>, tokens: TokenStore, users: Option<Users>) -> Self {
+        Self {
+            token: token.map(digest),
+            tokens,
+            users,
+        }
+    }
+
+    /// Whether the API can be used at all.
+    pub fn enabled(&self) -> bool {
+        self.token.is_some() || self.users.is_some()
+    }
+
+    /// Who sent `headers`, from their `Authorization: Bearer <token>`. Digests of the shared
+    /// token are compared rather than the tokens, so that timing doesn't give it away.
+    pub fn caller(&self, headers: &HeaderMap) -> Option<ApiCaller> {
+        let token = bearer(headers)?;
+        if self.token.is_some_and(|expected| expected == digest(token)) {
+            return Some(ApiCaller {
+                username: None,
+                role: Role::Admin,
+                instances: None,
+            });
+        }
+        let owner = self.tokens.verify(token)?;
+        let user = self.users.as_ref()?.user(&owner.username)?;
+        Some(ApiCaller {
+            role: owner.scope.cap(user.role),
+            instances: user.instances.clone(),
+            username: Some(owner.username),
+        })
+    }
+}
+
+/// The token of an `Authorization: Bearer` header.
+pub fn bearer(headers: &HeaderMap) -> Option<&str> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    scheme
+        .eq_ignore_ascii_case("bearer")
+        .then(|| token.trim())
+        .filter(|token| !token.is_empty())
+}
+
+fn digest(token: &str) -> [u8; 32] {
+    Sha256::digest(token.as_bytes()).into()
+}
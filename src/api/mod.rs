@@ -0,0 +1,13 @@
+//! The `/api/v1` REST API, for scripts and dashboards rather than browsers.
+//!
+//! It answers from what the background checks last synced, so that polling it never adds load
+//! on the torrent clients, and only makes the changes that can't lose anything. Callers send a
+//! bearer token rather than logging in, the shared one of `BITTOWER_API_TOKEN` or a personal
+//! one, which only sees the instances of its user and only changes anything for admins.
+
+mod auth;
+mod synced;
+pub mod v1;
+
+pub use auth::*;
+pub use synced::*;
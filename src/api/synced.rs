@@ -0,0 +1,53 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+use chrono::{DateTime, Utc};
+
+use crate::backend::model::{Torrent, TransferInfo};
+use crate::backend::SyncCursor;
+
+/// What the background checks last synced from each torrent client. Cheap to clone.
+#[derive(Clone, Debug, Default)]
+pub struct SyncedState {
+    instances: Arc<RwLock<BTreeMap<String, SyncedInstance>>>,
+}
+
+/// A torrent client as of its last successful sync.
+#[derive(Clone, Debug)]
+pub struct SyncedInstance {
+    /// Sorted by name.
+    pub torrents: Arc<[Torrent]>,
+    pub transfer: TransferInfo,
+    pub synced_at: DateTime<Utc>,
+    /// Session of the account it was synced with, which the API makes its changes with.
+    pub session: String,
+}
+
+impl SyncedState {
+    /// Records where `cursor` is at after a sync of `instance` with `session`.
+    pub fn update(&self, instance: &str, session: &str, cursor: &SyncCursor) {
+        let mut torrents: Vec<_> = cursor.torrents.values().cloned().collect();
+        torrents.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.hash.cmp(&b.hash)));
+        let synced = SyncedInstance {
+            torrents: torrents.into(),
+            transfer: cursor.transfer.clone(),
+            synced_at: Utc::now(),
+            session: session.to_owned(),
+        };
+        let mut instances = self.instances.write().unwrap();
+        instances.insert(instance.to_owned(), synced);
+    }
+
+    pub fn get(&self, instance: &str) -> Option<SyncedInstance> {
+        self.instances.read().unwrap().get(instance).cloned()
+    }
+
+    /// Every instance synced so far, by name.
+    pub fn all(&self) -> Vec<(String, SyncedInstance)> {
+        let instances = self.instances.read().unwrap();
+        instances
+            .iter()
+            .map(|(name, synced)| (name.clone(), synced.clone()))
+            .collect()
+    }
+}
@@ -0,0 +1,634 @@
+//! Version 1 of the REST API. Its types are its own rather than the [`model`]'s, so that the
+//! model can change without breaking scripts, and [`ApiDoc`] describes them as OpenAPI.
+
+use std::collections::BTreeMap;
+
+use axum::extract::{Path, Query, Request, State};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Extension, Json, Router};
+use chrono::{DateTime, Utc};
+use http::{header, StatusCode};
+use serde::{Deserialize, Serialize};
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::openapi::server::Server;
+use utoipa::{IntoParams, Modify, OpenApi, ToSchema};
+
+use super::{ApiAuth, ApiCaller, SyncedInstance, SyncedState};
+use crate::app::auth::Role;
+use crate::app::BasePath;
+use crate::backend::{model, BackendRegistry, Command};
+use crate::webhooks::{Delivery, Event, EventKind, Webhooks};
+
+/// What the API handlers need. Cheap to clone.
+#[derive(Clone, Debug)]
+pub struct ApiState {
+    pub backends: BackendRegistry,
+    pub synced: SyncedState,
+    pub auth: ApiAuth,
+    pub webhooks: Webhooks,
+}
+
+/// The routes of the API, to be nested under `/api/v1`. The OpenAPI document at
+/// `/openapi.json` is the only one that doesn't need a token.
+pub fn router<S>(state: ApiState) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    let protected = Router::new()
+        .route("/torrents", get(torrents))
+        .route("/torrents/:hash", get(torrent))
+        .route("/stats", get(stats))
+        .route("/pause-all", post(pause_all))
+        .route("/resume-all", post(resume_all))
+        .route("/toggle-alt-speed", post(toggle_alt_speed))
+        .route("/webhooks/deliveries", get(webhook_deliveries))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_token));
+    Router::new()
+        .route("/openapi.json", get(openapi))
+        .merge(protected)
+        .with_state(state)
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "bit-tower",
+        description = "Torrents of every client bit-tower manages, as of the last sync."
+    ),
+    paths(
+        torrents,
+        torrent,
+        stats,
+        pause_all,
+        resume_all,
+        toggle_alt_speed,
+        webhook_deliveries
+    ),
+    components(schemas(
+        Torrent,
+        TorrentState,
+        Stats,
+        InstanceStats,
+        ActionResult,
+        AltSpeedResult,
+        Event,
+        EventKind,
+        Delivery,
+        ApiError
+    )),
+    modifiers(&BearerAuth),
+    security(("bearer" = []))
+)]
+pub struct ApiDoc;
+
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TorrentState {
+    Downloading,
+    Seeding,
+    Stalled,
+    Paused,
+    Queued,
+    Checking,
+    Moving,
+    Error,
+    Unknown,
+}
+
+impl From<model::TorrentState> for TorrentState {
+    fn from(state: model::TorrentState) -> Self {
+        match state {
+            model::TorrentState::Downloading => Self::Downloading,
+            model::TorrentState::Seeding => Self::Seeding,
+            model::TorrentState::Stalled => Self::Stalled,
+            model::TorrentState::Paused => Self::Paused,
+            model::TorrentState::Queued => Self::Queued,
+            model::TorrentState::Checking => Self::Checking,
+            model::TorrentState::Moving => Self::Moving,
+            model::TorrentState::Error => Self::Error,
+            model::TorrentState::Unknown => Self::Unknown,
+        }
+    }
+}
+
+/// A torrent, whichever client it is on.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Torrent {
+    /// Name of the torrent client it is on.
+    pub instance: String,
+    /// Info hash.
+    pub hash: String,
+    pub name: String,
+    pub state: TorrentState,
+    /// Between 0 and 1.
+    pub progress: f64,
+    /// Size of the files selected for download, in bytes.
+    pub size: u64,
+    /// Size of every file in the torrent, in bytes.
+    pub total_size: u64,
+    pub downloaded: u64,
+    pub uploaded: u64,
+    /// Download speed, in bytes per second.
+    pub dl_speed: u64,
+    /// Upload speed, in bytes per second.
+    pub up_speed: u64,
+    /// Seconds until it completes, `null` when it isn't going to.
+    pub eta: Option<u64>,
+    pub seeds: u64,
+    pub leechs: u64,
+    pub ratio: f64,
+    /// How much of the torrent the swarm has, 1 meaning at least one full copy.
+    pub availability: f64,
+    pub added_on: Option<DateTime<Utc>>,
+    pub save_path: String,
+    pub category: String,
+    pub tags: Vec<String>,
+    /// Download speed limit, in bytes per second, `null` when unlimited.
+    pub dl_limit: Option<u64>,
+    /// Upload speed limit, in bytes per second, `null` when unlimited.
+    pub up_limit: Option<u64>,
+}
+
+impl Torrent {
+    pub(crate) fn new(instance: &str, torrent: &model::Torrent) -> Self {
+        Self {
+            instance: instance.to_owned(),
+            hash: torrent.hash.clone(),
+            name: torrent.name.clone(),
+            state: torrent.state.into(),
+            progress: torrent.progress,
+            size: torrent.size,
+            total_size: torrent.total_size,
+            downloaded: torrent.downloaded,
+            uploaded: torrent.uploaded,
+            dl_speed: torrent.dl_speed,
+            up_speed: torrent.up_speed,
+            eta: torrent.eta.map(|eta| eta.as_secs()),
+            seeds: torrent.seeds,
+            leechs: torrent.leechs,
+            ratio: torrent.ratio,
+            availability: torrent.availability,
+            added_on: torrent.added_on,
+            save_path: torrent.save_path.clone(),
+            category: torrent.category.clone(),
+            tags: torrent.tags.clone(),
+            dl_limit: torrent.dl_limit,
+            up_limit: torrent.up_limit,
+        }
+    }
+}
+
+/// Totals of every torrent client together.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Stats {
+    /// Download speed, in bytes per second.
+    pub dl_speed: u64,
+    /// Upload speed, in bytes per second.
+    pub up_speed: u64,
+    pub torrents: u64,
+    pub instances: Vec<InstanceStats>,
+}
+
+/// Totals of one torrent client.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct InstanceStats {
+    pub instance: String,
+    /// When these were synced from the client.
+    pub synced_at: DateTime<Utc>,
+    /// Download speed, in bytes per second.
+    pub dl_speed: u64,
+    /// Upload speed, in bytes per second.
+    pub up_speed: u64,
+    /// Downloaded since the client started, in bytes.
+    pub downloaded: u64,
+    /// Uploaded since the client started, in bytes.
+    pub uploaded: u64,
+    /// Global download limit, in bytes per second, `null` when unlimited.
+    pub dl_limit: Option<u64>,
+    /// Global upload limit, in bytes per second, `null` when unlimited.
+    pub up_limit: Option<u64>,
+    /// DHT nodes the client is connected to, 0 when it doesn't tell.
+    pub dht_nodes: u64,
+    /// Whether the alternative speed limits apply rather than the normal ones.
+    pub alt_speed: bool,
+    pub torrents: u64,
+    /// Torrents by state, every state included.
+    pub states: BTreeMap<TorrentState, u64>,
+}
+
+impl InstanceStats {
+    fn new(instance: &str, synced: &SyncedInstance) -> Self {
+        let mut states: BTreeMap<_, _> = [
+            TorrentState::Downloading,
+            TorrentState::Seeding,
+            TorrentState::Stalled,
+            TorrentState::Paused,
+            TorrentState::Queued,
+            TorrentState::Checking,
+            TorrentState::Moving,
+            TorrentState::Error,
+            TorrentState::Unknown,
+        ]
+        .into_iter()
+        .map(|state| (state, 0))
+        .collect();
+        for torrent in synced.torrents.iter() {
+            *states.entry(torrent.state.into()).or_default() += 1;
+        }
+        let transfer = &synced.transfer;
+        Self {
+            instance: instance.to_owned(),
+            synced_at: synced.synced_at,
+            dl_speed: transfer.dl_speed,
+            up_speed: transfer.up_speed,
+            downloaded: transfer.downloaded,
+            uploaded: transfer.uploaded,
+            dl_limit: transfer.dl_limit,
+            up_limit: transfer.up_limit,
+            dht_nodes: transfer.dht_nodes,
+            alt_speed: transfer.alt_speed,
+            torrents: synced.torrents.len() as u64,
+            states,
+        }
+    }
+}
+
+/// What pausing or resuming did on one torrent client.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ActionResult {
+    pub instance: String,
+    /// Torrents it applied to.
+    pub torrents: u64,
+    /// Why it failed, `null` when it didn't.
+    pub error: Option<String>,
+}
+
+/// What toggling the alternative speed limits did on one torrent client.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct AltSpeedResult {
+    pub instance: String,
+    /// Whether the alternative limits apply now, `null` when toggling them failed.
+    pub alt_speed: Option<bool>,
+    /// Why it failed, `null` when it didn't.
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ApiError {
+    pub error: String,
+}
+
+type Failure = (StatusCode, Json<ApiError>);
+
+fn failure(status: StatusCode, error: impl Into<String>) -> Failure {
+    (
+        status,
+        Json(ApiError {
+            error: error.into(),
+        }),
+    )
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct InstanceQuery {
+    /// Only this torrent client rather than every one.
+    instance: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TorrentsQuery {
+    /// Only the torrents of this torrent client.
+    instance: Option<String>,
+    /// Only the torrents in this state.
+    state: Option<TorrentState>,
+    /// Only the torrents in this category.
+    category: Option<String>,
+}
+
+async fn require_token(
+    State(state): State<ApiState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some(caller) = state.auth.caller(request.headers()) else {
+        return (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+            Json(ApiError {
+                error: "A valid API token is required".to_owned(),
+            }),
+        )
+            .into_response();
+    };
+    request.extensions_mut().insert(caller);
+    next.run(request).await
+}
+
+/// Fails unless `caller` is an admin, as only they may pause, resume or slow down every torrent.
+fn check_admin(caller: &ApiCaller) -> Result<(), Failure> {
+    if caller.role != Role::Admin {
+        return Err(failure(
+            StatusCode::FORBIDDEN,
+            "Only full tokens of admins may change anything",
+        ));
+    }
+    Ok(())
+}
+
+/// The OpenAPI document of this API, with the base path the caller sees as its server.
+async fn openapi(base_path: Option<Extension<BasePath>>) -> Json<utoipa::openapi::OpenApi> {
+    let mut doc = ApiDoc::openapi();
+    if let Some(Extension(base_path)) = base_path.filter(|base| !base.as_str().is_empty()) {
+        doc.servers = Some(vec![Server::new(base_path.as_str())]);
+    }
+    Json(doc)
+}
+
+/// The synced instances `caller` may use, or just `instance`.
+fn synced(
+    state: &ApiState,
+    caller: &ApiCaller,
+    instance: Option<&str>,
+) -> Result<Vec<(String, SyncedInstance)>, Failure> {
+    let Some(name) = instance else {
+        let mut instances = state.synced.all();
+        instances.retain(|(name, _)| caller.may_use(name));
+        return Ok(instances);
+    };
+    if state.backends.get(name).is_none() || !caller.may_use(name) {
+        return Err(failure(
+            StatusCode::NOT_FOUND,
+            format!("No instance named `{name}`"),
+        ));
+    }
+    Ok(state
+        .synced
+        .get(name)
+        .map(|synced| (name.to_owned(), synced))
+        .into_iter()
+        .collect())
+}
+
+/// Every torrent, sorted by instance and name.
+#[utoipa::path(
+    get,
+    path = "/api/v1/torrents",
+    params(TorrentsQuery),
+    responses(
+        (status = 200, body = [Torrent]),
+        (status = 401, body = ApiError),
+        (status = 404, description = "No such instance", body = ApiError)
+    )
+)]
+async fn torrents(
+    State(state): State<ApiState>,
+    Extension(caller): Extension<ApiCaller>,
+    Query(query): Query<TorrentsQuery>,
+) -> Result<Json<Vec<Torrent>>, Failure> {
+    let instances = synced(&state, &caller, query.instance.as_deref())?;
+    let torrents = instances
+        .iter()
+        .flat_map(|(name, synced)| {
+            synced
+                .torrents
+                .iter()
+                .map(move |torrent| Torrent::new(name, torrent))
+        })
+        .filter(|torrent| query.state.map_or(true, |state| torrent.state == state))
+        .filter(|torrent| {
+            query
+                .category
+                .as_ref()
+                .map_or(true, |category| torrent.category == *category)
+        })
+        .collect();
+    Ok(Json(torrents))
+}
+
+/// One torrent, from the first instance that has it unless `instance` tells which.
+#[utoipa::path(
+    get,
+    path = "/api/v1/torrents/{hash}",
+    params(("hash" = String, Path, description = "Info hash of the torrent"), InstanceQuery),
+    responses(
+        (status = 200, body = Torrent),
+        (status = 401, body = ApiError),
+        (status = 404, description = "No such torrent or instance", body = ApiError)
+    )
+)]
+async fn torrent(
+    State(state): State<ApiState>,
+    Extension(caller): Extension<ApiCaller>,
+    Path(hash): Path<String>,
+    Query(query): Query<InstanceQuery>,
+) -> Result<Json<Torrent>, Failure> {
+    let hash = hash.to_ascii_lowercase();
+    synced(&state, &caller, query.instance.as_deref())?
+        .iter()
+        .find_map(|(name, synced)| {
+            let torrent = synced
+                .torrents
+                .iter()
+                .find(|torrent| torrent.hash == hash)?;
+            Some(Json(Torrent::new(name, torrent)))
+        })
+        .ok_or_else(|| failure(StatusCode::NOT_FOUND, format!("No torrent `{hash}`")))
+}
+
+/// Speeds and torrent counts of every instance and of all of them together.
+#[utoipa::path(
+    get,
+    path = "/api/v1/stats",
+    responses((status = 200, body = Stats), (status = 401, body = ApiError))
+)]
+async fn stats(
+    State(state): State<ApiState>,
+    Extension(caller): Extension<ApiCaller>,
+) -> Result<Json<Stats>, Failure> {
+    let instances: Vec<_> = synced(&state, &caller, None)?
+        .iter()
+        .map(|(name, synced)| InstanceStats::new(name, synced))
+        .collect();
+    Ok(Json(Stats {
+        dl_speed: instances.iter().map(|instance| instance.dl_speed).sum(),
+        up_speed: instances.iter().map(|instance| instance.up_speed).sum(),
+        torrents: instances.iter().map(|instance| instance.torrents).sum(),
+        instances,
+    }))
+}
+
+/// Pauses every torrent that isn't paused yet.
+#[utoipa::path(
+    post,
+    path = "/api/v1/pause-all",
+    params(InstanceQuery),
+    responses(
+        (status = 200, body = [ActionResult]),
+        (status = 401, body = ApiError),
+        (status = 403, description = "The token may only look", body = ApiError),
+        (status = 404, description = "No such instance", body = ApiError),
+        (status = 502, description = "Some torrent client failed", body = [ActionResult])
+    )
+)]
+async fn pause_all(
+    State(state): State<ApiState>,
+    Extension(caller): Extension<ApiCaller>,
+    Query(query): Query<InstanceQuery>,
+) -> Result<Response, Failure> {
+    command_all(&state, &caller, query.instance.as_deref(), Command::Pause).await
+}
+
+/// Resumes every paused torrent.
+#[utoipa::path(
+    post,
+    path = "/api/v1/resume-all",
+    params(InstanceQuery),
+    responses(
+        (status = 200, body = [ActionResult]),
+        (status = 401, body = ApiError),
+        (status = 403, description = "The token may only look", body = ApiError),
+        (status = 404, description = "No such instance", body = ApiError),
+        (status = 502, description = "Some torrent client failed", body = [ActionResult])
+    )
+)]
+async fn resume_all(
+    State(state): State<ApiState>,
+    Extension(caller): Extension<ApiCaller>,
+    Query(query): Query<InstanceQuery>,
+) -> Result<Response, Failure> {
+    command_all(&state, &caller, query.instance.as_deref(), Command::Resume).await
+}
+
+async fn command_all(
+    state: &ApiState,
+    caller: &ApiCaller,
+    instance: Option<&str>,
+    command: Command,
+) -> Result<Response, Failure> {
+    check_admin(caller)?;
+    let mut results = Vec::new();
+    for (name, synced) in synced(state, caller, instance)? {
+        let Some(backend) = state.backends.get(&name) else {
+            continue;
+        };
+        let paused = command == Command::Resume;
+        let hashes: Vec<_> = synced
+            .torrents
+            .iter()
+            .filter(|torrent| (torrent.state == model::TorrentState::Paused) == paused)
+            .map(|torrent| torrent.hash.clone())
+            .collect();
+        let res = if hashes.is_empty() {
+            Ok(())
+        } else {
+            backend.command(&synced.session, command, &hashes).await
+        };
+        if let Err(err) = &res {
+            tracing::warn!(instance = %name, ?command, error = %err, "API command failed");
+        } else {
+            tracing::info!(
+                instance = %name, ?command, torrents = hashes.len(), username = ?caller.username,
+                "API command"
+            );
+        }
+        results.push(ActionResult {
+            instance: name,
+            torrents: hashes.len() as u64,
+            error: res.err().map(|err| err.to_string()),
+        });
+    }
+    Ok(respond(
+        results.iter().any(|result| result.error.is_some()),
+        results,
+    ))
+}
+
+/// Switches every instance between its normal and its alternative speed limits.
+#[utoipa::path(
+    post,
+    path = "/api/v1/toggle-alt-speed",
+    params(InstanceQuery),
+    responses(
+        (status = 200, body = [AltSpeedResult]),
+        (status = 401, body = ApiError),
+        (status = 403, description = "The token may only look", body = ApiError),
+        (status = 404, description = "No such instance", body = ApiError),
+        (status = 502, description = "Some torrent client failed", body = [AltSpeedResult])
+    )
+)]
+async fn toggle_alt_speed(
+    State(state): State<ApiState>,
+    Extension(caller): Extension<ApiCaller>,
+    Query(query): Query<InstanceQuery>,
+) -> Result<Response, Failure> {
+    check_admin(&caller)?;
+    let mut results = Vec::new();
+    for (name, synced) in synced(&state, &caller, query.instance.as_deref())? {
+        let Some(backend) = state.backends.get(&name) else {
+            continue;
+        };
+        let res = backend.toggle_alt_speed(&synced.session).await;
+        match &res {
+            Ok(alt_speed) => tracing::info!(instance = %name, alt_speed, "API toggled alt speed"),
+            Err(err) => tracing::warn!(instance = %name, error = %err, "API alt speed failed"),
+        }
+        results.push(AltSpeedResult {
+            instance: name,
+            error: res.as_ref().err().map(|err| err.to_string()),
+            alt_speed: res.ok(),
+        });
+    }
+    Ok(respond(
+        results.iter().any(|result| result.error.is_some()),
+        results,
+    ))
+}
+
+/// The results of a change, as a bad gateway when some torrent client failed it.
+fn respond<T: Serialize>(failed: bool, results: Vec<T>) -> Response {
+    let status = if failed {
+        StatusCode::BAD_GATEWAY
+    } else {
+        StatusCode::OK
+    };
+    (status, Json(results)).into_response()
+}
+
+/// The last webhook deliveries, newest first, for admins to see why a URL didn't get an event.
+#[utoipa::path(
+    get,
+    path = "/api/v1/webhooks/deliveries",
+    responses(
+        (status = 200, body = [Delivery]),
+        (status = 401, body = ApiError),
+        (status = 403, description = "Only admins see the deliveries", body = ApiError)
+    )
+)]
+async fn webhook_deliveries(
+    State(state): State<ApiState>,
+    Extension(caller): Extension<ApiCaller>,
+) -> Result<Json<Vec<Delivery>>, Failure> {
+    if caller.role != Role::Admin || caller.instances.is_some() {
+        return Err(failure(
+            StatusCode::FORBIDDEN,
+            "Only admins of every instance see the deliveries",
+        ));
+    }
+    Ok(Json(state.webhooks.deliveries()))
+}
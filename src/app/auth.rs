@@ -1,19 +1,93 @@
 use leptos::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "ssr")]
+mod csrf;
+#[cfg(feature = "ssr")]
+mod limiter;
+#[cfg(feature = "ssr")]
+mod proxies;
+mod role;
+#[cfg(feature = "ssr")]
+mod store;
+#[cfg(feature = "ssr")]
+mod token_store;
+mod tokens;
+#[cfg(feature = "ssr")]
+mod users;
+
+pub use self::role::*;
+pub use self::tokens::*;
 
 #[cfg(feature = "ssr")]
 pub mod ssr {
-    use crate::qbittorrent::client::QbtClient;
-    use base64::Engine;
+    use std::collections::BTreeMap;
+    use std::net::IpAddr;
+
+    use super::{Role, API_TOKEN_PREFIX};
+    use crate::backend::BackendRegistry;
     use http::header;
     use leptos::prelude::*;
     use serde::{Deserialize, Serialize};
 
+    pub use super::csrf::{check_csrf, AllowedOrigins, CsrfToken, CSRF_COOKIE};
+    pub use super::limiter::{LimiterOptions, LoginLimiter};
+    pub use super::proxies::{Forwarded, TrustedProxies};
+    pub(crate) use super::store::write_private;
+    pub use super::store::{SessionOptions, SessionStore};
+    pub use super::token_store::{TokenOwner, TokenStore};
+    pub use super::users::{hash_password, User, Users, UsersFile};
+
     pub static AUTH_COOKIE: &str = "bt-session";
-    pub static REMOVE_COOKIE: &str = "bt-session=; path=/; expires=Thu, 01 Jan 1970 00:00:00 GMT";
 
-    pub fn use_qbt() -> Result<QbtClient, ServerFnError> {
-        use_context::<QbtClient>()
-            .ok_or_else(|| ServerFnError::ServerError("Qbt client missing.".into()))
+    /// Target of the logs about who logged in from where, to keep them apart, e.g. with
+    /// `RUST_LOG=bittower::audit=info`.
+    pub const AUDIT_TARGET: &str = "bittower::audit";
+
+    /// Address of the browser a request came from, behind the trusted proxies if any.
+    #[derive(PartialEq, Eq, Debug, Clone, Copy)]
+    pub struct ClientIp(pub IpAddr);
+
+    pub fn use_registry() -> Result<BackendRegistry, ServerFnError> {
+        use_context::<BackendRegistry>()
+            .ok_or_else(|| ServerFnError::ServerError("Backend registry missing.".into()))
+    }
+
+    pub fn use_sessions() -> Result<SessionStore, ServerFnError> {
+        use_context::<SessionStore>()
+            .ok_or_else(|| ServerFnError::ServerError("Session store missing.".into()))
+    }
+
+    pub fn use_limiter() -> Result<LoginLimiter, ServerFnError> {
+        use_context::<LoginLimiter>()
+            .ok_or_else(|| ServerFnError::ServerError("Login limiter missing.".into()))
+    }
+
+    pub fn use_client_ip() -> Result<IpAddr, ServerFnError> {
+        use_context::<ClientIp>()
+            .map(|ClientIp(ip)| ip)
+            .ok_or_else(|| ServerFnError::ServerError("Client address missing.".into()))
+    }
+
+    /// Counts and logs a failed login. The error doesn't tell whether the user exists.
+    pub fn login_failed(limiter: &LoginLimiter, ip: IpAddr, username: &str) -> ServerFnError {
+        match limiter.failed(ip, username) {
+            Some(lockout) => tracing::warn!(
+                target: AUDIT_TARGET, %ip, %username, ?lockout, "Failed login, locked out"
+            ),
+            None => tracing::warn!(target: AUDIT_TARGET, %ip, %username, "Failed login"),
+        }
+        ServerFnError::ServerError("Wrong username or password".into())
+    }
+
+    pub fn use_tokens() -> Result<TokenStore, ServerFnError> {
+        use_context::<TokenStore>()
+            .ok_or_else(|| ServerFnError::ServerError("Token store missing.".into()))
+    }
+
+    /// bit-tower's own users, `None` when users log in with the torrent clients' credentials.
+    pub fn use_users() -> Option<Users> {
+        use_context::<Users>()
     }
 
     pub fn auth() -> Result<Option<Session>, ServerFnError> {
@@ -21,68 +95,389 @@ pub mod ssr {
         Ok(session)
     }
 
-    #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+    /// The session of a request, if its cookie points to one that is still going.
+    #[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
     pub struct AuthSession {
+        pub id: Option<String>,
         pub session: Option<Session>,
     }
     impl AuthSession {
-        pub fn new(session: Option<Session>) -> Self {
-            Self { session }
+        pub fn new(id: String, session: Session) -> Self {
+            Self {
+                id: Some(id),
+                session: Some(session),
+            }
+        }
+
+        /// Whether the session is that of a personal API token rather than of a browser.
+        pub fn is_token(&self) -> bool {
+            self.id
+                .as_deref()
+                .is_some_and(|id| id.starts_with(API_TOKEN_PREFIX))
+        }
+
+        /// Provides the [`SessionId`] and the [`Session`] to server functions and rendering.
+        /// Not those of API tokens, which are for the REST API and the websocket: anything
+        /// could send one along with a CSRF token of its own, and log the user out or change
+        /// their settings.
+        pub fn provide(&self) {
+            if self.is_token() {
+                return;
+            }
+            if let (Some(id), Some(session)) = (&self.id, &self.session) {
+                provide_context::<SessionId>(SessionId(id.clone()));
+                provide_context::<Session>(session.clone());
+            }
         }
     }
 
-    #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+    /// Id of the session of the request, provided to server functions next to the [`Session`].
+    #[derive(PartialEq, Eq, Debug, Clone)]
+    pub struct SessionId(pub String);
+
+    /// What the [`SessionStore`] keeps for a logged in user.
+    #[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
     pub struct Session {
-        pub sid: String,
+        /// Who logged in, to log them out everywhere at once. A bit-tower user when there is a
+        /// users file, whoever the torrent clients know otherwise.
+        pub username: String,
+        #[serde(default)]
+        pub role: Role,
+        /// Sessions handed out by the torrent clients, by instance name. Only the instances the user logged in to are here.
+        pub sids: BTreeMap<String, String>,
     }
     impl Session {
-        pub fn new(sid: String) -> Self {
-            Self { sid }
+        pub fn sid(&self, instance: &str) -> Option<&str> {
+            self.sids.get(instance).map(String::as_str)
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.sids.is_empty()
+        }
+
+        /// Hands the session over to `username` when it was someone else's, keeping nothing of
+        /// theirs. Returns their session, to log out of.
+        pub fn hand_over(&mut self, username: &str) -> Option<Session> {
+            if self.username == username {
+                return None;
+            }
+            let previous = std::mem::take(self);
+            self.username = username.to_owned();
+            Some(previous)
         }
     }
 
+    /// Stores `session` under a new id and hands it to the browser, ending the session the
+    /// request came with.
     #[tracing::instrument(skip_all)]
     pub fn set_session(session: Session) -> Result<(), ServerFnError> {
-        if let Some(res) = leptos::context::use_context::<leptos_axum::ResponseOptions>() {
-            let encoded: Vec<u8> = bincode::serialize(&session).unwrap();
-            let value = simple_crypt::encrypt(&encoded, b"test-password-please-ignore").unwrap();
-            let value = base64::prelude::BASE64_STANDARD.encode(value);
-            res.insert_header(
-                header::SET_COOKIE,
-                header::HeaderValue::from_str(&format!("bt-session={value}; path=/; HttpOnly"))
-                    .expect("header value couldn't be set"),
-            );
-            Ok(())
-        } else {
-            Err(ServerFnError::ServerError("No ".to_string()))
+        let store = use_sessions()?;
+        let old = use_context::<SessionId>();
+        let id = store.rotate(old.as_ref().map(|SessionId(id)| id.as_str()), session);
+        set_cookie(&store.cookie(&id))
+    }
+
+    /// Ends the sessions with the torrent clients that `session` holds.
+    pub async fn logout_instances(registry: &BackendRegistry, session: &Session) {
+        let logouts = session.sids.iter().filter_map(|(instance, sid)| {
+            let backend = registry.get(instance)?;
+            Some(async move {
+                if let Err(err) = backend.logout(sid).await {
+                    tracing::info!(%instance, error = %err, "Logout failed");
+                }
+            })
+        });
+        futures::future::join_all(logouts).await;
+    }
+
+    /// The user managing their API tokens. Only bit-tower's own users have any, since
+    /// bit-tower logs in to the instances for them, and tokens can't manage tokens.
+    pub fn token_user() -> Result<Session, ServerFnError> {
+        if use_users().is_none() {
+            return Err(ServerFnError::ServerError(
+                "API tokens need bit-tower's own users, see BITTOWER_USERS_FILE".into(),
+            ));
+        }
+        if use_context::<SessionId>().is_some_and(|SessionId(id)| id.starts_with(API_TOKEN_PREFIX))
+        {
+            return Err(ServerFnError::ServerError(
+                "API tokens can't manage API tokens".into(),
+            ));
         }
+        auth()?
+            .filter(|session| !session.is_empty())
+            .ok_or_else(|| ServerFnError::ServerError("Log in first".into()))
     }
 
-    #[tracing::instrument(skip_all)]
-    pub fn get_session(sealed_token: String) -> Result<Session, anyhow::Error> {
-        let sealed_bytes = base64::prelude::BASE64_STANDARD.decode(sealed_token)?;
-        let encoded = simple_crypt::decrypt(&sealed_bytes, b"test-password-please-ignore").unwrap();
-        let session: Session = bincode::deserialize(&encoded).unwrap();
-        Ok(session)
+    pub fn set_cookie(cookie: &str) -> Result<(), ServerFnError> {
+        let res = use_context::<leptos_axum::ResponseOptions>()
+            .ok_or_else(|| ServerFnError::ServerError("Response options missing.".into()))?;
+        let value = header::HeaderValue::from_str(cookie)
+            .map_err(|_| ServerFnError::ServerError("Invalid cookie.".into()))?;
+        res.insert_header(header::SET_COOKIE, value);
+        Ok(())
     }
 }
 
+/// Logs in to `instance`, or to every instance that takes these credentials when it's empty.
+/// Sessions with the other instances are kept.
+///
+/// With a users file these are the credentials of a bit-tower user, and bit-tower logs in to
+/// the instances they may use with the credentials it holds.
 #[server(Login, "/api")]
-pub async fn login(username: String, password: String) -> Result<(), ServerFnError> {
+pub async fn login(
+    csrf: String,
+    username: String,
+    password: String,
+    instance: Option<String>,
+    push_endpoint: Option<String>,
+) -> Result<(), ServerFnError> {
     use self::ssr::*;
+    use crate::app::push::ssr::use_push;
+
+    check_csrf(&csrf)?;
+    let registry = use_registry()?;
+    let instances: Vec<_> = match instance.filter(|instance| !instance.is_empty()) {
+        Some(name) => {
+            let instance = registry
+                .iter()
+                .find(|instance| instance.name == name)
+                .ok_or_else(|| ServerFnError::ServerError(format!("Unknown instance {name}")))?;
+            vec![instance]
+        }
+        None => registry.iter().collect(),
+    };
+
+    let limiter = use_limiter()?;
+    let ip = use_client_ip()?;
+    if let Some(lockout) = limiter.locked(ip, &username) {
+        tracing::warn!(target: AUDIT_TARGET, %ip, %username, ?lockout, "Login refused, locked out");
+        return Err(ServerFnError::ServerError(
+            "Too many failed logins, try again later".into(),
+        ));
+    }
+
+    let mut session = auth()?.unwrap_or_default();
+    // Someone else logs in on this browser: their sessions are over, and none of them is used
+    // for the instances that turn the new credentials down.
+    if let Some(previous) = session.hand_over(&username) {
+        logout_instances(&registry, &previous).await;
+    }
+    let users = use_users();
+    let logins: Vec<_> = match &users {
+        Some(users) => {
+            let Some(user) = users.verify(&username, &password).await else {
+                return Err(login_failed(&limiter, ip, &username));
+            };
+            session.role = user.role;
+            instances
+                .into_iter()
+                .filter(|instance| user.may_use(&instance.name))
+                .filter_map(|instance| {
+                    let credentials = users.credentials(&instance.name)?;
+                    Some((
+                        instance,
+                        credentials.username.clone(),
+                        credentials.password.clone(),
+                    ))
+                })
+                .collect()
+        }
+        None => {
+            // Whoever has the client's credentials can do anything with it anyway.
+            session.role = Role::Admin;
+            instances
+                .into_iter()
+                .map(|instance| (instance, username.clone(), password.clone()))
+                .collect()
+        }
+    };
+    if logins.is_empty() {
+        return Err(ServerFnError::ServerError(
+            "There is no instance to log in to".into(),
+        ));
+    }
+
+    let logins = logins
+        .into_iter()
+        .map(|(instance, username, password)| async move {
+            let res = instance.backend.login(username, password).await;
+            (instance.name.clone(), res)
+        });
+    session.username = username.clone();
+    let (mut logged_in, mut last_err) = (0, None);
+    for (name, res) in futures::future::join_all(logins).await {
+        match res {
+            Ok(sid) => {
+                session.sids.insert(name, sid);
+                logged_in += 1;
+            }
+            Err(err) => {
+                tracing::info!(instance = %name, error = %err, "Login failed");
+                last_err = Some(err);
+            }
+        }
+    }
+    // Logging in to some of the instances is enough.
+    match last_err {
+        // The client turned down the user's credentials, rather than those of a users file.
+        Some(err) if logged_in == 0 && users.is_none() && err.is_session_expired() => {
+            Err(login_failed(&limiter, ip, &username))
+        }
+        Some(err) if logged_in == 0 => Err(err.into()),
+        _ => {
+            limiter.succeeded(&username);
+            tracing::info!(target: AUDIT_TARGET, %ip, %username, role = %session.role, "Logged in");
+            // Whoever used this browser before isn't notified on it anymore.
+            if let Some(endpoint) = push_endpoint.filter(|endpoint| !endpoint.is_empty()) {
+                use_push()?.logged_in(&endpoint, &username);
+            }
+            set_session(session)
+        }
+    }
+}
 
-    let qbt = use_qbt()?;
+/// Logs out of bit-tower and of the torrent clients. With `everywhere`, every other session of
+/// the same user ends too, e.g. after losing a phone. The browser of `push_endpoint` isn't
+/// notified anymore.
+#[server(Logout, "/api")]
+pub async fn logout(
+    csrf: String,
+    everywhere: bool,
+    push_endpoint: Option<String>,
+) -> Result<(), ServerFnError> {
+    use self::ssr::*;
+    use crate::app::push::ssr::use_push;
 
-    let sid = qbt.auth_login(username, password).await?;
+    check_csrf(&csrf)?;
+    let store = use_sessions()?;
+    let registry = use_registry()?;
+    let sessions = match (use_context::<SessionId>(), auth()?) {
+        (_, Some(session)) if everywhere => store.remove_user(&session.username),
+        (Some(SessionId(id)), _) => store.remove(&id).into_iter().collect(),
+        _ => Vec::new(),
+    };
+    tracing::info!(everywhere, "Logged out of {} sessions", sessions.len());
+    for session in &sessions {
+        logout_instances(&registry, session).await;
+    }
+    // Only the user who logged out isn't notified on this browser anymore.
+    if let (Some(endpoint), Some(session)) = (
+        push_endpoint.filter(|endpoint| !endpoint.is_empty()),
+        sessions.first(),
+    ) {
+        use_push()?.unsubscribe(&session.username, &endpoint);
+    }
+    set_cookie(&store.removal_cookie())
+}
 
-    set_session(Session::new(sid))?;
+/// Token the forms send along with server functions that change anything, see
+/// [`ssr::check_csrf`].
+#[server]
+pub async fn csrf_token() -> Result<String, ServerFnError> {
+    use self::ssr::*;
 
-    Ok(())
+    let CsrfToken(token) = use_context::<CsrfToken>()
+        .ok_or_else(|| ServerFnError::ServerError("CSRF token missing.".into()))?;
+    Ok(token)
 }
 
 #[server]
 pub async fn has_auth() -> Result<bool, ServerFnError> {
     let auth = self::ssr::auth()?;
 
-    Ok(auth.is_some())
+    Ok(auth.is_some_and(|session| !session.is_empty()))
+}
+
+/// Who is logged in, so that the UI only offers what they may do.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct CurrentUser {
+    pub username: String,
+    pub role: Role,
+}
+
+#[server]
+pub async fn current_user() -> Result<Option<CurrentUser>, ServerFnError> {
+    let auth = self::ssr::auth()?;
+
+    Ok(auth
+        .filter(|session| !session.is_empty())
+        .map(|session| CurrentUser {
+            username: session.username,
+            role: session.role,
+        }))
+}
+
+/// A torrent client instance as the browser sees it.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct InstanceInfo {
+    pub name: String,
+    /// Whether the session holds a session with this instance.
+    pub logged_in: bool,
+}
+
+#[server]
+pub async fn list_instances() -> Result<Vec<InstanceInfo>, ServerFnError> {
+    use self::ssr::*;
+
+    let registry = use_registry()?;
+    let session = auth()?.unwrap_or_default();
+    Ok(registry
+        .names()
+        .map(|name| InstanceInfo {
+            name: name.to_owned(),
+            logged_in: session.sid(name).is_some(),
+        })
+        .collect())
+}
+
+/// The personal API tokens of the user, newest last.
+#[server]
+pub async fn list_api_tokens() -> Result<Vec<ApiTokenInfo>, ServerFnError> {
+    use self::ssr::*;
+
+    let session = token_user()?;
+    Ok(use_tokens()?.list(&session.username))
+}
+
+/// Creates a personal API token, returns it, which is the only time it is shown.
+#[server(CreateApiToken, "/api")]
+pub async fn create_api_token(
+    csrf: String,
+    name: String,
+    scope: TokenScope,
+) -> Result<String, ServerFnError> {
+    use self::ssr::*;
+
+    check_csrf(&csrf)?;
+    let session = token_user()?;
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > 64 {
+        return Err(ServerFnError::ServerError(
+            "Name the token in at most 64 characters, e.g. after the script that uses it".into(),
+        ));
+    }
+    let (token, info) = use_tokens()?.create(&session.username, name, scope);
+    tracing::info!(
+        target: AUDIT_TARGET, username = %session.username, id = %info.id, %scope,
+        "Created an API token"
+    );
+    Ok(token)
+}
+
+/// Revokes a personal API token, ending the websockets that use it.
+#[server(RevokeApiToken, "/api")]
+pub async fn revoke_api_token(csrf: String, id: String) -> Result<(), ServerFnError> {
+    use self::ssr::*;
+
+    check_csrf(&csrf)?;
+    let session = token_user()?;
+    let hashed = use_tokens()?
+        .revoke(&session.username, &id)
+        .ok_or_else(|| ServerFnError::ServerError("There is no such token".into()))?;
+    if let Some(token_session) = use_sessions()?.remove_hashed(&hashed) {
+        logout_instances(&use_registry()?, &token_session).await;
+    }
+    tracing::info!(target: AUDIT_TARGET, username = %session.username, %id, "Revoked an API token");
+    Ok(())
 }
@@ -0,0 +1,120 @@
+//! Keeps other sites from acting on behalf of a logged in user.
+//!
+//! Server functions that change anything take a token that has to match the `bt-csrf` cookie,
+//! which other sites can neither read nor set. Websocket upgrades, which browsers don't guard
+//! with CORS, are only accepted from our own origin and the allowed ones.
+
+use base64::Engine;
+use cookie::{Cookie, SameSite};
+use http::HeaderMap;
+use leptos::prelude::*;
+use rand::RngCore;
+
+use super::proxies::Forwarded;
+
+pub static CSRF_COOKIE: &str = "bt-csrf";
+
+/// Token of the request, from its `bt-csrf` cookie or new when it had none.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct CsrfToken(pub String);
+
+impl CsrfToken {
+    pub fn generate() -> Self {
+        let mut bytes = [0; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        Self(base64::prelude::BASE64_URL_SAFE_NO_PAD.encode(bytes))
+    }
+
+    /// The token of the cookie among `headers`, if it looks like one of ours.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        headers
+            .get_all(http::header::COOKIE)
+            .into_iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(Cookie::split_parse)
+            .filter_map(Result::ok)
+            .find(|cookie| cookie.name() == CSRF_COOKIE)
+            .map(|cookie| cookie.value().to_owned())
+            .filter(|token| token.len() == 43)
+            .map(Self)
+    }
+
+    /// `Set-Cookie` value handing the token to the browser, for as long as it runs.
+    pub fn cookie(&self, secure: bool) -> String {
+        Cookie::build((CSRF_COOKIE, self.0.clone()))
+            .path("/")
+            .http_only(true)
+            .secure(secure)
+            .same_site(SameSite::Strict)
+            .build()
+            .to_string()
+    }
+
+    /// Compares in constant time, so that timing doesn't give the token away.
+    pub fn matches(&self, token: &str) -> bool {
+        let (a, b) = (self.0.as_bytes(), token.as_bytes());
+        a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+    }
+}
+
+/// Fails unless `token`, sent along with a server function, is the one of the request's
+/// cookie.
+pub fn check_csrf(token: &str) -> Result<(), ServerFnError> {
+    let expected = use_context::<CsrfToken>()
+        .ok_or_else(|| ServerFnError::ServerError("CSRF token missing.".into()))?;
+    if !expected.matches(token) {
+        tracing::warn!("Rejected a request with a wrong CSRF token");
+        return Err(ServerFnError::ServerError(
+            "This page expired, please reload it".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Origins other than our own that may open websockets, e.g. when the UI is served from
+/// another host than the one bit-tower sees behind a reverse proxy.
+#[derive(Clone, Debug, Default)]
+pub struct AllowedOrigins {
+    origins: Vec<String>,
+}
+
+impl AllowedOrigins {
+    /// `origins` are separated by commas, e.g. `https://torrents.example.com`.
+    pub fn new(origins: &str) -> Self {
+        Self {
+            origins: origins
+                .split(',')
+                .map(|origin| origin.trim().trim_end_matches('/').to_ascii_lowercase())
+                .filter(|origin| !origin.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Whether a request with these headers comes from a page we served or an allowed one.
+    /// Requests without an `Origin` don't come from a browser, which is what this guards.
+    /// Behind a proxy, our own origin is the one it was `forwarded` from.
+    pub fn allows(&self, headers: &HeaderMap, forwarded: &Forwarded) -> bool {
+        let Some(origin) = headers.get(http::header::ORIGIN) else {
+            return true;
+        };
+        let Ok(origin) = origin.to_str() else {
+            return false;
+        };
+        let origin = origin.to_ascii_lowercase();
+        if self.origins.contains(&origin) {
+            return true;
+        }
+        if let Some(ours) = forwarded.origin() {
+            return origin.eq_ignore_ascii_case(&ours);
+        }
+        let host = forwarded.host.as_deref().or_else(|| {
+            headers
+                .get(http::header::HOST)
+                .and_then(|host| host.to_str().ok())
+        });
+        let origin_host = origin
+            .split_once("://")
+            .map_or(origin.as_str(), |(_, host)| host);
+        host.is_some_and(|host| host.eq_ignore_ascii_case(origin_host))
+    }
+}
@@ -0,0 +1,123 @@
+//! Slows down password guessing: after a few failed logins from the same address or for the
+//! same username, logging in is refused for a while, twice as long after every further failure.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use default_struct_builder::DefaultBuilder;
+
+/// How many failures are let through and how long logins are refused after that.
+#[derive(DefaultBuilder, Clone, Debug)]
+pub struct LimiterOptions {
+    /// Failed logins let through before the first lockout.
+    max_attempts: u32,
+    /// First lockout, doubled with every failure after it.
+    lockout: Duration,
+    /// Longest lockout.
+    max_lockout: Duration,
+    /// Failures are forgotten after this long without any.
+    forget_after: Duration,
+}
+
+impl Default for LimiterOptions {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            lockout: Duration::from_secs(30),
+            max_lockout: Duration::from_secs(15 * 60),
+            forget_after: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+/// Failed logins by address and by username. Cheap to clone.
+#[derive(Clone, Debug, Default)]
+pub struct LoginLimiter {
+    options: Arc<LimiterOptions>,
+    failures: Arc<Mutex<HashMap<Key, Failures>>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Key {
+    Ip(IpAddr),
+    Username(String),
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Failures {
+    count: u32,
+    last: Instant,
+    locked_until: Option<Instant>,
+}
+
+impl LoginLimiter {
+    pub fn new(options: LimiterOptions) -> Self {
+        Self {
+            options: Arc::new(options),
+            failures: Default::default(),
+        }
+    }
+
+    /// How long logging in is still refused for, if it is, from `ip` or as `username`.
+    pub fn locked(&self, ip: IpAddr, username: &str) -> Option<Duration> {
+        let now = Instant::now();
+        let failures = self.failures.lock().unwrap();
+        keys(ip, username)
+            .iter()
+            .filter_map(|key| failures.get(key)?.locked_until)
+            .filter_map(|until| until.checked_duration_since(now))
+            .max()
+    }
+
+    /// Counts a failed login, returns how long logging in is now refused for, if it is.
+    pub fn failed(&self, ip: IpAddr, username: &str) -> Option<Duration> {
+        let now = Instant::now();
+        let options = &self.options;
+        let mut failures = self.failures.lock().unwrap();
+        keys(ip, username)
+            .into_iter()
+            .filter_map(|key| {
+                let failures = failures.entry(key).or_insert(Failures {
+                    count: 0,
+                    last: now,
+                    locked_until: None,
+                });
+                if now.duration_since(failures.last) > options.forget_after {
+                    failures.count = 0;
+                }
+                failures.count += 1;
+                failures.last = now;
+                let over = failures.count.checked_sub(options.max_attempts)?;
+                let lockout = options
+                    .lockout
+                    .saturating_mul(2u32.saturating_pow(over))
+                    .min(options.max_lockout);
+                failures.locked_until = Some(now + lockout);
+                Some(lockout)
+            })
+            .max()
+    }
+
+    /// Forgets the failures for `username` once they got their password right. Those of the
+    /// address stay, so that one account can't be used to keep guessing the others.
+    pub fn succeeded(&self, username: &str) {
+        let mut failures = self.failures.lock().unwrap();
+        failures.remove(&Key::Username(username.to_owned()));
+    }
+
+    /// Forgets the failures that are old enough, to be called every now and then.
+    pub fn sweep(&self) {
+        let now = Instant::now();
+        let mut failures = self.failures.lock().unwrap();
+        failures.retain(|_, failures| {
+            failures.locked_until.is_some_and(|until| until > now)
+                || now.duration_since(failures.last) <= self.options.forget_after
+        });
+    }
+}
+
+fn keys(ip: IpAddr, username: &str) -> [Key; 2] {
+    [Key::Ip(ip), Key::Username(username.to_owned())]
+}
@@ -0,0 +1,173 @@
+//! Which address a request came from and which URL the browser asked for, looking past the
+//! reverse proxies we trust.
+
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+
+use anyhow::Context;
+use http::{HeaderMap, HeaderName};
+
+/// Reverse proxies allowed to tell the address of the client, and the header they tell it in.
+#[derive(Clone, Debug)]
+pub struct TrustedProxies {
+    proxies: Vec<IpNet>,
+    header: HeaderName,
+}
+
+impl Default for TrustedProxies {
+    fn default() -> Self {
+        Self {
+            proxies: Vec::new(),
+            header: HeaderName::from_static("x-forwarded-for"),
+        }
+    }
+}
+
+impl TrustedProxies {
+    /// `proxies` are addresses or networks separated by commas, e.g. `127.0.0.1,10.0.0.0/8`.
+    pub fn new(proxies: &str, header: Option<&str>) -> anyhow::Result<Self> {
+        let proxies = proxies
+            .split(',')
+            .map(str::trim)
+            .filter(|proxy| !proxy.is_empty())
+            .map(IpNet::from_str)
+            .collect::<anyhow::Result<_>>()?;
+        let header = match header {
+            Some(header) => HeaderName::from_str(header)
+                .with_context(|| format!("`{header}` isn't a header name"))?,
+            None => Self::default().header,
+        };
+        Ok(Self { proxies, header })
+    }
+
+    fn trusts(&self, ip: IpAddr) -> bool {
+        self.proxies.iter().any(|net| net.contains(ip))
+    }
+
+    /// The address of the client: the peer, unless it's a trusted proxy, then the last address
+    /// in the header that isn't one. Each proxy appends the address it got the request from,
+    /// the ones before the first proxy we trust could be made up by the client.
+    pub fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        if !self.trusts(peer) {
+            return peer;
+        }
+        let forwarded: Vec<IpAddr> = headers
+            .get_all(&self.header)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|ip| parse_ip(ip.trim()))
+            .collect();
+        forwarded
+            .iter()
+            .rev()
+            .find(|ip| !self.trusts(**ip))
+            .or(forwarded.first())
+            .copied()
+            .unwrap_or(peer)
+    }
+
+    /// The `X-Forwarded-*` headers, when the peer is a trusted proxy. The first proxy, the one
+    /// the browser talked to, comes first.
+    pub fn forwarded(&self, peer: IpAddr, headers: &HeaderMap) -> Forwarded {
+        if !self.trusts(peer) {
+            return Forwarded::default();
+        }
+        let first = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.split(',').next())
+                .map(|value| value.trim().to_owned())
+                .filter(|value| !value.is_empty())
+        };
+        Forwarded {
+            proto: first("x-forwarded-proto").map(|proto| proto.to_ascii_lowercase()),
+            host: first("x-forwarded-host"),
+            prefix: first("x-forwarded-prefix"),
+        }
+    }
+}
+
+/// What the trusted proxies tell about the URL the browser asked for, from the
+/// `X-Forwarded-Proto`, `X-Forwarded-Host` and `X-Forwarded-Prefix` headers.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Forwarded {
+    /// `http` or `https`.
+    pub proto: Option<String>,
+    pub host: Option<String>,
+    /// Path the proxy strips before passing requests on, e.g. `/torrents`.
+    pub prefix: Option<String>,
+}
+
+impl Forwarded {
+    /// The origin the browser sees, e.g. `https://home.example`, when the proxies tell both.
+    pub fn origin(&self) -> Option<String> {
+        Some(format!(
+            "{}://{}",
+            self.proto.as_ref()?,
+            self.host.as_ref()?
+        ))
+    }
+}
+
+/// Addresses may come with a port, e.g. `192.0.2.1:1234` or `[2001:db8::1]:1234`.
+fn parse_ip(ip: &str) -> Option<IpAddr> {
+    IpAddr::from_str(ip)
+        .ok()
+        .or_else(|| SocketAddr::from_str(ip).ok().map(|addr| addr.ip()))
+}
+
+/// An address with the number of leading bits that make up the network, e.g. `10.0.0.0/8`.
+#[derive(Clone, Copy, Debug)]
+struct IpNet {
+    addr: IpAddr,
+    prefix: u32,
+}
+
+impl FromStr for IpNet {
+    type Err = anyhow::Error;
+
+    fn from_str(net: &str) -> anyhow::Result<Self> {
+        let (addr, prefix) = match net.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (net, None),
+        };
+        let addr = IpAddr::from_str(addr)
+            .with_context(|| format!("`{net}` isn't an address or a network"))?;
+        let bits = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse()
+                .ok()
+                .filter(|prefix| *prefix <= bits)
+                .with_context(|| format!("`{net}` has an invalid prefix length"))?,
+            None => bits,
+        };
+        Ok(Self { addr, prefix })
+    }
+}
+
+impl IpNet {
+    fn contains(&self, ip: IpAddr) -> bool {
+        let ip = match (self.addr, ip) {
+            (IpAddr::V6(_), IpAddr::V4(v4)) => IpAddr::V6(v4.to_ipv6_mapped()),
+            (IpAddr::V4(_), IpAddr::V6(v6)) => match v6.to_ipv4_mapped() {
+                Some(v4) => IpAddr::V4(v4),
+                None => return false,
+            },
+            _ => ip,
+        };
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
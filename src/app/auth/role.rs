@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+
+use crate::backend::model::AddTorrent;
+use crate::protocol::TorrentAction;
+
+/// Tags the torrents a user added, e.g. `bt-user:alice`, which is what contributors may
+/// control.
+pub const OWNER_TAG_PREFIX: &str = "bt-user:";
+
+pub fn owner_tag(username: &str) -> String {
+    format!("{OWNER_TAG_PREFIX}{username}")
+}
+
+/// Tags `torrent` as added by `username`. Owner tags the client sent along are dropped, or
+/// contributors could claim torrents, or hand theirs to someone else.
+pub fn tag_owner(torrent: &mut AddTorrent, username: &str) {
+    torrent
+        .tags
+        .retain(|tag| !tag.starts_with(OWNER_TAG_PREFIX));
+    torrent.tags.push(owner_tag(username));
+}
+
+/// What a user may do with the torrents, from least to most.
+#[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Only looks at the torrents.
+    #[default]
+    Viewer,
+    /// Adds torrents, and pauses, resumes, rechecks, reannounces or removes the ones they
+    /// added. Their files stay.
+    Contributor,
+    /// Does anything, including deleting files and setting limits.
+    Admin,
+}
+
+impl Role {
+    pub fn may_add(self) -> bool {
+        self >= Role::Contributor
+    }
+
+    /// Pausing, resuming, rechecking or reannouncing torrents, `owned` when the user added
+    /// every one of them.
+    pub fn may_control(self, owned: bool) -> bool {
+        self == Role::Admin || (self == Role::Contributor && owned)
+    }
+
+    pub fn may_delete(self, delete_files: bool, owned: bool) -> bool {
+        self == Role::Admin || (self == Role::Contributor && owned && !delete_files)
+    }
+
+    pub fn may_set_limits(self) -> bool {
+        self == Role::Admin
+    }
+
+    /// Whether the role may run `action`, `owned` when the user added every torrent it is
+    /// about.
+    pub fn allows(self, action: &TorrentAction, owned: bool) -> bool {
+        match action {
+            TorrentAction::Pause { .. }
+            | TorrentAction::Resume { .. }
+            | TorrentAction::Recheck { .. }
+            | TorrentAction::Reannounce { .. } => self.may_control(owned),
+            TorrentAction::Delete { delete_files, .. } => self.may_delete(*delete_files, owned),
+            TorrentAction::SetLimits { .. } => self.may_set_limits(),
+            TorrentAction::Add(_) => self.may_add(),
+        }
+    }
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Role::Viewer => write!(f, "viewer"),
+            Role::Contributor => write!(f, "contributor"),
+            Role::Admin => write!(f, "admin"),
+        }
+    }
+}
@@ -0,0 +1,303 @@
+//! Sessions live on the server, the cookie only carries a random session id.
+//!
+//! Ids are only kept hashed, so that neither the memory nor the persisted file of a running
+//! bit-tower gives away a cookie that works.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Context;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use cookie::{Cookie, SameSite};
+use default_struct_builder::DefaultBuilder;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::ssr::{Session, AUTH_COOKIE};
+
+/// How long sessions last and how their cookie is sent.
+#[derive(DefaultBuilder, Clone, Debug)]
+pub struct SessionOptions {
+    /// Sessions that weren't used for this long are over.
+    idle_timeout: Duration,
+    /// Sessions are over this long after logging in, used or not.
+    max_age: Duration,
+    /// Only send the cookie over HTTPS.
+    secure: bool,
+    /// File to keep the sessions in across restarts, they are only kept in memory when `None`.
+    #[builder(into)]
+    file: Option<PathBuf>,
+}
+
+impl Default for SessionOptions {
+    fn default() -> Self {
+        Self {
+            idle_timeout: Duration::from_secs(7 * 24 * 60 * 60),
+            max_age: Duration::from_secs(30 * 24 * 60 * 60),
+            secure: true,
+            file: None,
+        }
+    }
+}
+
+/// Every session of bit-tower, by hashed session id. Cheap to clone.
+#[derive(Clone, Debug, Default)]
+pub struct SessionStore {
+    options: Arc<SessionOptions>,
+    state: Arc<Mutex<StoreState>>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StoreState {
+    sessions: HashMap<String, StoredSession>,
+    /// Something changed since the sessions were last written to the file.
+    #[serde(skip)]
+    dirty: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct StoredSession {
+    session: Session,
+    created_at: DateTime<Utc>,
+    last_seen: DateTime<Utc>,
+}
+
+impl StoredSession {
+    fn is_expired(&self, options: &SessionOptions, now: DateTime<Utc>) -> bool {
+        let since = |time: DateTime<Utc>| (now - time).to_std().unwrap_or_default();
+        since(self.last_seen) > options.idle_timeout || since(self.created_at) > options.max_age
+    }
+}
+
+impl SessionStore {
+    /// Reads back the sessions of the file in `options`, if any.
+    pub fn new(options: SessionOptions) -> anyhow::Result<Self> {
+        let mut state = StoreState::default();
+        if let Some(path) = &options.file {
+            match std::fs::read(path) {
+                Ok(bytes) => {
+                    state = serde_json::from_slice(&bytes).with_context(|| {
+                        format!("Could not read the sessions in {}", path.display())
+                    })?;
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => {
+                    return Err(err).with_context(|| format!("Could not read {}", path.display()));
+                }
+            }
+        }
+        let store = Self {
+            options: Arc::new(options),
+            state: Arc::new(Mutex::new(state)),
+        };
+        store.sweep();
+        Ok(store)
+    }
+
+    /// Starts a new session, returns the id to hand to the browser.
+    pub fn create(&self, session: Session) -> String {
+        let id = new_session_id();
+        self.insert(&id, session);
+        id
+    }
+
+    /// Starts a session under an `id` that is already as hard to guess as ours, e.g. an API
+    /// token.
+    pub fn insert(&self, id: &str, session: Session) {
+        let now = Utc::now();
+        self.with_state(|state| {
+            state.sessions.insert(
+                hash(id),
+                StoredSession {
+                    session,
+                    created_at: now,
+                    last_seen: now,
+                },
+            );
+            state.dirty = true;
+        });
+        self.persist();
+    }
+
+    /// Ends the session `old`, if any, and starts a new one. Ids change whenever the user
+    /// logs in so that an id planted before can't be used afterwards.
+    pub fn rotate(&self, old: Option<&str>, session: Session) -> String {
+        if let Some(old) = old {
+            self.with_state(|state| state.sessions.remove(&hash(old)));
+        }
+        self.create(session)
+    }
+
+    /// The session with this id, if it is still going. Counts as using it.
+    pub fn get(&self, id: &str) -> Option<Session> {
+        let now = Utc::now();
+        let options = self.options.clone();
+        self.with_state(|state| {
+            let key = hash(id);
+            let stored = state.sessions.get_mut(&key)?;
+            if stored.is_expired(&options, now) {
+                state.sessions.remove(&key);
+                state.dirty = true;
+                return None;
+            }
+            // Written down by the next sweep, it's not worth a write per request.
+            stored.last_seen = now;
+            state.dirty = true;
+            Some(stored.session.clone())
+        })
+    }
+
+    /// Whether the session is still going, without counting as using it.
+    pub fn contains(&self, id: &str) -> bool {
+        let now = Utc::now();
+        self.with_state(|state| {
+            state
+                .sessions
+                .get(&hash(id))
+                .is_some_and(|stored| !stored.is_expired(&self.options, now))
+        })
+    }
+
+    /// Ends the session, returns it so that whatever it holds can be cleaned up.
+    pub fn remove(&self, id: &str) -> Option<Session> {
+        let removed = self.with_state(|state| {
+            let removed = state.sessions.remove(&hash(id));
+            state.dirty |= removed.is_some();
+            removed
+        });
+        self.persist();
+        removed.map(|stored| stored.session)
+    }
+
+    /// Ends the session whose id hashes to `hashed`, e.g. that of a revoked API token.
+    pub(super) fn remove_hashed(&self, hashed: &str) -> Option<Session> {
+        let removed = self.with_state(|state| {
+            let removed = state.sessions.remove(hashed);
+            state.dirty |= removed.is_some();
+            removed
+        });
+        self.persist();
+        removed.map(|stored| stored.session)
+    }
+
+    /// Ends every session of `username`, wherever they logged in from.
+    pub fn remove_user(&self, username: &str) -> Vec<Session> {
+        let removed = self.with_state(|state| {
+            let ids: Vec<_> = state
+                .sessions
+                .iter()
+                .filter(|(_, stored)| stored.session.username == username)
+                .map(|(id, _)| id.clone())
+                .collect();
+            state.dirty |= !ids.is_empty();
+            ids.iter()
+                .filter_map(|id| state.sessions.remove(id))
+                .map(|stored| stored.session)
+                .collect()
+        });
+        self.persist();
+        removed
+    }
+
+    /// Drops the expired sessions and writes down what changed, to be called every now and
+    /// then.
+    pub fn sweep(&self) {
+        let now = Utc::now();
+        self.with_state(|state| {
+            let before = state.sessions.len();
+            state
+                .sessions
+                .retain(|_, stored| !stored.is_expired(&self.options, now));
+            if state.sessions.len() != before {
+                tracing::info!("{} sessions expired", before - state.sessions.len());
+                state.dirty = true;
+            }
+        });
+        self.persist();
+    }
+
+    /// Whether cookies are only sent over HTTPS.
+    pub fn secure(&self) -> bool {
+        self.options.secure
+    }
+
+    /// `Set-Cookie` value handing the session id to the browser.
+    pub fn cookie(&self, id: &str) -> String {
+        let max_age = cookie::time::Duration::try_from(self.options.max_age)
+            .unwrap_or(cookie::time::Duration::MAX);
+        self.cookie_builder(id).max_age(max_age).build().to_string()
+    }
+
+    /// `Set-Cookie` value removing the session cookie from the browser.
+    pub fn removal_cookie(&self) -> String {
+        let mut cookie = self.cookie_builder("").build();
+        cookie.make_removal();
+        cookie.to_string()
+    }
+
+    fn cookie_builder<'c>(&self, id: &str) -> cookie::CookieBuilder<'c> {
+        Cookie::build((AUTH_COOKIE, id.to_owned()))
+            .path("/")
+            .http_only(true)
+            .secure(self.options.secure)
+            .same_site(SameSite::Lax)
+    }
+
+    /// Writes the sessions to the file, if there is one and anything changed.
+    fn persist(&self) {
+        let Some(path) = &self.options.file else {
+            return;
+        };
+        let json = self.with_state(|state| {
+            if !std::mem::take(&mut state.dirty) {
+                return None;
+            }
+            Some(serde_json::to_vec(&*state).expect("sessions always serialize"))
+        });
+        if let Some(json) = json {
+            if let Err(err) = write_private(path, &json) {
+                tracing::error!(error = ?err, "Could not save the sessions");
+                self.with_state(|state| state.dirty = true);
+            }
+        }
+    }
+
+    fn with_state<T>(&self, f: impl FnOnce(&mut StoreState) -> T) -> T {
+        f(&mut self.state.lock().unwrap())
+    }
+}
+
+/// 256 random bits, URL safe.
+fn new_session_id() -> String {
+    let mut bytes = [0; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    base64::prelude::BASE64_URL_SAFE_NO_PAD.encode(bytes)
+}
+
+pub(super) fn hash(id: &str) -> String {
+    let digest = Sha256::digest(id.as_bytes());
+    digest.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Replaces the file at once, readable by us only since it holds the sessions with the
+/// torrent clients, the API tokens or the VAPID key.
+pub(crate) fn write_private(path: &Path, bytes: &[u8]) -> anyhow::Result<()> {
+    use std::io::Write;
+
+    let tmp = path.with_extension("tmp");
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options
+        .open(&tmp)
+        .with_context(|| format!("Could not create {}", tmp.display()))?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    std::fs::rename(&tmp, path).with_context(|| format!("Could not replace {}", path.display()))?;
+    Ok(())
+}
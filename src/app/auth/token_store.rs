@@ -0,0 +1,244 @@
+//! Personal API tokens, for scripts that can't log in with a password.
+//!
+//! Tokens belong to a bit-tower user and last until they revoke them. Like session ids, they
+//! are only kept hashed. A token used for the websocket gets a session of its own, stored
+//! under the token itself, that bit-tower logs in to the instances for with the credentials
+//! of the users file.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Context;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use super::ssr::Session;
+use super::store::{hash, write_private, SessionStore};
+use super::tokens::{ApiTokenInfo, TokenScope, API_TOKEN_PREFIX};
+use super::users::Users;
+use crate::backend::BackendRegistry;
+
+/// Every personal API token, by hashed token. Cheap to clone.
+#[derive(Clone, Debug, Default)]
+pub struct TokenStore {
+    /// File to keep the tokens in across restarts, they are only kept in memory when `None`.
+    file: Option<Arc<PathBuf>>,
+    state: Arc<Mutex<TokenState>>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct TokenState {
+    tokens: HashMap<String, StoredToken>,
+    /// Something changed since the tokens were last written to the file.
+    #[serde(skip)]
+    dirty: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct StoredToken {
+    id: String,
+    name: String,
+    username: String,
+    scope: TokenScope,
+    created_at: DateTime<Utc>,
+    last_used: Option<DateTime<Utc>>,
+}
+
+impl StoredToken {
+    fn info(&self) -> ApiTokenInfo {
+        ApiTokenInfo {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            scope: self.scope,
+            created_at: self.created_at,
+            last_used: self.last_used,
+        }
+    }
+}
+
+/// Who a token belongs to and what it may do.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TokenOwner {
+    pub id: String,
+    pub username: String,
+    pub scope: TokenScope,
+}
+
+impl TokenStore {
+    /// Reads back the tokens of `file`, if any.
+    pub fn new(file: Option<PathBuf>) -> anyhow::Result<Self> {
+        let mut state = TokenState::default();
+        if let Some(path) = &file {
+            match std::fs::read(path) {
+                Ok(bytes) => {
+                    state = serde_json::from_slice(&bytes).with_context(|| {
+                        format!("Could not read the API tokens in {}", path.display())
+                    })?;
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => {
+                    return Err(err).with_context(|| format!("Could not read {}", path.display()));
+                }
+            }
+        }
+        Ok(Self {
+            file: file.map(Arc::new),
+            state: Arc::new(Mutex::new(state)),
+        })
+    }
+
+    /// Creates a token for `username`, returns it, which is the only time it is shown, and
+    /// what the list shows of it.
+    pub fn create(&self, username: &str, name: &str, scope: TokenScope) -> (String, ApiTokenInfo) {
+        let token = format!("{API_TOKEN_PREFIX}{}", random(32));
+        let stored = StoredToken {
+            id: random(6),
+            name: name.to_owned(),
+            username: username.to_owned(),
+            scope,
+            created_at: Utc::now(),
+            last_used: None,
+        };
+        let info = stored.info();
+        self.with_state(|state| {
+            state.tokens.insert(hash(&token), stored);
+            state.dirty = true;
+        });
+        self.persist();
+        (token, info)
+    }
+
+    /// The tokens of `username`, oldest first.
+    pub fn list(&self, username: &str) -> Vec<ApiTokenInfo> {
+        let mut tokens: Vec<_> = self.with_state(|state| {
+            state
+                .tokens
+                .values()
+                .filter(|stored| stored.username == username)
+                .map(StoredToken::info)
+                .collect()
+        });
+        tokens.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+        tokens
+    }
+
+    /// Revokes the token `id` of `username`, returns the hash it was kept under, which is
+    /// also the key of its session, or `None` when they have no such token.
+    pub fn revoke(&self, username: &str, id: &str) -> Option<String> {
+        let hashed = self.with_state(|state| {
+            let hashed = state
+                .tokens
+                .iter()
+                .find(|(_, stored)| stored.username == username && stored.id == id)
+                .map(|(hashed, _)| hashed.clone())?;
+            state.tokens.remove(&hashed);
+            state.dirty = true;
+            Some(hashed)
+        });
+        self.persist();
+        hashed
+    }
+
+    /// Who `token` belongs to, if it is one. Counts as using it.
+    pub fn verify(&self, token: &str) -> Option<TokenOwner> {
+        if !token.starts_with(API_TOKEN_PREFIX) {
+            return None;
+        }
+        self.with_state(|state| {
+            let stored = state.tokens.get_mut(&hash(token))?;
+            // Written down by the next sweep, it's not worth a write per request.
+            stored.last_used = Some(Utc::now());
+            state.dirty = true;
+            Some(TokenOwner {
+                id: stored.id.clone(),
+                username: stored.username.clone(),
+                scope: stored.scope,
+            })
+        })
+    }
+
+    /// The session of `token`, started by logging in to every instance its user may use when
+    /// it has none yet. `None` when the token or its user is gone.
+    pub async fn session(
+        &self,
+        token: &str,
+        sessions: &SessionStore,
+        users: &Users,
+        registry: &BackendRegistry,
+    ) -> Option<Session> {
+        let owner = self.verify(token)?;
+        let user = users.user(&owner.username)?;
+        if let Some(session) = sessions.get(token) {
+            return Some(session);
+        }
+        let logins = registry
+            .iter()
+            .filter(|instance| user.may_use(&instance.name))
+            .filter_map(|instance| {
+                let credentials = users.credentials(&instance.name)?;
+                Some(async move {
+                    let res = instance
+                        .backend
+                        .login(credentials.username.clone(), credentials.password.clone())
+                        .await;
+                    (instance.name.clone(), res)
+                })
+            });
+        let mut sids = BTreeMap::new();
+        for (name, res) in futures::future::join_all(logins).await {
+            match res {
+                Ok(sid) => {
+                    sids.insert(name, sid);
+                }
+                Err(err) => {
+                    tracing::info!(instance = %name, error = %err, "Login for an API token failed")
+                }
+            }
+        }
+        let session = Session {
+            username: owner.username,
+            role: owner.scope.cap(user.role),
+            sids,
+        };
+        sessions.insert(token, session.clone());
+        Some(session)
+    }
+
+    /// Writes down when the tokens were last used, to be called every now and then.
+    pub fn sweep(&self) {
+        self.persist();
+    }
+
+    /// Writes the tokens to the file, if there is one and anything changed.
+    fn persist(&self) {
+        let Some(path) = &self.file else {
+            return;
+        };
+        let json = self.with_state(|state| {
+            if !std::mem::take(&mut state.dirty) {
+                return None;
+            }
+            Some(serde_json::to_vec(&*state).expect("tokens always serialize"))
+        });
+        if let Some(json) = json {
+            if let Err(err) = write_private(path, &json) {
+                tracing::error!(error = ?err, "Could not save the API tokens");
+                self.with_state(|state| state.dirty = true);
+            }
+        }
+    }
+
+    fn with_state<T>(&self, f: impl FnOnce(&mut TokenState) -> T) -> T {
+        f(&mut self.state.lock().unwrap())
+    }
+}
+
+/// `bytes` random bytes, URL safe.
+fn random(bytes: usize) -> String {
+    let mut buf = vec![0; bytes];
+    rand::thread_rng().fill_bytes(&mut buf);
+    base64::prelude::BASE64_URL_SAFE_NO_PAD.encode(buf)
+}
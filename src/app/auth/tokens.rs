@@ -0,0 +1,49 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::Role;
+
+/// Prefix of personal API tokens, so that they are easy to tell apart, e.g. by secret scanners.
+pub const API_TOKEN_PREFIX: &str = "btt_";
+
+/// What a personal API token may do.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenScope {
+    /// Only looks, like a viewer.
+    #[default]
+    ReadOnly,
+    /// Does whatever its user may do.
+    Full,
+}
+
+impl TokenScope {
+    /// The role of a token of `role`'s user.
+    pub fn cap(self, role: Role) -> Role {
+        match self {
+            TokenScope::ReadOnly => Role::Viewer,
+            TokenScope::Full => role,
+        }
+    }
+}
+
+impl std::fmt::Display for TokenScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            TokenScope::ReadOnly => "read-only",
+            TokenScope::Full => "full",
+        })
+    }
+}
+
+/// A personal API token as its user sees it, the secret aside.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct ApiTokenInfo {
+    /// Picks the token to revoke, it doesn't authenticate anything.
+    pub id: String,
+    /// What the user named it after, e.g. the script that uses it.
+    pub name: String,
+    pub scope: TokenScope,
+    pub created_at: DateTime<Utc>,
+    pub last_used: Option<DateTime<Utc>>,
+}
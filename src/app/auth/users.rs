@@ -0,0 +1,136 @@
+//! bit-tower's own users, so that nobody needs the torrent clients' passwords.
+//!
+//! The users file lists who may log in, with argon2 hashes of their passwords, and the
+//! credentials bit-tower logs in to each instance with on their behalf:
+//!
+//! ```json
+//! {
+//!   "users": {
+//!     "alice": { "password": "$argon2id$v=19$m=19456,t=2,p=1$...", "role": "admin" },
+//!     "bob": {
+//!       "password": "$argon2id$v=19$m=19456,t=2,p=1$...",
+//!       "role": "contributor",
+//!       "instances": ["seedbox1"]
+//!     }
+//!   },
+//!   "credentials": {
+//!     "seedbox1": { "username": "admin", "password": "adminadmin" }
+//!   }
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+use anyhow::Context;
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::SaltString;
+use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use serde::Deserialize;
+
+use super::Role;
+use crate::qbittorrent::client::BasicAuth;
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct UsersFile {
+    pub users: HashMap<String, User>,
+    /// What bit-tower logs in to the torrent clients with, by instance name.
+    #[serde(default)]
+    pub credentials: HashMap<String, BasicAuth>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct User {
+    /// PHC string of the argon2 hash of the password, see [`hash_password`].
+    pub password: String,
+    /// What the user may do, only look when not given.
+    #[serde(default)]
+    pub role: Role,
+    /// The instances the user may use, every one with credentials when `None`.
+    #[serde(default)]
+    pub instances: Option<Vec<String>>,
+}
+
+impl User {
+    pub fn may_use(&self, instance: &str) -> bool {
+        self.instances.as_ref().map_or(true, |instances| {
+            instances.iter().any(|name| name == instance)
+        })
+    }
+}
+
+/// The users of bit-tower, when it has its own. Cheap to clone.
+#[derive(Clone, Debug, Default)]
+pub struct Users {
+    file: Arc<UsersFile>,
+}
+
+impl Users {
+    /// Fails on hashes that aren't PHC strings rather than on the first login.
+    pub fn new(file: UsersFile) -> anyhow::Result<Self> {
+        for (username, user) in &file.users {
+            PasswordHash::new(&user.password)
+                .map_err(|err| anyhow::anyhow!("The password of {username} isn't a hash: {err}"))?;
+        }
+        Ok(Self {
+            file: Arc::new(file),
+        })
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let bytes =
+            std::fs::read(path).with_context(|| format!("Could not read {}", path.display()))?;
+        let file = serde_json::from_slice(&bytes)
+            .with_context(|| format!("Could not read the users in {}", path.display()))?;
+        Self::new(file)
+    }
+
+    /// The user, if the password is theirs. Unknown users take as long as wrong passwords,
+    /// so that trying doesn't tell who has an account.
+    pub async fn verify(&self, username: &str, password: &str) -> Option<User> {
+        let user = self.file.users.get(username).cloned();
+        let hash = user.as_ref().map(|user| user.password.clone());
+        let password = password.to_owned();
+        // Hashing is slow on purpose, keep it off the runtime's threads.
+        let matches = tokio::task::spawn_blocking(move || {
+            let hash = PasswordHash::new(hash.as_deref().unwrap_or_else(dummy_hash)).ok()?;
+            Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .ok()
+        })
+        .await
+        .ok()
+        .flatten()
+        .is_some();
+        user.filter(|_| matches)
+    }
+
+    pub fn user(&self, username: &str) -> Option<&User> {
+        self.file.users.get(username)
+    }
+
+    /// What bit-tower logs in to `instance` with.
+    pub fn credentials(&self, instance: &str) -> Option<&BasicAuth> {
+        self.file.credentials.get(instance)
+    }
+
+    pub fn instances_with_credentials(&self) -> impl Iterator<Item = &str> {
+        self.file.credentials.keys().map(String::as_str)
+    }
+}
+
+/// Hashes `password` for the users file.
+pub fn hash_password(password: &str) -> String {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .expect("argon2 takes any password with a generated salt")
+        .to_string()
+}
+
+/// Hash to check passwords of unknown users against.
+fn dummy_hash() -> &'static str {
+    static DUMMY: OnceLock<String> = OnceLock::new();
+    DUMMY.get_or_init(|| hash_password("not a password"))
+}
@@ -0,0 +1,58 @@
+//! Where bit-tower is served from, for when a reverse proxy puts it under a path such as
+//! `https://home.example/torrents/`.
+
+/// Name of the `<meta>` tag that hands the base path to the browser.
+pub const BASE_PATH_META: &str = "bittower-base-path";
+
+/// Path bit-tower is served under, e.g. `/torrents`, empty at the site root.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BasePath(String);
+
+impl BasePath {
+    /// Normalizes `path`, so that `torrents/`, `/torrents` and `/torrents/` are the same.
+    pub fn new(path: &str) -> Self {
+        let path = path.trim().trim_matches('/');
+        if path.is_empty() {
+            Self::default()
+        } else {
+            Self(format!("/{path}"))
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The URL of `path`, which starts with a `/`, under the base path.
+    pub fn join(&self, path: &str) -> String {
+        format!("{}{path}", self.0)
+    }
+
+    /// `path` relative to the base path, `None` when it isn't under it.
+    pub fn strip<'a>(&self, path: &'a str) -> Option<&'a str> {
+        match path.strip_prefix(self.0.as_str())? {
+            "" => Some("/"),
+            rest if rest.starts_with('/') => Some(rest),
+            _ => None,
+        }
+    }
+}
+
+/// The base path of the page: from the request on the server, from the `<meta>` tag the
+/// server rendered in the browser.
+pub fn use_base_path() -> BasePath {
+    #[cfg(feature = "ssr")]
+    {
+        leptos::prelude::use_context::<BasePath>().unwrap_or_default()
+    }
+    #[cfg(not(feature = "ssr"))]
+    {
+        leptos::prelude::document()
+            .query_selector(&format!("meta[name=\"{BASE_PATH_META}\"]"))
+            .ok()
+            .flatten()
+            .and_then(|meta| meta.get_attribute("content"))
+            .map(|path| BasePath::new(&path))
+            .unwrap_or_default()
+    }
+}
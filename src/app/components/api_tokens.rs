@@ -0,0 +1,119 @@
+use leptos::prelude::*;
+
+use crate::app::auth::{list_api_tokens, CreateApiToken, RevokeApiToken};
+use crate::app::signals::notifications::use_notifications;
+use crate::app::ui::components::{Alert, AlertVariant};
+
+/// The user's personal API tokens: creates them, shows a new one once and revokes them.
+#[component]
+pub fn ApiTokens(csrf: Signal<String>) -> impl IntoView {
+    let create = ServerAction::<CreateApiToken>::new();
+    let revoke = ServerAction::<RevokeApiToken>::new();
+    let notifications = use_notifications();
+    notifications.watch_action(create, "API token created", "Could not create the token");
+    notifications.watch_action(revoke, "API token revoked", "Could not revoke the token");
+    let tokens = Resource::new(
+        move || (create.version().get(), revoke.version().get()),
+        |_| list_api_tokens(),
+    );
+
+    let created = move || {
+        create.value().get().map(|res| match res {
+            Ok(token) => view! {
+                <Alert>
+                    "Copy the token now, it won't be shown again: "
+                    <code class="select-all break-all">{token}</code>
+                </Alert>
+            }
+            .into_any(),
+            Err(err) => {
+                view! { <Alert variant=AlertVariant::Destructive>{err.to_string()}</Alert> }
+                    .into_any()
+            }
+        })
+    };
+    let list = move || {
+        tokens.get().map(|res| match res {
+            Ok(tokens) if tokens.is_empty() => view! { <p class="text-sm">"No tokens yet."</p> }.into_any(),
+            Ok(tokens) => view! {
+                <table class="text-sm w-full">
+                    <thead>
+                        <tr class="text-left">
+                            <th>"Name"</th>
+                            <th>"Scope"</th>
+                            <th>"Created"</th>
+                            <th>"Last used"</th>
+                            <th></th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        {tokens
+                            .into_iter()
+                            .map(|token| {
+                                let last_used = token
+                                    .last_used
+                                    .map_or_else(
+                                        || "never".to_owned(),
+                                        |time| time.format("%Y-%m-%d %H:%M").to_string(),
+                                    );
+                                view! {
+                                    <tr>
+                                        <td>{token.name}</td>
+                                        <td>{token.scope.to_string()}</td>
+                                        <td>{token.created_at.format("%Y-%m-%d").to_string()}</td>
+                                        <td>{last_used}</td>
+                                        <td>
+                                            <ActionForm action=revoke>
+                                                <input type="hidden" name="csrf" value=move || csrf.get()/>
+                                                <input type="hidden" name="id" value=token.id/>
+                                                <button type="submit" class="text-sm px-1">"revoke"</button>
+                                            </ActionForm>
+                                        </td>
+                                    </tr>
+                                }
+                            })
+                            .collect_view()}
+                    </tbody>
+                </table>
+            }
+            .into_any(),
+            Err(err) => {
+                view! { <Alert variant=AlertVariant::Warning>{err.to_string()}</Alert> }
+                    .into_any()
+            }
+        })
+    };
+
+    view! {
+        <section class="p-2 flex flex-col gap-2 font-iosevka">
+            <h2>"API tokens"</h2>
+            <p class="text-sm">
+                "For scripts, the REST API and the websocket, sent as "
+                <code>"Authorization: Bearer <token>"</code>
+                ". Read-only tokens only look, full ones may do whatever you may."
+            </p>
+            <ActionForm action=create>
+                <input type="hidden" name="csrf" value=move || csrf.get()/>
+                <input
+                    type="text"
+                    name="name"
+                    placeholder="Name, e.g. the script that uses it"
+                    maxlength="64"
+                    required
+                    class="auth-input"
+                />
+                <select
+                    name="scope"
+                    aria-label="Scope"
+                    class="bg-transparent text-sm p-1 border border-gray-300 dark:border-gray-700 rounded"
+                >
+                    <option value="read_only">"read-only"</option>
+                    <option value="full">"full"</option>
+                </select>
+                <button type="submit" class="button">"Create"</button>
+            </ActionForm>
+            {created}
+            <Transition fallback=|| view! { <p class="text-sm">"Loading..."</p> }>{list}</Transition>
+        </section>
+    }
+}
@@ -0,0 +1,39 @@
+use leptos::prelude::*;
+
+use crate::app::auth::InstanceInfo;
+
+/// Picks the instance to look at, `None` being all of them at once. Only shown when
+/// there's more than one instance.
+#[component]
+pub fn InstanceSwitcher(
+    instances: Signal<Vec<InstanceInfo>>,
+    selected: RwSignal<Option<String>>,
+) -> impl IntoView {
+    view! {
+        <Show when=move || instances.with(|instances| instances.len() > 1)>
+            <select
+                class="bg-transparent text-sm p-1 border border-gray-300 dark:border-gray-700 rounded"
+                aria-label="Instance"
+                prop:value=move || selected.get().unwrap_or_default()
+                on:change=move |ev| {
+                    let name = event_target_value(&ev);
+                    selected.set((!name.is_empty()).then_some(name));
+                }
+            >
+                <option value="">"All instances"</option>
+                <For
+                    each=move || instances.get()
+                    key=|instance| instance.name.clone()
+                    children=move |instance| {
+                        let label = if instance.logged_in {
+                            instance.name.clone()
+                        } else {
+                            format!("{} (logged out)", instance.name)
+                        };
+                        view! { <option value=instance.name>{label}</option> }
+                    }
+                />
+            </select>
+        </Show>
+    }
+}
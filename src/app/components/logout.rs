@@ -0,0 +1,34 @@
+use leptos::prelude::*;
+
+use crate::app::auth::Logout;
+
+/// Logs out of this browser, or of every browser the user is logged in on. Either way this
+/// browser, `push_endpoint`, isn't notified anymore.
+#[component]
+pub fn LogoutButtons(
+    action: ServerAction<Logout>,
+    csrf: Signal<String>,
+    push_endpoint: Signal<Option<String>>,
+) -> impl IntoView {
+    let push_endpoint = move || push_endpoint.get().unwrap_or_default();
+    view! {
+        <ActionForm action=action>
+            <input type="hidden" name="csrf" value=move || csrf.get()/>
+            <input type="hidden" name="everywhere" value="false"/>
+            <input type="hidden" name="push_endpoint" prop:value=push_endpoint/>
+            <button type="submit" class="text-sm px-1">"log out"</button>
+        </ActionForm>
+        <ActionForm action=action>
+            <input type="hidden" name="csrf" value=move || csrf.get()/>
+            <input type="hidden" name="everywhere" value="true"/>
+            <input type="hidden" name="push_endpoint" prop:value=push_endpoint/>
+            <button
+                type="submit"
+                class="text-sm px-1"
+                title="Ends your sessions on every device"
+            >
+                "log out everywhere"
+            </button>
+        </ActionForm>
+    }
+}
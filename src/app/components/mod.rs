@@ -1,4 +1,10 @@
 // pub mod example;
 // pub mod icon;
+pub mod api_tokens;
+pub mod instance_switcher;
+pub mod logout;
+pub mod notifications;
+pub mod push_settings;
 pub mod status_bar;
 pub mod torrents;
+pub mod update_prompt;
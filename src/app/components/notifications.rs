@@ -0,0 +1,136 @@
+use chrono::Local;
+use icondata as i;
+use leptos::prelude::*;
+use leptos::text_prop::TextProp;
+use leptos_icons::Icon;
+use tailwind_fuse::tw_merge;
+
+use crate::app::signals::notifications::{use_notifications, Level, Notice};
+
+fn level_class(level: Level) -> &'static str {
+    match level {
+        Level::Info => "border-l-cyan-600",
+        Level::Success => "border-l-green-600",
+        Level::Warning => "border-l-amber-500",
+        Level::Error => "border-l-red-600",
+    }
+}
+
+#[component]
+fn NoticeItem(notice: Notice) -> impl IntoView {
+    let class = if notice.read {
+        "text-sm"
+    } else {
+        "text-sm font-bold"
+    };
+    view! {
+        <div class="flex flex-col">
+            <span class=class>{notice.title}</span>
+            {notice.body.map(|body| view! { <span class="text-xs">{body}</span> })}
+        </div>
+    }
+}
+
+/// Notices as they come, in a corner of the screen until they time out or are dismissed.
+#[component]
+pub fn Toasts() -> impl IntoView {
+    let notifications = use_notifications();
+    view! {
+        <div
+            role="status"
+            aria-live="polite"
+            class="fixed bottom-10 right-2 z-50 flex flex-col gap-2 w-72 font-iosevka"
+        >
+            <For each=move || notifications.toasts().get() key=|notice| notice.id let:notice>
+                {
+                    let id = notice.id;
+                    view! {
+                        <div class=tw_merge!(
+                            "flex flex-row justify-between gap-2 p-2 border border-l-4 shadow bg-gray-50 dark:bg-gray-900 border-gray-300 dark:border-gray-700",
+                            level_class(notice.level)
+                        )>
+                            <NoticeItem notice=notice/>
+                            <button
+                                aria-label="Dismiss"
+                                class="self-start"
+                                on:click=move |_| notifications.dismiss(id)
+                            >
+                                <Icon icon=i::TbX class=TextProp::from("w-4 h-4")/>
+                            </button>
+                        </div>
+                    }
+                }
+            </For>
+        </div>
+    }
+}
+
+/// A bell with the number of unread notices, opening the history of all of them.
+#[component]
+pub fn NotificationDrawer() -> impl IntoView {
+    let notifications = use_notifications();
+    let open = RwSignal::new(false);
+    let unread = notifications.unread();
+    // What came in while closed stays marked until the drawer is closed again.
+    let toggle = move |_| {
+        if open.get_untracked() {
+            notifications.mark_read();
+        }
+        open.update(|open| *open = !*open);
+    };
+    let history = move || {
+        let history = notifications.history().get();
+        if history.is_empty() {
+            return view! { <p class="text-sm p-2">"Nothing yet."</p> }.into_any();
+        }
+        history
+            .into_iter()
+            .map(|notice| {
+                let at = notice
+                    .at
+                    .with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string();
+                view! {
+                    <li class=tw_merge!(
+                        "flex flex-col p-2 border-l-4 border-b border-b-gray-300 dark:border-b-gray-700",
+                        level_class(notice.level)
+                    )>
+                        <NoticeItem notice=notice/>
+                        <span class="text-[11px] text-gray-500">{at}</span>
+                    </li>
+                }
+            })
+            .collect_view()
+            .into_any()
+    };
+
+    view! {
+        <div class="relative p-2 font-iosevka">
+            <button
+                aria-label="Notifications"
+                aria-expanded=move || open.get().to_string()
+                class="relative flex"
+                on:click=toggle
+            >
+                <Icon icon=i::TbBell class=TextProp::from("w-4 h-4")/>
+                <Show when=move || { unread.get() > 0 }>
+                    <span class="absolute -top-1 -right-2 text-[10px] px-1 rounded-full bg-red-600 text-white">
+                        {move || unread.get()}
+                    </span>
+                </Show>
+            </button>
+            <Show when=move || open.get()>
+                <div class="absolute right-0 top-9 w-80 max-h-[70vh] overflow-y-auto border bg-gray-50 dark:bg-gray-950 border-gray-300 dark:border-gray-700">
+                    <div class="flex flex-row justify-between p-2 border-b border-gray-300 dark:border-gray-700">
+                        <h2 class="text-sm">"Notifications"</h2>
+                        <button class="text-sm px-1" on:click=move |_| notifications.clear()>
+                            "clear"
+                        </button>
+                    </div>
+                    <ul>{history}</ul>
+                </div>
+            </Show>
+        </div>
+    }
+}
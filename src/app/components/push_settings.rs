@@ -0,0 +1,174 @@
+use leptos::prelude::*;
+use leptos::task::spawn_local;
+
+use crate::app::hooks::web_push::{
+    current_subscription, push_supported, subscribe, unsubscribe, use_push_endpoint, PushEndpoint,
+};
+use crate::app::push::{push_status, SetPushPrefs, SubscribePush, UnsubscribePush};
+use crate::app::signals::notifications::use_notifications;
+use crate::app::ui::components::{Alert, AlertVariant};
+
+/// Push notifications on this browser, and which events the user is notified of on all of them.
+#[component]
+pub fn PushSettings(csrf: Signal<String>) -> impl IntoView {
+    let subscribe_action = ServerAction::<SubscribePush>::new();
+    let unsubscribe_action = ServerAction::<UnsubscribePush>::new();
+    let prefs_action = ServerAction::<SetPushPrefs>::new();
+    let notifications = use_notifications();
+    notifications.watch_action(
+        subscribe_action,
+        "Notifications enabled on this device",
+        "Could not enable notifications",
+    );
+    notifications.watch_action(
+        unsubscribe_action,
+        "Notifications disabled on this device",
+        "Could not disable notifications",
+    );
+    notifications.watch_action(
+        prefs_action,
+        "Notification settings saved",
+        "Could not save the notification settings",
+    );
+    let status = Resource::new(
+        move || {
+            (
+                subscribe_action.version().get(),
+                unsubscribe_action.version().get(),
+                prefs_action.version().get(),
+            )
+        },
+        |_| push_status(),
+    );
+    // Only known in the browser, so not rendered until hydrated.
+    let supported = RwSignal::new(false);
+    let PushEndpoint(endpoint) = use_push_endpoint();
+    let error = RwSignal::new(None::<String>);
+    Effect::new(move |_| {
+        supported.set(push_supported());
+        if supported.get_untracked() {
+            spawn_local(async move {
+                match current_subscription().await {
+                    Ok(subscription) => endpoint.set(subscription.map(|sub| sub.endpoint)),
+                    Err(err) => error.set(Some(err)),
+                }
+            });
+        }
+    });
+    // Subscribed here and known to the server, which forgets subscriptions that are gone.
+    let subscribed = move || {
+        let endpoint = endpoint.get();
+        status.get().and_then(Result::ok).is_some_and(|status| {
+            endpoint.is_some_and(|endpoint| status.endpoints.contains(&endpoint))
+        })
+    };
+
+    let enable = move |_| {
+        let Some(Ok(status)) = status.get_untracked() else {
+            return;
+        };
+        error.set(None);
+        spawn_local(async move {
+            match subscribe(&status.public_key).await {
+                Ok(subscription) => {
+                    endpoint.set(Some(subscription.endpoint.clone()));
+                    subscribe_action.dispatch(SubscribePush {
+                        csrf: csrf.get_untracked(),
+                        endpoint: subscription.endpoint,
+                        p256dh: subscription.keys.p256dh,
+                        auth: subscription.keys.auth,
+                    });
+                }
+                Err(err) => error.set(Some(err)),
+            }
+        });
+    };
+    let disable = move |_| {
+        error.set(None);
+        spawn_local(async move {
+            match unsubscribe().await {
+                Ok(Some(old)) => {
+                    endpoint.set(None);
+                    unsubscribe_action.dispatch(UnsubscribePush {
+                        csrf: csrf.get_untracked(),
+                        endpoint: old,
+                    });
+                }
+                Ok(None) => endpoint.set(None),
+                Err(err) => error.set(Some(err)),
+            }
+        });
+    };
+    let set_prefs = move |completed: bool, errored: bool| {
+        prefs_action.dispatch(SetPushPrefs {
+            csrf: csrf.get_untracked(),
+            completed,
+            errored,
+        });
+    };
+    let prefs = move || {
+        status.get().map(|res| match res {
+            Ok(status) => {
+                let prefs = status.prefs;
+                view! {
+                    <label class="text-sm">
+                        <input
+                            type="checkbox"
+                            prop:checked=prefs.completed
+                            on:change=move |ev| set_prefs(event_target_checked(&ev), prefs.errored)
+                        />
+                        " Completed downloads"
+                    </label>
+                    <label class="text-sm">
+                        <input
+                            type="checkbox"
+                            prop:checked=prefs.errored
+                            on:change=move |ev| set_prefs(prefs.completed, event_target_checked(&ev))
+                        />
+                        " Torrents that fail"
+                    </label>
+                }
+                .into_any()
+            }
+            Err(err) => {
+                view! { <Alert variant=AlertVariant::Warning>{err.to_string()}</Alert> }.into_any()
+            }
+        })
+    };
+    let failure = move || {
+        let server = [
+            subscribe_action.value().get(),
+            unsubscribe_action.value().get(),
+            prefs_action.value().get(),
+        ]
+        .into_iter()
+        .flatten()
+        .find_map(Result::err)
+        .map(|err| err.to_string());
+        error.get().or(server).map(|err| {
+            view! { <Alert variant=AlertVariant::Destructive>{err}</Alert> }
+        })
+    };
+
+    view! {
+        <section class="p-2 flex flex-col gap-2 font-iosevka">
+            <h2>"Notifications"</h2>
+            <p class="text-sm">
+                "Get notified on this device when a download completes or fails, even with bit-tower closed."
+            </p>
+            <Show
+                when=move || supported.get()
+                fallback=|| view! { <p class="text-sm">"This browser can't receive notifications. On iOS, add bit-tower to the home screen first."</p> }
+            >
+                <Show
+                    when=subscribed
+                    fallback=move || view! { <button class="button" on:click=enable>"Enable on this device"</button> }
+                >
+                    <button class="button" on:click=disable>"Disable on this device"</button>
+                </Show>
+            </Show>
+            <Transition fallback=|| view! { <p class="text-sm">"Loading..."</p> }>{prefs}</Transition>
+            {failure}
+        </section>
+    }
+}
@@ -9,11 +9,16 @@ use leptos_icons::Icon;
 
 #[component]
 pub fn StatusBar(
-    server_state: ServerState,
+    /// Summed up when looking at several instances at once.
+    server_states: Signal<Vec<ServerState>>,
     ready_state: Signal<ConnectionReadyState>,
 ) -> impl IntoView {
-    let dl_speed = move || human_bytes(server_state.dl_info_speed.get());
-    let up_speed = move || human_bytes(server_state.up_info_speed.get());
+    let total = move |field: fn(&ServerState) -> u64| {
+        server_states.with(|states| states.iter().map(field).sum::<u64>())
+    };
+    let dl_speed = move || human_bytes(total(|state| state.dl_speed.get()) as f64);
+    let up_speed = move || human_bytes(total(|state| state.up_speed.get()) as f64);
+    let dht_nodes = move || total(|state| state.dht_nodes.get());
 
     let status = move || match ready_state.get() {
         ConnectionReadyState::Open => "Open",
@@ -49,7 +54,7 @@ pub fn StatusBar(
             </View>
             <View class="flex-row gap-1 items-center p-1 px-2 border-r border-t-gray-300 dark:border-r-gray-700 justify-start shrink-0">
                 <Icon icon=i::BiNetworkChartRegular class=TextProp::from("w-4 w-4") />
-                <span>{move || server_state.dht_nodes.get()}</span>
+                <span>{dht_nodes}</span>
             </View>
             <View class="flex-row gap-1 items-center p-1 px-2 justify-start">
                 {move || status_icon()} {move || status()}
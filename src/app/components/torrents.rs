@@ -1,5 +1,3 @@
-use std::time::Duration;
-
 use human_bytes::human_bytes;
 use humantime::format_duration;
 use rust_decimal::prelude::*;
@@ -12,7 +10,11 @@ use leptos::prelude::*;
 static CELL_CLASS: &'static str = "shadow-border p-2 whitespace-nowrap text-left font-normal";
 
 #[component]
-pub fn TorrentList(torrents: Signal<Vec<Torrent>>) -> impl IntoView {
+pub fn TorrentList(
+    torrents: Signal<Vec<Torrent>>,
+    /// Tags every torrent with its instance, when showing several instances at once.
+    show_instance: Signal<bool>,
+) -> impl IntoView {
     view! {
         <div class="h-full w-full overflow-auto overscroll-none">
             <table class=" border-grey border-spacing-[2px] border-collapse border-px w-full">
@@ -34,9 +36,9 @@ pub fn TorrentList(torrents: Signal<Vec<Torrent>>) -> impl IntoView {
                 <tbody>
                     <For
                         each=torrents
-                        key=|torrent| torrent.name.clone()
+                        key=|torrent| (torrent.instance.clone(), torrent.hash.clone())
                         children=move |torrent| {
-                            view! { <TorrentSummary torrent=torrent/> }
+                            view! { <TorrentSummary torrent=torrent show_instance=show_instance/> }
                         }
                     />
 
@@ -47,23 +49,34 @@ pub fn TorrentList(torrents: Signal<Vec<Torrent>>) -> impl IntoView {
 }
 
 #[component]
-pub fn TorrentSummary(torrent: Torrent) -> impl IntoView {
+pub fn TorrentSummary(torrent: Torrent, show_instance: Signal<bool>) -> impl IntoView {
     let name = move || torrent.name.get();
+    let instance = torrent.instance.clone();
 
     // let downloaded = move || human_bytes(torrent.downloaded.get());
     // let uploaded = move || human_bytes(torrent.downloaded.get());
-    let dlspeed = move || human_bytes(torrent.dlspeed.get());
-    let upspeed = move || human_bytes(torrent.upspeed.get());
+    let dlspeed = move || human_bytes(torrent.dl_speed.get() as f64);
+    let upspeed = move || human_bytes(torrent.up_speed.get() as f64);
 
     let availability = move || torrent.availability.get().min(1.0);
-    let eta = move || format_duration(Duration::from_secs_f64(torrent.eta.get())).to_string();
+    let eta = move || match torrent.eta.get() {
+        Some(eta) => format_duration(eta).to_string(),
+        None => "∞".to_owned(),
+    };
 
     view! {
         <tr class="gap-0">
             <th class=tw_merge!(
                 "sticky left-0 bg-gray-50 dark:bg-gray-950 z-10 overflow-hidden text-ellipsis whitespace-nowrap max-w-[40vw] shadow-border p-1 text-left font-normal",
                 CELL_CLASS
-            )>{move || name()}</th>
+            )>
+                <Show when=move || show_instance.get()>
+                    <span class="mr-1 px-1 rounded bg-gray-200 dark:bg-gray-800 text-[11px]">
+                        {instance.clone()}
+                    </span>
+                </Show>
+                {move || name()}
+            </th>
             <td class=CELL_CLASS>
                 <Progress
                     progress=torrent.progress
@@ -74,8 +87,8 @@ pub fn TorrentSummary(torrent: Torrent) -> impl IntoView {
             </td>
             <td class=tw_merge!(CELL_CLASS, "w-[90px]")>{move || dlspeed()}</td>
             <td class=tw_merge!(CELL_CLASS, "w-[90px]")>{move || upspeed()}</td>
-            <td class=CELL_CLASS>{move || torrent.num_seeds.get()}</td>
-            <td class=CELL_CLASS>{move || torrent.num_leechs.get()}</td>
+            <td class=CELL_CLASS>{move || torrent.seeds.get()}</td>
+            <td class=CELL_CLASS>{move || torrent.leechs.get()}</td>
             <td class=CELL_CLASS>{move || eta()}</td>
             <td class=CELL_CLASS>{move || availability()}</td>
         </tr>
@@ -84,13 +97,13 @@ pub fn TorrentSummary(torrent: Torrent) -> impl IntoView {
 
 #[component]
 fn Progress(
-    downloaded: ArcRwSignal<f64>,
+    downloaded: ArcRwSignal<u64>,
     progress: ArcRwSignal<f64>,
-    size: ArcRwSignal<f64>,
-    total_size: ArcRwSignal<f64>,
+    size: ArcRwSignal<u64>,
+    total_size: ArcRwSignal<u64>,
 ) -> impl IntoView {
     let total = total_size.clone();
-    let percent_selected = move || size.get() / total.get();
+    let percent_selected = move || size.get() as f64 / total.get() as f64;
     let inner_bar_w = move || (percent_selected().min(1.0) * 110.0).ceil();
     let inner_bar_w2 = inner_bar_w.clone();
     let percent_complete = move || (progress.get().min(1.0) * inner_bar_w()) - 8.0;
@@ -114,8 +127,8 @@ fn Progress(
                 </div>
             </div>
             <div class="flex flex-row justify-between text-2xs">
-                <div class="text-[11px]">{move || human_bytes(downloaded.get())}</div>
-                <div class="text-[11px]">{move || human_bytes(total_size.get())}</div>
+                <div class="text-[11px]">{move || human_bytes(downloaded.get() as f64)}</div>
+                <div class="text-[11px]">{move || human_bytes(total_size.get() as f64)}</div>
             </div>
         </div>
     }
@@ -0,0 +1,32 @@
+use leptos::prelude::*;
+
+/// Offers to reload with the new build once it's deployed and its service worker is ready.
+#[component]
+pub fn UpdatePrompt(
+    update_available: Signal<bool>,
+    update: impl Fn() + Clone + Send + Sync + 'static,
+) -> impl IntoView {
+    let dismissed = RwSignal::new(false);
+    view! {
+        <Show when=move || update_available.get() && !dismissed.get()>
+            <div
+                role="status"
+                class="fixed top-9 left-0 right-0 z-40 flex flex-row justify-center items-center gap-2 p-1 text-sm font-iosevka bg-cyan-600 text-white"
+            >
+                "A new version of bit-tower is available."
+                <button
+                    class="px-1 underline"
+                    on:click={
+                        let update = update.clone();
+                        move |_| update()
+                    }
+                >
+                    "Reload"
+                </button>
+                <button class="px-1" on:click=move |_| dismissed.set(true)>
+                    "Later"
+                </button>
+            </div>
+        </Show>
+    }
+}
@@ -0,0 +1,60 @@
+#![cfg_attr(feature = "ssr", allow(unused_variables, unused_imports, dead_code))]
+
+//! Values kept in the browser's local storage as JSON, surviving reloads and restarts.
+//!
+//! ## Server-Side Rendering
+//!
+//! On the server there is nothing stored, and storing does nothing.
+
+use serde::{de::DeserializeOwned, Serialize};
+
+#[cfg(not(feature = "ssr"))]
+use {leptos::prelude::window, web_sys::Storage};
+
+/// The value stored under `key`, `None` when there is none or it isn't a `T` (anymore).
+pub fn load<T: DeserializeOwned>(key: &str) -> Option<T> {
+    #[cfg(not(feature = "ssr"))]
+    {
+        let json = storage()?.get_item(key).ok()??;
+        serde_json::from_str(&json)
+            .inspect_err(|err| tracing::warn!(key, %err, "Ignoring what local storage has"))
+            .ok()
+    }
+    #[cfg(feature = "ssr")]
+    None
+}
+
+/// Stores `value` under `key`. Failures, e.g. because the storage is full or disabled, are
+/// only logged: whatever is stored is nice to have, not needed.
+pub fn store<T: Serialize>(key: &str, value: &T) {
+    #[cfg(not(feature = "ssr"))]
+    {
+        let Some(storage) = storage() else {
+            return;
+        };
+        let stored = serde_json::to_string(value)
+            .map_err(|err| err.to_string())
+            .and_then(|json| {
+                storage
+                    .set_item(key, &json)
+                    .map_err(|err| format!("{err:?}"))
+            });
+        if let Err(err) = stored {
+            tracing::warn!(key, %err, "Could not write to local storage");
+        }
+    }
+}
+
+/// Forgets the value stored under `key`.
+pub fn remove(key: &str) {
+    #[cfg(not(feature = "ssr"))]
+    if let Some(storage) = storage() {
+        let _ = storage.remove_item(key);
+    }
+}
+
+/// Local storage, `None` when the browser doesn't let us have it, e.g. in some private modes.
+#[cfg(not(feature = "ssr"))]
+fn storage() -> Option<Storage> {
+    window().local_storage().ok().flatten()
+}
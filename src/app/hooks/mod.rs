@@ -1 +1,6 @@
+pub mod local_storage;
+pub mod use_event_source;
+pub mod use_page_visibility;
+pub mod use_service_worker;
 pub mod use_websocket;
+pub mod web_push;
@@ -0,0 +1,251 @@
+#![cfg_attr(feature = "ssr", allow(unused_variables, unused_imports, dead_code))]
+
+use std::sync::{atomic::AtomicBool, Arc};
+
+use codee::Decoder;
+use default_struct_builder::DefaultBuilder;
+use leptos::prelude::*;
+use leptos::reactive_graph::diagnostics;
+use wasm_bindgen::prelude::*;
+use web_sys::{Event, EventSource, MessageEvent};
+
+use super::use_websocket::core::ConnectionReadyState;
+
+/// Reactive [EventSource](https://developer.mozilla.org/en-US/docs/Web/API/EventSource).
+///
+/// Opens a one-way connection to a server sending events in `text/event-stream` format. The data
+/// of every event with the configured name is decoded with the given string codec.
+///
+/// ```
+/// # use leptos::prelude::*;
+/// # use codee::string::JsonSerdeCodec;
+/// # use serde::{Deserialize, Serialize};
+/// #
+/// # #[component]
+/// # fn Demo() -> impl IntoView {
+/// #[derive(Serialize, Deserialize)]
+/// struct Update {
+///     count: i32,
+/// }
+///
+/// let UseEventSourceReturn {
+///     ready_state,
+///     message,
+///     ..
+/// } = use_event_source_with_options::<Update, JsonSerdeCodec>(
+///     "/events",
+///     UseEventSourceOptions::default().event("update"),
+/// );
+/// #
+/// # view! {}
+/// # }
+/// ```
+///
+/// The browser reconnects on its own when the connection drops, `ready_state` goes back to
+/// `Connecting` while it does so.
+///
+/// ## Server-Side Rendering
+///
+/// On the server the returned functions amount to no-ops.
+pub fn use_event_source_with_options<T, C>(
+    url: &str,
+    options: UseEventSourceOptions,
+) -> UseEventSourceReturn<
+    T,
+    impl Fn() + Clone + Send + Sync + 'static,
+    impl Fn() + Clone + Send + Sync + 'static,
+>
+where
+    T: Send + Sync + 'static,
+    C: Decoder<T, Encoded = str>,
+    <C as Decoder<T>>::Error: std::fmt::Debug,
+{
+    let UseEventSourceOptions { event, immediate } = options;
+
+    let (ready_state, set_ready_state) = signal(ConnectionReadyState::Closed);
+    let (message, set_message) = signal(None);
+
+    #[cfg(feature = "ssr")]
+    {
+        return UseEventSourceReturn {
+            ready_state: ready_state.into(),
+            message: message.into(),
+            open: || {},
+            close: || {},
+        };
+    }
+
+    #[cfg(not(feature = "ssr"))]
+    {
+        let url = url.to_owned();
+        let event_name = event;
+        let es_ref: StoredValue<Option<EventSource>, _> = StoredValue::new_local(None);
+
+        let unmounted = Arc::new(AtomicBool::new(false));
+
+        // Close connection
+        let close = move || {
+            if let Some(event_source) = es_ref.get_value() {
+                event_source.close();
+                set_ready_state.set(ConnectionReadyState::Closed);
+            }
+        };
+
+        // Open connection
+        let open = {
+            let unmounted = Arc::clone(&unmounted);
+
+            move || {
+                close();
+
+                let event_source = EventSource::new(&url).unwrap_throw();
+                set_ready_state.set(ConnectionReadyState::Connecting);
+
+                // onopen handler
+                {
+                    let unmounted = Arc::clone(&unmounted);
+
+                    let onopen_closure = Closure::wrap(Box::new(move |_: Event| {
+                        if unmounted.load(std::sync::atomic::Ordering::Relaxed) {
+                            return;
+                        }
+
+                        set_ready_state.set(ConnectionReadyState::Open);
+                    })
+                        as Box<dyn FnMut(Event)>);
+                    event_source.set_onopen(Some(onopen_closure.as_ref().unchecked_ref()));
+                    // Forget the closure to keep it alive
+                    onopen_closure.forget();
+                }
+
+                // onerror handler
+                {
+                    let unmounted = Arc::clone(&unmounted);
+
+                    let onerror_closure = Closure::wrap(Box::new(move |_: Event| {
+                        if unmounted.load(std::sync::atomic::Ordering::Relaxed) {
+                            return;
+                        }
+
+                        // The browser retries by itself unless the event source was closed for good.
+                        let closed = es_ref.get_value().map_or(true, |es: EventSource| {
+                            es.ready_state() == EventSource::CLOSED
+                        });
+                        set_ready_state.set(if closed {
+                            ConnectionReadyState::Closed
+                        } else {
+                            ConnectionReadyState::Connecting
+                        });
+                    })
+                        as Box<dyn FnMut(Event)>);
+                    event_source.set_onerror(Some(onerror_closure.as_ref().unchecked_ref()));
+                    onerror_closure.forget();
+                }
+
+                // named event handler
+                {
+                    let unmounted = Arc::clone(&unmounted);
+                    let event = event_name.clone();
+
+                    let onmessage_closure = Closure::wrap(Box::new(move |e: MessageEvent| {
+                        if unmounted.load(std::sync::atomic::Ordering::Relaxed) {
+                            return;
+                        }
+
+                        let Some(data) = e.data().as_string() else {
+                            return;
+                        };
+
+                        match C::decode(&data) {
+                            Ok(val) => {
+                                #[cfg(debug_assertions)]
+                                let zone = diagnostics::SpecialNonReactiveZone::enter();
+
+                                set_message.set(Some(val));
+
+                                #[cfg(debug_assertions)]
+                                drop(zone);
+                            }
+                            Err(err) => {
+                                tracing::warn!("Failed to decode `{event}` event: {err:?}");
+                            }
+                        }
+                    })
+                        as Box<dyn FnMut(MessageEvent)>);
+                    event_source
+                        .add_event_listener_with_callback(
+                            &event_name,
+                            onmessage_closure.as_ref().unchecked_ref(),
+                        )
+                        .unwrap_throw();
+                    onmessage_closure.forget();
+                }
+
+                es_ref.set_value(Some(event_source));
+            }
+        };
+
+        // Open connection (not called if option `immediate` is false)
+        Effect::new({
+            let open = open.clone();
+            move |_| {
+                if immediate {
+                    open();
+                }
+            }
+        });
+
+        // clean up (unmount)
+        on_cleanup(move || {
+            unmounted.store(true, std::sync::atomic::Ordering::Relaxed);
+            close();
+        });
+
+        return UseEventSourceReturn {
+            ready_state: ready_state.into(),
+            message: message.into(),
+            open,
+            close,
+        };
+    }
+}
+
+/// Options for [`use_event_source_with_options`].
+#[derive(DefaultBuilder)]
+pub struct UseEventSourceOptions {
+    /// Name of the event whose data is decoded into `message`. Defaults to `"message"`, which is
+    /// what the server sends when it doesn't name its events.
+    #[builder(into)]
+    event: String,
+    /// If `true` the `EventSource` connection will immediately be opened when calling this
+    /// function. If `false` you have to manually call the `open` function.
+    /// Defaults to `true`.
+    immediate: bool,
+}
+
+impl Default for UseEventSourceOptions {
+    fn default() -> Self {
+        Self {
+            event: "message".to_string(),
+            immediate: true,
+        }
+    }
+}
+
+/// Return type of [`use_event_source_with_options`].
+#[derive(Clone)]
+pub struct UseEventSourceReturn<T, OpenFn, CloseFn>
+where
+    T: Send + Sync + 'static,
+    OpenFn: Fn() + Clone + Send + Sync + 'static,
+    CloseFn: Fn() + Clone + Send + Sync + 'static,
+{
+    /// The current state of the `EventSource` connection.
+    pub ready_state: Signal<ConnectionReadyState>,
+    /// Latest decoded event data.
+    pub message: Signal<Option<T>>,
+    /// Opens the `EventSource` connection, closing the previous one if any.
+    pub open: OpenFn,
+    /// Closes the `EventSource` connection.
+    pub close: CloseFn,
+}
@@ -0,0 +1,49 @@
+#![cfg_attr(feature = "ssr", allow(unused_variables, unused_imports, dead_code))]
+
+use leptos::ev;
+use leptos::prelude::*;
+
+/// Tracks whether the page is visible, i.e. not in a background tab or minimized window, and
+/// whether it has the focus.
+///
+/// ## Server-Side Rendering
+///
+/// On the server the page is always considered visible and focused.
+pub fn use_page_visibility() -> UsePageVisibilityReturn {
+    let (visible, set_visible) = signal(true);
+    let (focused, set_focused) = signal(true);
+
+    #[cfg(not(feature = "ssr"))]
+    {
+        let is_visible = || document().visibility_state() == web_sys::VisibilityState::Visible;
+        set_visible.set(is_visible());
+        set_focused.set(document().has_focus().unwrap_or(true));
+
+        // `visibilitychange` is dispatched on the document and bubbles up to the window.
+        let handles = [
+            window_event_listener(ev::visibilitychange, move |_| set_visible.set(is_visible())),
+            window_event_listener(ev::focus, move |_| set_focused.set(true)),
+            window_event_listener(ev::blur, move |_| set_focused.set(false)),
+        ];
+
+        on_cleanup(move || {
+            for handle in handles {
+                handle.remove();
+            }
+        });
+    }
+
+    UsePageVisibilityReturn {
+        visible: visible.into(),
+        focused: focused.into(),
+    }
+}
+
+/// Return type of [`use_page_visibility`].
+#[derive(Clone, Copy)]
+pub struct UsePageVisibilityReturn {
+    /// Whether the page is visible.
+    pub visible: Signal<bool>,
+    /// Whether the page has the focus.
+    pub focused: Signal<bool>,
+}
@@ -0,0 +1,153 @@
+#![cfg_attr(feature = "ssr", allow(unused_variables, unused_imports, dead_code))]
+
+//! Registers bit-tower's service worker, which keeps the app for offline use and shows push
+//! notifications, and tells when the worker of a newer build waits to take over.
+//!
+//! ## Server-Side Rendering
+//!
+//! On the server there is no service worker, and no update either.
+
+use std::time::Duration;
+
+use leptos::prelude::*;
+use web_sys::ServiceWorkerRegistration;
+
+#[cfg(not(feature = "ssr"))]
+use {
+    super::web_push::js_error,
+    leptos::task::spawn_local,
+    wasm_bindgen::{closure::Closure, JsCast},
+    wasm_bindgen_futures::JsFuture,
+    web_sys::ServiceWorkerState,
+};
+
+/// How often a page that stays open asks whether there is a new build.
+const UPDATE_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+pub struct UseServiceWorkerReturn<UpdateFn>
+where
+    UpdateFn: Fn() + Clone + Send + Sync + 'static,
+{
+    /// A new build was deployed and its service worker is ready to take over.
+    pub update_available: Signal<bool>,
+    /// Lets the waiting service worker take over and reloads the page with the new build.
+    pub update: UpdateFn,
+}
+
+/// Whether the browser has service workers, which it only has for secure origins.
+pub fn service_worker_supported() -> bool {
+    #[cfg(not(feature = "ssr"))]
+    {
+        js_sys::Reflect::has(&window().navigator(), &"serviceWorker".into()).unwrap_or(false)
+    }
+    #[cfg(feature = "ssr")]
+    false
+}
+
+/// Has the service worker drop the pages it keeps for offline use, which were rendered for
+/// whoever is logged in. To be called on logout.
+pub fn forget_cached_pages() {
+    #[cfg(not(feature = "ssr"))]
+    {
+        if !service_worker_supported() {
+            return;
+        }
+        let Some(worker) = window().navigator().service_worker().controller() else {
+            return;
+        };
+        let message = js_sys::Object::new();
+        let _ = js_sys::Reflect::set(&message, &"type".into(), &"forget-pages".into());
+        if let Err(err) = worker.post_message(&message) {
+            tracing::warn!(err = js_error(err), "Could not clear the cached pages");
+        }
+    }
+}
+
+/// Registers the service worker at `url`, the page's scope being where it is. Registering it
+/// again is a no-op unless the script changed, which it does with every build.
+pub fn use_service_worker(
+    url: String,
+) -> UseServiceWorkerReturn<impl Fn() + Clone + Send + Sync + 'static> {
+    let (update_available, set_update_available) = signal(false);
+    let registration: StoredValue<Option<ServiceWorkerRegistration>, _> =
+        StoredValue::new_local(None);
+
+    #[cfg(not(feature = "ssr"))]
+    Effect::new(move |_| {
+        if !service_worker_supported() {
+            return;
+        }
+        let url = url.clone();
+        spawn_local(async move {
+            let container = window().navigator().service_worker();
+            let reg = match JsFuture::from(container.register(&url)).await {
+                Ok(reg) => reg.unchecked_into::<ServiceWorkerRegistration>(),
+                Err(err) => {
+                    tracing::warn!(err = js_error(err), "Could not register the service worker");
+                    return;
+                }
+            };
+            // Without a controller the worker is the first one, which takes over right away.
+            let has_controller = || window().navigator().service_worker().controller().is_some();
+            if reg.waiting().is_some() && has_controller() {
+                set_update_available.set(true);
+            }
+            let on_update_found = Closure::<dyn Fn()>::new({
+                let reg = reg.clone();
+                move || {
+                    let Some(installing) = reg.installing() else {
+                        return;
+                    };
+                    let worker = installing.clone();
+                    let on_state_change = Closure::<dyn Fn()>::new(move || {
+                        if worker.state() == ServiceWorkerState::Installed && has_controller() {
+                            set_update_available.set(true);
+                        }
+                    });
+                    installing.set_onstatechange(Some(on_state_change.as_ref().unchecked_ref()));
+                    on_state_change.forget();
+                }
+            });
+            reg.set_onupdatefound(Some(on_update_found.as_ref().unchecked_ref()));
+            on_update_found.forget();
+            registration.set_value(Some(reg));
+        });
+        set_interval(
+            move || {
+                registration.with_value(|reg| {
+                    if let Some(reg) = reg {
+                        let _ = reg.update();
+                    }
+                })
+            },
+            UPDATE_CHECK_INTERVAL,
+        );
+    });
+
+    let update = move || {
+        #[cfg(not(feature = "ssr"))]
+        {
+            let Some(waiting) = registration.with_value(|reg| reg.as_ref()?.waiting()) else {
+                return;
+            };
+            // Reloaded once the new worker controls the page, so that it serves the new build.
+            let reload = Closure::once_into_js(|| {
+                let _ = window().location().reload();
+            });
+            window()
+                .navigator()
+                .service_worker()
+                .set_oncontrollerchange(Some(reload.unchecked_ref()));
+            let message = js_sys::Object::new();
+            let _ = js_sys::Reflect::set(&message, &"type".into(), &"skip-waiting".into());
+            if let Err(err) = waiting.post_message(&message) {
+                tracing::warn!(err = js_error(err), "Could not update the service worker");
+            }
+        }
+    };
+
+    UseServiceWorkerReturn {
+        update_available: update_available.into(),
+        update,
+    }
+}
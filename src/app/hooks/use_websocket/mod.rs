@@ -28,6 +28,8 @@ use web_sys::{BinaryType, CloseEvent, Event, MessageEvent, WebSocket};
 /// ## Usage
 ///
 /// Values are (en)decoded via the given codec. You can use any of the codecs, string or binary.
+/// `Tx` is the type of the messages you send and `Rx` the type of the messages you receive, both
+/// go through the same codec.
 ///
 /// > Please check [the codec chapter](https://leptos-use.rs/codecs.html) to see what codecs are
 /// > available and what feature flags they require.
@@ -47,7 +49,7 @@ use web_sys::{BinaryType, CloseEvent, Event, MessageEvent, WebSocket};
 ///     open,
 ///     close,
 ///     ..
-/// } = use_websocket::<String, FromToStringCodec>("wss://echo.websocket.events/");
+/// } = use_websocket::<String, String, FromToStringCodec>("wss://echo.websocket.events/");
 ///
 /// let send_message = move |_| {
 ///     send(&"Hello, world!".to_string());
@@ -100,7 +102,7 @@ use web_sys::{BinaryType, CloseEvent, Event, MessageEvent, WebSocket};
 ///     message,
 ///     send,
 ///     ..
-/// } = use_websocket::<SomeData, MsgpackSerdeCodec>("wss://some.websocket.server/");
+/// } = use_websocket::<SomeData, SomeData, MsgpackSerdeCodec>("wss://some.websocket.server/");
 ///
 /// let send_data = move || {
 ///     send(&SomeData {
@@ -191,7 +193,7 @@ use web_sys::{BinaryType, CloseEvent, Event, MessageEvent, WebSocket};
 ///     message,
 ///     send,
 ///     ..
-/// } = use_websocket::<String, FromToStringCodec>("ws:://some.websocket.io");
+/// } = use_websocket::<String, String, FromToStringCodec>("ws:://some.websocket.io");
 ///
 /// provide_context(WebsocketContext::new(message, Arc::new(send.clone())));
 /// #
@@ -231,45 +233,49 @@ use web_sys::{BinaryType, CloseEvent, Event, MessageEvent, WebSocket};
 /// ## Server-Side Rendering
 ///
 /// On the server the returned functions amount to no-ops.
-pub fn use_websocket<T, C>(
+pub fn use_websocket<Tx, Rx, C>(
     url: &str,
 ) -> UseWebSocketReturn<
-    T,
+    Tx,
+    Rx,
     impl Fn() + Clone + 'static,
     impl Fn() + Clone + 'static,
-    impl Fn(&T) + Clone + 'static,
+    impl Fn(&Tx) + Clone + 'static,
 >
 where
-    T: Send + Sync + 'static,
-    C: Encoder<T> + Decoder<T>,
-    C: IsBinary<T, <C as Decoder<T>>::Encoded>,
-    C: HybridDecoder<T, <C as Decoder<T>>::Encoded, Error = <C as Decoder<T>>::Error>,
-    C: HybridEncoder<T, <C as Encoder<T>>::Encoded, Error = <C as Encoder<T>>::Error>,
+    Tx: Send + Sync + 'static,
+    Rx: Send + Sync + 'static,
+    C: Encoder<Tx> + Decoder<Rx>,
+    C: IsBinary<Rx, <C as Decoder<Rx>>::Encoded>,
+    C: HybridDecoder<Rx, <C as Decoder<Rx>>::Encoded, Error = <C as Decoder<Rx>>::Error>,
+    C: HybridEncoder<Tx, <C as Encoder<Tx>>::Encoded, Error = <C as Encoder<Tx>>::Error>,
 {
-    use_websocket_with_options::<T, C>(url, UseWebSocketOptions::default())
+    use_websocket_with_options::<Tx, Rx, C>(url, UseWebSocketOptions::default())
 }
 
 /// Version of [`use_websocket`] that takes `UseWebSocketOptions`. See [`use_websocket`] for how to use.
 #[allow(clippy::type_complexity)]
-pub fn use_websocket_with_options<T, C>(
+pub fn use_websocket_with_options<Tx, Rx, C>(
     url: &str,
     options: UseWebSocketOptions<
-        T,
-        HybridCoderError<<C as Encoder<T>>::Error>,
-        HybridCoderError<<C as Decoder<T>>::Error>,
+        Rx,
+        HybridCoderError<<C as Encoder<Tx>>::Error>,
+        HybridCoderError<<C as Decoder<Rx>>::Error>,
     >,
 ) -> UseWebSocketReturn<
-    T,
+    Tx,
+    Rx,
     impl Fn() + Clone + 'static,
     impl Fn() + Clone + 'static,
-    impl Fn(&T) + Clone + 'static,
+    impl Fn(&Tx) + Clone + 'static,
 >
 where
-    T: Send + Sync + 'static,
-    C: Encoder<T> + Decoder<T>,
-    C: IsBinary<T, <C as Decoder<T>>::Encoded>,
-    C: HybridDecoder<T, <C as Decoder<T>>::Encoded, Error = <C as Decoder<T>>::Error>,
-    C: HybridEncoder<T, <C as Encoder<T>>::Encoded, Error = <C as Encoder<T>>::Error>,
+    Tx: Send + Sync + 'static,
+    Rx: Send + Sync + 'static,
+    C: Encoder<Tx> + Decoder<Rx>,
+    C: IsBinary<Rx, <C as Decoder<Rx>>::Encoded>,
+    C: HybridDecoder<Rx, <C as Decoder<Rx>>::Encoded, Error = <C as Decoder<Rx>>::Error>,
+    C: HybridEncoder<Tx, <C as Encoder<Tx>>::Encoded, Error = <C as Encoder<Tx>>::Error>,
 {
     let url = normalize_url(url);
 
@@ -566,7 +572,7 @@ where
         let send = {
             let on_error = Arc::clone(&on_error);
 
-            move |value: &T| {
+            move |value: &Tx| {
                 if C::is_binary() {
                     match C::encode_bin(value) {
                         Ok(val) => send_bytes(&val),
@@ -722,17 +728,18 @@ impl<T: ?Sized, E, D> Default for UseWebSocketOptions<T, E, D> {
 
 /// Return type of [`use_websocket`].
 #[derive(Clone)]
-pub struct UseWebSocketReturn<T, OpenFn, CloseFn, SendFn>
+pub struct UseWebSocketReturn<Tx, Rx, OpenFn, CloseFn, SendFn>
 where
-    T: Send + Sync + 'static,
+    Tx: Send + Sync + 'static,
+    Rx: Send + Sync + 'static,
     OpenFn: Fn() + Clone + 'static,
     CloseFn: Fn() + Clone + 'static,
-    SendFn: Fn(&T) + Clone + 'static,
+    SendFn: Fn(&Tx) + Clone + 'static,
 {
     /// The current state of the `WebSocket` connection.
     pub ready_state: Signal<ConnectionReadyState>,
     /// Latest message received from `WebSocket`.
-    pub message: Signal<Option<T>>,
+    pub message: Signal<Option<Rx>>,
     /// The `WebSocket` instance.
     // pub ws: Option<WebSocket>,
     /// Opens the `WebSocket` connection
@@ -0,0 +1,153 @@
+#![cfg_attr(feature = "ssr", allow(unused_variables, unused_imports, dead_code))]
+
+//! The browser's side of push notifications: the subscription with the push service of the
+//! browser's vendor, through the service worker of
+//! [`use_service_worker`](super::use_service_worker).
+//!
+//! ## Server-Side Rendering
+//!
+//! On the server push isn't supported and every function fails.
+
+use leptos::prelude::*;
+use leptos::task::spawn_local;
+
+use crate::app::push::PushSubscription;
+
+#[cfg(not(feature = "ssr"))]
+use {
+    wasm_bindgen::{JsCast, JsValue},
+    wasm_bindgen_futures::JsFuture,
+    web_sys::{Notification, PushManager, PushSubscriptionOptionsInit, ServiceWorkerRegistration},
+};
+
+/// Whether the browser has service workers and push. Safari only has them for pages added to
+/// the home screen.
+pub fn push_supported() -> bool {
+    #[cfg(not(feature = "ssr"))]
+    {
+        let window = window();
+        js_sys::Reflect::has(&window.navigator(), &"serviceWorker".into()).unwrap_or(false)
+            && js_sys::Reflect::has(&window, &"PushManager".into()).unwrap_or(false)
+    }
+    #[cfg(feature = "ssr")]
+    false
+}
+
+/// The endpoint of this browser's subscription, if it has one. Shared through the context, as
+/// logging in and out tells the server which browser it is.
+#[derive(Clone, Copy)]
+pub struct PushEndpoint(pub RwSignal<Option<String>>);
+
+/// Provides the [`PushEndpoint`], looked up once hydrated.
+pub fn provide_push_endpoint() -> PushEndpoint {
+    let endpoint = PushEndpoint(RwSignal::new(None));
+    Effect::new(move |_| {
+        if push_supported() {
+            spawn_local(async move {
+                if let Ok(Some(subscription)) = current_subscription().await {
+                    endpoint.0.set(Some(subscription.endpoint));
+                }
+            });
+        }
+    });
+    provide_context(endpoint);
+    endpoint
+}
+
+pub fn use_push_endpoint() -> PushEndpoint {
+    expect_context()
+}
+
+/// The subscription of this browser, if it has one.
+pub async fn current_subscription() -> Result<Option<PushSubscription>, String> {
+    #[cfg(not(feature = "ssr"))]
+    {
+        let manager = push_manager().await?;
+        let subscription = JsFuture::from(manager.get_subscription().map_err(js_error)?)
+            .await
+            .map_err(js_error)?;
+        if subscription.is_null() || subscription.is_undefined() {
+            return Ok(None);
+        }
+        to_subscription(subscription).map(Some)
+    }
+    #[cfg(feature = "ssr")]
+    Err("No push on the server".to_owned())
+}
+
+/// Asks for the permission to notify and subscribes with `public_key`, the server's public
+/// VAPID key.
+pub async fn subscribe(public_key: &[u8]) -> Result<PushSubscription, String> {
+    #[cfg(not(feature = "ssr"))]
+    {
+        let permission = JsFuture::from(Notification::request_permission().map_err(js_error)?)
+            .await
+            .map_err(js_error)?;
+        if permission.as_string().as_deref() != Some("granted") {
+            return Err("Notifications aren't allowed for this site".to_owned());
+        }
+        let key = js_sys::Uint8Array::from(public_key);
+        let mut options = PushSubscriptionOptionsInit::new();
+        options.user_visible_only(true);
+        options.application_server_key(Some(&key));
+        let manager = push_manager().await?;
+        let subscription =
+            JsFuture::from(manager.subscribe_with_options(&options).map_err(js_error)?)
+                .await
+                .map_err(js_error)?;
+        to_subscription(subscription)
+    }
+    #[cfg(feature = "ssr")]
+    Err("No push on the server".to_owned())
+}
+
+/// Ends the subscription of this browser, returns its endpoint, `None` when it had none.
+pub async fn unsubscribe() -> Result<Option<String>, String> {
+    #[cfg(not(feature = "ssr"))]
+    {
+        let manager = push_manager().await?;
+        let subscription = JsFuture::from(manager.get_subscription().map_err(js_error)?)
+            .await
+            .map_err(js_error)?;
+        let Ok(subscription) = subscription.dyn_into::<web_sys::PushSubscription>() else {
+            return Ok(None);
+        };
+        JsFuture::from(subscription.unsubscribe().map_err(js_error)?)
+            .await
+            .map_err(js_error)?;
+        Ok(Some(subscription.endpoint()))
+    }
+    #[cfg(feature = "ssr")]
+    Err("No push on the server".to_owned())
+}
+
+#[cfg(not(feature = "ssr"))]
+async fn push_manager() -> Result<PushManager, String> {
+    let container = window().navigator().service_worker();
+    let registration = JsFuture::from(container.ready().map_err(js_error)?)
+        .await
+        .map_err(js_error)?
+        .unchecked_into::<ServiceWorkerRegistration>();
+    registration.push_manager().map_err(js_error)
+}
+
+/// Reads the endpoint and keys of a `PushSubscription` through its JSON, which has them
+/// base64url encoded like the server wants them.
+#[cfg(not(feature = "ssr"))]
+fn to_subscription(subscription: JsValue) -> Result<PushSubscription, String> {
+    let subscription = subscription.unchecked_into::<web_sys::PushSubscription>();
+    let json = subscription.to_json().map_err(js_error)?;
+    let json = js_sys::JSON::stringify(&json)
+        .map_err(js_error)?
+        .as_string()
+        .unwrap_or_default();
+    serde_json::from_str(&json).map_err(|err| err.to_string())
+}
+
+#[cfg(not(feature = "ssr"))]
+pub(super) fn js_error(err: JsValue) -> String {
+    err.dyn_ref::<js_sys::Error>()
+        .map(|err| String::from(err.message()))
+        .or_else(|| err.as_string())
+        .unwrap_or_else(|| format!("{err:?}"))
+}
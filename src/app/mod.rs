@@ -1,13 +1,31 @@
 pub mod auth;
+mod base_path;
 mod components;
 pub mod error_template;
 mod hooks;
+pub mod push;
 mod routes;
-mod signals;
+pub mod signals;
 mod ui;
 
-use auth::{has_auth, Login};
-use components::{status_bar::StatusBar, torrents::TorrentList};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+pub use base_path::*;
+
+use chrono::{DateTime, Local, Utc};
+
+use auth::{csrf_token, current_user, has_auth, list_instances, InstanceInfo, Login, Logout, Role};
+use components::{
+    api_tokens::ApiTokens,
+    instance_switcher::InstanceSwitcher,
+    logout::LogoutButtons,
+    notifications::{NotificationDrawer, Toasts},
+    push_settings::PushSettings,
+    status_bar::StatusBar,
+    torrents::TorrentList,
+    update_prompt::UpdatePrompt,
+};
 use error_template::{AppError, ErrorTemplate};
 use icondata as i;
 use leptos::text_prop::TextProp;
@@ -18,10 +36,19 @@ use leptos_router::{components::*, StaticSegment};
 use serde::{Deserialize, Serialize};
 // use utils::hydration::HydrationScripts;
 
+use crate::app::hooks::use_service_worker::{
+    forget_cached_pages, use_service_worker, UseServiceWorkerReturn,
+};
 use crate::app::hooks::use_websocket::core::ConnectionReadyState;
-use crate::app::signals::syncstate::{ServerState, SyncState, Torrent};
-use crate::app::signals::use_sync_maindata::{use_sync_maindata, UseSyncMaindataReturn};
-use crate::app::ui::components::{Button, Navbar, NavbarBrand, Text, View};
+use crate::app::hooks::web_push::{provide_push_endpoint, PushEndpoint};
+use crate::app::signals::notifications::provide_notifications;
+use crate::app::signals::snapshot;
+use crate::app::signals::syncstate::SyncState;
+use crate::app::signals::use_sync_maindata::{
+    use_sync_maindata, UseSyncMaindataOptions, UseSyncMaindataReturn,
+};
+use crate::app::ui::components::{Alert, AlertVariant, Button, Navbar, NavbarBrand, Text, View};
+use crate::protocol::SyncError;
 
 // pub fn shell(options: LeptosOptions) -> impl IntoView {
 //     view! {
@@ -46,9 +73,52 @@ pub fn App() -> impl IntoView {
     // Provides context that manages stylesheets, titles, meta tags, etc.
     provide_meta_context();
 
+    let notifications = provide_notifications();
+    let PushEndpoint(push_endpoint) = provide_push_endpoint();
     let login = ServerAction::<Login>::new();
-    let is_auth = Resource::new(move || login.version(), move |_| has_auth());
+    let logout = ServerAction::<Logout>::new();
+    // Nothing of the user is kept for whoever uses the browser next, whichever page they logged
+    // out on. Before the notice of the logout, which stays.
+    Effect::new(move |_| {
+        if let Some(Ok(())) = logout.value().get() {
+            snapshot::clear();
+            forget_cached_pages();
+            notifications.clear();
+        }
+    });
+    notifications.watch_action(login, "Logged in", "Could not log in");
+    notifications.watch_action(logout, "Logged out", "Could not log out");
+    let auth_version = move || (login.version().get(), logout.version().get());
+    let is_auth = Resource::new(auth_version, move |_| has_auth());
     let auth = Signal::derive(move || is_auth.get().map(|v| v.unwrap_or(false)).unwrap_or(false));
+    let instances = Resource::new(auth_version, move |_| list_instances());
+    let instances =
+        Signal::derive(move || instances.get().and_then(Result::ok).unwrap_or_default());
+    let user = Resource::new(auth_version, move |_| current_user());
+    // Only offer what the user may do, the server checks anyway.
+    let role = Signal::derive(move || {
+        user.get()
+            .and_then(Result::ok)
+            .flatten()
+            .map_or(Role::Viewer, |user| user.role)
+    });
+    let username = Signal::derive(move || {
+        user.get()
+            .and_then(Result::ok)
+            .flatten()
+            .map(|user| user.username)
+    });
+    // Sent along with the forms, the server only takes them from pages it served.
+    let csrf = Resource::new(|| (), |_| csrf_token());
+    let csrf = Signal::derive(move || csrf.get().and_then(Result::ok).unwrap_or_default());
+    // The instance we're looking at, all of them when `None`.
+    let selected = RwSignal::new(None::<String>);
+    let base_path = use_base_path();
+    // Keeps the app for offline use and shows the push notifications.
+    let UseServiceWorkerReturn {
+        update_available,
+        update,
+    } = use_service_worker(base_path.join("/sw.js"));
 
     // let torrents = Signal::derive(move || {
     //     let v: Vec<_> = data().torrents.into_iter().map(|(_h, v)| v).collect();
@@ -61,15 +131,21 @@ pub fn App() -> impl IntoView {
         <Title text="bit-tower"/>
 
         // content for this welcome page
-        <Router>
+        <Router base=base_path.as_str().to_owned()>
             <Navbar class="z-50 dark:bg-gray-950 border-b border-t-gray-300 dark:border-b-gray-700">
                 <NavbarBrand class="font-display text-cyan-600">"bit-tower"</NavbarBrand>
                 <ul class="p-2 font-cubic">
                     <Show when=move || auth.get() fallback=|| view! { <p>hello</p> }>
+                    <InstanceSwitcher instances=instances selected=selected/>
                     <A href="/menu">menu</A>
+                    <A href="/settings">settings</A>
+                    <LogoutButtons action=logout csrf=csrf push_endpoint=push_endpoint.into()/>
                     </Show>
                 </ul>
+                <NotificationDrawer/>
             </Navbar>
+            <UpdatePrompt update_available=update_available update=update/>
+            <Toasts/>
             <main class="pt-9">
                 <FlatRoutes fallback=|| {
                     let mut outside_errors = Errors::default();
@@ -79,10 +155,52 @@ pub fn App() -> impl IntoView {
                     <Route
                         path=StaticSegment("")
                         view=move || {
-                            let UseSyncMaindataReturn { ready_state, data, .. } = use_sync_maindata(
-                                "ws://10.0.33.171:3010/ws",
+                            let UseSyncMaindataReturn { ready_state, data, updated_at, stale, errors, open, close, .. } = use_sync_maindata(
+                                &base_path.join("/ws"),
+                                &base_path.join("/events"),
+                                UseSyncMaindataOptions::default()
+                                    .on_event(Arc::new(move |event| notifications.sync_event(event)))
+                                    .username(username),
                             );
-                            view! { <HomePage is_auth=auth action=login data=data ready_state=ready_state /> }
+                            let closed_on_purpose = StoredValue::new(false);
+                            notifications.watch_connection(ready_state, closed_on_purpose);
+                            // Reconnect with the new session once the user logged in again.
+                            Effect::new(move |_| {
+                                if let Some(Ok(())) = login.value().get() {
+                                    closed_on_purpose.set_value(false);
+                                    open();
+                                }
+                            });
+                            // Nothing to sync anymore once logged out.
+                            Effect::new(move |_| {
+                                if let Some(Ok(())) = logout.value().get() {
+                                    closed_on_purpose.set_value(true);
+                                    close();
+                                }
+                            });
+                            view! {
+                                <HomePage
+                                    is_auth=auth
+                                    action=login
+                                    csrf=csrf
+                                    push_endpoint=push_endpoint.into()
+                                    instances=instances
+                                    selected=selected
+                                    role=role
+                                    data=data
+                                    updated_at=updated_at
+                                    stale=stale
+                                    ready_state=ready_state
+                                    sync_errors=errors
+                                />
+                            }
+                        }
+                    />
+                    <Route
+                        path=StaticSegment("settings")
+                        view=move || view! {
+                            <ApiTokens csrf=csrf/>
+                            <PushSettings csrf=csrf/>
                         }
                     />
 
@@ -97,20 +215,65 @@ pub fn App() -> impl IntoView {
 fn HomePage(
     is_auth: Signal<bool>,
     action: ServerAction<Login>,
-    data: ReadSignal<SyncState>,
+    csrf: Signal<String>,
+    push_endpoint: Signal<Option<String>>,
+    instances: Signal<Vec<InstanceInfo>>,
+    selected: RwSignal<Option<String>>,
+    role: Signal<Role>,
+    data: ReadSignal<HashMap<String, SyncState>>,
+    updated_at: ReadSignal<Option<DateTime<Utc>>>,
+    stale: Signal<bool>,
     ready_state: Signal<ConnectionReadyState>,
+    sync_errors: ReadSignal<HashMap<String, SyncError>>,
 ) -> impl IntoView {
+    // An expired qBittorrent session means we're logged out, whatever our cookie says.
+    let logged_in = move |instance: &InstanceInfo| {
+        instance.logged_in
+            && sync_errors
+                .with(|errors| errors.get(&instance.name) != Some(&SyncError::SessionExpired))
+    };
+    let needs_login = move || {
+        let selected = selected.get();
+        !is_auth()
+            || instances.with(|instances| {
+                let mut shown = instances
+                    .iter()
+                    .filter(|instance| {
+                        selected
+                            .as_ref()
+                            .map_or(true, |name| *name == instance.name)
+                    })
+                    .peekable();
+                shown.peek().is_some() && !shown.any(logged_in)
+            })
+    };
     let res = move || {
-        if is_auth() {
+        if !needs_login() {
             Either::Left(view! {
                 <div class="font-iosevka">
-                    <Dashboard data=data  ready_state=ready_state />
+                    <Dashboard data=data selected=selected role=role ready_state=ready_state />
                 </div>
             })
         } else {
             Either::Right(view! {
                 <ActionForm action=action>
-                    <h1>"Log In"</h1>
+                    <h1>
+                        "Log In"
+                        {move || selected.get().map(|name| format!(" to {name}"))}
+                    </h1>
+                    <input type="hidden" name="csrf" value=move || csrf.get()/>
+                    // Logs in to every instance that takes the credentials when none is selected.
+                    <input
+                        type="hidden"
+                        name="instance"
+                        prop:value=move || selected.get().unwrap_or_default()
+                    />
+                    // Whoever used this browser before isn't notified on it anymore.
+                    <input
+                        type="hidden"
+                        name="push_endpoint"
+                        prop:value=move || push_endpoint.get().unwrap_or_default()
+                    />
                     <label>
                         "User ID:"
                         <input
@@ -138,34 +301,85 @@ fn HomePage(
         }
     };
 
-    view! { <div>{res()}</div> }
+    let banner = move || {
+        let mut errors: Vec<_> = sync_errors.get().into_iter().collect();
+        errors.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let several = instances.with(|instances| instances.len() > 1);
+        errors
+            .into_iter()
+            .map(|(instance, err)| {
+                let variant = match err {
+                    SyncError::SessionExpired | SyncError::Unreachable(_) => AlertVariant::Warning,
+                    _ => AlertVariant::Destructive,
+                };
+                let message = if several {
+                    format!("{instance}: {err}")
+                } else {
+                    err.to_string()
+                };
+                view! { <Alert variant=variant>{message}</Alert> }
+            })
+            .collect_view()
+    };
+    // What's shown may be gone or done by now, e.g. when offline.
+    let stale_banner = move || {
+        let updated_at = updated_at.get()?;
+        stale.get().then(|| {
+            let at = updated_at.with_timezone(&Local).format("%Y-%m-%d %H:%M");
+            view! {
+                <Alert variant=AlertVariant::Warning>
+                    {format!("Not live: showing what bit-tower knew at {at}")}
+                </Alert>
+            }
+        })
+    };
+
+    view! { <div>{stale_banner}{banner}{res}</div> }
 }
 
 #[component]
 fn Dashboard(
-    data: ReadSignal<SyncState>,
-
+    data: ReadSignal<HashMap<String, SyncState>>,
+    selected: RwSignal<Option<String>>,
+    role: Signal<Role>,
     ready_state: Signal<ConnectionReadyState>,
 ) -> impl IntoView {
+    // The instances we're looking at.
+    let shown = move || {
+        let selected = selected.get();
+        let mut shown: Vec<_> = data()
+            .into_iter()
+            .filter(|(name, _)| selected.as_ref().map_or(true, |selected| selected == name))
+            .collect();
+        shown.sort_by(|(a, _), (b, _)| a.cmp(b));
+        shown.into_iter().map(|(_, state)| state)
+    };
     let torrents = Signal::derive(move || {
-        let v: Vec<_> = data().torrents.into_iter().map(|(_h, v)| v).collect();
-        v
+        shown()
+            .flat_map(|state| state.torrents.into_values())
+            .collect::<Vec<_>>()
     });
+    let server_states =
+        Signal::derive(move || shown().map(|state| state.server_state).collect::<Vec<_>>());
+    let show_instance =
+        Signal::derive(move || selected.get().is_none() && data.with(|data| data.len() > 1));
     view! {
         <View>
-            <TorrentList torrents=torrents/>
-            <MobileNavBar />
-            {move || view! { <StatusBar server_state=data().server_state ready_state=ready_state /> }}
+            <TorrentList torrents=torrents show_instance=show_instance/>
+            <MobileNavBar role=role />
+            <StatusBar server_states=server_states ready_state=ready_state />
         </View>
     }
 }
 
 #[component]
-fn MobileNavBar() -> impl IntoView {
+fn MobileNavBar(role: Signal<Role>) -> impl IntoView {
     view! {
         <View class="flex-row gap-0">
-            <Button><Icon icon=i::TbMagnet class=TextProp::from("w-4 w-4 text-grey-300") /></Button>
-            <Button><Icon icon=i::TbFileUpload class=TextProp::from("w-4 w-4 text-grey-300") /></Button>
+            <Show when=move || role.get().may_add()>
+                <Button><Icon icon=i::TbMagnet class=TextProp::from("w-4 w-4 text-grey-300") /></Button>
+                <Button><Icon icon=i::TbFileUpload class=TextProp::from("w-4 w-4 text-grey-300") /></Button>
+            </Show>
         </View>
     }
 }
@@ -0,0 +1,142 @@
+//! Browser push notifications, for hearing about torrents with the tab closed, e.g. on a phone.
+//!
+//! Browsers subscribe with the push service of their vendor, using the public half of a VAPID
+//! key that bit-tower generates once, and hand the subscription over. When the background
+//! checks see a torrent complete or fail, bit-tower encrypts a message for every subscription
+//! of the users who want to hear about it and POSTs it to the push service, which wakes up the
+//! service worker served from `/sw.js`, `src/server/pwa/sw.js`, to show it.
+
+use leptos::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "ssr")]
+mod service;
+
+/// Which events a user is notified of, on every browser they subscribed.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+pub struct PushPrefs {
+    /// A torrent finished downloading.
+    pub completed: bool,
+    /// A torrent ran into an error.
+    pub errored: bool,
+}
+
+impl Default for PushPrefs {
+    fn default() -> Self {
+        Self {
+            completed: true,
+            errored: true,
+        }
+    }
+}
+
+/// A browser's subscription, as `PushSubscription.toJSON()` has it.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct PushSubscription {
+    /// URL of the push service to POST messages to, unique to the browser.
+    pub endpoint: String,
+    pub keys: PushKeys,
+}
+
+/// What messages are encrypted for, both base64url encoded.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct PushKeys {
+    pub p256dh: String,
+    pub auth: String,
+}
+
+/// What the settings page needs to subscribe and show the preferences.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct PushStatus {
+    /// Public VAPID key, the uncompressed point browsers subscribe with.
+    pub public_key: Vec<u8>,
+    pub prefs: PushPrefs,
+    /// Endpoints of the user's subscriptions, to tell whether this browser is one of them.
+    pub endpoints: Vec<String>,
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use leptos::prelude::*;
+
+    use crate::app::auth::ssr::{auth, Session, SessionId};
+    use crate::app::auth::API_TOKEN_PREFIX;
+
+    pub use super::service::PushService;
+
+    pub fn use_push() -> Result<PushService, ServerFnError> {
+        use_context::<PushService>()
+            .ok_or_else(|| ServerFnError::ServerError("Push service missing.".into()))
+    }
+
+    /// The session of a user managing their notifications. API tokens have no browser to
+    /// notify.
+    pub fn push_user() -> Result<Session, ServerFnError> {
+        if use_context::<SessionId>().is_some_and(|SessionId(id)| id.starts_with(API_TOKEN_PREFIX))
+        {
+            return Err(ServerFnError::ServerError(
+                "API tokens can't subscribe to notifications".into(),
+            ));
+        }
+        auth()?
+            .filter(|session| !session.is_empty())
+            .ok_or_else(|| ServerFnError::ServerError("Log in first".into()))
+    }
+}
+
+#[server]
+pub async fn push_status() -> Result<PushStatus, ServerFnError> {
+    use self::ssr::*;
+
+    let session = push_user()?;
+    Ok(use_push()?.status(&session.username))
+}
+
+/// Notifies the user on the browser of `endpoint` from now on.
+#[server(SubscribePush, "/api")]
+pub async fn subscribe_push(
+    csrf: String,
+    endpoint: String,
+    p256dh: String,
+    auth: String,
+) -> Result<(), ServerFnError> {
+    use self::ssr::*;
+    use crate::app::auth::ssr::check_csrf;
+
+    check_csrf(&csrf)?;
+    let session = push_user()?;
+    let subscription = PushSubscription {
+        endpoint,
+        keys: PushKeys { p256dh, auth },
+    };
+    use_push()?
+        .subscribe(&session.username, subscription)
+        .map_err(|err| ServerFnError::ServerError(err.to_string()))
+}
+
+/// Stops notifying the browser of `endpoint`.
+#[server(UnsubscribePush, "/api")]
+pub async fn unsubscribe_push(csrf: String, endpoint: String) -> Result<(), ServerFnError> {
+    use self::ssr::*;
+    use crate::app::auth::ssr::check_csrf;
+
+    check_csrf(&csrf)?;
+    let session = push_user()?;
+    use_push()?.unsubscribe(&session.username, &endpoint);
+    Ok(())
+}
+
+#[server(SetPushPrefs, "/api")]
+pub async fn set_push_prefs(
+    csrf: String,
+    completed: bool,
+    errored: bool,
+) -> Result<(), ServerFnError> {
+    use self::ssr::*;
+    use crate::app::auth::ssr::check_csrf;
+
+    check_csrf(&csrf)?;
+    let session = push_user()?;
+    use_push()?.set_prefs(&session.username, PushPrefs { completed, errored });
+    Ok(())
+}
@@ -0,0 +1,355 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Context;
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use http::{header, StatusCode};
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use serde::{Deserialize, Serialize};
+use web_push::{
+    ContentEncoding, SubscriptionInfo, VapidSignatureBuilder, WebPushMessage, WebPushMessageBuilder,
+};
+
+use super::{PushPrefs, PushStatus, PushSubscription};
+use crate::app::auth::ssr::{write_private, Users};
+use crate::backend::model::Torrent;
+use crate::webhooks::{Event, EventDetector, EventKind, Thresholds};
+
+/// How long push services keep a message for a browser that is offline.
+const MESSAGE_TTL: u32 = 24 * 60 * 60;
+
+/// Keeps the VAPID key, the subscriptions and the preferences, and sends the notifications.
+/// Cheap to clone.
+#[derive(Clone, Debug)]
+pub struct PushService {
+    /// File to keep everything in across restarts, they are only kept in memory when `None`.
+    file: Option<Arc<PathBuf>>,
+    /// `sub` claim of the VAPID signatures, how push services can reach whoever runs bit-tower.
+    subject: Option<Arc<str>>,
+    users: Option<Users>,
+    /// Whether endpoints may be plain HTTP, for tests standing in for the push services.
+    insecure_endpoints: bool,
+    public_key: Arc<[u8]>,
+    client: reqwest::Client,
+    state: Arc<Mutex<PushState>>,
+    detector: Arc<Mutex<EventDetector>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PushState {
+    /// Private VAPID key, the base64url encoded scalar.
+    vapid_key: String,
+    subscriptions: Vec<StoredSubscription>,
+    prefs: HashMap<String, PushPrefs>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct StoredSubscription {
+    username: String,
+    subscription: PushSubscription,
+    created_at: DateTime<Utc>,
+}
+
+/// Why a notification wasn't sent.
+#[derive(Debug, thiserror::Error)]
+enum SendError {
+    /// The browser unsubscribed, or the push service forgot it.
+    #[error("The subscription is gone")]
+    Gone,
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl PushService {
+    /// Reads back what was kept in `file`, generating the VAPID key on the first start.
+    /// `users` tells who may hear about which instance, everybody about every one when `None`.
+    /// Only tests set `insecure_endpoints`, to take subscriptions to a plain HTTP endpoint.
+    pub fn new(
+        file: Option<PathBuf>,
+        subject: Option<String>,
+        users: Option<Users>,
+        insecure_endpoints: bool,
+    ) -> anyhow::Result<Self> {
+        let state = match &file {
+            Some(path) => match std::fs::read(path) {
+                Ok(bytes) => serde_json::from_slice(&bytes).with_context(|| {
+                    format!(
+                        "Could not read the push subscriptions in {}",
+                        path.display()
+                    )
+                })?,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => PushState::generate(),
+                Err(err) => {
+                    return Err(err).with_context(|| format!("Could not read {}", path.display()))
+                }
+            },
+            None => PushState::generate(),
+        };
+        let secret = BASE64_URL_SAFE_NO_PAD
+            .decode(&state.vapid_key)
+            .ok()
+            .and_then(|bytes| p256::SecretKey::from_slice(&bytes).ok())
+            .context("The VAPID key of the push subscriptions file is invalid")?;
+        let public_key = secret
+            .public_key()
+            .to_encoded_point(false)
+            .as_bytes()
+            .into();
+        let service = Self {
+            file: file.map(Arc::new),
+            subject: subject.map(Into::into),
+            users,
+            insecure_endpoints,
+            public_key,
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()?,
+            state: Arc::new(Mutex::new(state)),
+            detector: Arc::new(Mutex::new(EventDetector::new(Thresholds {
+                stalled_after: Duration::MAX,
+                ratio_target: None,
+            }))),
+        };
+        service.persist();
+        Ok(service)
+    }
+
+    /// The public VAPID key, as browsers subscribe with it.
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    pub fn status(&self, username: &str) -> PushStatus {
+        self.with_state(|state| PushStatus {
+            public_key: self.public_key.to_vec(),
+            prefs: state.prefs.get(username).copied().unwrap_or_default(),
+            endpoints: state
+                .subscriptions
+                .iter()
+                .filter(|stored| stored.username == username)
+                .map(|stored| stored.subscription.endpoint.clone())
+                .collect(),
+        })
+    }
+
+    /// Notifies `username` on the browser of `subscription`, instead of whoever it notified.
+    pub fn subscribe(&self, username: &str, subscription: PushSubscription) -> anyhow::Result<()> {
+        let endpoint = reqwest::Url::parse(&subscription.endpoint)
+            .context("The subscription's endpoint isn't a URL")?;
+        // Push services are always HTTPS, anything else is bit-tower being used to POST to
+        // wherever somebody likes.
+        anyhow::ensure!(
+            endpoint.scheme() == "https" || self.insecure_endpoints,
+            "The subscription's endpoint isn't HTTPS"
+        );
+        self.with_state(|state| {
+            state
+                .subscriptions
+                .retain(|stored| stored.subscription.endpoint != subscription.endpoint);
+            state.subscriptions.push(StoredSubscription {
+                username: username.to_owned(),
+                subscription,
+                created_at: Utc::now(),
+            });
+        });
+        self.persist();
+        Ok(())
+    }
+
+    pub fn unsubscribe(&self, username: &str, endpoint: &str) {
+        self.with_state(|state| {
+            state.subscriptions.retain(|stored| {
+                stored.username != username || stored.subscription.endpoint != endpoint
+            })
+        });
+        self.persist();
+    }
+
+    /// Stops notifying anybody but `username` on the browser of `endpoint`, as they logged in
+    /// on it.
+    pub fn logged_in(&self, endpoint: &str, username: &str) {
+        self.with_state(|state| {
+            state.subscriptions.retain(|stored| {
+                stored.subscription.endpoint != endpoint || stored.username == username
+            })
+        });
+        self.persist();
+    }
+
+    pub fn set_prefs(&self, username: &str, prefs: PushPrefs) {
+        self.with_state(|state| state.prefs.insert(username.to_owned(), prefs));
+        self.persist();
+    }
+
+    /// To be called after every sync of `instance`, with its torrents as of the sync. Sends
+    /// the notifications in the background.
+    pub fn synced(&self, instance: &str, torrents: &HashMap<String, Torrent>) {
+        // Diffed even without subscriptions, so that the first one after a while isn't
+        // notified of everything that happened in between.
+        let events = self
+            .detector
+            .lock()
+            .unwrap()
+            .diff(instance, torrents, Utc::now());
+        if self.with_state(|state| state.subscriptions.is_empty()) {
+            return;
+        }
+        for event in events {
+            let service = self.clone();
+            tokio::spawn(async move { service.notify(&event).await });
+        }
+    }
+
+    /// Notifies of `event` every subscription of the users who want to hear about it, returns
+    /// to how many it was sent. Subscriptions that are gone are dropped.
+    pub async fn notify(&self, event: &Event) -> usize {
+        let title = match event.event {
+            EventKind::Completed => format!("{} completed", event.torrent.name),
+            EventKind::Errored => format!("{} failed", event.torrent.name),
+            _ => return 0,
+        };
+        let payload = serde_json::json!({
+            "title": title,
+            "body": format!("On {}", event.instance),
+            // Replaces an older notification of the same torrent.
+            "tag": format!("{}/{}", event.instance, event.torrent.hash),
+            "event": event.event,
+            "instance": event.instance,
+            "hash": event.torrent.hash,
+        })
+        .to_string();
+        let targets: Vec<_> = self.with_state(|state| {
+            state
+                .subscriptions
+                .iter()
+                .filter(|stored| {
+                    let prefs = state
+                        .prefs
+                        .get(&stored.username)
+                        .copied()
+                        .unwrap_or_default();
+                    let wanted = match event.event {
+                        EventKind::Completed => prefs.completed,
+                        EventKind::Errored => prefs.errored,
+                        _ => false,
+                    };
+                    wanted && self.may_hear(&stored.username, &event.instance)
+                })
+                .map(|stored| stored.subscription.clone())
+                .collect()
+        });
+        let sends = targets
+            .iter()
+            .map(|subscription| self.send(subscription, payload.as_bytes()));
+        let mut sent = 0;
+        for (subscription, res) in targets.iter().zip(futures::future::join_all(sends).await) {
+            match res {
+                Ok(()) => sent += 1,
+                Err(SendError::Gone) => {
+                    tracing::info!(endpoint = %subscription.endpoint, "Push subscription gone");
+                    self.with_state(|state| {
+                        state
+                            .subscriptions
+                            .retain(|stored| stored.subscription.endpoint != subscription.endpoint)
+                    });
+                    self.persist();
+                }
+                Err(SendError::Other(err)) => {
+                    tracing::warn!(endpoint = %subscription.endpoint, error = %err, "Push failed")
+                }
+            }
+        }
+        sent
+    }
+
+    fn may_hear(&self, username: &str, instance: &str) -> bool {
+        match &self.users {
+            Some(users) => users
+                .user(username)
+                .is_some_and(|user| user.may_use(instance)),
+            None => true,
+        }
+    }
+
+    /// Encrypts `payload` for `subscription` and POSTs it to its push service.
+    async fn send(&self, subscription: &PushSubscription, payload: &[u8]) -> Result<(), SendError> {
+        let message = self.message(subscription, payload)?;
+        let mut request = self
+            .client
+            .post(message.endpoint.to_string())
+            .header("TTL", message.ttl.to_string());
+        if let Some(payload) = message.payload {
+            request = request
+                .header(header::CONTENT_ENCODING, payload.content_encoding.to_str())
+                .header(header::CONTENT_TYPE, "application/octet-stream");
+            for (name, value) in payload.crypto_headers {
+                request = request.header(name, value);
+            }
+            request = request.body(payload.content);
+        }
+        let response = request
+            .send()
+            .await
+            .context("Could not reach the push service")?;
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            StatusCode::NOT_FOUND | StatusCode::GONE => Err(SendError::Gone),
+            status => Err(anyhow::anyhow!("The push service answered {status}").into()),
+        }
+    }
+
+    fn message(
+        &self,
+        subscription: &PushSubscription,
+        payload: &[u8],
+    ) -> anyhow::Result<WebPushMessage> {
+        let info = SubscriptionInfo::new(
+            &subscription.endpoint,
+            &subscription.keys.p256dh,
+            &subscription.keys.auth,
+        );
+        let vapid_key = self.with_state(|state| state.vapid_key.clone());
+        let mut signature = VapidSignatureBuilder::from_base64(&vapid_key, &info)?;
+        if let Some(subject) = &self.subject {
+            signature.add_claim("sub", &**subject);
+        }
+        let mut builder = WebPushMessageBuilder::new(&info);
+        builder.set_ttl(MESSAGE_TTL);
+        builder.set_payload(ContentEncoding::Aes128Gcm, payload);
+        builder.set_vapid_signature(signature.build()?);
+        Ok(builder.build()?)
+    }
+
+    /// Writes everything to the file, if there is one.
+    fn persist(&self) {
+        let Some(path) = &self.file else {
+            return;
+        };
+        let json = self.with_state(|state| {
+            serde_json::to_vec(&*state).expect("push subscriptions always serialize")
+        });
+        if let Err(err) = write_private(path, &json) {
+            tracing::error!(error = ?err, "Could not save the push subscriptions");
+        }
+    }
+
+    fn with_state<T>(&self, f: impl FnOnce(&mut PushState) -> T) -> T {
+        f(&mut self.state.lock().unwrap())
+    }
+}
+
+impl PushState {
+    /// No subscriptions yet, with a new VAPID key.
+    fn generate() -> Self {
+        let secret = p256::SecretKey::random(&mut rand::rngs::OsRng);
+        Self {
+            vapid_key: BASE64_URL_SAFE_NO_PAD.encode(secret.to_bytes()),
+            subscriptions: Vec::new(),
+            prefs: HashMap::new(),
+        }
+    }
+}
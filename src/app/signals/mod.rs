@@ -1,2 +1,4 @@
+pub mod notifications;
+pub mod snapshot;
 pub mod syncstate;
 pub mod use_sync_maindata;
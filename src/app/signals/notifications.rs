@@ -0,0 +1,259 @@
+//! What the user is told about: the results of their actions, what happened to their torrents and
+//! the state of the connection. Every notice shows as a toast for a moment and stays in the
+//! history of the drawer, which is kept in local storage.
+
+use std::fmt::Display;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use human_bytes::human_bytes;
+use leptos::prelude::*;
+use leptos::server_fn::ServerFn;
+use serde::{Deserialize, Serialize};
+
+use crate::app::hooks::local_storage;
+use crate::app::hooks::use_websocket::core::ConnectionReadyState;
+use crate::app::signals::use_sync_maindata::SyncEvent;
+
+/// Where the history is kept in local storage.
+const STORAGE_KEY: &str = "bit-tower.notifications";
+/// How many notices the history keeps, the oldest go first.
+const HISTORY_LIMIT: usize = 100;
+/// How long a toast shows, errors stay until dismissed.
+const TOAST_DURATION: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Level {
+    Info,
+    Success,
+    Warning,
+    Error,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Notice {
+    pub id: u64,
+    pub level: Level,
+    pub title: String,
+    pub body: Option<String>,
+    pub at: DateTime<Utc>,
+    /// Whether the user saw it in the drawer.
+    pub read: bool,
+}
+
+/// The notices, shared through the context by [`provide_notifications`].
+#[derive(Clone, Copy)]
+pub struct Notifications {
+    /// Newest first.
+    history: RwSignal<Vec<Notice>>,
+    toasts: RwSignal<Vec<Notice>>,
+    next_id: StoredValue<u64>,
+}
+
+/// Provides the [`Notifications`] of the app, with the history of earlier visits.
+pub fn provide_notifications() -> Notifications {
+    let notifications = Notifications {
+        history: RwSignal::new(Vec::new()),
+        toasts: RwSignal::new(Vec::new()),
+        next_id: StoredValue::new(0),
+    };
+    let history = notifications.history;
+    // Loaded once hydrated, the server doesn't know what the browser has. Stored whenever it
+    // changes after that.
+    Effect::new(move |loaded: Option<()>| {
+        if loaded.is_none() {
+            if let Some(saved) = local_storage::load::<Vec<Notice>>(STORAGE_KEY) {
+                notifications.restore(saved);
+            }
+            history.track();
+        } else {
+            history.with(|history| local_storage::store(STORAGE_KEY, history));
+        }
+    });
+    provide_context(notifications);
+    notifications
+}
+
+pub fn use_notifications() -> Notifications {
+    expect_context()
+}
+
+impl Notifications {
+    /// Tells the user about something, unless they weren't done reading the same already.
+    pub fn notify(&self, level: Level, title: impl Into<String>, body: Option<String>) {
+        let title = title.into();
+        let repeated = self.history.with_untracked(|history| {
+            history
+                .iter()
+                .any(|notice| !notice.read && notice.title == title && notice.body == body)
+        });
+        if repeated {
+            return;
+        }
+        let notice = Notice {
+            id: self.next_id(),
+            level,
+            title,
+            body,
+            at: Utc::now(),
+            read: false,
+        };
+        let id = notice.id;
+        self.toasts.update(|toasts| toasts.push(notice.clone()));
+        self.history.update(|history| {
+            history.insert(0, notice);
+            history.truncate(HISTORY_LIMIT);
+        });
+        if level != Level::Error {
+            let this = *self;
+            set_timeout(move || this.dismiss(id), TOAST_DURATION);
+        }
+    }
+
+    /// Adds the history of earlier visits after the notices of this one. They get new ids, the
+    /// ones they were saved with may be taken by notices told before the history was loaded.
+    pub fn restore(&self, saved: Vec<Notice>) {
+        let saved: Vec<Notice> = saved
+            .into_iter()
+            .map(|notice| Notice {
+                id: self.next_id(),
+                ..notice
+            })
+            .collect();
+        self.history.update(|history| {
+            history.extend(saved);
+            history.truncate(HISTORY_LIMIT);
+        });
+    }
+
+    fn next_id(&self) -> u64 {
+        let id = self.next_id.get_value();
+        self.next_id.set_value(id + 1);
+        id
+    }
+
+    pub fn toasts(&self) -> Signal<Vec<Notice>> {
+        self.toasts.into()
+    }
+
+    pub fn history(&self) -> Signal<Vec<Notice>> {
+        self.history.into()
+    }
+
+    pub fn unread(&self) -> Signal<usize> {
+        let history = self.history;
+        Signal::derive(move || history.with(|history| history.iter().filter(|n| !n.read).count()))
+    }
+
+    /// Hides the toast of a notice, it stays in the history.
+    pub fn dismiss(&self, id: u64) {
+        if self
+            .toasts
+            .with_untracked(|toasts| toasts.iter().any(|notice| notice.id == id))
+        {
+            self.toasts
+                .update(|toasts| toasts.retain(|notice| notice.id != id));
+        }
+    }
+
+    pub fn mark_read(&self) {
+        if self.unread().get_untracked() > 0 {
+            self.history.update(|history| {
+                for notice in history {
+                    notice.read = true;
+                }
+            });
+        }
+    }
+
+    pub fn clear(&self) {
+        self.toasts.set(Vec::new());
+        self.history.set(Vec::new());
+    }
+
+    /// Tells whether `action` went through each time it completes: `done` when it did,
+    /// `failed` along with the error otherwise.
+    pub fn watch_action<S>(&self, action: ServerAction<S>, done: &'static str, failed: &'static str)
+    where
+        S: ServerFn + Clone + Send + Sync + 'static,
+        S::Output: Send + Sync + 'static,
+        S::Error: Display + Send + Sync + 'static,
+    {
+        let this = *self;
+        Effect::new(move |_| {
+            if action.version().get() == 0 {
+                return;
+            }
+            action.value().with_untracked(|value| match value {
+                Some(Ok(_)) => this.notify(Level::Success, done, None),
+                Some(Err(err)) => this.notify(Level::Error, failed, Some(err.to_string())),
+                None => {}
+            });
+        });
+    }
+
+    /// Tells when the connection to the server is lost, comes back or can't be made.
+    /// `closed_on_purpose` is set while we closed it ourselves, e.g. when logging out.
+    pub fn watch_connection(
+        &self,
+        ready_state: Signal<ConnectionReadyState>,
+        closed_on_purpose: StoredValue<bool>,
+    ) {
+        let this = *self;
+        let opened = StoredValue::new(false);
+        Effect::new(move |previous: Option<ConnectionReadyState>| {
+            let state = ready_state.get();
+            match (previous, state) {
+                (Some(previous), ConnectionReadyState::Open)
+                    if previous != ConnectionReadyState::Open =>
+                {
+                    if opened.get_value() {
+                        this.notify(Level::Info, "Connected again", None);
+                    }
+                    opened.set_value(true);
+                }
+                (Some(ConnectionReadyState::Open), ConnectionReadyState::Closed)
+                    if !closed_on_purpose.get_value() =>
+                {
+                    this.notify(
+                        Level::Warning,
+                        "Connection lost",
+                        Some("Updates stop until bit-tower reconnects.".to_owned()),
+                    );
+                }
+                (Some(ConnectionReadyState::Connecting), ConnectionReadyState::Closed)
+                    if !opened.get_value() && !closed_on_purpose.get_value() =>
+                {
+                    this.notify(
+                        Level::Error,
+                        "Can't connect to the server",
+                        Some("Retrying...".to_owned()),
+                    );
+                }
+                _ => {}
+            }
+            state
+        });
+    }
+
+    /// Tells about a torrent or instance the user should know about.
+    pub fn sync_event(&self, event: &SyncEvent) {
+        match event {
+            SyncEvent::Finished { instance, name, .. } => self.notify(
+                Level::Success,
+                "Download finished",
+                Some(format!("{name} on {instance}")),
+            ),
+            SyncEvent::TrackerError { instance, name, .. } => self.notify(
+                Level::Warning,
+                "Tracker error",
+                Some(format!("No tracker of {name} on {instance} works anymore")),
+            ),
+            SyncEvent::LowDiskSpace { instance, free } => self.notify(
+                Level::Warning,
+                "Disk almost full",
+                Some(format!("{instance} has {} left", human_bytes(*free as f64))),
+            ),
+        }
+    }
+}
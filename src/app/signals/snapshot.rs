@@ -0,0 +1,51 @@
+//! The last state the server sent, kept in local storage so that there is something to show
+//! while offline.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::app::hooks::local_storage;
+use crate::app::signals::syncstate::SyncState;
+use crate::backend::model::SyncUpdate;
+
+const STORAGE_KEY: &str = "bit-tower.snapshot";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Who was logged in, the snapshot isn't shown to anyone else.
+    pub username: String,
+    /// When the state was last updated.
+    pub updated_at: DateTime<Utc>,
+    /// Full updates that make the state of each instance again, by instance name.
+    pub instances: HashMap<String, SyncUpdate>,
+}
+
+/// The snapshot of `username`. That of anybody else is forgotten, e.g. when the browser wasn't
+/// logged out before somebody else logged in.
+pub fn load(username: &str) -> Option<Snapshot> {
+    let snapshot = local_storage::load::<Snapshot>(STORAGE_KEY)?;
+    if snapshot.username != username {
+        clear();
+        return None;
+    }
+    Some(snapshot)
+}
+
+pub fn save(username: &str, data: &HashMap<String, SyncState>, updated_at: DateTime<Utc>) {
+    let snapshot = Snapshot {
+        username: username.to_owned(),
+        updated_at,
+        instances: data
+            .iter()
+            .map(|(instance, state)| (instance.clone(), state.snapshot()))
+            .collect(),
+    };
+    local_storage::store(STORAGE_KEY, &snapshot);
+}
+
+/// Forgets the snapshot, e.g. when the user logs out.
+pub fn clear() {
+    local_storage::remove(STORAGE_KEY);
+}
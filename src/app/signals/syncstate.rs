@@ -1,10 +1,7 @@
 use std::collections::HashMap;
+use std::time::Duration;
 
-use crate::qbittorrent::proto::{
-    sync::SyncMainDataFull,
-    torrents::{TorrentInfo, TorrentInfoPartial},
-    transfer::{ConnectionStatus, ServerStateFull, ServerStatePartial},
-};
+use crate::backend::model::{self, SyncUpdate, TorrentState, TransferInfo};
 use leptos::prelude::*;
 
 #[derive(Debug, Clone, Default)]
@@ -12,161 +9,176 @@ pub struct SyncState {
     pub torrents: HashMap<String, Torrent>,
     pub server_state: ServerState,
 }
-impl From<SyncMainDataFull> for SyncState {
-    fn from(value: SyncMainDataFull) -> Self {
+impl SyncState {
+    /// The state of `instance` after a full update, every torrent tagged with the instance.
+    pub fn new(instance: &str, update: &SyncUpdate) -> Self {
         Self {
-            torrents: value
+            torrents: update
                 .torrents
-                .into_iter()
-                .map(|(hash, torrent)| (hash, Torrent::from(torrent)))
+                .iter()
+                .map(|torrent| {
+                    (
+                        torrent.hash.clone(),
+                        Torrent::new(instance, torrent.clone()),
+                    )
+                })
                 .collect(),
-            server_state: ServerState::from(value.server_state),
+            server_state: ServerState::from(update.transfer.clone().unwrap_or_default()),
         }
     }
-}
-impl From<&SyncMainDataFull> for SyncState {
-    fn from(value: &SyncMainDataFull) -> Self {
-        Self {
-            torrents: value
-                .torrents
-                .clone()
-                .into_iter()
-                .map(|(hash, torrent)| (hash, Torrent::from(torrent)))
-                .collect(),
-            server_state: ServerState::from(value.server_state.clone()),
+
+    /// A full update that makes the same state again, with what the signals have.
+    pub fn snapshot(&self) -> SyncUpdate {
+        SyncUpdate {
+            full: true,
+            torrents: self.torrents.values().map(Torrent::snapshot).collect(),
+            removed: Vec::new(),
+            transfer: Some(self.server_state.snapshot()),
         }
     }
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct Torrent {
-    pub infohash_v1: String,
+    /// Name of the instance the torrent is on.
+    pub instance: String,
+    pub hash: String,
     pub name: ArcRwSignal<String>,
+    pub state: ArcRwSignal<TorrentState>,
     pub progress: ArcRwSignal<f64>,
-    pub downloaded: ArcRwSignal<f64>,
-    pub uploaded: ArcRwSignal<f64>,
-    pub dlspeed: ArcRwSignal<f64>,
-    pub upspeed: ArcRwSignal<f64>,
-    pub num_seeds: ArcRwSignal<f64>,
-    pub num_leechs: ArcRwSignal<f64>,
-    pub size: ArcRwSignal<f64>,
-    pub total_size: ArcRwSignal<f64>,
+    pub downloaded: ArcRwSignal<u64>,
+    pub uploaded: ArcRwSignal<u64>,
+    pub dl_speed: ArcRwSignal<u64>,
+    pub up_speed: ArcRwSignal<u64>,
+    pub seeds: ArcRwSignal<u64>,
+    pub leechs: ArcRwSignal<u64>,
+    pub size: ArcRwSignal<u64>,
+    pub total_size: ArcRwSignal<u64>,
     pub availability: ArcRwSignal<f64>,
-    pub eta: ArcRwSignal<f64>,
+    /// `None` when the torrent isn't going to complete.
+    pub eta: ArcRwSignal<Option<Duration>>,
+    /// No tracker of the torrent works.
+    pub tracker_error: ArcRwSignal<bool>,
 }
 
-impl From<TorrentInfo> for Torrent {
-    fn from(value: TorrentInfo) -> Self {
+impl Torrent {
+    pub fn new(instance: &str, value: model::Torrent) -> Self {
         Torrent {
-            infohash_v1: value.infohash_v1,
+            instance: instance.to_owned(),
+            hash: value.hash,
             name: ArcRwSignal::new(value.name),
+            state: ArcRwSignal::new(value.state),
             progress: ArcRwSignal::new(value.progress),
             downloaded: ArcRwSignal::new(value.downloaded),
             uploaded: ArcRwSignal::new(value.uploaded),
-            dlspeed: ArcRwSignal::new(value.dlspeed),
-            upspeed: ArcRwSignal::new(value.upspeed),
-            num_seeds: ArcRwSignal::new(value.num_seeds),
-            num_leechs: ArcRwSignal::new(value.num_leechs),
+            dl_speed: ArcRwSignal::new(value.dl_speed),
+            up_speed: ArcRwSignal::new(value.up_speed),
+            seeds: ArcRwSignal::new(value.seeds),
+            leechs: ArcRwSignal::new(value.leechs),
             size: ArcRwSignal::new(value.size),
             total_size: ArcRwSignal::new(value.total_size),
             availability: ArcRwSignal::new(value.availability),
             eta: ArcRwSignal::new(value.eta),
+            tracker_error: ArcRwSignal::new(value.tracker_error),
         }
     }
-}
-impl Torrent {
-    pub fn apply_partial(&self, partial: TorrentInfoPartial) {
-        if let Some(new_value) = partial.name {
-            self.name.set(new_value);
-        }
-        if let Some(new_value) = partial.progress {
-            self.progress.set(new_value);
-        }
-        if let Some(new_value) = partial.downloaded {
-            self.downloaded.set(new_value);
-        }
-        if let Some(new_value) = partial.uploaded {
-            self.uploaded.set(new_value);
-        }
-        if let Some(new_value) = partial.dlspeed {
-            self.dlspeed.set(new_value);
-        }
-        if let Some(new_value) = partial.upspeed {
-            self.upspeed.set(new_value);
-        }
-        if let Some(new_value) = partial.num_seeds {
-            self.num_seeds.set(new_value);
-        }
-        if let Some(new_value) = partial.num_leechs {
-            self.num_leechs.set(new_value);
-        }
-        if let Some(new_value) = partial.size {
-            self.size.set(new_value);
-        }
-        if let Some(new_value) = partial.total_size {
-            self.total_size.set(new_value);
-        }
-        if let Some(new_value) = partial.availability {
-            self.availability.set(new_value);
-        }
-        if let Some(new_value) = partial.eta {
-            self.eta.set(new_value);
+
+    /// Takes on the values of a changed torrent, only notifying about the ones that changed.
+    pub fn update(&self, value: model::Torrent) {
+        set_if_changed(&self.name, value.name);
+        set_if_changed(&self.state, value.state);
+        set_if_changed(&self.progress, value.progress);
+        set_if_changed(&self.downloaded, value.downloaded);
+        set_if_changed(&self.uploaded, value.uploaded);
+        set_if_changed(&self.dl_speed, value.dl_speed);
+        set_if_changed(&self.up_speed, value.up_speed);
+        set_if_changed(&self.seeds, value.seeds);
+        set_if_changed(&self.leechs, value.leechs);
+        set_if_changed(&self.size, value.size);
+        set_if_changed(&self.total_size, value.total_size);
+        set_if_changed(&self.availability, value.availability);
+        set_if_changed(&self.eta, value.eta);
+        set_if_changed(&self.tracker_error, value.tracker_error);
+    }
+
+    pub fn snapshot(&self) -> model::Torrent {
+        model::Torrent {
+            hash: self.hash.clone(),
+            name: self.name.get_untracked(),
+            state: self.state.get_untracked(),
+            progress: self.progress.get_untracked(),
+            downloaded: self.downloaded.get_untracked(),
+            uploaded: self.uploaded.get_untracked(),
+            dl_speed: self.dl_speed.get_untracked(),
+            up_speed: self.up_speed.get_untracked(),
+            seeds: self.seeds.get_untracked(),
+            leechs: self.leechs.get_untracked(),
+            size: self.size.get_untracked(),
+            total_size: self.total_size.get_untracked(),
+            availability: self.availability.get_untracked(),
+            eta: self.eta.get_untracked(),
+            tracker_error: self.tracker_error.get_untracked(),
+            ..Default::default()
         }
     }
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct ServerState {
-    pub dl_info_speed: ArcRwSignal<f64>, // Global download rate (bytes/s)
-    pub dl_info_data: ArcRwSignal<f64>,  // Data downloaded this session (bytes)
-    pub up_info_speed: ArcRwSignal<f64>, // Global upload rate (bytes/s)
-    pub up_info_data: ArcRwSignal<f64>,  // Data uploaded this session (bytes)
-    pub dl_rate_limit: ArcRwSignal<f64>, // Download rate limit (bytes/s)
-    pub up_rate_limit: ArcRwSignal<f64>, // Upload rate limit (bytes/s)
-    pub dht_nodes: ArcRwSignal<f64>,     // DHT nodes connected to
-    pub connection_status: ArcRwSignal<ConnectionStatus>, // Connection status
+    pub dl_speed: ArcRwSignal<u64>,   // Global download rate (bytes/s)
+    pub up_speed: ArcRwSignal<u64>,   // Global upload rate (bytes/s)
+    pub downloaded: ArcRwSignal<u64>, // Data downloaded this session (bytes)
+    pub uploaded: ArcRwSignal<u64>,   // Data uploaded this session (bytes)
+    pub dl_limit: ArcRwSignal<Option<u64>>, // Download rate limit (bytes/s)
+    pub up_limit: ArcRwSignal<Option<u64>>, // Upload rate limit (bytes/s)
+    pub dht_nodes: ArcRwSignal<u64>,  // DHT nodes connected to
+    pub free_space: ArcRwSignal<Option<u64>>, // Free space in the download directory (bytes)
 }
 
-impl From<ServerStateFull> for ServerState {
-    fn from(value: ServerStateFull) -> Self {
+impl From<TransferInfo> for ServerState {
+    fn from(value: TransferInfo) -> Self {
         ServerState {
-            dl_info_speed: ArcRwSignal::new(value.dl_info_speed),
-            dl_info_data: ArcRwSignal::new(value.dl_info_data),
-            up_info_speed: ArcRwSignal::new(value.up_info_speed),
-            up_info_data: ArcRwSignal::new(value.up_info_data),
-            dl_rate_limit: ArcRwSignal::new(value.dl_rate_limit),
-            up_rate_limit: ArcRwSignal::new(value.up_rate_limit),
+            dl_speed: ArcRwSignal::new(value.dl_speed),
+            up_speed: ArcRwSignal::new(value.up_speed),
+            downloaded: ArcRwSignal::new(value.downloaded),
+            uploaded: ArcRwSignal::new(value.uploaded),
+            dl_limit: ArcRwSignal::new(value.dl_limit),
+            up_limit: ArcRwSignal::new(value.up_limit),
             dht_nodes: ArcRwSignal::new(value.dht_nodes),
-            connection_status: ArcRwSignal::new(value.connection_status),
+            free_space: ArcRwSignal::new(value.free_space),
         }
     }
 }
 
 impl ServerState {
-    pub fn apply_partial(&self, partial: ServerStatePartial) {
-        if let Some(cs) = partial.connection_status {
-            self.connection_status.set(cs);
-        }
-        if let Some(v) = partial.dl_info_speed {
-            self.dl_info_speed.set(v);
-        }
-        if let Some(v) = partial.up_info_speed {
-            self.up_info_speed.set(v);
-        }
-        if let Some(v) = partial.dl_info_data {
-            self.dl_info_data.set(v);
-        }
-        if let Some(v) = partial.up_info_data {
-            self.up_info_data.set(v);
-        }
-        if let Some(v) = partial.dl_rate_limit {
-            self.dl_rate_limit.set(v);
-        }
-        if let Some(v) = partial.up_rate_limit {
-            self.up_rate_limit.set(v);
-        }
-        if let Some(v) = partial.dht_nodes {
-            self.dht_nodes.set(v);
+    pub fn update(&self, value: TransferInfo) {
+        set_if_changed(&self.dl_speed, value.dl_speed);
+        set_if_changed(&self.up_speed, value.up_speed);
+        set_if_changed(&self.downloaded, value.downloaded);
+        set_if_changed(&self.uploaded, value.uploaded);
+        set_if_changed(&self.dl_limit, value.dl_limit);
+        set_if_changed(&self.up_limit, value.up_limit);
+        set_if_changed(&self.dht_nodes, value.dht_nodes);
+        set_if_changed(&self.free_space, value.free_space);
+    }
+
+    pub fn snapshot(&self) -> TransferInfo {
+        TransferInfo {
+            dl_speed: self.dl_speed.get_untracked(),
+            up_speed: self.up_speed.get_untracked(),
+            downloaded: self.downloaded.get_untracked(),
+            uploaded: self.uploaded.get_untracked(),
+            dl_limit: self.dl_limit.get_untracked(),
+            up_limit: self.up_limit.get_untracked(),
+            dht_nodes: self.dht_nodes.get_untracked(),
+            free_space: self.free_space.get_untracked(),
+            ..Default::default()
         }
     }
 }
+
+fn set_if_changed<T: PartialEq + Send + Sync + 'static>(signal: &ArcRwSignal<T>, value: T) {
+    if signal.with_untracked(|current| *current != value) {
+        signal.set(value);
+    }
+}
@@ -1,17 +1,77 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::app::hooks::use_event_source::{
+    use_event_source_with_options, UseEventSourceOptions, UseEventSourceReturn,
+};
+use crate::app::hooks::use_page_visibility::{use_page_visibility, UsePageVisibilityReturn};
 use crate::app::hooks::use_websocket::{
-    core::ConnectionReadyState, use_websocket, use_websocket_with_options, UseWebSocketError,
-    UseWebSocketOptions, UseWebSocketReturn,
+    core::ConnectionReadyState, use_websocket, use_websocket_with_options, ReconnectLimit,
+    UseWebSocketError, UseWebSocketOptions, UseWebSocketReturn,
+};
+use crate::backend::model::{self, Peer, SyncUpdate, TorrentFile, TorrentState, Tracker};
+use crate::protocol::{
+    ClientMessage, ServerMessage, Subscription, SyncError, TorrentAction, PROTOCOL_VERSION,
 };
-use crate::qbittorrent::proto::sync::MainData;
+use chrono::{DateTime, Utc};
 use leptos::prelude::*;
 
 use codee::binary::MsgpackSerdeCodec;
+use codee::string::JsonSerdeCodec;
+use default_struct_builder::DefaultBuilder;
 
 use crate::app::{
     components::{status_bar::StatusBar, torrents::TorrentList},
-    signals::syncstate::SyncState,
+    signals::snapshot,
+    signals::syncstate::{SyncState, Torrent},
 };
 
+/// How many times the websocket tries to reconnect before we fall back to server-sent events.
+const WS_RECONNECT_LIMIT: u64 = 3;
+
+/// Name of the server-sent event carrying a JSON encoded `ServerMessage`.
+const SYNC_EVENT: &str = "sync";
+
+/// How often the state is saved for offline use, at most.
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Free space below which an instance is reported to run out of it, 5 GiB.
+const LOW_DISK_SPACE: u64 = 5 * 1024 * 1024 * 1024;
+
+/// Something the user should know about, found by comparing an update with what we had.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SyncEvent {
+    /// The torrent has everything selected for download.
+    Finished { instance: String, name: String },
+    /// The torrent had a working tracker and now none works, while it was active.
+    TrackerError { instance: String, name: String },
+    /// The free space in the download directory of the instance went below the threshold.
+    LowDiskSpace { instance: String, free: u64 },
+}
+
+/// Options for [`use_sync_maindata`].
+#[derive(DefaultBuilder)]
+pub struct UseSyncMaindataOptions {
+    /// Called with every [`SyncEvent`] as updates arrive.
+    on_event: Arc<dyn Fn(&SyncEvent) + Send + Sync>,
+    /// Free space below which [`SyncEvent::LowDiskSpace`] is reported (bytes). Defaults to 5 GiB.
+    low_disk_space: u64,
+    /// Who is logged in. The state is only saved for offline use while it's known, and only
+    /// that user's snapshot is shown.
+    username: Signal<Option<String>>,
+}
+
+impl Default for UseSyncMaindataOptions {
+    fn default() -> Self {
+        Self {
+            on_event: Arc::new(|_| {}),
+            low_disk_space: LOW_DISK_SPACE,
+            username: Signal::derive(|| None),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct UseSyncMaindataReturn<OpenFn, CloseFn>
 where
@@ -20,58 +80,499 @@ where
 {
     pub ready_state: Signal<ConnectionReadyState>,
     pub connected: Signal<bool>,
-    pub data: ReadSignal<SyncState>,
+    /// The state of every instance we got an update for, by instance name. Until the server
+    /// sends it, that of the snapshot saved on an earlier visit.
+    pub data: ReadSignal<HashMap<String, SyncState>>,
+    /// When `data` was last updated by the server, possibly on an earlier visit.
+    pub updated_at: ReadSignal<Option<DateTime<Utc>>>,
+    /// `data` may be out of date: it's from an earlier visit or we're not connected.
+    pub stale: Signal<bool>,
+    /// Peers of the torrents subscribed to with [`Subscription::TorrentPeers`], by instance and
+    /// torrent hash.
+    pub peers: ReadSignal<HashMap<(String, String), Vec<Peer>>>,
+    /// Files of the torrents subscribed to with [`Subscription::TorrentFiles`], by instance and
+    /// torrent hash.
+    pub files: ReadSignal<HashMap<(String, String), Vec<TorrentFile>>>,
+    /// Trackers of the torrents subscribed to with [`Subscription::TorrentTrackers`], by
+    /// instance and torrent hash.
+    pub trackers: ReadSignal<HashMap<(String, String), Vec<Tracker>>>,
+    /// Request ID and outcome of the last action acknowledged by the server.
+    pub last_ack: ReadSignal<Option<(u64, Result<(), String>)>>,
+    /// Why the server couldn't reach an instance, by instance name. Cleared by the next
+    /// successful update of that instance.
+    ///
+    /// Once every instance reported [`SyncError::SessionExpired`] the connection is closed for
+    /// good, call `open` once the user logged in again.
+    pub errors: ReadSignal<HashMap<String, SyncError>>,
+    pub commands: SyncCommands,
     pub open: OpenFn,
     pub close: CloseFn,
 }
 
+/// Sends commands to the server over the sync websocket.
+///
+/// Commands are dropped while the websocket isn't open, which includes the time we are reading
+/// updates from server-sent events instead. Subscriptions are remembered and sent again whenever
+/// the websocket (re)connects.
+#[derive(Clone)]
+pub struct SyncCommands {
+    send: Arc<dyn Fn(&ClientMessage) + Send + Sync>,
+    next_request_id: StoredValue<u64>,
+    subscriptions: StoredValue<HashSet<Subscription>>,
+}
+
+impl SyncCommands {
+    /// Asks the server to run `action` on `instance`, returns the request ID its
+    /// [`ServerMessage::Ack`] will carry.
+    pub fn execute(&self, instance: &str, action: TorrentAction) -> u64 {
+        let request_id = self.next_request_id.get_value();
+        self.next_request_id.set_value(request_id + 1);
+        (self.send)(&ClientMessage::Action {
+            request_id,
+            instance: instance.to_owned(),
+            action,
+        });
+        request_id
+    }
+
+    pub fn subscribe(&self, subscription: Subscription) {
+        self.subscriptions.update_value(|subscriptions| {
+            subscriptions.insert(subscription.clone());
+        });
+        (self.send)(&ClientMessage::Subscribe(subscription));
+    }
+
+    pub fn unsubscribe(&self, subscription: Subscription) {
+        self.subscriptions.update_value(|subscriptions| {
+            subscriptions.remove(&subscription);
+        });
+        (self.send)(&ClientMessage::Unsubscribe(subscription));
+    }
+
+    pub fn set_poll_interval(&self, interval: Duration) {
+        (self.send)(&ClientMessage::SetPollInterval {
+            interval_ms: interval.as_millis() as u64,
+        });
+    }
+
+    /// Lets the server know whether anyone is looking, so it can poll less often when not.
+    pub fn set_visibility(&self, visible: bool, focused: bool) {
+        (self.send)(&ClientMessage::Visibility { visible, focused });
+    }
+
+    /// Introduces ourselves to the server, to be called whenever the websocket opens.
+    fn hello(&self) {
+        (self.send)(&ClientMessage::Hello {
+            version: PROTOCOL_VERSION,
+        });
+        for subscription in self.subscriptions.get_value() {
+            (self.send)(&ClientMessage::Subscribe(subscription));
+        }
+    }
+}
+
+/// Keeps a [`SyncState`] per instance in sync with the server, over a websocket at
+/// `ws_url`.
+///
+/// If the websocket never manages to open, e.g. because a proxy in between doesn't support
+/// websockets, the updates are read from the server-sent events at `sse_url` instead.
 pub fn use_sync_maindata(
-    url: &str,
+    ws_url: &str,
+    sse_url: &str,
+    options: UseSyncMaindataOptions,
 ) -> UseSyncMaindataReturn<impl Fn() + Clone + 'static, impl Fn() + Clone + 'static> {
-    let (data, set_data) = signal(SyncState::default());
+    let UseSyncMaindataOptions {
+        on_event,
+        low_disk_space,
+        username,
+    } = options;
+    let (data, set_data) = signal(HashMap::<String, SyncState>::new());
+    let (peers, set_peers) = signal(HashMap::<(String, String), Vec<Peer>>::new());
+    let (files, set_files) = signal(HashMap::<(String, String), Vec<TorrentFile>>::new());
+    let (trackers, set_trackers) = signal(HashMap::<(String, String), Vec<Tracker>>::new());
+    let (last_ack, set_last_ack) = signal(None);
+    let (errors, set_errors) = signal(HashMap::<String, SyncError>::new());
+    let (updated_at, set_updated_at) = signal(None::<DateTime<Utc>>);
+    // Instances whose state is still that of the snapshot.
+    let (from_snapshot, set_from_snapshot) = signal(HashSet::<String>::new());
+    let saved_at = StoredValue::new(None::<DateTime<Utc>>);
+    let (use_sse, set_use_sse) = signal(false);
+    let ws_opened = StoredValue::new(false);
+    let ws_failures = StoredValue::new(0_u64);
+
+    let UseEventSourceReturn {
+        ready_state: sse_ready_state,
+        message: sse_message,
+        open: open_sse,
+        close: close_sse,
+    } = use_event_source_with_options::<ServerMessage, JsonSerdeCodec>(
+        sse_url,
+        UseEventSourceOptions::default()
+            .event(SYNC_EVENT)
+            .immediate(false),
+    );
 
-    let opts = UseWebSocketOptions::default();
+    let opts = UseWebSocketOptions::default()
+        .reconnect_limit(ReconnectLimit::Limited(WS_RECONNECT_LIMIT))
+        .on_open(Arc::new(move |_| ws_opened.set_value(true)))
+        .on_close(Arc::new({
+            let open_sse = open_sse.clone();
+            move |_| {
+                if ws_opened.get_value() || use_sse.get_untracked() {
+                    return;
+                }
+                ws_failures.update_value(|failures| *failures += 1);
+                // The first attempt and every reconnect attempt failed to open.
+                if ws_failures.get_value() > WS_RECONNECT_LIMIT {
+                    tracing::warn!(
+                        "Websocket could not be opened, falling back to server-sent events"
+                    );
+                    set_use_sse.set(true);
+                    open_sse();
+                }
+            }
+        }));
 
     let UseWebSocketReturn {
-        ready_state,
+        ready_state: ws_ready_state,
         message,
         open,
         close,
-        ..
-    } = use_websocket_with_options::<MainData, MsgpackSerdeCodec>(url, opts);
+        send,
+    } = use_websocket_with_options::<ClientMessage, ServerMessage, MsgpackSerdeCodec>(ws_url, opts);
+
+    let commands = SyncCommands {
+        send: Arc::new(send),
+        next_request_id: StoredValue::new(0),
+        subscriptions: StoredValue::new(HashSet::new()),
+    };
 
+    let ready_state = Signal::derive(move || {
+        if use_sse.get() {
+            sse_ready_state.get()
+        } else {
+            ws_ready_state.get()
+        }
+    });
     let connected = Signal::derive(move || ready_state.get() == ConnectionReadyState::Open);
+    let stale = Signal::derive(move || {
+        !data.with(HashMap::is_empty)
+            && (!connected.get() || from_snapshot.with(|instances| !instances.is_empty()))
+    });
 
+    // Loaded once hydrated, the server doesn't know what the browser has.
     Effect::new(move |_| {
-        message.with(|message| {
-            if let Some(m) = message {
-                match m {
-                    MainData::Full(full_data) => set_data.set(SyncState::from(full_data)),
-                    MainData::Partial(partial_data) => {
-                        data.with(|data| {
-                            let torrents = partial_data.clone().torrents;
-                            if let Some(torrents) = torrents {
-                                for (hash, partial) in torrents {
-                                    data.torrents
-                                        .get(&hash)
-                                        .map(|torrent| torrent.apply_partial(partial));
-                                }
-                            }
-                            if let Some(server_state) = partial_data.clone().server_state {
-                                data.server_state.apply_partial(server_state);
-                            }
-                        });
-                    }
+        let Some(username) = username.get() else {
+            return;
+        };
+        let Some(snapshot) = snapshot::load(&username) else {
+            return;
+        };
+        if !data.with_untracked(HashMap::is_empty) {
+            return;
+        }
+        set_data.set(
+            snapshot
+                .instances
+                .iter()
+                .map(|(instance, update)| (instance.clone(), SyncState::new(instance, update)))
+                .collect(),
+        );
+        set_from_snapshot.set(snapshot.instances.into_keys().collect());
+        set_updated_at.set(Some(snapshot.updated_at));
+    });
+
+    Effect::new({
+        let commands = commands.clone();
+        move |_| {
+            if ws_ready_state.get() == ConnectionReadyState::Open {
+                commands.hello();
+            }
+        }
+    });
+
+    // Report visibility changes, and the current visibility whenever the websocket (re)opens.
+    let UsePageVisibilityReturn { visible, focused } = use_page_visibility();
+    Effect::new({
+        let commands = commands.clone();
+        move |_| {
+            let (visible, focused) = (visible.get(), focused.get());
+            if ws_ready_state.get() == ConnectionReadyState::Open {
+                commands.set_visibility(visible, focused);
+            }
+        }
+    });
+    let save_snapshot = move |at| {
+        if let Some(username) = username.get_untracked() {
+            data.with_untracked(|data| snapshot::save(&username, data, at));
+        }
+    };
+    // Hidden pages may be closed without another chance, so what's newer than the snapshot is
+    // saved. Not once closed, which logging out does, the snapshot is forgotten then.
+    Effect::new(move |_| {
+        if visible.get() || !connected.get_untracked() {
+            return;
+        }
+        if let Some(at) = updated_at.get_untracked() {
+            if saved_at.get_value().is_some_and(|saved_at| saved_at < at) {
+                saved_at.set_value(Some(at));
+                save_snapshot(at);
+            }
+        }
+    });
+
+    let close = move || {
+        close();
+        close_sse();
+    };
+
+    // Updates arrive the same way over both transports.
+    let handle_message = {
+        let close = close.clone();
+        move |message: &ServerMessage| match message {
+            ServerMessage::Hello { version } => {
+                if *version != PROTOCOL_VERSION {
+                    tracing::error!(
+                        "Server speaks protocol version {version}, we speak {PROTOCOL_VERSION}"
+                    );
                 }
             }
-        });
+            ServerMessage::Sync { instance, update } => {
+                if errors.with_untracked(|errors| errors.contains_key(instance)) {
+                    set_errors.update(|errors| {
+                        errors.remove(instance);
+                    });
+                }
+                if update.full
+                    && from_snapshot.with_untracked(|instances| instances.contains(instance))
+                {
+                    set_from_snapshot.update(|instances| {
+                        instances.remove(instance);
+                    });
+                }
+                for event in apply_sync(data, set_data, instance, update, low_disk_space) {
+                    on_event(&event);
+                }
+                let now = Utc::now();
+                set_updated_at.set(Some(now));
+                let due = saved_at.get_value().map_or(true, |at| {
+                    (now - at)
+                        .to_std()
+                        .map_or(true, |elapsed| elapsed >= SNAPSHOT_INTERVAL)
+                });
+                if due {
+                    saved_at.set_value(Some(now));
+                    save_snapshot(now);
+                }
+            }
+            ServerMessage::TorrentPeers {
+                instance,
+                hash,
+                peers,
+            } => set_peers.update(|all| {
+                all.insert((instance.clone(), hash.clone()), peers.clone());
+            }),
+            ServerMessage::TorrentFiles {
+                instance,
+                hash,
+                files,
+            } => set_files.update(|all| {
+                all.insert((instance.clone(), hash.clone()), files.clone());
+            }),
+            ServerMessage::TorrentTrackers {
+                instance,
+                hash,
+                trackers,
+            } => set_trackers.update(|all| {
+                all.insert((instance.clone(), hash.clone()), trackers.clone());
+            }),
+            ServerMessage::Ack { request_id, result } => {
+                set_last_ack.set(Some((*request_id, result.clone())));
+            }
+            ServerMessage::SyncError { instance, error } => {
+                tracing::warn!("Server could not sync with {instance}: {error:?}");
+                set_errors.update(|errors| {
+                    errors.insert(instance.clone(), error.clone());
+                });
+                // Reconnecting won't bring the sessions back, the user has to log in again.
+                let all_expired = errors.with_untracked(|errors| {
+                    data.with_untracked(|data| data.keys().all(|i| errors.contains_key(i)))
+                        && errors.values().all(|err| *err == SyncError::SessionExpired)
+                });
+                if all_expired {
+                    close();
+                }
+            }
+            ServerMessage::Error { message } => {
+                tracing::error!("Server rejected a message: {message}");
+            }
+        }
+    };
+
+    Effect::new({
+        let handle_message = handle_message.clone();
+        move |_| {
+            message.with(|message| {
+                if let Some(message) = message {
+                    handle_message(message);
+                }
+            })
+        }
+    });
+
+    Effect::new(move |_| {
+        sse_message.with(|message| {
+            if let Some(message) = message {
+                handle_message(message);
+            }
+        })
     });
 
+    let open = move || {
+        set_errors.set(HashMap::new());
+        if use_sse.get_untracked() {
+            open_sse();
+        } else {
+            open();
+        }
+    };
+
     UseSyncMaindataReturn {
         ready_state,
         connected,
         data,
+        updated_at,
+        stale,
+        peers,
+        files,
+        trackers,
+        last_ack,
+        errors,
+        commands,
         open,
         close,
     }
 }
+
+/// Applies `update` to the state of `instance`, returns what the user should know about.
+fn apply_sync(
+    data: ReadSignal<HashMap<String, SyncState>>,
+    set_data: WriteSignal<HashMap<String, SyncState>>,
+    instance: &str,
+    update: &SyncUpdate,
+    low_disk_space: u64,
+) -> Vec<SyncEvent> {
+    let mut events = Vec::new();
+    let free_space = update
+        .transfer
+        .as_ref()
+        .and_then(|transfer| transfer.free_space);
+
+    if update.full {
+        // A reconnect may have missed some changes, what we had tells which.
+        data.with_untracked(|data| {
+            let previous = data.get(instance);
+            if let Some(previous) = previous {
+                for torrent in &update.torrents {
+                    if let Some(current) = previous.torrents.get(&torrent.hash) {
+                        torrent_events(instance, current, torrent, &mut events);
+                    }
+                }
+            }
+            let before =
+                previous.and_then(|previous| previous.server_state.free_space.get_untracked());
+            events.extend(space_event(instance, before, free_space, low_disk_space));
+        });
+        set_data.update(|data| {
+            data.insert(instance.to_owned(), SyncState::new(instance, update));
+        });
+        return events;
+    }
+
+    // Changed torrents update their signals in place, the list itself only changes when
+    // torrents come or go.
+    let mut added = Vec::new();
+    let known = data.with_untracked(|data| {
+        let Some(data) = data.get(instance) else {
+            return false;
+        };
+        for torrent in &update.torrents {
+            match data.torrents.get(&torrent.hash) {
+                Some(current) => {
+                    torrent_events(instance, current, torrent, &mut events);
+                    current.update(torrent.clone());
+                }
+                None => added.push(torrent.clone()),
+            }
+        }
+        if let Some(transfer) = &update.transfer {
+            let before = data.server_state.free_space.get_untracked();
+            events.extend(space_event(instance, before, free_space, low_disk_space));
+            data.server_state.update(transfer.clone());
+        }
+        true
+    });
+    if !known || (added.is_empty() && update.removed.is_empty()) {
+        return events;
+    }
+    set_data.update(|data| {
+        let Some(data) = data.get_mut(instance) else {
+            return;
+        };
+        for hash in &update.removed {
+            data.torrents.remove(hash);
+        }
+        for torrent in added {
+            data.torrents
+                .insert(torrent.hash.clone(), Torrent::new(instance, torrent));
+        }
+    });
+    events
+}
+
+/// What changed about `current` that the user should know about, now that it's `new`.
+fn torrent_events(
+    instance: &str,
+    current: &Torrent,
+    new: &model::Torrent,
+    events: &mut Vec<SyncEvent>,
+) {
+    // Paused or queued torrents don't talk to trackers, and trackerless ones never have a
+    // working one.
+    let active = |state: TorrentState| {
+        matches!(
+            state,
+            TorrentState::Downloading | TorrentState::Seeding | TorrentState::Stalled
+        )
+    };
+    if current.progress.get_untracked() < 1.0 && new.progress >= 1.0 {
+        events.push(SyncEvent::Finished {
+            instance: instance.to_owned(),
+            name: new.name.clone(),
+        });
+    }
+    if !current.tracker_error.get_untracked()
+        && new.tracker_error
+        && active(current.state.get_untracked())
+        && active(new.state)
+    {
+        events.push(SyncEvent::TrackerError {
+            instance: instance.to_owned(),
+            name: new.name.clone(),
+        });
+    }
+}
+
+/// Reports the free space once it goes below `low`, not again until it went back up.
+fn space_event(
+    instance: &str,
+    before: Option<u64>,
+    after: Option<u64>,
+    low: u64,
+) -> Option<SyncEvent> {
+    let free = after?;
+    (free < low && before.map_or(true, |before| before >= low)).then(|| SyncEvent::LowDiskSpace {
+        instance: instance.to_owned(),
+        free,
+    })
+}
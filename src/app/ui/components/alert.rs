@@ -0,0 +1,35 @@
+use leptos::prelude::*;
+use tailwind_fuse::*;
+
+#[derive(TwClass)]
+#[tw(class = "relative w-full border px-4 py-2 text-sm")]
+pub struct AlertVariants {
+    pub variant: AlertVariant,
+}
+
+#[derive(TwVariant)]
+pub enum AlertVariant {
+    #[tw(default, class = "bg-background text-foreground")]
+    Default,
+    #[tw(
+        class = "border-amber-500/50 bg-amber-50 text-amber-900 dark:bg-amber-950 dark:text-amber-100"
+    )]
+    Warning,
+    #[tw(class = "border-destructive/50 bg-red-50 text-destructive dark:bg-red-950")]
+    Destructive,
+}
+
+#[component]
+pub fn Alert(
+    #[prop(optional, into)] class: String,
+    #[prop(optional)] variant: AlertVariant,
+    children: Children,
+) -> impl IntoView {
+    let variants = AlertVariants { variant };
+
+    view! {
+        <div role="alert" class=tw_merge!(variants.to_class(), class)>
+            {children()}
+        </div>
+    }
+}
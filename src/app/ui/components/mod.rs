@@ -1,9 +1,11 @@
+mod alert;
 mod button;
 mod input;
 mod navbar;
 mod typography;
 mod view;
 
+pub use alert::*;
 pub use button::*;
 pub use input::*;
 pub use navbar::*;
@@ -0,0 +1,24 @@
+use crate::qbittorrent::client::QbtError;
+use crate::transmission::client::TransmissionError;
+
+#[derive(thiserror::Error, Debug)]
+pub enum BackendError {
+    #[error(transparent)]
+    Qbittorrent(#[from] QbtError),
+    #[error(transparent)]
+    Transmission(#[from] TransmissionError),
+}
+
+impl BackendError {
+    /// The session is gone, e.g. the client restarted, so there's no point in trying again
+    /// until the user logs in again.
+    pub fn is_session_expired(&self) -> bool {
+        matches!(
+            self,
+            BackendError::Qbittorrent(QbtError::SessionExpired | QbtError::Unauthenticated)
+                | BackendError::Transmission(
+                    TransmissionError::SessionExpired | TransmissionError::Unauthenticated
+                )
+        )
+    }
+}
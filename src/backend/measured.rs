@@ -0,0 +1,124 @@
+use std::future::Future;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use tokio::time::Instant;
+
+use super::model::{AddTorrent, Limits, Peer, SyncUpdate, TorrentFile, Tracker};
+use super::{BackendError, Command, SyncCursor, TorrentBackend};
+use crate::metrics::Metrics;
+
+/// Records how long syncs with the wrapped backend take and how often its calls fail, under
+/// the name of its instance. Expired sessions are part of the job and don't count as failures.
+#[derive(Debug)]
+pub struct Measured {
+    instance: String,
+    inner: Arc<dyn TorrentBackend>,
+    metrics: Metrics,
+}
+
+impl Measured {
+    pub fn new(instance: &str, inner: Arc<dyn TorrentBackend>, metrics: Metrics) -> Self {
+        Self {
+            instance: instance.to_owned(),
+            inner,
+            metrics,
+        }
+    }
+
+    async fn count<T>(
+        &self,
+        call: &'static str,
+        res: impl Future<Output = Result<T, BackendError>>,
+    ) -> Result<T, BackendError> {
+        let res = res.await;
+        if res.as_ref().is_err_and(|err| !err.is_session_expired()) {
+            self.metrics.failed(&self.instance, call);
+        }
+        res
+    }
+}
+
+impl TorrentBackend for Measured {
+    fn login(
+        &self,
+        username: String,
+        password: String,
+    ) -> BoxFuture<'_, Result<String, BackendError>> {
+        Box::pin(self.count("login", self.inner.login(username, password)))
+    }
+
+    fn logout<'a>(&'a self, session: &'a str) -> BoxFuture<'a, Result<(), BackendError>> {
+        Box::pin(self.count("logout", self.inner.logout(session)))
+    }
+
+    fn sync<'a>(
+        &'a self,
+        session: &'a str,
+        cursor: &'a mut SyncCursor,
+    ) -> BoxFuture<'a, Result<SyncUpdate, BackendError>> {
+        Box::pin(async move {
+            let start = Instant::now();
+            let res = self.count("sync", self.inner.sync(session, cursor)).await;
+            self.metrics.synced(&self.instance, start.elapsed());
+            res
+        })
+    }
+
+    fn command<'a>(
+        &'a self,
+        session: &'a str,
+        command: Command,
+        hashes: &'a [String],
+    ) -> BoxFuture<'a, Result<(), BackendError>> {
+        Box::pin(self.count("command", self.inner.command(session, command, hashes)))
+    }
+
+    fn add<'a>(
+        &'a self,
+        session: &'a str,
+        torrent: AddTorrent,
+    ) -> BoxFuture<'a, Result<(), BackendError>> {
+        Box::pin(self.count("add", self.inner.add(session, torrent)))
+    }
+
+    fn set_limits<'a>(
+        &'a self,
+        session: &'a str,
+        hashes: &'a [String],
+        limits: Limits,
+    ) -> BoxFuture<'a, Result<(), BackendError>> {
+        Box::pin(self.count("set_limits", self.inner.set_limits(session, hashes, limits)))
+    }
+
+    fn files<'a>(
+        &'a self,
+        session: &'a str,
+        hash: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<TorrentFile>, BackendError>> {
+        Box::pin(self.count("files", self.inner.files(session, hash)))
+    }
+
+    fn peers<'a>(
+        &'a self,
+        session: &'a str,
+        hash: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<Peer>, BackendError>> {
+        Box::pin(self.count("peers", self.inner.peers(session, hash)))
+    }
+
+    fn trackers<'a>(
+        &'a self,
+        session: &'a str,
+        hash: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<Tracker>, BackendError>> {
+        Box::pin(self.count("trackers", self.inner.trackers(session, hash)))
+    }
+
+    fn toggle_alt_speed<'a>(
+        &'a self,
+        session: &'a str,
+    ) -> BoxFuture<'a, Result<bool, BackendError>> {
+        Box::pin(self.count("toggle_alt_speed", self.inner.toggle_alt_speed(session)))
+    }
+}
@@ -0,0 +1,218 @@
+//! The torrent clients bit-tower can manage, behind one [`TorrentBackend`] trait.
+//!
+//! Backends translate their client's API into the [`model`], so that the server, the protocol
+//! and the UI don't need to know whether a torrent is on qBittorrent or on Transmission.
+//! Each backend hands out its own session strings at login, which the session cookie keeps by
+//! instance name like it used to keep qBittorrent SIDs.
+
+pub mod model;
+
+#[cfg(feature = "ssr")]
+mod error;
+#[cfg(feature = "ssr")]
+mod measured;
+#[cfg(feature = "ssr")]
+mod qbittorrent;
+#[cfg(feature = "ssr")]
+mod registry;
+#[cfg(feature = "ssr")]
+mod relogin;
+#[cfg(feature = "ssr")]
+mod transmission;
+
+#[cfg(feature = "ssr")]
+pub use self::ssr::*;
+
+#[cfg(feature = "ssr")]
+mod ssr {
+    use std::collections::HashMap;
+    use std::fmt::Debug;
+    use std::time::Duration;
+
+    use futures::future::BoxFuture;
+
+    use super::model::{
+        AddTorrent, Limits, Peer, SyncUpdate, Torrent, TorrentFile, Tracker, TransferInfo,
+    };
+    use crate::protocol::TorrentAction;
+
+    pub use super::error::BackendError;
+    pub use super::measured::Measured;
+    pub use super::qbittorrent::QbittorrentBackend;
+    pub use super::registry::*;
+    pub use super::relogin::Relogin;
+    pub use super::transmission::TransmissionBackend;
+
+    /// Commands that apply to a list of torrents.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Command {
+        Pause,
+        Resume,
+        Recheck,
+        Reannounce,
+        Delete { delete_files: bool },
+    }
+
+    /// A torrent client. `session` is what [`TorrentBackend::login`] handed out.
+    pub trait TorrentBackend: Debug + Send + Sync {
+        /// Checks the credentials, returns the session to pass to every other call.
+        fn login(
+            &self,
+            username: String,
+            password: String,
+        ) -> BoxFuture<'_, Result<String, BackendError>>;
+
+        /// Ends the session, e.g. when the user logs out of bit-tower.
+        fn logout<'a>(&'a self, session: &'a str) -> BoxFuture<'a, Result<(), BackendError>>;
+
+        /// The next update of the torrent list. The first call with a new `cursor` gets a full
+        /// update, the next ones only what changed since the previous call.
+        fn sync<'a>(
+            &'a self,
+            session: &'a str,
+            cursor: &'a mut SyncCursor,
+        ) -> BoxFuture<'a, Result<SyncUpdate, BackendError>>;
+
+        fn command<'a>(
+            &'a self,
+            session: &'a str,
+            command: Command,
+            hashes: &'a [String],
+        ) -> BoxFuture<'a, Result<(), BackendError>>;
+
+        fn add<'a>(
+            &'a self,
+            session: &'a str,
+            torrent: AddTorrent,
+        ) -> BoxFuture<'a, Result<(), BackendError>>;
+
+        fn set_limits<'a>(
+            &'a self,
+            session: &'a str,
+            hashes: &'a [String],
+            limits: Limits,
+        ) -> BoxFuture<'a, Result<(), BackendError>>;
+
+        fn files<'a>(
+            &'a self,
+            session: &'a str,
+            hash: &'a str,
+        ) -> BoxFuture<'a, Result<Vec<TorrentFile>, BackendError>>;
+
+        fn peers<'a>(
+            &'a self,
+            session: &'a str,
+            hash: &'a str,
+        ) -> BoxFuture<'a, Result<Vec<Peer>, BackendError>>;
+
+        fn trackers<'a>(
+            &'a self,
+            session: &'a str,
+            hash: &'a str,
+        ) -> BoxFuture<'a, Result<Vec<Tracker>, BackendError>>;
+
+        /// Switches between the client's normal and alternative speed limits, returns whether
+        /// the alternative ones apply now.
+        fn toggle_alt_speed<'a>(
+            &'a self,
+            session: &'a str,
+        ) -> BoxFuture<'a, Result<bool, BackendError>>;
+    }
+
+    /// Runs an action sent by the browser.
+    pub async fn run_action(
+        backend: &dyn TorrentBackend,
+        session: &str,
+        action: TorrentAction,
+    ) -> Result<(), BackendError> {
+        match action {
+            TorrentAction::Pause { hashes } => {
+                backend.command(session, Command::Pause, &hashes).await
+            }
+            TorrentAction::Resume { hashes } => {
+                backend.command(session, Command::Resume, &hashes).await
+            }
+            TorrentAction::Recheck { hashes } => {
+                backend.command(session, Command::Recheck, &hashes).await
+            }
+            TorrentAction::Reannounce { hashes } => {
+                backend.command(session, Command::Reannounce, &hashes).await
+            }
+            TorrentAction::Delete {
+                hashes,
+                delete_files,
+            } => {
+                let command = Command::Delete { delete_files };
+                backend.command(session, command, &hashes).await
+            }
+            TorrentAction::SetLimits { hashes, limits } => {
+                backend.set_limits(session, &hashes, limits).await
+            }
+            TorrentAction::Add(torrent) => backend.add(session, torrent).await,
+        }
+    }
+
+    /// Where a poller is at with a backend: what it already told the browser, and whatever the
+    /// backend needs to ask for the next update.
+    #[derive(Debug, Default)]
+    pub struct SyncCursor {
+        /// qBittorrent's `rid`, 0 until the first update.
+        pub rid: u64,
+        /// The torrents as of the last update, by hash.
+        pub torrents: HashMap<String, Torrent>,
+        pub transfer: TransferInfo,
+        /// Transmission's ids of the torrents, which is how it reports removed ones.
+        pub ids: HashMap<i64, String>,
+        /// How often the client itself refreshes, when it says.
+        pub refresh_interval: Option<Duration>,
+    }
+
+    impl SyncCursor {
+        /// Remembers the state of the client after an update and tells what the browser needs
+        /// to know about it: everything when `full`, otherwise the torrents that actually
+        /// changed. `torrents` may hold unchanged ones, `removed` unknown ones.
+        pub fn update(
+            &mut self,
+            full: bool,
+            torrents: Vec<Torrent>,
+            removed: Vec<String>,
+            transfer: TransferInfo,
+        ) -> SyncUpdate {
+            if full {
+                self.torrents = torrents
+                    .iter()
+                    .map(|torrent| (torrent.hash.clone(), torrent.clone()))
+                    .collect();
+                self.transfer = transfer.clone();
+                return SyncUpdate {
+                    full,
+                    torrents,
+                    removed: Vec::new(),
+                    transfer: Some(transfer),
+                };
+            }
+
+            let torrents: Vec<_> = torrents
+                .into_iter()
+                .filter(|torrent| self.torrents.get(&torrent.hash) != Some(torrent))
+                .collect();
+            for torrent in &torrents {
+                self.torrents.insert(torrent.hash.clone(), torrent.clone());
+            }
+            let removed = removed
+                .into_iter()
+                .filter(|hash| self.torrents.remove(hash).is_some())
+                .collect();
+            let transfer = (transfer != self.transfer).then(|| {
+                self.transfer = transfer.clone();
+                transfer
+            });
+            SyncUpdate {
+                full,
+                torrents,
+                removed,
+                transfer,
+            }
+        }
+    }
+}
@@ -0,0 +1,195 @@
+//! What bit-tower knows about torrents, whichever client they are on. Backends translate their
+//! own API into these, the browser only ever sees them.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TorrentState {
+    /// Downloading and transferring data.
+    Downloading,
+    /// Complete and uploading, or waiting for peers to upload to.
+    Seeding,
+    /// Not complete, but no peer sends us anything.
+    Stalled,
+    /// Paused or stopped by the user.
+    Paused,
+    /// Waiting for its turn in the queue.
+    Queued,
+    /// Checking its data, or queued to.
+    Checking,
+    /// Its data is being moved somewhere else.
+    Moving,
+    Error,
+    #[default]
+    Unknown,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Torrent {
+    /// Info hash, which is how every backend identifies torrents.
+    pub hash: String,
+    pub name: String,
+    pub state: TorrentState,
+    /// Between 0 and 1.
+    pub progress: f64,
+    /// Size of the files selected for download (bytes).
+    pub size: u64,
+    /// Size of every file in the torrent (bytes).
+    pub total_size: u64,
+    pub downloaded: u64,
+    pub uploaded: u64,
+    /// Download speed (bytes/s).
+    pub dl_speed: u64,
+    /// Upload speed (bytes/s).
+    pub up_speed: u64,
+    /// `None` when the torrent isn't going to complete.
+    pub eta: Option<Duration>,
+    /// Seeds we're connected to.
+    pub seeds: u64,
+    /// Leechers we're connected to.
+    pub leechs: u64,
+    pub ratio: f64,
+    /// How much of the torrent the swarm has, 1 meaning at least one full copy.
+    pub availability: f64,
+    pub added_on: Option<DateTime<Utc>>,
+    pub save_path: String,
+    pub category: String,
+    pub tags: Vec<String>,
+    /// Download speed limit of this torrent (bytes/s), `None` when unlimited.
+    pub dl_limit: Option<u64>,
+    /// Upload speed limit of this torrent (bytes/s), `None` when unlimited.
+    pub up_limit: Option<u64>,
+    /// No tracker of the torrent works. Torrents without trackers never have a working one, so
+    /// only the change from a working tracker to none tells that something is wrong.
+    #[serde(default)]
+    pub tracker_error: bool,
+}
+
+/// Transfer totals of a whole client.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferInfo {
+    /// Global download speed (bytes/s).
+    pub dl_speed: u64,
+    /// Global upload speed (bytes/s).
+    pub up_speed: u64,
+    /// Downloaded this session (bytes).
+    pub downloaded: u64,
+    /// Uploaded this session (bytes).
+    pub uploaded: u64,
+    /// Global download limit (bytes/s), `None` when unlimited.
+    pub dl_limit: Option<u64>,
+    /// Global upload limit (bytes/s), `None` when unlimited.
+    pub up_limit: Option<u64>,
+    /// DHT nodes we're connected to, 0 when the client doesn't tell.
+    pub dht_nodes: u64,
+    /// Whether the alternative speed limits apply rather than the normal ones.
+    #[serde(default)]
+    pub alt_speed: bool,
+    /// Free space in the default download directory (bytes), `None` when the client doesn't
+    /// tell.
+    #[serde(default)]
+    pub free_space: Option<u64>,
+}
+
+/// An update of the torrents of one client.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SyncUpdate {
+    /// The update replaces whatever we had, otherwise it only carries what changed.
+    pub full: bool,
+    /// Torrents that are new or changed, every one of them when `full`.
+    pub torrents: Vec<Torrent>,
+    /// Hashes of the torrents that are gone.
+    pub removed: Vec<String>,
+    /// The client's totals, when they changed.
+    pub transfer: Option<TransferInfo>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Peer {
+    /// `ip:port`.
+    pub address: String,
+    pub client: String,
+    /// How much of the torrent the peer has, between 0 and 1.
+    pub progress: f64,
+    /// Download speed from this peer (bytes/s).
+    pub dl_speed: u64,
+    /// Upload speed to this peer (bytes/s).
+    pub up_speed: u64,
+    /// Client specific flags, e.g. `D U E`.
+    pub flags: String,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FilePriority {
+    /// Not downloaded at all.
+    Skip,
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TorrentFile {
+    pub index: u64,
+    /// File name, including its path inside the torrent.
+    pub name: String,
+    pub size: u64,
+    /// Between 0 and 1.
+    pub progress: f64,
+    pub priority: FilePriority,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TrackerStatus {
+    Working,
+    #[default]
+    NotContacted,
+    Updating,
+    NotWorking,
+    /// DHT, PeX and LSD show up as disabled trackers when they are off.
+    Disabled,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tracker {
+    pub url: String,
+    /// `None` for DHT, PeX and LSD.
+    pub tier: Option<u64>,
+    pub status: TrackerStatus,
+    /// What the tracker answered last, e.g. an error.
+    pub message: String,
+    /// Seeds and leechers the tracker knows about, `None` when it didn't say.
+    pub seeds: Option<u64>,
+    pub leechs: Option<u64>,
+}
+
+/// Speed limits to give torrents, in bytes/s. `None` lifts the limit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Limits {
+    pub dl_limit: Option<u64>,
+    pub up_limit: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TorrentSource {
+    /// A magnet link or an URL to a `.torrent` file.
+    Url(String),
+    /// The content of a `.torrent` file.
+    File { name: String, bytes: Vec<u8> },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AddTorrent {
+    pub source: TorrentSource,
+    /// Where to download to, the client's default when `None`.
+    pub save_path: Option<String>,
+    /// Add the torrent without starting it.
+    pub paused: bool,
+    /// Tags, or labels on Transmission, e.g. who added the torrent.
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
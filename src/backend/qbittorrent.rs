@@ -0,0 +1,433 @@
+use futures::future::BoxFuture;
+
+use super::model::{
+    AddTorrent, FilePriority, Limits, Peer, SyncUpdate, Torrent, TorrentFile, TorrentSource,
+    TorrentState, Tracker, TrackerStatus, TransferInfo,
+};
+use super::{BackendError, Command, SyncCursor, TorrentBackend};
+use crate::qbittorrent::client::{QbtClient, UploadFile};
+use crate::qbittorrent::proto::adapters::Limit;
+use crate::qbittorrent::proto::sync::MainData;
+use crate::qbittorrent::proto::torrents::{TorrentInfo, TorrentInfoPartial, TorrentStatus};
+use crate::qbittorrent::proto::transfer::{ServerStateFull, ServerStatePartial};
+
+/// qBittorrent, through its WebAPI. Sessions are qBittorrent's SIDs.
+#[derive(Clone, Debug)]
+pub struct QbittorrentBackend {
+    client: QbtClient,
+}
+
+impl QbittorrentBackend {
+    pub fn new(client: QbtClient) -> Self {
+        Self { client }
+    }
+
+    pub fn client(&self) -> &QbtClient {
+        &self.client
+    }
+}
+
+impl TorrentBackend for QbittorrentBackend {
+    fn login(
+        &self,
+        username: String,
+        password: String,
+    ) -> BoxFuture<'_, Result<String, BackendError>> {
+        Box::pin(async move { Ok(self.client.auth_login(username, password).await?) })
+    }
+
+    fn logout<'a>(&'a self, session: &'a str) -> BoxFuture<'a, Result<(), BackendError>> {
+        Box::pin(async move { Ok(self.client.auth_logout(session).await?) })
+    }
+
+    /// Follows `/sync/maindata`, applying its partial updates to the torrents we already have.
+    fn sync<'a>(
+        &'a self,
+        session: &'a str,
+        cursor: &'a mut SyncCursor,
+    ) -> BoxFuture<'a, Result<SyncUpdate, BackendError>> {
+        Box::pin(async move {
+            let maindata = self.client.sync_maindata(session, cursor.rid).await?;
+            cursor.rid = maindata.rid();
+            if let Some(refresh_interval) = maindata.refresh_interval() {
+                cursor.refresh_interval = Some(refresh_interval);
+            }
+            let update = match maindata {
+                MainData::Full(full) => {
+                    let torrents = full
+                        .torrents
+                        .into_iter()
+                        .map(|(hash, info)| torrent(hash, info))
+                        .collect();
+                    cursor.update(true, torrents, Vec::new(), transfer(full.server_state))
+                }
+                MainData::Partial(partial) => {
+                    let torrents =
+                        partial
+                            .torrents
+                            .unwrap_or_default()
+                            .into_iter()
+                            .map(|(hash, partial)| {
+                                let mut torrent =
+                                    cursor.torrents.get(&hash).cloned().unwrap_or_else(|| {
+                                        Torrent {
+                                            hash,
+                                            ..Default::default()
+                                        }
+                                    });
+                                apply_partial(&mut torrent, partial);
+                                torrent
+                            })
+                            .collect();
+                    let mut transfer = cursor.transfer.clone();
+                    if let Some(server_state) = partial.server_state {
+                        apply_server_state(&mut transfer, server_state);
+                    }
+                    let removed = partial.torrents_removed.unwrap_or_default();
+                    cursor.update(false, torrents, removed, transfer)
+                }
+            };
+            Ok(update)
+        })
+    }
+
+    fn command<'a>(
+        &'a self,
+        session: &'a str,
+        command: Command,
+        hashes: &'a [String],
+    ) -> BoxFuture<'a, Result<(), BackendError>> {
+        Box::pin(async move {
+            let qbt = &self.client;
+            match command {
+                Command::Pause => qbt.torrents_pause(session, hashes).await?,
+                Command::Resume => qbt.torrents_resume(session, hashes).await?,
+                Command::Recheck => qbt.torrents_recheck(session, hashes).await?,
+                Command::Reannounce => qbt.torrents_reannounce(session, hashes).await?,
+                Command::Delete { delete_files } => {
+                    qbt.torrents_delete(session, hashes, delete_files).await?
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn add<'a>(
+        &'a self,
+        session: &'a str,
+        torrent: AddTorrent,
+    ) -> BoxFuture<'a, Result<(), BackendError>> {
+        Box::pin(async move {
+            let mut options = Vec::new();
+            if let Some(save_path) = torrent.save_path {
+                options.push(("savepath", save_path));
+            }
+            if torrent.paused {
+                // qBittorrent 5 calls it `stopped`, older ones ignore it.
+                options.push(("paused", "true".to_owned()));
+                options.push(("stopped", "true".to_owned()));
+            }
+            if !torrent.tags.is_empty() {
+                options.push(("tags", torrent.tags.join(",")));
+            }
+            let (urls, files) = match torrent.source {
+                TorrentSource::Url(url) => (vec![url], Vec::new()),
+                TorrentSource::File { name, bytes } => {
+                    let file = UploadFile {
+                        field: "torrents".to_owned(),
+                        file_name: name,
+                        bytes,
+                    };
+                    (Vec::new(), vec![file])
+                }
+            };
+            self.client
+                .torrents_add(session, &urls, files, &options)
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn set_limits<'a>(
+        &'a self,
+        session: &'a str,
+        hashes: &'a [String],
+        limits: Limits,
+    ) -> BoxFuture<'a, Result<(), BackendError>> {
+        Box::pin(async move {
+            self.client
+                .torrents_set_download_limit(session, hashes, limits.dl_limit)
+                .await?;
+            self.client
+                .torrents_set_upload_limit(session, hashes, limits.up_limit)
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn files<'a>(
+        &'a self,
+        session: &'a str,
+        hash: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<TorrentFile>, BackendError>> {
+        Box::pin(async move {
+            let files = self.client.torrents_files(session, hash).await?;
+            Ok(files
+                .into_iter()
+                .map(|file| TorrentFile {
+                    index: file.index,
+                    name: file.name,
+                    size: file.size,
+                    progress: file.progress,
+                    // 1 is normal, 6 high and 7 maximal.
+                    priority: match file.priority {
+                        0 => FilePriority::Skip,
+                        priority if priority >= 6 => FilePriority::High,
+                        _ => FilePriority::Normal,
+                    },
+                })
+                .collect())
+        })
+    }
+
+    /// Always asks for every peer, the browser gets a full list each time.
+    fn peers<'a>(
+        &'a self,
+        session: &'a str,
+        hash: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<Peer>, BackendError>> {
+        Box::pin(async move {
+            let peers = self.client.sync_torrent_peers(session, hash, 0).await?;
+            Ok(peers
+                .peers
+                .into_iter()
+                .map(|(address, peer)| Peer {
+                    address,
+                    client: peer.client.unwrap_or_default(),
+                    progress: peer.progress.unwrap_or_default(),
+                    dl_speed: peer.dl_speed.unwrap_or_default(),
+                    up_speed: peer.up_speed.unwrap_or_default(),
+                    flags: peer.flags.unwrap_or_default(),
+                })
+                .collect())
+        })
+    }
+
+    fn trackers<'a>(
+        &'a self,
+        session: &'a str,
+        hash: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<Tracker>, BackendError>> {
+        Box::pin(async move {
+            let trackers = self.client.torrents_trackers(session, hash).await?;
+            let count = |n: i64| u64::try_from(n).ok();
+            Ok(trackers
+                .into_iter()
+                .map(|tracker| Tracker {
+                    tier: tracker.tier.as_i64().and_then(count),
+                    status: match tracker.status {
+                        0 => TrackerStatus::Disabled,
+                        2 => TrackerStatus::Working,
+                        3 => TrackerStatus::Updating,
+                        4 => TrackerStatus::NotWorking,
+                        _ => TrackerStatus::NotContacted,
+                    },
+                    message: tracker.msg,
+                    seeds: count(tracker.num_seeds),
+                    leechs: count(tracker.num_leeches),
+                    url: tracker.url,
+                })
+                .collect())
+        })
+    }
+
+    fn toggle_alt_speed<'a>(
+        &'a self,
+        session: &'a str,
+    ) -> BoxFuture<'a, Result<bool, BackendError>> {
+        Box::pin(async move {
+            self.client
+                .transfer_toggle_speed_limits_mode(session)
+                .await?;
+            Ok(self.client.transfer_speed_limits_mode(session).await?)
+        })
+    }
+}
+
+fn state(status: &TorrentStatus) -> TorrentState {
+    match status {
+        TorrentStatus::Error | TorrentStatus::MissingFiles => TorrentState::Error,
+        TorrentStatus::Uploading | TorrentStatus::StalledUP | TorrentStatus::ForcedUP => {
+            TorrentState::Seeding
+        }
+        TorrentStatus::PausedUP
+        | TorrentStatus::PausedDL
+        | TorrentStatus::StoppedUP
+        | TorrentStatus::StoppedDL => TorrentState::Paused,
+        TorrentStatus::QueuedUP | TorrentStatus::QueuedDL => TorrentState::Queued,
+        TorrentStatus::CheckingUP
+        | TorrentStatus::CheckingDL
+        | TorrentStatus::CheckingResumeData => TorrentState::Checking,
+        TorrentStatus::Allocating
+        | TorrentStatus::Downloading
+        | TorrentStatus::MetaDL
+        | TorrentStatus::ForcedDL => TorrentState::Downloading,
+        TorrentStatus::StalledDL => TorrentState::Stalled,
+        TorrentStatus::Moving => TorrentState::Moving,
+        TorrentStatus::Unknown | TorrentStatus::Other(_) => TorrentState::Unknown,
+    }
+}
+
+/// A torrent's own limit, the global one doesn't count.
+fn limit(limit: Option<Limit<u64>>) -> Option<u64> {
+    match limit {
+        Some(Limit::Value(limit)) => Some(limit),
+        Some(Limit::Global) | None => None,
+    }
+}
+
+fn tags(tags: &str) -> Vec<String> {
+    tags.split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// `hash` is the key of the torrent in the update, before qBittorrent 4.4 `infohash_v1` is empty.
+fn torrent(hash: String, info: TorrentInfo) -> Torrent {
+    Torrent {
+        hash,
+        state: state(&info.state),
+        progress: info.progress,
+        size: info.size,
+        total_size: info.total_size,
+        downloaded: info.downloaded,
+        uploaded: info.uploaded,
+        dl_speed: info.dlspeed,
+        up_speed: info.upspeed,
+        eta: info.eta,
+        seeds: info.num_seeds,
+        leechs: info.num_leechs,
+        ratio: info.ratio,
+        availability: info.availability,
+        added_on: info.added_on,
+        tags: tags(&info.tags),
+        dl_limit: limit(info.dl_limit),
+        up_limit: limit(info.up_limit),
+        tracker_error: info.tracker.is_empty(),
+        name: info.name,
+        save_path: info.save_path,
+        category: info.category,
+    }
+}
+
+fn apply_partial(torrent: &mut Torrent, partial: TorrentInfoPartial) {
+    if let Some(v) = partial.name {
+        torrent.name = v;
+    }
+    if let Some(v) = partial.state {
+        torrent.state = state(&v);
+    }
+    if let Some(v) = partial.progress {
+        torrent.progress = v;
+    }
+    if let Some(v) = partial.size {
+        torrent.size = v;
+    }
+    if let Some(v) = partial.total_size {
+        torrent.total_size = v;
+    }
+    if let Some(v) = partial.downloaded {
+        torrent.downloaded = v;
+    }
+    if let Some(v) = partial.uploaded {
+        torrent.uploaded = v;
+    }
+    if let Some(v) = partial.dlspeed {
+        torrent.dl_speed = v;
+    }
+    if let Some(v) = partial.upspeed {
+        torrent.up_speed = v;
+    }
+    if let Some(v) = partial.eta {
+        torrent.eta = v;
+    }
+    if let Some(v) = partial.num_seeds {
+        torrent.seeds = v;
+    }
+    if let Some(v) = partial.num_leechs {
+        torrent.leechs = v;
+    }
+    if let Some(v) = partial.ratio {
+        torrent.ratio = v;
+    }
+    if let Some(v) = partial.availability {
+        torrent.availability = v;
+    }
+    if let Some(v) = partial.added_on {
+        torrent.added_on = v;
+    }
+    if let Some(v) = partial.save_path {
+        torrent.save_path = v;
+    }
+    if let Some(v) = partial.category {
+        torrent.category = v;
+    }
+    if let Some(v) = partial.tags {
+        torrent.tags = tags(&v);
+    }
+    if let Some(v) = partial.dl_limit {
+        torrent.dl_limit = limit(v);
+    }
+    if let Some(v) = partial.up_limit {
+        torrent.up_limit = limit(v);
+    }
+    if let Some(v) = partial.tracker {
+        torrent.tracker_error = v.is_empty();
+    }
+}
+
+fn transfer(server_state: ServerStateFull) -> TransferInfo {
+    TransferInfo {
+        dl_speed: server_state.dl_info_speed,
+        up_speed: server_state.up_info_speed,
+        downloaded: server_state.dl_info_data,
+        uploaded: server_state.up_info_data,
+        dl_limit: limit(server_state.dl_rate_limit),
+        up_limit: limit(server_state.up_rate_limit),
+        dht_nodes: server_state.dht_nodes,
+        alt_speed: server_state.use_alt_speed_limits,
+        free_space: server_state.free_space_on_disk,
+    }
+}
+
+fn apply_server_state(transfer: &mut TransferInfo, partial: ServerStatePartial) {
+    if let Some(v) = partial.dl_info_speed {
+        transfer.dl_speed = v;
+    }
+    if let Some(v) = partial.up_info_speed {
+        transfer.up_speed = v;
+    }
+    if let Some(v) = partial.dl_info_data {
+        transfer.downloaded = v;
+    }
+    if let Some(v) = partial.up_info_data {
+        transfer.uploaded = v;
+    }
+    if let Some(v) = partial.dl_rate_limit {
+        transfer.dl_limit = limit(v);
+    }
+    if let Some(v) = partial.up_rate_limit {
+        transfer.up_limit = limit(v);
+    }
+    if let Some(v) = partial.dht_nodes {
+        transfer.dht_nodes = v;
+    }
+    if let Some(v) = partial.use_alt_speed_limits {
+        transfer.alt_speed = v;
+    }
+    if let Some(v) = partial.free_space_on_disk {
+        transfer.free_space = Some(v);
+    }
+}
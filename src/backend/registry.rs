@@ -0,0 +1,69 @@
+use std::sync::Arc;
+
+use super::TorrentBackend;
+
+/// Name of the only instance when bit-tower manages a single torrent client.
+pub static DEFAULT_INSTANCE: &str = "default";
+
+/// A torrent client managed by bit-tower, under the name users pick it by.
+#[derive(Clone, Debug)]
+pub struct BackendInstance {
+    pub name: String,
+    pub backend: Arc<dyn TorrentBackend>,
+}
+
+/// Every torrent client bit-tower manages, in the order they were configured. Cheap to clone.
+#[derive(Clone, Debug, Default)]
+pub struct BackendRegistry {
+    instances: Arc<Vec<BackendInstance>>,
+}
+
+impl BackendRegistry {
+    /// Fails when two instances share a name.
+    pub fn new(instances: Vec<BackendInstance>) -> anyhow::Result<Self> {
+        for (i, instance) in instances.iter().enumerate() {
+            if instances[..i]
+                .iter()
+                .any(|other| other.name == instance.name)
+            {
+                anyhow::bail!("There are two instances named `{}`", instance.name);
+            }
+        }
+        Ok(Self {
+            instances: Arc::new(instances),
+        })
+    }
+
+    /// A registry with just `backend`, named [`DEFAULT_INSTANCE`].
+    pub fn single(backend: impl TorrentBackend + 'static) -> Self {
+        Self {
+            instances: Arc::new(vec![BackendInstance {
+                name: DEFAULT_INSTANCE.to_owned(),
+                backend: Arc::new(backend),
+            }]),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<dyn TorrentBackend>> {
+        self.instances
+            .iter()
+            .find(|instance| instance.name == name)
+            .map(|instance| &instance.backend)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &BackendInstance> {
+        self.instances.iter()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.instances.iter().map(|instance| instance.name.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instances.is_empty()
+    }
+}
@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use futures::future::BoxFuture;
+
+use super::model::{AddTorrent, Limits, Peer, SyncUpdate, TorrentFile, Tracker};
+use super::{BackendError, Command, SyncCursor, TorrentBackend};
+use crate::qbittorrent::client::{BasicAuth, QbtError};
+
+/// Logs in to the wrapped backend again whenever its session expires, e.g. when qBittorrent
+/// restarted or dropped an idle SID, with the credentials the session was opened with.
+///
+/// Meant for the credentials bit-tower holds on behalf of its users: they can't be asked to
+/// log in to a client whose password they don't know. Sessions handed out stay the same across
+/// logins again, so that the ones users keep remain good.
+#[derive(Debug)]
+pub struct Relogin {
+    inner: Arc<dyn TorrentBackend>,
+    sessions: Mutex<HashMap<String, ReloginSession>>,
+}
+
+#[derive(Debug, Clone)]
+struct ReloginSession {
+    credentials: BasicAuth,
+    /// The session of `inner` currently behind ours.
+    current: String,
+}
+
+impl Relogin {
+    pub fn new(inner: Arc<dyn TorrentBackend>) -> Self {
+        Self {
+            inner,
+            sessions: Default::default(),
+        }
+    }
+
+    fn current(&self, session: &str) -> Option<String> {
+        let sessions = self.sessions.lock().unwrap();
+        sessions.get(session).map(|relogin| relogin.current.clone())
+    }
+
+    /// Logs in to `inner` again for `session`. The session ends when the credentials stopped
+    /// working.
+    async fn relogin(&self, session: &str, expired: &str) -> Result<String, BackendError> {
+        let credentials = {
+            let sessions = self.sessions.lock().unwrap();
+            let relogin = sessions.get(session).ok_or_else(expired_error)?;
+            if relogin.current != expired {
+                // Another call got there first.
+                return Ok(relogin.current.clone());
+            }
+            relogin.credentials.clone()
+        };
+        let current = self
+            .inner
+            .login(credentials.username, credentials.password)
+            .await
+            .inspect_err(|err| {
+                tracing::warn!(error = %err, "Could not log in to the torrent client again");
+                self.sessions.lock().unwrap().remove(session);
+            })?;
+        tracing::info!("Logged in to the torrent client again");
+        if let Some(relogin) = self.sessions.lock().unwrap().get_mut(session) {
+            relogin.current = current.clone();
+        }
+        Ok(current)
+    }
+
+    /// Runs `call` with the current session of `inner` behind `session`, once more after
+    /// logging in again when it expired.
+    async fn with_session<'a, T, F>(
+        &'a self,
+        session: &str,
+        call: impl Fn(&'a dyn TorrentBackend, String) -> F,
+    ) -> Result<T, BackendError>
+    where
+        F: Future<Output = Result<T, BackendError>> + 'a,
+    {
+        let current = self.current(session).ok_or_else(expired_error)?;
+        match call(&*self.inner, current.clone()).await {
+            Err(err) if err.is_session_expired() => {
+                let current = self.relogin(session, &current).await?;
+                call(&*self.inner, current).await
+            }
+            res => res,
+        }
+    }
+}
+
+/// What sessions unknown to us get, like the backends answer for sessions they dropped.
+fn expired_error() -> BackendError {
+    QbtError::SessionExpired.into()
+}
+
+impl TorrentBackend for Relogin {
+    fn login(
+        &self,
+        username: String,
+        password: String,
+    ) -> BoxFuture<'_, Result<String, BackendError>> {
+        Box::pin(async move {
+            let credentials = BasicAuth {
+                username: username.clone(),
+                password: password.clone(),
+            };
+            let current = self.inner.login(username, password).await?;
+            let session = uuid::Uuid::new_v4().simple().to_string();
+            self.sessions.lock().unwrap().insert(
+                session.clone(),
+                ReloginSession {
+                    credentials,
+                    current,
+                },
+            );
+            Ok(session)
+        })
+    }
+
+    fn logout<'a>(&'a self, session: &'a str) -> BoxFuture<'a, Result<(), BackendError>> {
+        Box::pin(async move {
+            let removed = self.sessions.lock().unwrap().remove(session);
+            match removed {
+                Some(relogin) => self.inner.logout(&relogin.current).await,
+                None => Ok(()),
+            }
+        })
+    }
+
+    /// A new session of `inner` doesn't know the `cursor`, the update after logging in again
+    /// is a full one.
+    fn sync<'a>(
+        &'a self,
+        session: &'a str,
+        cursor: &'a mut SyncCursor,
+    ) -> BoxFuture<'a, Result<SyncUpdate, BackendError>> {
+        Box::pin(async move {
+            let current = self.current(session).ok_or_else(expired_error)?;
+            match self.inner.sync(&current, cursor).await {
+                Err(err) if err.is_session_expired() => {
+                    let current = self.relogin(session, &current).await?;
+                    *cursor = SyncCursor::default();
+                    self.inner.sync(&current, cursor).await
+                }
+                res => res,
+            }
+        })
+    }
+
+    fn command<'a>(
+        &'a self,
+        session: &'a str,
+        command: Command,
+        hashes: &'a [String],
+    ) -> BoxFuture<'a, Result<(), BackendError>> {
+        Box::pin(
+            self.with_session(session, move |inner, current| async move {
+                inner.command(&current, command, hashes).await
+            }),
+        )
+    }
+
+    fn add<'a>(
+        &'a self,
+        session: &'a str,
+        torrent: AddTorrent,
+    ) -> BoxFuture<'a, Result<(), BackendError>> {
+        Box::pin(self.with_session(session, move |inner, current| {
+            let torrent = torrent.clone();
+            async move { inner.add(&current, torrent).await }
+        }))
+    }
+
+    fn set_limits<'a>(
+        &'a self,
+        session: &'a str,
+        hashes: &'a [String],
+        limits: Limits,
+    ) -> BoxFuture<'a, Result<(), BackendError>> {
+        Box::pin(
+            self.with_session(session, move |inner, current| async move {
+                inner.set_limits(&current, hashes, limits).await
+            }),
+        )
+    }
+
+    fn files<'a>(
+        &'a self,
+        session: &'a str,
+        hash: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<TorrentFile>, BackendError>> {
+        Box::pin(
+            self.with_session(session, move |inner, current| async move {
+                inner.files(&current, hash).await
+            }),
+        )
+    }
+
+    fn peers<'a>(
+        &'a self,
+        session: &'a str,
+        hash: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<Peer>, BackendError>> {
+        Box::pin(
+            self.with_session(session, move |inner, current| async move {
+                inner.peers(&current, hash).await
+            }),
+        )
+    }
+
+    fn trackers<'a>(
+        &'a self,
+        session: &'a str,
+        hash: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<Tracker>, BackendError>> {
+        Box::pin(
+            self.with_session(session, move |inner, current| async move {
+                inner.trackers(&current, hash).await
+            }),
+        )
+    }
+
+    fn toggle_alt_speed<'a>(
+        &'a self,
+        session: &'a str,
+    ) -> BoxFuture<'a, Result<bool, BackendError>> {
+        Box::pin(
+            self.with_session(session, move |inner, current| async move {
+                inner.toggle_alt_speed(&current).await
+            }),
+        )
+    }
+}
@@ -0,0 +1,398 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use base64::Engine;
+use chrono::DateTime;
+use futures::future::BoxFuture;
+use serde_json::json;
+
+use super::model::{
+    AddTorrent, FilePriority, Limits, Peer, SyncUpdate, Torrent, TorrentFile, TorrentSource,
+    TorrentState, Tracker, TrackerStatus, TransferInfo,
+};
+use super::{BackendError, Command, SyncCursor, TorrentBackend};
+use crate::qbittorrent::client::BasicAuth;
+use crate::transmission::client::{TransmissionClient, TransmissionError};
+use crate::transmission::proto::{
+    status, SessionGet, SessionStats, TorrentFields, SPEED_UNIT, TORRENT_FIELDS,
+};
+
+/// Transmission, through its RPC.
+///
+/// Transmission wants the credentials with every request, so they stay here and the session is
+/// a random token pointing to them. Sessions don't survive a restart of bit-tower.
+#[derive(Debug)]
+pub struct TransmissionBackend {
+    client: TransmissionClient,
+    sessions: Mutex<HashMap<String, BasicAuth>>,
+}
+
+impl TransmissionBackend {
+    pub fn new(client: TransmissionClient) -> Self {
+        Self {
+            client,
+            sessions: Default::default(),
+        }
+    }
+
+    fn credentials(&self, session: &str) -> Result<BasicAuth, BackendError> {
+        let sessions = self.sessions.lock().unwrap();
+        let credentials = sessions
+            .get(session)
+            .ok_or(TransmissionError::SessionExpired)?;
+        Ok(credentials.clone())
+    }
+
+    /// Runs `call` with the credentials of `session`. Credentials that stopped working, e.g.
+    /// because the password changed, end the session.
+    async fn with_credentials<'a, T, F>(
+        &'a self,
+        session: &str,
+        call: impl FnOnce(&'a TransmissionClient, BasicAuth) -> F,
+    ) -> Result<T, BackendError>
+    where
+        F: std::future::Future<Output = Result<T, TransmissionError>> + 'a,
+    {
+        let credentials = self.credentials(session)?;
+        match call(&self.client, credentials).await {
+            Err(TransmissionError::Unauthenticated) => {
+                self.sessions.lock().unwrap().remove(session);
+                Err(TransmissionError::SessionExpired.into())
+            }
+            res => Ok(res?),
+        }
+    }
+}
+
+impl TorrentBackend for TransmissionBackend {
+    /// Checks the credentials with a `session-get`.
+    fn login(
+        &self,
+        username: String,
+        password: String,
+    ) -> BoxFuture<'_, Result<String, BackendError>> {
+        Box::pin(async move {
+            let credentials = BasicAuth { username, password };
+            self.client.session_get(&credentials).await?;
+            let session = uuid::Uuid::new_v4().simple().to_string();
+            self.sessions
+                .lock()
+                .unwrap()
+                .insert(session.clone(), credentials);
+            Ok(session)
+        })
+    }
+
+    /// Transmission has nothing to log out of, we just forget the credentials.
+    fn logout<'a>(&'a self, session: &'a str) -> BoxFuture<'a, Result<(), BackendError>> {
+        self.sessions.lock().unwrap().remove(session);
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Asks for every torrent first, then only for the recently active ones, which also tells
+    /// which torrents were removed.
+    fn sync<'a>(
+        &'a self,
+        session: &'a str,
+        cursor: &'a mut SyncCursor,
+    ) -> BoxFuture<'a, Result<SyncUpdate, BackendError>> {
+        Box::pin(async move {
+            let full = cursor.rid == 0;
+            let ids = (!full).then(|| json!("recently-active"));
+            let (torrents, stats, settings) = self
+                .with_credentials(session, |client, auth| async move {
+                    futures::try_join!(
+                        client.torrent_get(&auth, TORRENT_FIELDS, ids),
+                        client.session_stats(&auth),
+                        client.session_get(&auth),
+                    )
+                })
+                .await?;
+
+            cursor.rid += 1;
+            if full {
+                cursor.ids.clear();
+            }
+            for torrent in &torrents.torrents {
+                cursor.ids.insert(torrent.id, torrent.hash_string.clone());
+            }
+            let removed = torrents
+                .removed
+                .iter()
+                .filter_map(|id| cursor.ids.remove(id))
+                .collect();
+            let torrents = torrents.torrents.into_iter().map(torrent).collect();
+            Ok(cursor.update(full, torrents, removed, transfer(stats, settings)))
+        })
+    }
+
+    fn command<'a>(
+        &'a self,
+        session: &'a str,
+        command: Command,
+        hashes: &'a [String],
+    ) -> BoxFuture<'a, Result<(), BackendError>> {
+        Box::pin(
+            self.with_credentials(session, move |client, auth| async move {
+                let method = match command {
+                    Command::Pause => "torrent-stop",
+                    Command::Resume => "torrent-start",
+                    Command::Recheck => "torrent-verify",
+                    Command::Reannounce => "torrent-reannounce",
+                    Command::Delete { delete_files } => {
+                        return client.torrent_remove(&auth, hashes, delete_files).await;
+                    }
+                };
+                client.torrent_command(&auth, method, hashes).await
+            }),
+        )
+    }
+
+    fn add<'a>(
+        &'a self,
+        session: &'a str,
+        torrent: AddTorrent,
+    ) -> BoxFuture<'a, Result<(), BackendError>> {
+        let mut arguments = match torrent.source {
+            TorrentSource::Url(url) => json!({ "filename": url }),
+            TorrentSource::File { bytes, .. } => {
+                json!({ "metainfo": base64::prelude::BASE64_STANDARD.encode(bytes) })
+            }
+        };
+        arguments["paused"] = json!(torrent.paused);
+        if let Some(save_path) = torrent.save_path {
+            arguments["download-dir"] = json!(save_path);
+        }
+        if !torrent.tags.is_empty() {
+            // Transmission 4 and later, older ones ignore it.
+            arguments["labels"] = json!(torrent.tags);
+        }
+        Box::pin(
+            self.with_credentials(session, move |client, auth| async move {
+                client.torrent_add(&auth, arguments).await
+            }),
+        )
+    }
+
+    fn set_limits<'a>(
+        &'a self,
+        session: &'a str,
+        hashes: &'a [String],
+        limits: Limits,
+    ) -> BoxFuture<'a, Result<(), BackendError>> {
+        // Transmission keeps the limit around when it's lifted, we leave it be.
+        let mut arguments = json!({
+            "downloadLimited": limits.dl_limit.is_some(),
+            "uploadLimited": limits.up_limit.is_some(),
+        });
+        if let Some(limit) = limits.dl_limit {
+            arguments["downloadLimit"] = json!(limit.div_ceil(SPEED_UNIT));
+        }
+        if let Some(limit) = limits.up_limit {
+            arguments["uploadLimit"] = json!(limit.div_ceil(SPEED_UNIT));
+        }
+        Box::pin(
+            self.with_credentials(session, move |client, auth| async move {
+                client.torrent_set(&auth, hashes, arguments).await
+            }),
+        )
+    }
+
+    fn files<'a>(
+        &'a self,
+        session: &'a str,
+        hash: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<TorrentFile>, BackendError>> {
+        Box::pin(async move {
+            let torrent = self.torrent(session, hash, &["files", "fileStats"]).await?;
+            Ok(torrent
+                .files
+                .into_iter()
+                .zip(torrent.file_stats)
+                .enumerate()
+                .map(|(index, (file, stats))| TorrentFile {
+                    index: index as u64,
+                    progress: if file.length > 0 {
+                        file.bytes_completed as f64 / file.length as f64
+                    } else {
+                        1.0
+                    },
+                    priority: match (stats.wanted, stats.priority) {
+                        (false, _) => FilePriority::Skip,
+                        (true, priority) if priority < 0 => FilePriority::Low,
+                        (true, priority) if priority > 0 => FilePriority::High,
+                        (true, _) => FilePriority::Normal,
+                    },
+                    name: file.name,
+                    size: file.length,
+                })
+                .collect())
+        })
+    }
+
+    fn peers<'a>(
+        &'a self,
+        session: &'a str,
+        hash: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<Peer>, BackendError>> {
+        Box::pin(async move {
+            let torrent = self.torrent(session, hash, &["peers"]).await?;
+            Ok(torrent
+                .peers
+                .into_iter()
+                .map(|peer| Peer {
+                    // The same `ip:port` qBittorrent uses, with brackets around IPv6 addresses.
+                    address: if peer.address.contains(':') {
+                        format!("[{}]:{}", peer.address, peer.port)
+                    } else {
+                        format!("{}:{}", peer.address, peer.port)
+                    },
+                    client: peer.client_name,
+                    progress: peer.progress,
+                    dl_speed: peer.rate_to_client,
+                    up_speed: peer.rate_to_peer,
+                    flags: peer.flag_str,
+                })
+                .collect())
+        })
+    }
+
+    fn trackers<'a>(
+        &'a self,
+        session: &'a str,
+        hash: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<Tracker>, BackendError>> {
+        Box::pin(async move {
+            let torrent = self.torrent(session, hash, &["trackerStats"]).await?;
+            let count = |n: i64| u64::try_from(n).ok();
+            Ok(torrent
+                .tracker_stats
+                .into_iter()
+                .map(|tracker| Tracker {
+                    tier: Some(tracker.tier),
+                    status: match tracker.announce_state {
+                        // Announcing right now.
+                        3 => TrackerStatus::Updating,
+                        _ if !tracker.has_announced => TrackerStatus::NotContacted,
+                        _ if tracker.last_announce_succeeded => TrackerStatus::Working,
+                        _ => TrackerStatus::NotWorking,
+                    },
+                    seeds: count(tracker.seeder_count),
+                    leechs: count(tracker.leecher_count),
+                    url: tracker.announce,
+                    message: tracker.last_announce_result,
+                })
+                .collect())
+        })
+    }
+
+    fn toggle_alt_speed<'a>(
+        &'a self,
+        session: &'a str,
+    ) -> BoxFuture<'a, Result<bool, BackendError>> {
+        Box::pin(self.with_credentials(session, |client, auth| async move {
+            let enabled = !client.session_get(&auth).await?.alt_speed_enabled;
+            client
+                .session_set(&auth, json!({ "alt-speed-enabled": enabled }))
+                .await?;
+            Ok(enabled)
+        }))
+    }
+}
+
+impl TransmissionBackend {
+    /// `fields` of the torrent with the given hash.
+    async fn torrent(
+        &self,
+        session: &str,
+        hash: &str,
+        fields: &[&str],
+    ) -> Result<TorrentFields, BackendError> {
+        let ids = Some(json!([hash]));
+        let res = self
+            .with_credentials(session, |client, auth| async move {
+                client.torrent_get(&auth, fields, ids).await
+            })
+            .await?;
+        let torrent = res.torrents.into_iter().next();
+        torrent.ok_or_else(|| TransmissionError::Rpc(format!("no torrent {hash}")).into())
+    }
+}
+
+fn state(torrent: &TorrentFields) -> TorrentState {
+    // 1 and 2 are tracker warnings and errors, the torrent still works.
+    if torrent.error == 3 {
+        return TorrentState::Error;
+    }
+    match torrent.status {
+        status::STOPPED => TorrentState::Paused,
+        status::CHECK_WAIT | status::CHECK => TorrentState::Checking,
+        status::DOWNLOAD_WAIT | status::SEED_WAIT => TorrentState::Queued,
+        status::DOWNLOAD if torrent.rate_download > 0 => TorrentState::Downloading,
+        status::DOWNLOAD => TorrentState::Stalled,
+        status::SEED => TorrentState::Seeding,
+        _ => TorrentState::Unknown,
+    }
+}
+
+fn torrent(torrent: TorrentFields) -> Torrent {
+    let have = torrent
+        .size_when_done
+        .saturating_sub(torrent.left_until_done);
+    let availability = if torrent.size_when_done == 0 || torrent.left_until_done == 0 {
+        1.0
+    } else {
+        (have + torrent.desired_available) as f64 / torrent.size_when_done as f64
+    };
+    let limit = |limited: bool, limit: u64| limited.then_some(limit * SPEED_UNIT);
+    Torrent {
+        state: state(&torrent),
+        progress: torrent.percent_done,
+        size: torrent.size_when_done,
+        total_size: torrent.total_size,
+        downloaded: torrent.downloaded_ever,
+        uploaded: torrent.uploaded_ever,
+        dl_speed: torrent.rate_download,
+        up_speed: torrent.rate_upload,
+        eta: u64::try_from(torrent.eta)
+            .ok()
+            .map(std::time::Duration::from_secs),
+        seeds: torrent.peers_sending_to_us,
+        leechs: torrent.peers_getting_from_us,
+        ratio: torrent.upload_ratio.max(0.0),
+        availability,
+        added_on: (torrent.added_date > 0)
+            .then(|| DateTime::from_timestamp(torrent.added_date, 0))
+            .flatten(),
+        dl_limit: limit(torrent.download_limited, torrent.download_limit),
+        up_limit: limit(torrent.upload_limited, torrent.upload_limit),
+        tracker_error: torrent.error == 2,
+        // Transmission has no categories, only labels.
+        category: String::new(),
+        tags: torrent.labels,
+        hash: torrent.hash_string,
+        name: torrent.name,
+        save_path: torrent.download_dir,
+    }
+}
+
+fn transfer(stats: SessionStats, settings: SessionGet) -> TransferInfo {
+    TransferInfo {
+        dl_speed: stats.download_speed,
+        up_speed: stats.upload_speed,
+        downloaded: stats.current_stats.downloaded_bytes,
+        uploaded: stats.current_stats.uploaded_bytes,
+        dl_limit: settings
+            .speed_limit_down_enabled
+            .then_some(settings.speed_limit_down * SPEED_UNIT),
+        up_limit: settings
+            .speed_limit_up_enabled
+            .then_some(settings.speed_limit_up * SPEED_UNIT),
+        // Transmission doesn't tell.
+        dht_nodes: 0,
+        alt_speed: settings.alt_speed_enabled,
+        free_space: settings
+            .download_dir_free_space
+            .and_then(|free| u64::try_from(free).ok()),
+    }
+}
@@ -1,10 +1,26 @@
+#[cfg(feature = "ssr")]
+pub mod api;
 pub mod app;
+pub mod backend;
+#[cfg(feature = "ssr")]
+pub mod metrics;
+pub mod protocol;
 pub mod qbittorrent;
+#[cfg(feature = "ssr")]
+pub mod tls;
+pub mod transmission;
+#[cfg(feature = "ssr")]
+pub mod webhooks;
 
 #[cfg(feature = "hydrate")]
 #[wasm_bindgen::prelude::wasm_bindgen()]
 pub fn hydrate() {
     tracing_wasm::set_as_global_default();
     console_error_panic_hook::set_once();
+    // Server functions live under the base path too.
+    let base_path = app::use_base_path();
+    if !base_path.as_str().is_empty() {
+        leptos::server_fn::client::set_server_url(base_path.as_str().to_owned().leak());
+    }
     leptos::mount::hydrate_body(app::App);
 }
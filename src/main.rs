@@ -4,6 +4,10 @@ mod server;
 #[cfg(feature = "ssr")]
 #[tokio::main]
 async fn main() {
+    if std::env::args().nth(1).as_deref() == Some("hash-password") {
+        server::hash_password();
+        return;
+    }
     server::serve().await;
 }
 
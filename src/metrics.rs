@@ -0,0 +1,394 @@
+//! What `/metrics` tells Prometheus about bit-tower and the torrent clients it manages, and
+//! what `/readyz` tells about whether they can be reached.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::backend::model::{TorrentState, TransferInfo};
+use crate::backend::SyncCursor;
+
+/// Upper bounds of the sync duration buckets, in seconds.
+const DURATION_BUCKETS: &[f64] = &[0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+/// Upper bounds of the frame size buckets, in bytes.
+const SIZE_BUCKETS: &[f64] = &[
+    128.0, 512.0, 1024.0, 4096.0, 16384.0, 65536.0, 262144.0, 1048576.0,
+];
+
+const STATES: [TorrentState; 9] = [
+    TorrentState::Downloading,
+    TorrentState::Seeding,
+    TorrentState::Stalled,
+    TorrentState::Paused,
+    TorrentState::Queued,
+    TorrentState::Checking,
+    TorrentState::Moving,
+    TorrentState::Error,
+    TorrentState::Unknown,
+];
+
+/// Counters shared by everything that serves requests. Cheap to clone.
+#[derive(Clone, Debug, Default)]
+pub struct Metrics {
+    websockets: Arc<AtomicI64>,
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    sync_durations: BTreeMap<String, Histogram>,
+    /// Failed calls to the torrent clients, by instance and call.
+    errors: BTreeMap<(String, &'static str), u64>,
+    /// Sizes of the frames sent to browsers, by transport.
+    frames: BTreeMap<&'static str, Histogram>,
+    instances: BTreeMap<String, InstanceCheck>,
+}
+
+impl Default for Inner {
+    fn default() -> Self {
+        Self {
+            sync_durations: BTreeMap::new(),
+            errors: BTreeMap::new(),
+            frames: ["sse", "ws"]
+                .into_iter()
+                .map(|transport| (transport, Histogram::new(SIZE_BUCKETS)))
+                .collect(),
+            instances: BTreeMap::new(),
+        }
+    }
+}
+
+/// What the last check of a torrent client found.
+#[derive(Debug, Default)]
+struct InstanceCheck {
+    /// Why the last check failed, `None` when it succeeded.
+    error: Option<String>,
+    /// The client's state as of the last successful check.
+    state: Option<ClientState>,
+}
+
+#[derive(Debug)]
+struct ClientState {
+    transfer: TransferInfo,
+    states: BTreeMap<&'static str, u64>,
+    categories: BTreeMap<String, CategoryTotals>,
+}
+
+#[derive(Debug, Default)]
+struct CategoryTotals {
+    torrents: u64,
+    size: u64,
+    dl_speed: u64,
+    up_speed: u64,
+}
+
+impl Metrics {
+    /// Counts an open websocket until the guard is dropped.
+    pub fn websocket_opened(&self) -> WebsocketGuard {
+        self.websockets.fetch_add(1, Ordering::Relaxed);
+        WebsocketGuard(self.websockets.clone())
+    }
+
+    /// Records a frame of `bytes` sent to a browser over `transport`, `ws` or `sse`.
+    pub fn frame_sent(&self, transport: &'static str, bytes: usize) {
+        let mut inner = self.inner.lock().unwrap();
+        inner
+            .frames
+            .entry(transport)
+            .or_insert_with(|| Histogram::new(SIZE_BUCKETS))
+            .observe(bytes as f64);
+    }
+
+    /// Records how long a sync with `instance` took.
+    pub fn synced(&self, instance: &str, duration: Duration) {
+        let mut inner = self.inner.lock().unwrap();
+        inner
+            .sync_durations
+            .entry(instance.to_owned())
+            .or_insert_with(|| Histogram::new(DURATION_BUCKETS))
+            .observe(duration.as_secs_f64());
+    }
+
+    /// Counts a failed `call` to `instance`, e.g. `sync` or `command`.
+    pub fn failed(&self, instance: &str, call: &'static str) {
+        let mut inner = self.inner.lock().unwrap();
+        *inner.errors.entry((instance.to_owned(), call)).or_default() += 1;
+    }
+
+    /// Records a successful check of `instance`, with the state its `cursor` is at.
+    pub fn checked(&self, instance: &str, cursor: &SyncCursor) {
+        let mut states: BTreeMap<_, _> = STATES.iter().map(|state| (label(*state), 0)).collect();
+        let mut categories = BTreeMap::<String, CategoryTotals>::new();
+        for torrent in cursor.torrents.values() {
+            *states.entry(label(torrent.state)).or_default() += 1;
+            let totals = categories.entry(torrent.category.clone()).or_default();
+            totals.torrents += 1;
+            totals.size += torrent.size;
+            totals.dl_speed += torrent.dl_speed;
+            totals.up_speed += torrent.up_speed;
+        }
+        let mut inner = self.inner.lock().unwrap();
+        inner.instances.insert(
+            instance.to_owned(),
+            InstanceCheck {
+                error: None,
+                state: Some(ClientState {
+                    transfer: cursor.transfer.clone(),
+                    states,
+                    categories,
+                }),
+            },
+        );
+    }
+
+    /// Records a failed check of `instance`. The last known state stays in the metrics.
+    pub fn check_failed(&self, instance: &str, error: impl Into<String>) {
+        let mut inner = self.inner.lock().unwrap();
+        inner
+            .instances
+            .entry(instance.to_owned())
+            .or_default()
+            .error = Some(error.into());
+    }
+
+    /// The instances whose last check failed, with why. Ready when there are none.
+    pub fn not_ready(&self) -> Vec<(String, String)> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .instances
+            .iter()
+            .filter_map(|(name, check)| Some((name.clone(), check.error.clone()?)))
+            .collect()
+    }
+
+    /// Everything in Prometheus' text format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let inner = self.inner.lock().unwrap();
+
+        header(&mut out, "bittower_websockets", "gauge", "Open websockets.");
+        let _ = writeln!(
+            out,
+            "bittower_websockets {}",
+            self.websockets.load(Ordering::Relaxed)
+        );
+
+        header(
+            &mut out,
+            "bittower_frame_bytes",
+            "histogram",
+            "Size of the frames sent to browsers.",
+        );
+        for (transport, histogram) in &inner.frames {
+            histogram.render(
+                &mut out,
+                "bittower_frame_bytes",
+                &format!("transport=\"{transport}\""),
+            );
+        }
+
+        header(
+            &mut out,
+            "bittower_sync_duration_seconds",
+            "histogram",
+            "How long syncing with a torrent client took.",
+        );
+        for (instance, histogram) in &inner.sync_durations {
+            histogram.render(
+                &mut out,
+                "bittower_sync_duration_seconds",
+                &format!("instance=\"{}\"", escape(instance)),
+            );
+        }
+
+        header(
+            &mut out,
+            "bittower_backend_errors_total",
+            "counter",
+            "Failed calls to a torrent client.",
+        );
+        for ((instance, call), count) in &inner.errors {
+            let instance = escape(instance);
+            let _ = writeln!(
+                out,
+                "bittower_backend_errors_total{{instance=\"{instance}\",call=\"{call}\"}} {count}"
+            );
+        }
+
+        header(
+            &mut out,
+            "bittower_instance_up",
+            "gauge",
+            "Whether the last check of a torrent client succeeded.",
+        );
+        for (instance, check) in &inner.instances {
+            let up = u8::from(check.error.is_none());
+            let _ = writeln!(
+                out,
+                "bittower_instance_up{{instance=\"{}\"}} {up}",
+                escape(instance)
+            );
+        }
+
+        let states = || {
+            inner
+                .instances
+                .iter()
+                .filter_map(|(instance, check)| Some((escape(instance), check.state.as_ref()?)))
+        };
+        for (name, help, value) in [
+            (
+                "bittower_download_speed_bytes",
+                "Download speed of a torrent client, in bytes per second.",
+                (|transfer: &TransferInfo| transfer.dl_speed) as fn(&TransferInfo) -> u64,
+            ),
+            (
+                "bittower_upload_speed_bytes",
+                "Upload speed of a torrent client, in bytes per second.",
+                |transfer| transfer.up_speed,
+            ),
+            (
+                "bittower_downloaded_bytes",
+                "Downloaded by a torrent client this session.",
+                |transfer| transfer.downloaded,
+            ),
+            (
+                "bittower_uploaded_bytes",
+                "Uploaded by a torrent client this session.",
+                |transfer| transfer.uploaded,
+            ),
+        ] {
+            header(&mut out, name, "gauge", help);
+            for (instance, state) in states() {
+                let value = value(&state.transfer);
+                let _ = writeln!(out, "{name}{{instance=\"{instance}\"}} {value}");
+            }
+        }
+
+        header(&mut out, "bittower_torrents", "gauge", "Torrents by state.");
+        for (instance, state) in states() {
+            for (label, count) in &state.states {
+                let _ = writeln!(
+                    out,
+                    "bittower_torrents{{instance=\"{instance}\",state=\"{label}\"}} {count}"
+                );
+            }
+        }
+
+        for (name, help, value) in [
+            (
+                "bittower_category_torrents",
+                "Torrents in a category.",
+                (|totals: &CategoryTotals| totals.torrents) as fn(&CategoryTotals) -> u64,
+            ),
+            (
+                "bittower_category_size_bytes",
+                "Size of the torrents in a category.",
+                |totals| totals.size,
+            ),
+            (
+                "bittower_category_download_speed_bytes",
+                "Download speed of the torrents in a category, in bytes per second.",
+                |totals| totals.dl_speed,
+            ),
+            (
+                "bittower_category_upload_speed_bytes",
+                "Upload speed of the torrents in a category, in bytes per second.",
+                |totals| totals.up_speed,
+            ),
+        ] {
+            header(&mut out, name, "gauge", help);
+            for (instance, state) in states() {
+                for (category, totals) in &state.categories {
+                    let _ = writeln!(
+                        out,
+                        "{name}{{instance=\"{instance}\",category=\"{}\"}} {}",
+                        escape(category),
+                        value(totals)
+                    );
+                }
+            }
+        }
+
+        out
+    }
+}
+
+/// Counts an open websocket for as long as it lives.
+#[derive(Debug)]
+pub struct WebsocketGuard(Arc<AtomicI64>);
+
+impl Drop for WebsocketGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug)]
+struct Histogram {
+    /// Upper bounds of the buckets, `+Inf` aside.
+    bounds: &'static [f64],
+    /// Observations in each bucket, not cumulative.
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            counts: vec![0; bounds.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        if let Some(i) = self.bounds.iter().position(|bound| value <= *bound) {
+            self.counts[i] += 1;
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let mut cumulative = 0;
+        for (bound, count) in self.bounds.iter().zip(&self.counts) {
+            cumulative += count;
+            let _ = writeln!(out, "{name}_bucket{{{labels},le=\"{bound}\"}} {cumulative}");
+        }
+        let _ = writeln!(out, "{name}_bucket{{{labels},le=\"+Inf\"}} {}", self.count);
+        let _ = writeln!(out, "{name}_sum{{{labels}}} {}", self.sum);
+        let _ = writeln!(out, "{name}_count{{{labels}}} {}", self.count);
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn label(state: TorrentState) -> &'static str {
+    match state {
+        TorrentState::Downloading => "downloading",
+        TorrentState::Seeding => "seeding",
+        TorrentState::Stalled => "stalled",
+        TorrentState::Paused => "paused",
+        TorrentState::Queued => "queued",
+        TorrentState::Checking => "checking",
+        TorrentState::Moving => "moving",
+        TorrentState::Error => "error",
+        TorrentState::Unknown => "unknown",
+    }
+}
+
+/// Escapes a label value, instance names are safe but categories come from users.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,210 @@
+//! Messages exchanged over the `/ws` websocket, encoded with msgpack. The `/events` server-sent
+//! events carry the same [`ServerMessage`]s, encoded as JSON.
+//!
+//! Both sides start by sending a [`ClientMessage::Hello`]/[`ServerMessage::Hello`] with the
+//! [`PROTOCOL_VERSION`] they speak, the server drops the connection when they don't match.
+//!
+//! One connection carries the updates of every instance the user is logged in to, anything about
+//! torrents names the instance it is about. Torrents are described with the backend agnostic
+//! [`crate::backend::model`], whether they are on qBittorrent or Transmission.
+
+use serde::{Deserialize, Serialize};
+
+use crate::backend::model::{AddTorrent, Limits, Peer, SyncUpdate, TorrentFile, Tracker};
+
+/// Bumped whenever a change to [`ClientMessage`] or [`ServerMessage`] breaks compatibility.
+pub const PROTOCOL_VERSION: u32 = 5;
+
+/// Frames sent by the browser to the server.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum ClientMessage {
+    /// First frame on every connection.
+    Hello { version: u32 },
+    /// Start receiving updates for the given subscription on every poll.
+    Subscribe(Subscription),
+    /// Stop receiving updates for the given subscription.
+    Unsubscribe(Subscription),
+    /// Change how often the server polls the torrent clients for this connection.
+    SetPollInterval { interval_ms: u64 },
+    /// The page went to the background or came back, the server polls less often while it is
+    /// hidden or unfocused.
+    Visibility { visible: bool, focused: bool },
+    /// Run an action on an instance, answered with a [`ServerMessage::Ack`] carrying the same
+    /// `request_id`.
+    Action {
+        request_id: u64,
+        instance: String,
+        action: TorrentAction,
+    },
+}
+
+/// Per-torrent data that is only polled while a client is looking at it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum Subscription {
+    TorrentPeers { instance: String, hash: String },
+    TorrentFiles { instance: String, hash: String },
+    TorrentTrackers { instance: String, hash: String },
+}
+
+impl Subscription {
+    pub fn instance(&self) -> &str {
+        match self {
+            Subscription::TorrentPeers { instance, .. }
+            | Subscription::TorrentFiles { instance, .. }
+            | Subscription::TorrentTrackers { instance, .. } => instance,
+        }
+    }
+
+    pub fn hash(&self) -> &str {
+        match self {
+            Subscription::TorrentPeers { hash, .. }
+            | Subscription::TorrentFiles { hash, .. }
+            | Subscription::TorrentTrackers { hash, .. } => hash,
+        }
+    }
+}
+
+/// Whether `hash` looks like a torrent's info hash: 40 hex characters for v1, 64 for v2.
+pub fn is_torrent_hash(hash: &str) -> bool {
+    matches!(hash.len(), 40 | 64) && hash.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum TorrentAction {
+    Pause {
+        hashes: Vec<String>,
+    },
+    Resume {
+        hashes: Vec<String>,
+    },
+    Recheck {
+        hashes: Vec<String>,
+    },
+    Reannounce {
+        hashes: Vec<String>,
+    },
+    Delete {
+        hashes: Vec<String>,
+        delete_files: bool,
+    },
+    SetLimits {
+        hashes: Vec<String>,
+        limits: Limits,
+    },
+    Add(AddTorrent),
+}
+
+impl TorrentAction {
+    /// The torrents the action is about, none when adding one.
+    pub fn hashes(&self) -> &[String] {
+        match self {
+            TorrentAction::Pause { hashes }
+            | TorrentAction::Resume { hashes }
+            | TorrentAction::Recheck { hashes }
+            | TorrentAction::Reannounce { hashes }
+            | TorrentAction::Delete { hashes, .. }
+            | TorrentAction::SetLimits { hashes, .. } => hashes,
+            TorrentAction::Add(_) => &[],
+        }
+    }
+}
+
+/// Frames sent by the server to the browser.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum ServerMessage {
+    /// First frame on every connection.
+    Hello { version: u32 },
+    /// An update of the torrent list and transfer totals of an instance.
+    Sync {
+        instance: String,
+        update: SyncUpdate,
+    },
+    /// Every peer, for a [`Subscription::TorrentPeers`].
+    TorrentPeers {
+        instance: String,
+        hash: String,
+        peers: Vec<Peer>,
+    },
+    /// File list for a [`Subscription::TorrentFiles`].
+    TorrentFiles {
+        instance: String,
+        hash: String,
+        files: Vec<TorrentFile>,
+    },
+    /// Trackers for a [`Subscription::TorrentTrackers`].
+    TorrentTrackers {
+        instance: String,
+        hash: String,
+        trackers: Vec<Tracker>,
+    },
+    /// Outcome of the [`ClientMessage::Action`] with the same `request_id`.
+    Ack {
+        request_id: u64,
+        result: Result<(), String>,
+    },
+    /// Polling an instance failed. The server keeps trying, except after
+    /// [`SyncError::SessionExpired`] which ends the updates of that instance.
+    SyncError { instance: String, error: SyncError },
+    /// The server couldn't make sense of a frame sent by the client.
+    Error { message: String },
+}
+
+/// Why the server couldn't get an update from an instance.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum SyncError {
+    /// The session with the torrent client is gone, the user has to log in again.
+    SessionExpired,
+    /// The torrent client could not be reached.
+    Unreachable(String),
+    /// The torrent client answered with an unexpected HTTP status.
+    Http {
+        status: u16,
+    },
+    /// The torrent client answered with something we could not decode.
+    Decode(String),
+    Other(String),
+}
+
+impl std::fmt::Display for SyncError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SyncError::SessionExpired => write!(f, "Your session has expired."),
+            SyncError::Unreachable(_) => {
+                write!(f, "The torrent client can't be reached, retrying…")
+            }
+            SyncError::Http { status } => {
+                write!(f, "The torrent client answered with HTTP {status}.")
+            }
+            SyncError::Decode(_) => write!(f, "The torrent client sent data we don't understand."),
+            SyncError::Other(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<&crate::backend::BackendError> for SyncError {
+    fn from(err: &crate::backend::BackendError) -> Self {
+        use crate::backend::BackendError;
+        use crate::qbittorrent::client::QbtError;
+        use crate::transmission::client::TransmissionError;
+
+        if err.is_session_expired() {
+            return SyncError::SessionExpired;
+        }
+        match err {
+            BackendError::Qbittorrent(QbtError::Unreachable(err))
+            | BackendError::Transmission(TransmissionError::Unreachable(err)) => {
+                SyncError::Unreachable(err.to_string())
+            }
+            BackendError::Qbittorrent(QbtError::Http { status })
+            | BackendError::Transmission(TransmissionError::Http { status }) => SyncError::Http {
+                status: status.as_u16(),
+            },
+            BackendError::Qbittorrent(QbtError::Decode { .. })
+            | BackendError::Transmission(TransmissionError::Decode(_)) => {
+                SyncError::Decode(err.to_string())
+            }
+            err => SyncError::Other(err.to_string()),
+        }
+    }
+}
@@ -0,0 +1,213 @@
+//! Recording real qBittorrent exchanges to fixture files, and replaying them in tests.
+//!
+//! A fixture is a JSON file with the requests made and what qBittorrent answered, in order:
+//!
+//! ```json
+//! {
+//!   "qbittorrent": "v4.6.5",
+//!   "exchanges": [
+//!     {
+//!       "request": { "method": "GET", "path": "/sync/maindata?rid=0" },
+//!       "response": { "status": 200, "body": { "rid": 1, "full_update": true } }
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! JSON bodies are kept as JSON so fixtures are easy to read and edit, any other body is kept as
+//! a string. SIDs and passwords are redacted when recording.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Context;
+use futures::future::BoxFuture;
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::transport::{QbtRequest, QbtResponse, Transport};
+use super::QbtError;
+
+/// Stands in for the SIDs and passwords of recorded sessions.
+const REDACTED: &str = "fixture";
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Fixture {
+    /// Version of the qBittorrent that was recorded, for humans.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub qbittorrent: Option<String>,
+    pub exchanges: Vec<Exchange>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Exchange {
+    pub request: QbtRequest,
+    pub response: FixtureResponse,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FixtureResponse {
+    pub status: u16,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub set_cookies: Vec<String>,
+    /// The JSON qBittorrent answered with, or a string holding any other body.
+    #[serde(default)]
+    pub body: Value,
+}
+
+impl Fixture {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let json = std::fs::read(path)
+            .with_context(|| format!("Could not read the fixture {}", path.display()))?;
+        serde_json::from_slice(&json).with_context(|| format!("Invalid fixture {}", path.display()))
+    }
+
+    fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let json = serde_json::to_vec_pretty(self)?;
+        std::fs::write(path, json)
+            .with_context(|| format!("Could not write the fixture {}", path.display()))
+    }
+}
+
+impl From<&QbtResponse> for FixtureResponse {
+    fn from(response: &QbtResponse) -> Self {
+        let body = serde_json::from_slice(&response.body).unwrap_or_else(|_| {
+            Value::String(String::from_utf8_lossy(&response.body).into_owned())
+        });
+        let set_cookies = response
+            .set_cookies
+            .iter()
+            .map(|cookie| match cookie.strip_prefix("SID=") {
+                Some(rest) => {
+                    let attributes = rest.split_once(';').map_or("", |(_, attrs)| attrs);
+                    format!("SID={REDACTED};{attributes}")
+                }
+                None => cookie.clone(),
+            })
+            .collect();
+        Self {
+            status: response.status.as_u16(),
+            set_cookies,
+            body,
+        }
+    }
+}
+
+impl TryFrom<&FixtureResponse> for QbtResponse {
+    type Error = QbtError;
+
+    fn try_from(response: &FixtureResponse) -> Result<Self, Self::Error> {
+        let body = match &response.body {
+            Value::String(text) => text.clone().into_bytes(),
+            Value::Null => Vec::new(),
+            json => serde_json::to_vec(json).map_err(|err| QbtError::decode(err, &[]))?,
+        };
+        Ok(Self {
+            status: StatusCode::from_u16(response.status).unwrap_or(StatusCode::OK),
+            set_cookies: response.set_cookies.clone(),
+            body,
+        })
+    }
+}
+
+/// Passes requests on to another transport and writes every exchange to a fixture file.
+#[derive(Debug)]
+pub struct RecordingTransport {
+    inner: Arc<dyn Transport>,
+    path: PathBuf,
+    fixture: Mutex<Fixture>,
+}
+
+impl RecordingTransport {
+    pub fn new(inner: Arc<dyn Transport>, path: PathBuf) -> Self {
+        tracing::warn!("Recording qBittorrent requests to {}", path.display());
+        Self {
+            inner,
+            path,
+            fixture: Mutex::default(),
+        }
+    }
+}
+
+impl Transport for RecordingTransport {
+    fn send(&self, request: QbtRequest) -> BoxFuture<'_, Result<QbtResponse, QbtError>> {
+        Box::pin(async move {
+            let res = self.inner.send(request.clone()).await;
+            if let Ok(response) = &res {
+                let exchange = Exchange {
+                    request: redact(request),
+                    response: response.into(),
+                };
+                let mut fixture = self.fixture.lock().unwrap();
+                fixture.exchanges.push(exchange);
+                if let Err(err) = fixture.save(&self.path) {
+                    tracing::error!(error = ?err, "Failed to record the exchange");
+                }
+            }
+            res
+        })
+    }
+}
+
+/// Keeps credentials out of fixtures, the SID is never recorded in the first place.
+fn redact(mut request: QbtRequest) -> QbtRequest {
+    for (key, value) in request.form.iter_mut().flatten() {
+        if key == "password" {
+            *value = REDACTED.to_owned();
+        }
+    }
+    request
+}
+
+/// Answers requests from a fixture: each one gets the first exchange not used yet that has the
+/// same method, path and form. Requests without a matching exchange get a 404.
+#[derive(Debug)]
+pub struct ReplayTransport {
+    exchanges: Mutex<Vec<Option<Exchange>>>,
+}
+
+impl ReplayTransport {
+    pub fn new(fixture: Fixture) -> Self {
+        Self {
+            exchanges: Mutex::new(fixture.exchanges.into_iter().map(Some).collect()),
+        }
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        Ok(Self::new(Fixture::load(path)?))
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn send(&self, request: QbtRequest) -> BoxFuture<'_, Result<QbtResponse, QbtError>> {
+        let request = redact(request);
+        let exchange = self
+            .exchanges
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .find(|exchange| {
+                exchange.as_ref().is_some_and(|exchange| {
+                    exchange.request.method == request.method
+                        && exchange.request.path == request.path
+                        && exchange.request.form == request.form
+                })
+            })
+            .and_then(Option::take);
+
+        Box::pin(async move {
+            match exchange {
+                Some(exchange) => QbtResponse::try_from(&exchange.response),
+                None => {
+                    tracing::warn!("No fixture for {} {}", request.method, request.path);
+                    Ok(QbtResponse {
+                        status: StatusCode::NOT_FOUND,
+                        set_cookies: Vec::new(),
+                        body: Vec::new(),
+                    })
+                }
+            }
+        })
+    }
+}
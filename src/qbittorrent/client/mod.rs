@@ -1,61 +1,195 @@
-use http::header::COOKIE;
-use reqwest::Response;
+pub mod fixtures;
+mod options;
+mod transport;
+mod version;
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use http::{Method, StatusCode};
+use serde::de::DeserializeOwned;
 use serde_json::Value;
+use tokio::sync::OnceCell;
 
+use super::proto::files::TorrentFile;
+use super::proto::peers::SyncTorrentPeers;
 use super::proto::sync::{MainData, SyncMainDataFull, SyncMainDataPartial};
 use super::proto::torrents::TorrentSummary;
+use super::proto::trackers::TrackerInfo;
+
+use fixtures::{RecordingTransport, ReplayTransport};
+pub use options::*;
+pub use transport::*;
+pub use version::*;
 
 pub static BASE_QBT_URL: &str = "http://localhost:9090/api/v2";
+pub static APP_API: &str = "/app";
+pub static WEBAPI_VERSION_API: &str = "/webapiVersion";
 pub static TORRENTS_API: &str = "/torrents";
 pub static INFO_API: &str = "/info";
 pub static SYNC_API: &str = "/sync";
 pub static MAINDATA_API: &str = "/maindata";
+pub static TORRENT_PEERS_API: &str = "/torrentPeers";
+pub static FILES_API: &str = "/files";
+pub static PAUSE_API: &str = "/pause";
+pub static RESUME_API: &str = "/resume";
+pub static STOP_API: &str = "/stop";
+pub static START_API: &str = "/start";
+pub static RECHECK_API: &str = "/recheck";
+pub static REANNOUNCE_API: &str = "/reannounce";
+pub static DELETE_API: &str = "/delete";
+pub static TRACKERS_API: &str = "/trackers";
+pub static ADD_API: &str = "/add";
+pub static SET_DOWNLOAD_LIMIT_API: &str = "/setDownloadLimit";
+pub static SET_UPLOAD_LIMIT_API: &str = "/setUploadLimit";
+pub static TRANSFER_API: &str = "/transfer";
+pub static SPEED_LIMITS_MODE_API: &str = "/speedLimitsMode";
+pub static TOGGLE_SPEED_LIMITS_MODE_API: &str = "/toggleSpeedLimitsMode";
 
 #[derive(Clone, Debug)]
 pub struct QbtClient {
-    base_url: String,
+    transport: Arc<dyn Transport>,
+    /// Detected on first use, to pick endpoint names.
+    webapi_version: Arc<OnceCell<WebApiVersion>>,
 }
 
 #[derive(thiserror::Error, Debug)]
 pub enum QbtError {
     #[error("Unauthenticated")]
     Unauthenticated,
+    /// qBittorrent answered 403, the SID expired or was never valid.
+    #[error("The qBittorrent session has expired")]
+    SessionExpired,
+    #[error("qBittorrent is unreachable: {0}")]
+    Unreachable(#[source] reqwest::Error),
+    #[error("qBittorrent answered with HTTP {status}")]
+    Http { status: StatusCode },
+    #[error("Could not decode the qBittorrent response: {error}, got `{snippet}`")]
+    Decode {
+        #[source]
+        error: serde_json::Error,
+        /// The beginning of the payload that failed to decode.
+        snippet: String,
+    },
+    /// qBittorrent answered 200 but didn't do what it was asked.
+    #[error("qBittorrent refused: {0}")]
+    Rejected(String),
     #[error(transparent)]
-    ReqwestError(#[from] reqwest::Error),
+    ReqwestError(reqwest::Error),
+}
+
+impl From<reqwest::Error> for QbtError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_connect() || err.is_timeout() {
+            return QbtError::Unreachable(err);
+        }
+        match err.status() {
+            Some(StatusCode::FORBIDDEN) => QbtError::SessionExpired,
+            Some(status) => QbtError::Http { status },
+            None => QbtError::ReqwestError(err),
+        }
+    }
+}
+
+impl QbtError {
+    /// How many bytes of an undecodable payload end up in [`QbtError::Decode`].
+    const SNIPPET_LEN: usize = 200;
+
+    fn decode(error: serde_json::Error, payload: &[u8]) -> Self {
+        let payload = String::from_utf8_lossy(payload);
+        let snippet = match payload.char_indices().nth(Self::SNIPPET_LEN) {
+            Some((end, _)) => format!("{}…", &payload[..end]),
+            None => payload.into_owned(),
+        };
+        QbtError::Decode { error, snippet }
+    }
 }
 
 impl QbtClient {
     #[tracing::instrument]
     pub fn new(base_url: &str) -> Self {
+        Self::with_options(base_url, QbtClientOptions::default())
+            .expect("the default options always build a client")
+    }
+
+    /// Fails when the TLS settings in `options` can't be applied, e.g. an unreadable CA.
+    #[tracing::instrument]
+    pub fn with_options(base_url: &str, options: QbtClientOptions) -> anyhow::Result<Self> {
+        Ok(Self::with_transport(HttpTransport::new(base_url, options)?))
+    }
+
+    pub fn with_transport(transport: impl Transport + 'static) -> Self {
         Self {
-            base_url: base_url.to_owned(),
+            transport: Arc::new(transport),
+            webapi_version: Default::default(),
         }
     }
 
-    #[tracing::instrument]
-    async fn get(&self, sid: &str, path: String) -> Result<Response, reqwest::Error> {
-        let url = format!("{}{}", self.base_url, path);
-        let client = reqwest::Client::builder().build()?;
+    /// A client answered from the fixture at `path` instead of a real qBittorrent.
+    pub fn replay(path: &Path) -> anyhow::Result<Self> {
+        Ok(Self::with_transport(ReplayTransport::load(path)?))
+    }
 
-        client
-            .get(url)
-            .header(COOKIE, format!("SID={}", sid).to_string())
-            .send()
-            .await
+    /// Records every request and its response to a fixture at `path`, see [`fixtures`].
+    pub fn record_to(self, path: PathBuf) -> Self {
+        Self::with_transport(RecordingTransport::new(self.transport, path))
     }
 
     #[tracing::instrument]
+    async fn get(&self, sid: &str, path: String) -> Result<QbtResponse, QbtError> {
+        let request = QbtRequest {
+            method: Method::GET,
+            path,
+            form: None,
+            files: Vec::new(),
+            sid: Some(sid.to_owned()),
+        };
+        check_status(self.transport.send(request).await?)
+    }
+
+    #[tracing::instrument(skip(form))]
+    async fn post(
+        &self,
+        sid: &str,
+        path: String,
+        form: &[(&str, String)],
+    ) -> Result<QbtResponse, QbtError> {
+        let request = QbtRequest {
+            method: Method::POST,
+            path,
+            form: Some(
+                form.iter()
+                    .map(|(key, value)| (key.to_string(), value.clone()))
+                    .collect(),
+            ),
+            files: Vec::new(),
+            sid: Some(sid.to_owned()),
+        };
+        check_status(self.transport.send(request).await?)
+    }
+
+    #[tracing::instrument(skip(password))]
     pub async fn auth_login(&self, username: String, password: String) -> Result<String, QbtError> {
         tracing::info!("Going to do login");
-        let url = format!("{}/auth/login", self.base_url);
-        let client = reqwest::Client::builder().build()?;
 
-        let params = [("username", username), ("password", password)];
-        let response = client.post(url).form(&params).send().await?;
-        let cookies: Vec<_> = response.cookies().collect();
-        let status = response.status();
+        let request = QbtRequest {
+            method: Method::POST,
+            path: "/auth/login".to_owned(),
+            form: Some(vec![
+                ("username".to_owned(), username),
+                ("password".to_owned(), password),
+            ]),
+            files: Vec::new(),
+            sid: None,
+        };
+        let response = check_status(self.transport.send(request).await?)?;
+        let cookies: Vec<_> = response
+            .set_cookies
+            .iter()
+            .filter_map(|cookie| cookie::Cookie::parse(cookie.as_str()).ok())
+            .collect();
 
-        tracing::info!(cookies = ?cookies, status = ?status);
+        tracing::info!(cookies = ?cookies, status = ?response.status);
 
         let Some(sid) = cookies.into_iter().find(|c| c.name() == "SID") else {
             return Err(QbtError::Unauthenticated);
@@ -65,37 +199,269 @@ impl QbtClient {
         Ok(sid.value().to_owned())
     }
 
+    /// Ends the session, qBittorrent forgets the SID.
+    #[tracing::instrument(skip(sid))]
+    pub async fn auth_logout(&self, sid: &str) -> Result<(), QbtError> {
+        self.post(sid, "/auth/logout".to_owned(), &[]).await?;
+        Ok(())
+    }
+
+    /// The WebAPI version of qBittorrent, asked for once and remembered.
+    #[tracing::instrument]
+    pub async fn webapi_version(&self, sid: &str) -> Result<WebApiVersion, QbtError> {
+        let version = self.webapi_version.get_or_try_init(|| async {
+            let url = format!("{}{}", APP_API, WEBAPI_VERSION_API);
+            let response = self.get(sid, url).await?;
+            let version = String::from_utf8_lossy(&response.body);
+            Ok::<_, QbtError>(version.parse().unwrap_or_else(|_| {
+                tracing::warn!("Unexpected WebAPI version `{version}`, assuming an old one");
+                WebApiVersion::default()
+            }))
+        });
+        let version = *version.await?;
+        tracing::debug!(%version);
+        Ok(version)
+    }
+
     #[tracing::instrument]
     pub async fn torrents_info(&self, sid: &str) -> Result<Vec<TorrentSummary>, QbtError> {
         let url = format!("{}{}", TORRENTS_API, INFO_API);
         let response = self.get(sid, url);
 
-        Ok(response.await?.json().await?)
+        decode_json(response.await?)
     }
 
     #[tracing::instrument]
     pub async fn sync_maindata(&self, sid: &str, rid: u64) -> Result<MainData, QbtError> {
-        let url = format!("{}{}{}?rid={}", self.base_url, SYNC_API, MAINDATA_API, rid);
-        let client = reqwest::Client::builder().build()?;
-
-        let response = client
-            .get(url)
-            .header(COOKIE, format!("SID={}", sid).to_string())
-            .send()
-            .await?;
-
-        let data = response.json::<Value>().await?;
-        let is_full_update = match data.get("full_update") {
-            Some(full_update) => serde_json::from_value(full_update.to_owned()).unwrap(),
-            None => false,
-        };
+        let url = format!("{}{}?rid={}", SYNC_API, MAINDATA_API, rid);
+        let response = self.get(sid, url).await?;
+
+        let payload = response.body;
+        let data: Value =
+            serde_json::from_slice(&payload).map_err(|err| QbtError::decode(err, &payload))?;
+        let is_full_update = data
+            .get("full_update")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
 
-        if is_full_update == true {
-            let data: SyncMainDataFull = serde_json::from_value(data).unwrap();
+        if is_full_update {
+            let data: SyncMainDataFull =
+                serde_json::from_value(data).map_err(|err| QbtError::decode(err, &payload))?;
             Ok(MainData::Full(data))
         } else {
-            let data: SyncMainDataPartial = serde_json::from_value(data).unwrap();
+            let data: SyncMainDataPartial =
+                serde_json::from_value(data).map_err(|err| QbtError::decode(err, &payload))?;
             Ok(MainData::Partial(data))
         }
     }
+
+    #[tracing::instrument]
+    pub async fn sync_torrent_peers(
+        &self,
+        sid: &str,
+        hash: &str,
+        rid: u64,
+    ) -> Result<SyncTorrentPeers, QbtError> {
+        let path = format!("{}{}", SYNC_API, TORRENT_PEERS_API);
+        let url = with_query(&path, &[("hash", hash), ("rid", &rid.to_string())]);
+        let response = self.get(sid, url);
+
+        decode_json(response.await?)
+    }
+
+    #[tracing::instrument]
+    pub async fn torrents_files(
+        &self,
+        sid: &str,
+        hash: &str,
+    ) -> Result<Vec<TorrentFile>, QbtError> {
+        let url = with_query(&format!("{}{}", TORRENTS_API, FILES_API), &[("hash", hash)]);
+        let response = self.get(sid, url);
+
+        decode_json(response.await?)
+    }
+
+    #[tracing::instrument]
+    pub async fn torrents_pause(&self, sid: &str, hashes: &[String]) -> Result<(), QbtError> {
+        let api = if self.webapi_version(sid).await? >= WebApiVersion::STOP_START {
+            STOP_API
+        } else {
+            PAUSE_API
+        };
+        self.torrents_command(sid, api, hashes).await
+    }
+
+    #[tracing::instrument]
+    pub async fn torrents_resume(&self, sid: &str, hashes: &[String]) -> Result<(), QbtError> {
+        let api = if self.webapi_version(sid).await? >= WebApiVersion::STOP_START {
+            START_API
+        } else {
+            RESUME_API
+        };
+        self.torrents_command(sid, api, hashes).await
+    }
+
+    #[tracing::instrument]
+    pub async fn torrents_recheck(&self, sid: &str, hashes: &[String]) -> Result<(), QbtError> {
+        self.torrents_command(sid, RECHECK_API, hashes).await
+    }
+
+    #[tracing::instrument]
+    pub async fn torrents_reannounce(&self, sid: &str, hashes: &[String]) -> Result<(), QbtError> {
+        self.torrents_command(sid, REANNOUNCE_API, hashes).await
+    }
+
+    #[tracing::instrument]
+    pub async fn torrents_delete(
+        &self,
+        sid: &str,
+        hashes: &[String],
+        delete_files: bool,
+    ) -> Result<(), QbtError> {
+        let url = format!("{}{}", TORRENTS_API, DELETE_API);
+        let form = [
+            ("hashes", hashes.join("|")),
+            ("deleteFiles", delete_files.to_string()),
+        ];
+        self.post(sid, url, &form).await?;
+        Ok(())
+    }
+
+    #[tracing::instrument]
+    pub async fn torrents_trackers(
+        &self,
+        sid: &str,
+        hash: &str,
+    ) -> Result<Vec<TrackerInfo>, QbtError> {
+        let url = with_query(
+            &format!("{}{}", TORRENTS_API, TRACKERS_API),
+            &[("hash", hash)],
+        );
+        let response = self.get(sid, url);
+
+        decode_json(response.await?)
+    }
+
+    /// Adds torrents from magnet links or URLs, and from `.torrent` files. `options` are any of
+    /// the other `/torrents/add` parameters, e.g. `savepath`.
+    #[tracing::instrument(skip(files))]
+    pub async fn torrents_add(
+        &self,
+        sid: &str,
+        urls: &[String],
+        files: Vec<UploadFile>,
+        options: &[(&str, String)],
+    ) -> Result<(), QbtError> {
+        let mut form: Vec<_> = options
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect();
+        if !urls.is_empty() {
+            form.push(("urls".to_owned(), urls.join("\n")));
+        }
+        let request = QbtRequest {
+            method: Method::POST,
+            path: format!("{}{}", TORRENTS_API, ADD_API),
+            form: Some(form),
+            files,
+            sid: Some(sid.to_owned()),
+        };
+        let response = check_status(self.transport.send(request).await?)?;
+        // qBittorrent answers 200 with `Fails.` when it couldn't add any of them.
+        if response.body.starts_with(b"Fails") {
+            return Err(QbtError::Rejected(
+                "none of the torrents could be added".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Sets the download limit (bytes/s) of torrents, `None` lifts it.
+    #[tracing::instrument]
+    pub async fn torrents_set_download_limit(
+        &self,
+        sid: &str,
+        hashes: &[String],
+        limit: Option<u64>,
+    ) -> Result<(), QbtError> {
+        self.torrents_set_limit(sid, SET_DOWNLOAD_LIMIT_API, hashes, limit)
+            .await
+    }
+
+    /// Sets the upload limit (bytes/s) of torrents, `None` lifts it.
+    #[tracing::instrument]
+    pub async fn torrents_set_upload_limit(
+        &self,
+        sid: &str,
+        hashes: &[String],
+        limit: Option<u64>,
+    ) -> Result<(), QbtError> {
+        self.torrents_set_limit(sid, SET_UPLOAD_LIMIT_API, hashes, limit)
+            .await
+    }
+
+    /// Whether the alternative speed limits apply.
+    #[tracing::instrument]
+    pub async fn transfer_speed_limits_mode(&self, sid: &str) -> Result<bool, QbtError> {
+        let url = format!("{}{}", TRANSFER_API, SPEED_LIMITS_MODE_API);
+        let response = self.get(sid, url).await?;
+        Ok(response.body.trim_ascii() == b"1")
+    }
+
+    /// Switches between the normal and the alternative speed limits.
+    #[tracing::instrument]
+    pub async fn transfer_toggle_speed_limits_mode(&self, sid: &str) -> Result<(), QbtError> {
+        let url = format!("{}{}", TRANSFER_API, TOGGLE_SPEED_LIMITS_MODE_API);
+        self.post(sid, url, &[]).await?;
+        Ok(())
+    }
+
+    async fn torrents_set_limit(
+        &self,
+        sid: &str,
+        api: &str,
+        hashes: &[String],
+        limit: Option<u64>,
+    ) -> Result<(), QbtError> {
+        let url = format!("{}{}", TORRENTS_API, api);
+        // qBittorrent takes 0 for no limit.
+        let form = [
+            ("hashes", hashes.join("|")),
+            ("limit", limit.unwrap_or(0).to_string()),
+        ];
+        self.post(sid, url, &form).await?;
+        Ok(())
+    }
+
+    /// Runs one of the `/torrents/*` commands that only take a list of hashes.
+    async fn torrents_command(
+        &self,
+        sid: &str,
+        api: &str,
+        hashes: &[String],
+    ) -> Result<(), QbtError> {
+        let url = format!("{}{}", TORRENTS_API, api);
+        self.post(sid, url, &[("hashes", hashes.join("|"))]).await?;
+        Ok(())
+    }
+}
+
+/// Turns the HTTP errors qBittorrent answers with into a [`QbtError`].
+fn check_status(response: QbtResponse) -> Result<QbtResponse, QbtError> {
+    match response.status {
+        status if status.is_success() => Ok(response),
+        StatusCode::FORBIDDEN => Err(QbtError::SessionExpired),
+        status => Err(QbtError::Http { status }),
+    }
+}
+
+/// Decodes a JSON response, keeping the beginning of the payload around when that fails.
+/// `path` with `params` as its encoded query string, the hashes come from the clients.
+fn with_query(path: &str, params: &[(&str, &str)]) -> String {
+    let query = serde_urlencoded::to_string(params).expect("string pairs always encode");
+    format!("{path}?{query}")
+}
+
+fn decode_json<T: DeserializeOwned>(response: QbtResponse) -> Result<T, QbtError> {
+    serde_json::from_slice(&response.body).map_err(|err| QbtError::decode(err, &response.body))
 }
@@ -0,0 +1,80 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::Context;
+use default_struct_builder::DefaultBuilder;
+
+/// How the [`super::QbtClient`] talks to qBittorrent.
+#[derive(DefaultBuilder, Clone, Debug)]
+pub struct QbtClientOptions {
+    /// How long to wait for the TCP and TLS handshakes.
+    pub(super) connect_timeout: Duration,
+    /// How long a whole request may take, including reading the response.
+    pub(super) timeout: Duration,
+    /// How many times a failed GET is retried. Other requests are never retried since they
+    /// may not be idempotent.
+    pub(super) retries: u32,
+    /// Delay before the first retry, doubled for every following one and jittered.
+    pub(super) retry_backoff: Duration,
+    /// PEM file with an extra root certificate, e.g. for a seedbox with a self-signed one.
+    #[builder(into)]
+    pub(super) ca_cert: Option<PathBuf>,
+    /// Don't verify the TLS certificate at all. Only use this on networks you trust.
+    pub(super) accept_invalid_certs: bool,
+    /// Credentials for a reverse proxy in front of qBittorrent.
+    #[builder(into)]
+    pub(super) basic_auth: Option<BasicAuth>,
+}
+
+impl Default for QbtClientOptions {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(5),
+            timeout: Duration::from_secs(30),
+            retries: 2,
+            retry_backoff: Duration::from_millis(250),
+            ca_cert: None,
+            accept_invalid_certs: false,
+            basic_auth: None,
+        }
+    }
+}
+
+impl QbtClientOptions {
+    /// Builds the `reqwest::Client` shared by every request, so that connections and TLS
+    /// sessions are pooled.
+    pub(crate) fn build_http_client(&self) -> anyhow::Result<reqwest::Client> {
+        // No cookie store: the client is shared by every user, their SIDs are sent explicitly.
+        let mut builder = reqwest::Client::builder()
+            .connect_timeout(self.connect_timeout)
+            .timeout(self.timeout)
+            .danger_accept_invalid_certs(self.accept_invalid_certs);
+
+        if let Some(path) = &self.ca_cert {
+            let pem = std::fs::read(path)
+                .with_context(|| format!("Could not read the CA certificate {}", path.display()))?;
+            let cert = reqwest::Certificate::from_pem(&pem)
+                .with_context(|| format!("Invalid CA certificate {}", path.display()))?;
+            builder = builder.add_root_certificate(cert);
+        }
+
+        Ok(builder.build()?)
+    }
+}
+
+/// HTTP basic auth credentials, or the ones bit-tower logs in with, see the users file.
+#[derive(Clone, serde::Deserialize)]
+pub struct BasicAuth {
+    pub username: String,
+    pub password: String,
+}
+
+impl std::fmt::Debug for BasicAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Requests are traced with the client, keep the password out of the logs.
+        f.debug_struct("BasicAuth")
+            .field("username", &self.username)
+            .field("password", &"********")
+            .finish()
+    }
+}
@@ -0,0 +1,161 @@
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use http::header::{COOKIE, SET_COOKIE};
+use http::{Method, StatusCode};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use super::{QbtClientOptions, QbtError};
+
+/// A request to the WebAPI, `path` being relative to its base URL, e.g. `/sync/maindata?rid=0`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct QbtRequest {
+    #[serde(with = "http_serde_method")]
+    pub method: Method,
+    pub path: String,
+    /// Sent as an url encoded form.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub form: Option<Vec<(String, String)>>,
+    /// Files to upload, the request is then sent as a multipart form along with `form`. Left
+    /// out of fixtures.
+    #[serde(skip)]
+    pub files: Vec<UploadFile>,
+    #[serde(skip)]
+    pub sid: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct UploadFile {
+    /// Name of the form field.
+    pub field: String,
+    pub file_name: String,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Clone, Debug)]
+pub struct QbtResponse {
+    pub status: StatusCode,
+    /// Raw `Set-Cookie` headers, that's how the SID is handed out.
+    pub set_cookies: Vec<String>,
+    pub body: Vec<u8>,
+}
+
+/// How a [`super::QbtClient`] gets its requests answered: over HTTP, or from fixtures in tests.
+pub trait Transport: std::fmt::Debug + Send + Sync {
+    fn send(&self, request: QbtRequest) -> BoxFuture<'_, Result<QbtResponse, QbtError>>;
+}
+
+/// Talks to qBittorrent over one pooled `reqwest::Client`.
+#[derive(Debug)]
+pub struct HttpTransport {
+    base_url: String,
+    http: reqwest::Client,
+    options: QbtClientOptions,
+}
+
+impl HttpTransport {
+    /// Fails when the TLS settings in `options` can't be applied, e.g. an unreadable CA.
+    pub fn new(base_url: &str, options: QbtClientOptions) -> anyhow::Result<Self> {
+        Ok(Self {
+            base_url: base_url.to_owned(),
+            http: options.build_http_client()?,
+            options,
+        })
+    }
+
+    async fn send_once(&self, request: &QbtRequest) -> Result<QbtResponse, QbtError> {
+        let url = format!("{}{}", self.base_url, request.path);
+        let mut builder = self.http.request(request.method.clone(), url);
+        if let Some(auth) = &self.options.basic_auth {
+            builder = builder.basic_auth(&auth.username, Some(&auth.password));
+        }
+        if let Some(sid) = &request.sid {
+            builder = builder.header(COOKIE, format!("SID={}", sid));
+        }
+        if !request.files.is_empty() {
+            let mut multipart = reqwest::multipart::Form::new();
+            for (key, value) in request.form.iter().flatten() {
+                multipart = multipart.text(key.clone(), value.clone());
+            }
+            for file in &request.files {
+                let part = reqwest::multipart::Part::bytes(file.bytes.clone())
+                    .file_name(file.file_name.clone());
+                multipart = multipart.part(file.field.clone(), part);
+            }
+            builder = builder.multipart(multipart);
+        } else if let Some(form) = &request.form {
+            builder = builder.form(form);
+        }
+
+        let response = builder.send().await?;
+        let set_cookies = response
+            .headers()
+            .get_all(SET_COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .map(str::to_owned)
+            .collect();
+        Ok(QbtResponse {
+            status: response.status(),
+            set_cookies,
+            body: response.bytes().await?.to_vec(),
+        })
+    }
+}
+
+impl Transport for HttpTransport {
+    /// GETs are idempotent, so they are retried with a jittered exponential backoff when
+    /// qBittorrent can't be reached or a proxy in front of it fails.
+    fn send(&self, request: QbtRequest) -> BoxFuture<'_, Result<QbtResponse, QbtError>> {
+        Box::pin(async move {
+            let retries = if request.method == Method::GET {
+                self.options.retries
+            } else {
+                0
+            };
+            let mut attempt = 0;
+            loop {
+                let res = self.send_once(&request).await;
+                let transient = match &res {
+                    Err(QbtError::Unreachable(_)) => true,
+                    Ok(response) => matches!(
+                        response.status,
+                        StatusCode::BAD_GATEWAY
+                            | StatusCode::SERVICE_UNAVAILABLE
+                            | StatusCode::GATEWAY_TIMEOUT
+                    ),
+                    Err(_) => false,
+                };
+                if !transient || attempt >= retries {
+                    return res;
+                }
+                let delay = retry_delay(self.options.retry_backoff, attempt);
+                tracing::debug!(path = %request.path, attempt, ?delay, "Retrying");
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+        })
+    }
+}
+
+/// Exponential backoff with jitter, so that clients failing together don't retry together.
+fn retry_delay(backoff: Duration, attempt: u32) -> Duration {
+    let delay = backoff.saturating_mul(1 << attempt.min(16));
+    delay.mul_f64(rand::thread_rng().gen_range(0.5..1.5))
+}
+
+/// Fixtures spell methods out, e.g. `"GET"`.
+mod http_serde_method {
+    use http::Method;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(method: &Method, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(method.as_str())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Method, D::Error> {
+        let method = String::deserialize(deserializer)?;
+        method.parse().map_err(serde::de::Error::custom)
+    }
+}
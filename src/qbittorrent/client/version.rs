@@ -0,0 +1,40 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// A WebAPI version as `/app/webapiVersion` reports it, e.g. `2.8.3`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct WebApiVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl WebApiVersion {
+    /// qBittorrent 5.0 renamed `/torrents/pause` and `/torrents/resume` to `stop` and `start`.
+    pub const STOP_START: Self = Self::new(2, 11, 0);
+
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+}
+
+impl FromStr for WebApiVersion {
+    type Err = std::num::ParseIntError;
+
+    /// Missing parts count as 0, `2.8` is `2.8.0`.
+    fn from_str(version: &str) -> Result<Self, Self::Err> {
+        let mut parts = version.trim().trim_start_matches('v').splitn(3, '.');
+        let mut next = || parts.next().map_or(Ok(0), str::parse);
+        Ok(Self::new(next()?, next()?, next()?))
+    }
+}
+
+impl fmt::Display for WebApiVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
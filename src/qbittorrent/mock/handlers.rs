@@ -0,0 +1,272 @@
+use axum::extract::{Query, Request, State};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Form, Json, Router};
+use axum_extra::extract::CookieJar;
+use http::header::SET_COOKIE;
+use http::StatusCode;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::MockQbittorrent;
+use crate::qbittorrent::client::WebApiVersion;
+
+pub(super) fn routes(qbt: MockQbittorrent) -> Router {
+    let webapi_version = qbt.with_state(|state| state.webapi_version.parse());
+    // qBittorrent 5 renamed pause/resume to stop/start.
+    let (pause, resume) = match webapi_version {
+        Ok(version) if version >= WebApiVersion::STOP_START => {
+            ("/torrents/stop", "/torrents/start")
+        }
+        _ => ("/torrents/pause", "/torrents/resume"),
+    };
+    let authenticated = Router::new()
+        .route("/app/version", get(app_version))
+        .route("/app/webapiVersion", get(app_webapi_version))
+        .route("/sync/maindata", get(sync_maindata))
+        .route("/sync/torrentPeers", get(sync_torrent_peers))
+        .route("/torrents/info", get(torrents_info))
+        .route("/torrents/files", get(torrents_files))
+        .route(pause, post(torrents_pause))
+        .route(resume, post(torrents_resume))
+        .route("/torrents/recheck", post(torrents_noop))
+        .route("/torrents/reannounce", post(torrents_noop))
+        .route("/torrents/delete", post(torrents_delete))
+        .route("/transfer/speedLimitsMode", get(transfer_speed_limits_mode))
+        .route(
+            "/transfer/toggleSpeedLimitsMode",
+            post(transfer_toggle_speed_limits_mode),
+        )
+        .route_layer(middleware::from_fn_with_state(qbt.clone(), require_session));
+
+    Router::new()
+        .route("/auth/login", post(auth_login))
+        .route("/auth/logout", post(auth_logout))
+        .merge(authenticated)
+        .layer(middleware::from_fn_with_state(
+            qbt.clone(),
+            injected_failures,
+        ))
+        .with_state(qbt)
+}
+
+/// Answers with the failures queued by [`MockQbittorrent::fail_next`] first.
+async fn injected_failures(
+    State(qbt): State<MockQbittorrent>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match qbt.with_state(|state| state.failures.pop_front()) {
+        Some(status) => status.into_response(),
+        None => next.run(request).await,
+    }
+}
+
+/// qBittorrent answers 403 to anything but login without a valid SID.
+async fn require_session(
+    State(qbt): State<MockQbittorrent>,
+    jar: CookieJar,
+    request: Request,
+    next: Next,
+) -> Response {
+    let valid = jar
+        .get("SID")
+        .is_some_and(|sid| qbt.with_state(|state| state.sessions.contains(sid.value())));
+    if valid {
+        next.run(request).await
+    } else {
+        (StatusCode::FORBIDDEN, "Forbidden").into_response()
+    }
+}
+
+#[derive(Deserialize)]
+struct LoginForm {
+    username: String,
+    password: String,
+}
+
+async fn auth_login(State(qbt): State<MockQbittorrent>, Form(form): Form<LoginForm>) -> Response {
+    let sid = qbt.with_state(|state| {
+        (form.username == state.username && form.password == state.password).then(|| {
+            let sid = uuid::Uuid::new_v4().simple().to_string();
+            state.sessions.insert(sid.clone());
+            sid
+        })
+    });
+    match sid {
+        Some(sid) => (
+            [(
+                SET_COOKIE,
+                format!("SID={sid}; HttpOnly; path=/; SameSite=Strict"),
+            )],
+            "Ok.",
+        )
+            .into_response(),
+        // Yes, qBittorrent answers 200 to a failed login.
+        None => "Fails.".into_response(),
+    }
+}
+
+async fn auth_logout(State(qbt): State<MockQbittorrent>, jar: CookieJar) -> StatusCode {
+    if let Some(sid) = jar.get("SID") {
+        qbt.with_state(|state| state.sessions.remove(sid.value()));
+    }
+    StatusCode::OK
+}
+
+async fn app_version(State(qbt): State<MockQbittorrent>) -> String {
+    qbt.with_state(|state| state.version.clone())
+}
+
+async fn app_webapi_version(State(qbt): State<MockQbittorrent>) -> String {
+    qbt.with_state(|state| state.webapi_version.clone())
+}
+
+#[derive(Deserialize)]
+struct RidQuery {
+    #[serde(default)]
+    rid: u64,
+}
+
+async fn sync_maindata(
+    State(qbt): State<MockQbittorrent>,
+    Query(query): Query<RidQuery>,
+) -> impl IntoResponse {
+    Json(qbt.with_state(|state| state.maindata(query.rid)))
+}
+
+#[derive(Deserialize)]
+struct PeersQuery {
+    hash: String,
+    #[serde(default)]
+    rid: u64,
+}
+
+/// Every active torrent gets one made up peer. The peers never change, so after the first
+/// (full) update there's nothing new to report.
+async fn sync_torrent_peers(
+    State(qbt): State<MockQbittorrent>,
+    Query(query): Query<PeersQuery>,
+) -> Response {
+    let torrent = qbt.with_state(|state| state.torrents.get(&query.hash).cloned());
+    let Some(torrent) = torrent else {
+        return (StatusCode::NOT_FOUND, "Torrent hash was not found").into_response();
+    };
+    let mut peers = json!({});
+    if query.rid == 0 && !torrent.paused {
+        peers["192.0.2.1:6881"] = json!({
+            "client": "qBittorrent/4.6.5",
+            "connection": "BT",
+            "country": "",
+            "country_code": "",
+            "dl_speed": torrent.info.upspeed,
+            "downloaded": torrent.info.uploaded,
+            "files": torrent.info.name,
+            "flags": "D U",
+            "flags_desc": "D = interested(local) and unchoked(peer)\nU = interested(peer) and unchoked(local)",
+            "ip": "192.0.2.1",
+            "port": 6881,
+            "progress": 0.5,
+            "relevance": 1,
+            "up_speed": torrent.info.dlspeed,
+            "uploaded": torrent.info.downloaded,
+        });
+    }
+    Json(json!({
+        "full_update": query.rid == 0,
+        "rid": query.rid + 1,
+        "peers": peers,
+        "show_flags": true,
+    }))
+    .into_response()
+}
+
+async fn torrents_info(State(qbt): State<MockQbittorrent>) -> impl IntoResponse {
+    let torrents: Vec<_> = qbt.with_state(|state| {
+        state
+            .torrents
+            .values()
+            .map(|torrent| torrent.info.clone())
+            .collect()
+    });
+    Json(torrents)
+}
+
+#[derive(Deserialize)]
+struct HashQuery {
+    hash: String,
+}
+
+async fn torrents_files(
+    State(qbt): State<MockQbittorrent>,
+    Query(query): Query<HashQuery>,
+) -> Response {
+    match qbt.with_state(|state| state.torrents.get(&query.hash).map(|t| t.files())) {
+        Some(files) => Json(files).into_response(),
+        None => (StatusCode::NOT_FOUND, "Torrent hash was not found").into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+struct HashesForm {
+    hashes: String,
+}
+
+async fn torrents_pause(
+    State(qbt): State<MockQbittorrent>,
+    Form(form): Form<HashesForm>,
+) -> StatusCode {
+    qbt.set_paused(&form.hashes, true);
+    StatusCode::OK
+}
+
+async fn torrents_resume(
+    State(qbt): State<MockQbittorrent>,
+    Form(form): Form<HashesForm>,
+) -> StatusCode {
+    qbt.set_paused(&form.hashes, false);
+    StatusCode::OK
+}
+
+/// Recheck and reannounce have nothing to simulate.
+async fn transfer_speed_limits_mode(State(qbt): State<MockQbittorrent>) -> &'static str {
+    if qbt.with_state(|state| state.alt_speed) {
+        "1"
+    } else {
+        "0"
+    }
+}
+
+async fn transfer_toggle_speed_limits_mode(State(qbt): State<MockQbittorrent>) -> StatusCode {
+    qbt.with_state(|state| state.alt_speed = !state.alt_speed);
+    StatusCode::OK
+}
+
+async fn torrents_noop(Form(_form): Form<HashesForm>) -> StatusCode {
+    StatusCode::OK
+}
+
+#[derive(Deserialize)]
+struct DeleteForm {
+    hashes: String,
+    #[serde(rename = "deleteFiles", default)]
+    delete_files: bool,
+}
+
+async fn torrents_delete(
+    State(qbt): State<MockQbittorrent>,
+    Form(form): Form<DeleteForm>,
+) -> StatusCode {
+    tracing::debug!(hashes = %form.hashes, delete_files = form.delete_files, "Deleting");
+    qbt.with_state(|state| {
+        let hashes: Vec<_> = state
+            .select(&form.hashes)
+            .map(|torrent| torrent.info.infohash_v1.clone())
+            .collect();
+        for hash in hashes {
+            state.torrents.remove(&hash);
+        }
+    });
+    StatusCode::OK
+}
@@ -0,0 +1,208 @@
+//! A fake qBittorrent WebAPI, to test against without a real qBittorrent.
+//!
+//! It implements login, `/sync/maindata` with the same `rid`/full/partial semantics as
+//! qBittorrent, peers and files, and the `/torrents/*` actions, over a simulation that moves
+//! torrents forward on every [`MockQbittorrent::tick`]. Tests drive it directly or with a
+//! [`Step`] script, e.g.:
+//!
+//! ```ignore
+//! let server = MockQbittorrent::default().spawn().await?;
+//! server.qbt.add_torrent(MockTorrent::default().name("debian.iso").size(1 << 30));
+//! let client = QbtClient::new(&server.base_url());
+//! ```
+//!
+//! To point a running bit-tower at it, see `examples/mock_qbittorrent.rs`.
+
+mod handlers;
+mod state;
+mod torrent;
+
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::Router;
+use http::StatusCode;
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+use state::MockState;
+pub use torrent::MockTorrent;
+
+/// Handle to the fake qBittorrent, cheap to clone. Every clone sees the same torrents.
+#[derive(Clone, Debug, Default)]
+pub struct MockQbittorrent {
+    state: Arc<Mutex<MockState>>,
+}
+
+/// One step of a script played with [`MockQbittorrent::play`].
+#[derive(Clone, Debug)]
+pub enum Step {
+    /// Wait for real, e.g. to let a poller catch up.
+    Wait(Duration),
+    /// Move the simulation forward without waiting.
+    Tick(Duration),
+    Add(MockTorrent),
+    Remove(String),
+    Pause(String),
+    Resume(String),
+    /// Forget every SID, as if qBittorrent restarted.
+    ExpireSessions,
+    /// Answer the next `count` requests with `status`.
+    Fail {
+        count: usize,
+        status: StatusCode,
+    },
+}
+
+impl MockQbittorrent {
+    /// Accepts these credentials instead of qBittorrent's default `admin`/`adminadmin`.
+    pub fn with_credentials(self, username: &str, password: &str) -> Self {
+        self.with_state(|state| {
+            state.username = username.to_owned();
+            state.password = password.to_owned();
+        });
+        self
+    }
+
+    /// Pretends to be another qBittorrent, e.g. `("v5.0.0", "2.11.2")`.
+    pub fn with_version(self, version: &str, webapi_version: &str) -> Self {
+        self.with_state(|state| {
+            state.version = version.to_owned();
+            state.webapi_version = webapi_version.to_owned();
+        });
+        self
+    }
+
+    /// Adds a torrent, returns its hash.
+    pub fn add_torrent(&self, torrent: MockTorrent) -> String {
+        self.with_state(|state| state.add_torrent(torrent))
+    }
+
+    pub fn remove_torrent(&self, hash: &str) {
+        self.with_state(|state| state.torrents.remove(hash));
+    }
+
+    pub fn pause(&self, hash: &str) {
+        self.set_paused(hash, true);
+    }
+
+    pub fn resume(&self, hash: &str) {
+        self.set_paused(hash, false);
+    }
+
+    pub fn is_paused(&self, hash: &str) -> Option<bool> {
+        self.with_state(|state| state.torrents.get(hash).map(|torrent| torrent.paused))
+    }
+
+    /// Whether the alternative speed limits apply.
+    pub fn alt_speed(&self) -> bool {
+        self.with_state(|state| state.alt_speed)
+    }
+
+    pub fn torrent_hashes(&self) -> Vec<String> {
+        self.with_state(|state| state.torrents.keys().cloned().collect())
+    }
+
+    /// Moves every active torrent forward by `dt`.
+    pub fn tick(&self, dt: Duration) {
+        self.with_state(|state| state.tick(dt));
+    }
+
+    pub fn expire_sessions(&self) {
+        self.with_state(|state| state.sessions.clear());
+    }
+
+    /// Answers the next `count` requests with `status`, whatever they ask for.
+    pub fn fail_next(&self, count: usize, status: StatusCode) {
+        self.with_state(|state| state.failures.extend(std::iter::repeat(status).take(count)));
+    }
+
+    /// Runs `steps` one after the other.
+    pub async fn play(&self, steps: impl IntoIterator<Item = Step>) {
+        for step in steps {
+            match step {
+                Step::Wait(duration) => tokio::time::sleep(duration).await,
+                Step::Tick(dt) => self.tick(dt),
+                Step::Add(torrent) => {
+                    self.add_torrent(torrent);
+                }
+                Step::Remove(hash) => self.remove_torrent(&hash),
+                Step::Pause(hash) => self.pause(&hash),
+                Step::Resume(hash) => self.resume(&hash),
+                Step::ExpireSessions => self.expire_sessions(),
+                Step::Fail { count, status } => self.fail_next(count, status),
+            }
+        }
+    }
+
+    /// Ticks the simulation every `period` in the background, like time passing on a real
+    /// qBittorrent.
+    pub fn simulate(&self, period: Duration) -> JoinHandle<()> {
+        let qbt = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                qbt.tick(period);
+            }
+        })
+    }
+
+    /// The WebAPI routes, nested under `/api/v2` like qBittorrent's.
+    pub fn router(&self) -> Router {
+        Router::new().nest("/api/v2", handlers::routes(self.clone()))
+    }
+
+    /// Serves the fake on a random local port until the returned server is dropped.
+    pub async fn spawn(self) -> std::io::Result<MockServer> {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+        let addr = listener.local_addr()?;
+        let router = self.router();
+        let task = tokio::spawn(async move {
+            if let Err(err) = axum::serve(listener, router).await {
+                tracing::error!(error = %err, "Mock qBittorrent stopped");
+            }
+        });
+        Ok(MockServer {
+            addr,
+            qbt: self,
+            task,
+        })
+    }
+
+    fn set_paused(&self, hash: &str, paused: bool) {
+        self.with_state(|state| {
+            for torrent in state.select(hash) {
+                torrent.paused = paused;
+                torrent.refresh();
+            }
+        });
+    }
+
+    fn with_state<T>(&self, f: impl FnOnce(&mut MockState) -> T) -> T {
+        f(&mut self.state.lock().unwrap())
+    }
+}
+
+/// A [`MockQbittorrent`] listening on [`MockServer::addr`].
+#[derive(Debug)]
+pub struct MockServer {
+    pub addr: SocketAddr,
+    pub qbt: MockQbittorrent,
+    task: JoinHandle<()>,
+}
+
+impl MockServer {
+    /// What to give [`crate::qbittorrent::client::QbtClient::new`].
+    pub fn base_url(&self) -> String {
+        format!("http://{}/api/v2", self.addr)
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use http::StatusCode;
+use serde_json::{json, Map, Value};
+
+use super::torrent::{MockTorrent, SimTorrent};
+use crate::qbittorrent::proto::transfer::{ConnectionStatus, ServerStateFull};
+
+/// How many `/sync/maindata` responses we remember to compute partial updates against, older
+/// `rid`s get a full update like qBittorrent does.
+const MAX_SNAPSHOTS: usize = 64;
+
+/// Everything the fake qBittorrent knows.
+#[derive(Debug)]
+pub(super) struct MockState {
+    pub username: String,
+    pub password: String,
+    pub version: String,
+    pub webapi_version: String,
+    /// SIDs handed out by `/auth/login` that haven't expired.
+    pub sessions: HashSet<String>,
+    pub torrents: BTreeMap<String, SimTorrent>,
+    pub refresh_interval: Duration,
+    /// Whether the alternative speed limits apply.
+    pub alt_speed: bool,
+    /// Last `rid` handed out, shared by every session like qBittorrent does.
+    rid: u64,
+    /// What each recent `rid` looked like.
+    snapshots: VecDeque<(u64, Value)>,
+    /// Statuses to answer the next requests with, whatever they ask for.
+    pub failures: VecDeque<StatusCode>,
+}
+
+impl Default for MockState {
+    fn default() -> Self {
+        Self {
+            username: "admin".to_owned(),
+            password: "adminadmin".to_owned(),
+            version: "v4.6.5".to_owned(),
+            webapi_version: "2.9.3".to_owned(),
+            sessions: HashSet::new(),
+            torrents: BTreeMap::new(),
+            refresh_interval: Duration::from_millis(1500),
+            alt_speed: false,
+            rid: 0,
+            snapshots: VecDeque::new(),
+            failures: VecDeque::new(),
+        }
+    }
+}
+
+impl MockState {
+    pub fn add_torrent(&mut self, torrent: MockTorrent) -> String {
+        let torrent = torrent.into_sim(now());
+        let hash = torrent.info.infohash_v1.clone();
+        self.torrents.insert(hash.clone(), torrent);
+        hash
+    }
+
+    pub fn tick(&mut self, dt: Duration) {
+        let now = now();
+        for torrent in self.torrents.values_mut() {
+            torrent.tick(dt, now);
+        }
+    }
+
+    /// The torrents matching a `hashes` parameter, `a|b|c` or `all`.
+    pub fn select<'a>(&'a mut self, hashes: &str) -> impl Iterator<Item = &'a mut SimTorrent> {
+        let all = hashes == "all";
+        let hashes: HashSet<String> = hashes.split('|').map(str::to_lowercase).collect();
+        self.torrents
+            .iter_mut()
+            .filter(move |(hash, _)| all || hashes.contains(*hash))
+            .map(|(_, torrent)| torrent)
+    }
+
+    pub fn server_state(&self) -> ServerStateFull {
+        let torrents = self.torrents.values().map(|torrent| &torrent.info);
+        let (mut state, mut downloading) = (ServerStateFull::default(), false);
+        for info in torrents {
+            state.dl_info_speed += info.dlspeed;
+            state.up_info_speed += info.upspeed;
+            state.dl_info_data += info.downloaded_session;
+            state.up_info_data += info.uploaded_session;
+            downloading |= info.dlspeed > 0;
+        }
+        state.dht_nodes = if downloading { 312 } else { 0 };
+        state.connection_status = ConnectionStatus::Connected;
+        state.use_alt_speed_limits = self.alt_speed;
+        state.refresh_interval = self.refresh_interval;
+        state
+    }
+
+    /// Answers `/sync/maindata?rid=`: everything when `rid` is 0 or too old, otherwise only
+    /// what changed since.
+    pub fn maindata(&mut self, rid: u64) -> Value {
+        let torrents: Map<String, Value> = self
+            .torrents
+            .iter()
+            .map(|(hash, torrent)| (hash.clone(), json!(torrent.info)))
+            .collect();
+        let current = json!({
+            "torrents": torrents,
+            "server_state": self.server_state(),
+            "categories": {},
+            "tags": [],
+        });
+
+        let previous = self
+            .snapshots
+            .iter()
+            .find(|(snapshot_rid, _)| rid != 0 && *snapshot_rid == rid)
+            .map(|(_, snapshot)| snapshot);
+
+        let mut response = match previous {
+            Some(previous) => diff_maindata(previous, &current),
+            None => {
+                let mut full = current.as_object().cloned().unwrap_or_default();
+                full.insert("full_update".to_owned(), Value::Bool(true));
+                full
+            }
+        };
+
+        self.rid += 1;
+        response.insert("rid".to_owned(), json!(self.rid));
+        self.snapshots.push_back((self.rid, current));
+        if self.snapshots.len() > MAX_SNAPSHOTS {
+            self.snapshots.pop_front();
+        }
+        Value::Object(response)
+    }
+}
+
+/// What changed between two `/sync/maindata` snapshots, the way qBittorrent reports it.
+fn diff_maindata(previous: &Value, current: &Value) -> Map<String, Value> {
+    let empty = Map::new();
+    let previous_torrents = previous["torrents"].as_object().unwrap_or(&empty);
+    let current_torrents = current["torrents"].as_object().unwrap_or(&empty);
+
+    let mut torrents = Map::new();
+    for (hash, torrent) in current_torrents {
+        match previous_torrents.get(hash) {
+            Some(previous) => {
+                let changed = diff_object(previous, torrent);
+                if !changed.is_empty() {
+                    torrents.insert(hash.clone(), Value::Object(changed));
+                }
+            }
+            None => {
+                torrents.insert(hash.clone(), torrent.clone());
+            }
+        }
+    }
+    let removed: Vec<_> = previous_torrents
+        .keys()
+        .filter(|hash| !current_torrents.contains_key(*hash))
+        .cloned()
+        .collect();
+
+    let mut diff = Map::new();
+    if !torrents.is_empty() {
+        diff.insert("torrents".to_owned(), Value::Object(torrents));
+    }
+    if !removed.is_empty() {
+        diff.insert("torrents_removed".to_owned(), json!(removed));
+    }
+    let server_state = diff_object(&previous["server_state"], &current["server_state"]);
+    if !server_state.is_empty() {
+        diff.insert("server_state".to_owned(), Value::Object(server_state));
+    }
+    diff
+}
+
+/// The fields of `current` that differ from `previous`.
+fn diff_object(previous: &Value, current: &Value) -> Map<String, Value> {
+    current
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter(|(key, value)| previous.get(key.as_str()) != Some(value))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
@@ -0,0 +1,187 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use default_struct_builder::DefaultBuilder;
+
+use crate::qbittorrent::proto::adapters::Limit;
+use crate::qbittorrent::proto::files::TorrentFile;
+use crate::qbittorrent::proto::torrents::{TorrentInfo, TorrentStatus};
+
+/// A torrent to add to the [`super::MockQbittorrent`].
+///
+/// ```ignore
+/// let torrent = MockTorrent::default()
+///     .name("debian-12.iso")
+///     .size(650 * 1024 * 1024)
+///     .download_rate(2 * 1024 * 1024);
+/// ```
+#[derive(DefaultBuilder, Default, Clone, Debug)]
+pub struct MockTorrent {
+    /// Info hash, derived from the name when not given.
+    #[builder(into)]
+    hash: Option<String>,
+    #[builder(into)]
+    name: String,
+    /// Total size in bytes.
+    size: u64,
+    /// Initial progress, between 0 and 1.
+    progress: f64,
+    /// Download speed (bytes/s) while the torrent is downloading.
+    download_rate: u64,
+    /// Upload speed (bytes/s) while the torrent is active.
+    upload_rate: u64,
+    paused: bool,
+    #[builder(into)]
+    category: String,
+    /// Comma separated, like qBittorrent sends them.
+    #[builder(into)]
+    tags: String,
+}
+
+impl MockTorrent {
+    pub(super) fn into_sim(self, now: u64) -> SimTorrent {
+        let hash = self.hash.unwrap_or_else(|| fake_hash(&self.name));
+        let size = self.size;
+        let completed = (size as f64 * self.progress.clamp(0.0, 1.0)).floor() as u64;
+        let info = TorrentInfo {
+            added_on: epoch(now),
+            infohash_v1: hash.clone(),
+            name: self.name.clone(),
+            category: self.category,
+            tags: self.tags,
+            size,
+            total_size: size,
+            completed,
+            downloaded: completed,
+            save_path: "/downloads".to_owned(),
+            content_path: format!("/downloads/{}", self.name),
+            magnet_uri: format!("magnet:?xt=urn:btih:{hash}&dn={}", self.name),
+            ratio_limit: Some(Limit::Global),
+            seeding_time_limit: Some(Limit::Global),
+            availability: 1.0,
+            ..Default::default()
+        };
+        let mut torrent = SimTorrent {
+            info,
+            download_rate: self.download_rate,
+            upload_rate: self.upload_rate,
+            paused: self.paused,
+        };
+        torrent.refresh();
+        torrent
+    }
+}
+
+/// A torrent as the mock keeps track of it.
+#[derive(Clone, Debug)]
+pub(super) struct SimTorrent {
+    pub info: TorrentInfo,
+    /// Speeds the torrent runs at whenever it is active.
+    pub download_rate: u64,
+    pub upload_rate: u64,
+    pub paused: bool,
+}
+
+impl SimTorrent {
+    pub fn is_complete(&self) -> bool {
+        self.info.completed >= self.info.size
+    }
+
+    /// Moves the transfer forward by `dt`.
+    pub fn tick(&mut self, dt: Duration, now: u64) {
+        if self.paused {
+            return;
+        }
+        let transferred = |rate: u64| (rate as f64 * dt.as_secs_f64()) as u64;
+        let was_complete = self.is_complete();
+        if !was_complete {
+            let downloaded =
+                transferred(self.download_rate).min(self.info.size - self.info.completed);
+            self.info.completed += downloaded;
+            self.info.downloaded += downloaded;
+            self.info.downloaded_session += downloaded;
+        }
+        let uploaded = transferred(self.upload_rate);
+        self.info.uploaded += uploaded;
+        self.info.uploaded_session += uploaded;
+        self.info.time_active += dt;
+        if was_complete {
+            self.info.seeding_time += dt;
+        }
+        if self.info.dlspeed > 0 || self.info.upspeed > 0 {
+            self.info.last_activity = epoch(now);
+        }
+        if !was_complete && self.is_complete() {
+            self.info.completion_on = epoch(now);
+            self.info.seen_complete = epoch(now);
+        }
+        self.refresh();
+    }
+
+    /// Recomputes everything derived from the amounts transferred and whether we're paused.
+    pub fn refresh(&mut self) {
+        let complete = self.is_complete();
+        let info = &mut self.info;
+        info.amount_left = info.size - info.completed;
+        info.progress = if info.size > 0 {
+            info.completed as f64 / info.size as f64
+        } else {
+            1.0
+        };
+        info.ratio = if info.downloaded > 0 {
+            info.uploaded as f64 / info.downloaded as f64
+        } else {
+            0.0
+        };
+        info.dlspeed = if self.paused || complete {
+            0
+        } else {
+            self.download_rate
+        };
+        info.upspeed = if self.paused { 0 } else { self.upload_rate };
+        info.eta = (info.dlspeed > 0)
+            .then(|| Duration::from_secs(info.amount_left.div_ceil(info.dlspeed)));
+        info.state = match (self.paused, complete) {
+            (true, true) => TorrentStatus::PausedUP,
+            (true, false) => TorrentStatus::PausedDL,
+            (false, true) if info.upspeed > 0 => TorrentStatus::Uploading,
+            (false, true) => TorrentStatus::StalledUP,
+            (false, false) if info.dlspeed > 0 => TorrentStatus::Downloading,
+            (false, false) => TorrentStatus::StalledDL,
+        };
+        info.num_seeds = if info.dlspeed > 0 { 3 } else { 0 };
+        info.num_leechs = if info.upspeed > 0 { 2 } else { 0 };
+    }
+
+    /// The single file this torrent consists of.
+    pub fn files(&self) -> Vec<TorrentFile> {
+        vec![TorrentFile {
+            index: 0,
+            name: self.info.name.clone(),
+            size: self.info.size,
+            progress: self.info.progress,
+            priority: 1,
+            is_seed: self.is_complete(),
+            piece_range: vec![0, self.info.size / (4 * 1024 * 1024)],
+            availability: self.info.availability,
+        }]
+    }
+}
+
+fn epoch(secs: u64) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp(secs as i64, 0)
+}
+
+/// A stable, info hash looking string.
+fn fake_hash(name: &str) -> String {
+    (0..3_u8)
+        .map(|salt| {
+            let mut hasher = DefaultHasher::new();
+            (salt, name).hash(&mut hasher);
+            format!("{:016x}", hasher.finish())
+        })
+        .collect::<String>()[..40]
+        .to_owned()
+}
@@ -2,3 +2,6 @@ pub mod proto;
 
 #[cfg(feature = "ssr")]
 pub mod client;
+
+#[cfg(feature = "mock")]
+pub mod mock;
@@ -0,0 +1,200 @@
+//! Serde adapters between qBittorrent's numbers and the types they stand for.
+//!
+//! qBittorrent sends everything as plain JSON numbers and spells special values with sentinels:
+//! `-1` for "no limit", `-2` for "use the global setting", `8640000` for an infinite ETA, `0` or
+//! `4294967295` for "never". Each adapter reads those into a proper type and writes them back the
+//! same way, so what we forward over the websocket still looks like what qBittorrent sent. Use
+//! them with `#[serde(with = "adapters::bytes")]`, or `adapters::bytes::option` together with
+//! `#[serde(default)]` on the fields of partial updates.
+
+use std::fmt;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// qBittorrent's ETA for torrents that won't complete, 100 days.
+pub const ETA_INFINITY: i64 = 8_640_000;
+
+/// A limit that may defer to qBittorrent's global setting. Fields holding one are
+/// `Option<Limit<T>>`, `None` meaning no limit at all.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Limit<T> {
+    /// The global setting applies, `-2`.
+    Global,
+    Value(T),
+}
+
+/// An integer on the wire. Some versions send whole numbers as floats, so those are read too.
+#[derive(Debug, Clone, Copy)]
+struct Int(i64);
+
+impl Serialize for Int {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Int {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct IntVisitor;
+
+        impl Visitor<'_> for IntVisitor {
+            type Value = Int;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a number")
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Int, E> {
+                Ok(Int(v))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Int, E> {
+                Ok(Int(i64::try_from(v).unwrap_or(i64::MAX)))
+            }
+
+            fn visit_f64<E: de::Error>(self, v: f64) -> Result<Int, E> {
+                Ok(Int(v as i64))
+            }
+        }
+
+        deserializer.deserialize_any(IntVisitor)
+    }
+}
+
+macro_rules! adapter {
+    ($(#[$doc:meta])* $name:ident: $wire:ty => $value:ty, $from_wire:expr, $to_wire:expr) => {
+        $(#[$doc])*
+        pub mod $name {
+            use super::*;
+
+            pub fn serialize<S: Serializer>(value: &$value, serializer: S) -> Result<S::Ok, S::Error> {
+                to_wire(value).serialize(serializer)
+            }
+
+            pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<$value, D::Error> {
+                <$wire>::deserialize(deserializer).map(from_wire)
+            }
+
+            fn from_wire(wire: $wire) -> $value {
+                ($from_wire)(wire)
+            }
+
+            fn to_wire(value: &$value) -> $wire {
+                ($to_wire)(value)
+            }
+
+            /// For fields that partial updates leave out.
+            pub mod option {
+                use super::*;
+
+                pub fn serialize<S: Serializer>(
+                    value: &Option<$value>,
+                    serializer: S,
+                ) -> Result<S::Ok, S::Error> {
+                    value.as_ref().map(to_wire).serialize(serializer)
+                }
+
+                pub fn deserialize<'de, D: Deserializer<'de>>(
+                    deserializer: D,
+                ) -> Result<Option<$value>, D::Error> {
+                    Ok(Option::<$wire>::deserialize(deserializer)?.map(from_wire))
+                }
+            }
+        }
+    };
+}
+
+adapter!(
+    /// Byte counts, speeds and other counts. qBittorrent uses `-1` for "unknown", read as 0.
+    bytes: Int => u64,
+    |Int(n): Int| n.max(0) as u64,
+    |n: &u64| Int(*n as i64)
+);
+
+adapter!(
+    /// A Unix timestamp (seconds), `None` for "never".
+    epoch: Int => Option<DateTime<Utc>>,
+    |Int(secs): Int| {
+        // Before 4.2 "never" was a -1 sent as an unsigned 32 bits integer.
+        (secs > 0 && secs < i64::from(u32::MAX))
+            .then(|| DateTime::from_timestamp(secs, 0))
+            .flatten()
+    },
+    |time: &Option<DateTime<Utc>>| Int(time.map_or(-1, |time| time.timestamp()))
+);
+
+adapter!(
+    /// A duration in seconds.
+    seconds: Int => Duration,
+    |Int(secs): Int| Duration::from_secs(secs.max(0) as u64),
+    |duration: &Duration| Int(duration.as_secs() as i64)
+);
+
+adapter!(
+    /// A duration in milliseconds.
+    millis: Int => Duration,
+    |Int(millis): Int| Duration::from_millis(millis.max(0) as u64),
+    |duration: &Duration| Int(duration.as_millis() as i64)
+);
+
+adapter!(
+    /// An ETA in seconds, `None` when the torrent isn't going anywhere.
+    eta: Int => Option<Duration>,
+    |Int(secs): Int| (0..ETA_INFINITY).contains(&secs).then(|| Duration::from_secs(secs as u64)),
+    |eta: &Option<Duration>| Int(eta.map_or(ETA_INFINITY, |eta| eta.as_secs() as i64))
+);
+
+adapter!(
+    /// A speed limit (bytes/s), where both 0 and -1 mean unlimited.
+    rate_limit: Int => Option<Limit<u64>>,
+    |Int(n): Int| match n {
+        -2 => Some(Limit::Global),
+        n if n > 0 => Some(Limit::Value(n as u64)),
+        _ => None,
+    },
+    |limit: &Option<Limit<u64>>| Int(match limit {
+        None => -1,
+        Some(Limit::Global) => -2,
+        Some(Limit::Value(n)) => *n as i64,
+    })
+);
+
+adapter!(
+    /// A share ratio limit.
+    ratio_limit: f64 => Option<Limit<f64>>,
+    |ratio: f64| match ratio {
+        ratio if ratio == -2.0 => Some(Limit::Global),
+        ratio if ratio >= 0.0 => Some(Limit::Value(ratio)),
+        _ => None,
+    },
+    |limit: &Option<Limit<f64>>| match limit {
+        None => -1.0,
+        Some(Limit::Global) => -2.0,
+        Some(Limit::Value(ratio)) => *ratio,
+    }
+);
+
+adapter!(
+    /// A seeding time limit, which qBittorrent keeps in minutes.
+    minutes_limit: Int => Option<Limit<Duration>>,
+    |Int(minutes): Int| match minutes {
+        -2 => Some(Limit::Global),
+        minutes if minutes >= 0 => Some(Limit::Value(Duration::from_secs(minutes as u64 * 60))),
+        _ => None,
+    },
+    |limit: &Option<Limit<Duration>>| Int(match limit {
+        None => -1,
+        Some(Limit::Global) => -2,
+        Some(Limit::Value(duration)) => (duration.as_secs() / 60) as i64,
+    })
+);
+
+adapter!(
+    /// A position in the queue, `None` when queueing is off or the torrent is seeding.
+    queue_position: Int => Option<u64>,
+    |Int(n): Int| (n > 0).then_some(n as u64),
+    |position: &Option<u64>| Int(position.map_or(0, |n| n as i64))
+);
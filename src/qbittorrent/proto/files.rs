@@ -0,0 +1,18 @@
+use serde::{Deserialize, Serialize};
+
+use super::adapters;
+
+/// One entry of the `/torrents/files` response.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct TorrentFile {
+    pub index: u64,   // File index
+    pub name: String, // File name, including its relative path
+    #[serde(with = "adapters::bytes")]
+    pub size: u64, // File size (bytes)
+    pub progress: f64, // File progress (percentage/100)
+    pub priority: i64, // File priority. 0 means the file is not downloaded.
+    pub is_seed: bool, // True if file is seeding/complete
+    pub piece_range: Vec<u64>, // The first and last piece index of the file
+    pub availability: f64, // Percentage of file pieces currently available (percentage/100)
+}
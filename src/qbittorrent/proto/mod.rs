@@ -1,3 +1,7 @@
+pub mod adapters;
+pub mod files;
+pub mod peers;
 pub mod sync;
 pub mod torrents;
+pub mod trackers;
 pub mod transfer;
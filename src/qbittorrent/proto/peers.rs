@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::adapters;
+
+/// Response of `/sync/torrentPeers`, peers are keyed by `ip:port`.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SyncTorrentPeers {
+    #[serde(default)]
+    pub full_update: bool,
+    pub rid: u64,
+    #[serde(default)]
+    pub peers: HashMap<String, PeerInfoPartial>,
+    #[serde(default)]
+    pub peers_removed: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PeerInfoPartial {
+    pub client: Option<String>,       // Client used by the peer
+    pub connection: Option<String>,   // Connection type (BT, uTP, Web)
+    pub country: Option<String>,      // Country name, when geolocation is enabled
+    pub country_code: Option<String>, // Country code, when geolocation is enabled
+    #[serde(default, with = "adapters::bytes::option")]
+    pub dl_speed: Option<u64>, // Download speed from this peer (bytes/s)
+    #[serde(default, with = "adapters::bytes::option")]
+    pub downloaded: Option<u64>, // Amount of data downloaded from this peer (bytes)
+    pub files: Option<String>,        // Files of the torrent this peer is currently transferring
+    pub flags: Option<String>,        // Peer flags, e.g. "D U I"
+    pub flags_desc: Option<String>,   // Human readable description of the flags
+    pub ip: Option<String>,           // Peer IP address
+    pub port: Option<u16>,            // Peer port
+    pub progress: Option<f64>,        // Peer progress (percentage/100)
+    pub relevance: Option<f64>,       // How much of what we need this peer has (percentage/100)
+    #[serde(default, with = "adapters::bytes::option")]
+    pub up_speed: Option<u64>, // Upload speed to this peer (bytes/s)
+    #[serde(default, with = "adapters::bytes::option")]
+    pub uploaded: Option<u64>, // Amount of data uploaded to this peer (bytes)
+}
+
+impl SyncTorrentPeers {
+    pub fn apply_partial(&mut self, partial: SyncTorrentPeers) {
+        self.rid = partial.rid;
+        for key in partial.peers_removed {
+            self.peers.remove(&key);
+        }
+        for (key, peer) in partial.peers {
+            self.peers.entry(key).or_default().apply_partial(peer);
+        }
+    }
+}
+
+impl PeerInfoPartial {
+    pub fn apply_partial(&mut self, partial: PeerInfoPartial) {
+        if partial.client.is_some() {
+            self.client = partial.client;
+        }
+        if partial.connection.is_some() {
+            self.connection = partial.connection;
+        }
+        if partial.country.is_some() {
+            self.country = partial.country;
+        }
+        if partial.country_code.is_some() {
+            self.country_code = partial.country_code;
+        }
+        if partial.dl_speed.is_some() {
+            self.dl_speed = partial.dl_speed;
+        }
+        if partial.downloaded.is_some() {
+            self.downloaded = partial.downloaded;
+        }
+        if partial.files.is_some() {
+            self.files = partial.files;
+        }
+        if partial.flags.is_some() {
+            self.flags = partial.flags;
+        }
+        if partial.flags_desc.is_some() {
+            self.flags_desc = partial.flags_desc;
+        }
+        if partial.ip.is_some() {
+            self.ip = partial.ip;
+        }
+        if partial.port.is_some() {
+            self.port = partial.port;
+        }
+        if partial.progress.is_some() {
+            self.progress = partial.progress;
+        }
+        if partial.relevance.is_some() {
+            self.relevance = partial.relevance;
+        }
+        if partial.up_speed.is_some() {
+            self.up_speed = partial.up_speed;
+        }
+        if partial.uploaded.is_some() {
+            self.uploaded = partial.uploaded;
+        }
+    }
+}
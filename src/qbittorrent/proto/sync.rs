@@ -1,16 +1,22 @@
 use std::collections::HashMap;
+use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 use super::torrents::{TorrentInfo, TorrentInfoPartial};
 use super::transfer::{ServerStateFull, ServerStatePartial};
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
 pub struct SyncMainDataFull {
     pub full_update: bool,
     pub rid: u64,
     pub torrents: HashMap<String, TorrentInfo>,
     pub server_state: ServerStateFull,
+    /// Categories, tags, trackers and whatever else this version doesn't look at.
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
 }
 
 impl SyncMainDataFull {
@@ -27,6 +33,12 @@ pub struct SyncMainDataPartial {
     pub rid: u64,
     pub torrents: Option<HashMap<String, TorrentInfoPartial>>,
     pub server_state: Option<ServerStatePartial>,
+    /// Hashes of the torrents removed since `rid`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub torrents_removed: Option<Vec<String>>,
+    /// Changed categories, tags and whatever else this version doesn't look at.
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -42,6 +54,15 @@ impl MainData {
             Self::Partial(pd) => pd.rid,
         }
     }
+
+    /// qBittorrent's web UI refresh interval, if this update carries it.
+    pub fn refresh_interval(&self) -> Option<Duration> {
+        let refresh_interval = match self {
+            Self::Full(fd) => Some(fd.server_state.refresh_interval),
+            Self::Partial(pd) => pd.server_state.as_ref().and_then(|ss| ss.refresh_interval),
+        }?;
+        (!refresh_interval.is_zero()).then_some(refresh_interval)
+    }
 }
 
 impl Default for MainData {
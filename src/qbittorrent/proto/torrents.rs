@@ -1,88 +1,160 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::adapters::{self, Limit};
 
+/// A torrent's `state`. States we don't know about yet end up in [`TorrentStatus::Other`] instead
+/// of failing the whole update.
 #[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(from = "String", into = "String")]
 pub enum TorrentStatus {
-    #[serde(rename = "error")]
     /// Some error occurred, applies to paused torrents
     Error,
 
-    #[serde(rename = "missingFiles")]
     /// Torrent data files is missing
     MissingFiles,
 
-    #[serde(rename = "uploading")]
     /// Torrent is being seeded and data is being transferred
     Uploading,
 
-    #[serde(rename = "pausedUP")]
     /// Torrent is paused and has finished downloading
     PausedUP,
 
-    #[serde(rename = "queuedUP")]
+    /// Torrent is stopped and has finished downloading (qBittorrent 5 and later)
+    StoppedUP,
+
     /// Queuing is enabled and torrent is queued for upload
     QueuedUP,
 
-    #[serde(rename = "stalledUP")]
     /// Torrent is being seeded, but no connection were made
     StalledUP,
 
-    #[serde(rename = "checkingUP")]
     /// Torrent has finished downloading and is being checked
     CheckingUP,
 
-    #[serde(rename = "forcedUP")]
     /// Torrent is forced to uploading and ignore queue limit
     ForcedUP,
 
-    #[serde(rename = "allocating")]
     /// Torrent is allocating disk space for download
     Allocating,
 
-    #[serde(rename = "downloading")]
     /// Torrent is being downloaded and data is being transferred
     Downloading,
 
-    #[serde(rename = "metaDL")]
     /// Torrent has just started downloading and is fetching metadata
     MetaDL,
 
-    #[serde(rename = "pausedDL")]
     /// Torrent is paused and has NOT finished downloading
     PausedDL,
 
-    #[serde(rename = "queuedDL")]
+    /// Torrent is stopped and has NOT finished downloading (qBittorrent 5 and later)
+    StoppedDL,
+
     /// Queuing is enabled and torrent is queued for download
     QueuedDL,
 
-    #[serde(rename = "stalledDL")]
     /// Torrent is being downloaded, but no connection were made
     StalledDL,
 
-    #[serde(rename = "checkingDL")]
     /// Same as checkingUP, but torrent has NOT finished downloading
     CheckingDL,
 
-    #[serde(rename = "forcedDL")]
     /// Torrent is forced to downloading to ignore queue limit
     ForcedDL,
 
-    #[serde(rename = "checkingResumeData")]
     /// Checking resume data on qBt startup
     CheckingResumeData,
 
-    #[serde(rename = "moving")]
     /// Torrent is moving to another location
     Moving,
 
     #[default]
-    #[serde(rename = "unknown")]
     /// Unknown status
     Unknown,
+
+    /// A state added by a newer qBittorrent, as it was sent
+    Other(String),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+impl TorrentStatus {
+    /// The state as qBittorrent spells it.
+    pub fn as_str(&self) -> &str {
+        match self {
+            TorrentStatus::Error => "error",
+            TorrentStatus::MissingFiles => "missingFiles",
+            TorrentStatus::Uploading => "uploading",
+            TorrentStatus::PausedUP => "pausedUP",
+            TorrentStatus::StoppedUP => "stoppedUP",
+            TorrentStatus::QueuedUP => "queuedUP",
+            TorrentStatus::StalledUP => "stalledUP",
+            TorrentStatus::CheckingUP => "checkingUP",
+            TorrentStatus::ForcedUP => "forcedUP",
+            TorrentStatus::Allocating => "allocating",
+            TorrentStatus::Downloading => "downloading",
+            TorrentStatus::MetaDL => "metaDL",
+            TorrentStatus::PausedDL => "pausedDL",
+            TorrentStatus::StoppedDL => "stoppedDL",
+            TorrentStatus::QueuedDL => "queuedDL",
+            TorrentStatus::StalledDL => "stalledDL",
+            TorrentStatus::CheckingDL => "checkingDL",
+            TorrentStatus::ForcedDL => "forcedDL",
+            TorrentStatus::CheckingResumeData => "checkingResumeData",
+            TorrentStatus::Moving => "moving",
+            TorrentStatus::Unknown => "unknown",
+            TorrentStatus::Other(state) => state,
+        }
+    }
+}
+
+impl From<String> for TorrentStatus {
+    fn from(state: String) -> Self {
+        match state.as_str() {
+            "error" => TorrentStatus::Error,
+            "missingFiles" => TorrentStatus::MissingFiles,
+            "uploading" => TorrentStatus::Uploading,
+            "pausedUP" => TorrentStatus::PausedUP,
+            "stoppedUP" => TorrentStatus::StoppedUP,
+            "queuedUP" => TorrentStatus::QueuedUP,
+            "stalledUP" => TorrentStatus::StalledUP,
+            "checkingUP" => TorrentStatus::CheckingUP,
+            "forcedUP" => TorrentStatus::ForcedUP,
+            "allocating" => TorrentStatus::Allocating,
+            "downloading" => TorrentStatus::Downloading,
+            "metaDL" => TorrentStatus::MetaDL,
+            "pausedDL" => TorrentStatus::PausedDL,
+            "stoppedDL" => TorrentStatus::StoppedDL,
+            "queuedDL" => TorrentStatus::QueuedDL,
+            "stalledDL" => TorrentStatus::StalledDL,
+            "checkingDL" => TorrentStatus::CheckingDL,
+            "forcedDL" => TorrentStatus::ForcedDL,
+            "checkingResumeData" => TorrentStatus::CheckingResumeData,
+            "moving" => TorrentStatus::Moving,
+            "unknown" => TorrentStatus::Unknown,
+            _ => TorrentStatus::Other(state),
+        }
+    }
+}
+
+impl From<TorrentStatus> for String {
+    fn from(state: TorrentStatus) -> Self {
+        match state {
+            TorrentStatus::Other(state) => state,
+            state => state.as_str().to_owned(),
+        }
+    }
+}
+
+/// One entry of the `/torrents/info` response.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
 pub struct TorrentSummary {
     pub name: String,
+    /// Torrent hash, before qBittorrent 4.4 this is the only one there is.
+    pub hash: String,
     pub infohash_v1: String,
     pub progress: f64,
     pub state: TorrentStatus,
@@ -96,100 +168,165 @@ pub struct TorrentSummaryPartial {
     pub state: Option<TorrentStatus>,
 }
 
+/// A torrent in a full `/sync/maindata` update. Fields older qBittorrents don't send are
+/// defaulted.
 #[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
 pub struct TorrentInfo {
-    pub added_on: f64,     // Time (Unix Epoch) when the torrent was added to the client
-    pub amount_left: f64,  // Amount of data left to download (bytes)
-    pub auto_tmm: bool,    // Whether this torrent is managed by Automatic Torrent Management
+    #[serde(with = "adapters::epoch")]
+    pub added_on: Option<DateTime<Utc>>, // Time (Unix Epoch) when the torrent was added to the client
+    #[serde(with = "adapters::bytes")]
+    pub amount_left: u64, // Amount of data left to download (bytes)
+    pub auto_tmm: bool, // Whether this torrent is managed by Automatic Torrent Management
     pub availability: f64, // Percentage of file pieces currently available
-    pub category: String,  // Category of the torrent
-    pub completed: f64,    // Amount of transfer data completed (bytes)
-    pub completion_on: f64, // Time (Unix Epoch) when the torrent completed
+    pub category: String, // Category of the torrent
+    #[serde(with = "adapters::bytes")]
+    pub completed: u64, // Amount of transfer data completed (bytes)
+    #[serde(with = "adapters::epoch")]
+    pub completion_on: Option<DateTime<Utc>>, // Time (Unix Epoch) when the torrent completed
     pub content_path: String, // Absolute path of torrent content (root path for multifile torrents, absolute file path for singlefile torrents)
-    pub dl_limit: f64,        // Torrent download speed limit (bytes/s). -1 if ulimited.
-    pub dlspeed: f64,         // Torrent download speed (bytes/s)
-    pub downloaded: f64,      // Amount of data downloaded
-    pub downloaded_session: f64, // Amount of data downloaded this session
-    pub eta: f64,             // Torrent ETA (seconds)
+    #[serde(with = "adapters::rate_limit")]
+    pub dl_limit: Option<Limit<u64>>, // Torrent download speed limit (bytes/s), None if unlimited
+    #[serde(with = "adapters::bytes")]
+    pub dlspeed: u64, // Torrent download speed (bytes/s)
+    #[serde(with = "adapters::bytes")]
+    pub downloaded: u64, // Amount of data downloaded
+    #[serde(with = "adapters::bytes")]
+    pub downloaded_session: u64, // Amount of data downloaded this session
+    #[serde(with = "adapters::eta")]
+    pub eta: Option<Duration>, // Torrent ETA, None when it never completes
     pub infohash_v1: String,  // Torrent hash
     pub f_l_piece_prio: bool, // True if first last piece are prioritized
     pub force_start: bool,    // True if force start is enabled for this torrent
-    pub last_activity: f64,   // Last time (Unix Epoch) when a chunk was downloaded/uploaded
-    pub magnet_uri: String,   // Magnet URI corresponding to this torrent
-    pub max_ratio: f64,       // Maximum share ratio until torrent is stopped from seeding/uploading
-    pub max_seeding_time: f64, // Maximum seeding time (seconds) until torrent is stopped from seeding
-    pub name: String,          // Torrent name
-    pub num_complete: f64,     // Number of seeds in the swarm
-    pub num_incomplete: f64,   // Number of leechers in the swarm
-    pub num_leechs: f64,       // Number of leechers connected to
-    pub num_seeds: f64,        // Number of seeds connected to
-    pub priority: f64, // Torrent priority. Returns -1 if queuing is disabled or torrent is in seed mode
+    #[serde(with = "adapters::epoch")]
+    pub last_activity: Option<DateTime<Utc>>, // Last time (Unix Epoch) when a chunk was downloaded/uploaded
+    pub magnet_uri: String, // Magnet URI corresponding to this torrent
+    #[serde(with = "adapters::ratio_limit")]
+    pub max_ratio: Option<Limit<f64>>, // Maximum share ratio until torrent is stopped from seeding/uploading
+    #[serde(with = "adapters::minutes_limit")]
+    pub max_seeding_time: Option<Limit<Duration>>, // Maximum seeding time until torrent is stopped from seeding
+    pub name: String, // Torrent name
+    #[serde(with = "adapters::bytes")]
+    pub num_complete: u64, // Number of seeds in the swarm
+    #[serde(with = "adapters::bytes")]
+    pub num_incomplete: u64, // Number of leechers in the swarm
+    #[serde(with = "adapters::bytes")]
+    pub num_leechs: u64, // Number of leechers connected to
+    #[serde(with = "adapters::bytes")]
+    pub num_seeds: u64, // Number of seeds connected to
+    #[serde(with = "adapters::queue_position")]
+    pub priority: Option<u64>, // Torrent priority. None if queuing is disabled or torrent is in seed mode
     pub progress: f64, // Torrent progress (percentage/100)
     pub ratio: f64,    // Torrent share ratio. Max ratio value: 9999.
-    pub ratio_limit: f64, // TODO (what is different from max_ratio?)
+    #[serde(with = "adapters::ratio_limit")]
+    pub ratio_limit: Option<Limit<f64>>, // TODO (what is different from max_ratio?)
     pub save_path: String, // Path where this torrent's data is stored
-    pub seeding_time: f64, // Torrent elapsed time while complete (seconds)
-    pub seeding_time_limit: f64, // TODO (what is different from max_seeding_time?) seeding_time_limit is a per torrent setting, when Automatic Torrent Management is disabled, furthermore then max_seeding_time is set to seeding_time_limit for this torrent. If Automatic Torrent Management is enabled, the value is -2. And if max_seeding_time is unset it have a default value -1.
-    pub seen_complete: f64,      // Time (Unix Epoch) when this torrent was last seen complete
-    pub seq_dl: bool,            // True if sequential download is enabled
-    pub size: f64,               // Total size (bytes) of files selected for download
-    pub state: TorrentStatus,    // Torrent state. See table here below for the possible values
-    pub super_seeding: bool,     // True if super seeding is enabled
-    pub tags: String,            // Comma-concatenated tag list of the torrent
-    pub time_active: f64,        // Total active time (seconds)
-    pub total_size: f64, // Total size (bytes) of all file in this torrent (including unselected ones)
+    #[serde(with = "adapters::seconds")]
+    pub seeding_time: Duration, // Torrent elapsed time while complete (seconds)
+    #[serde(with = "adapters::minutes_limit")]
+    pub seeding_time_limit: Option<Limit<Duration>>, // TODO (what is different from max_seeding_time?) seeding_time_limit is a per torrent setting, when Automatic Torrent Management is disabled, furthermore then max_seeding_time is set to seeding_time_limit for this torrent. If Automatic Torrent Management is enabled, the value is -2. And if max_seeding_time is unset it have a default value -1.
+    #[serde(with = "adapters::epoch")]
+    pub seen_complete: Option<DateTime<Utc>>, // Time (Unix Epoch) when this torrent was last seen complete
+    pub seq_dl: bool, // True if sequential download is enabled
+    #[serde(with = "adapters::bytes")]
+    pub size: u64, // Total size (bytes) of files selected for download
+    pub state: TorrentStatus, // Torrent state. See table here below for the possible values
+    pub super_seeding: bool, // True if super seeding is enabled
+    pub tags: String, // Comma-concatenated tag list of the torrent
+    #[serde(with = "adapters::seconds")]
+    pub time_active: Duration, // Total active time (seconds)
+    #[serde(with = "adapters::bytes")]
+    pub total_size: u64, // Total size (bytes) of all file in this torrent (including unselected ones)
     pub tracker: String, // The first tracker with working status. Returns empty string if no tracker is working.
-    pub up_limit: f64,   // Torrent upload speed limit (bytes/s). -1 if ulimited.
-    pub uploaded: f64,   // Amount of data uploaded
-    pub uploaded_session: f64, // Amount of data uploaded this session
-    pub upspeed: f64,    // Torrent upload speed (bytes/s)
+    #[serde(with = "adapters::rate_limit")]
+    pub up_limit: Option<Limit<u64>>, // Torrent upload speed limit (bytes/s), None if unlimited
+    #[serde(with = "adapters::bytes")]
+    pub uploaded: u64, // Amount of data uploaded
+    #[serde(with = "adapters::bytes")]
+    pub uploaded_session: u64, // Amount of data uploaded this session
+    #[serde(with = "adapters::bytes")]
+    pub upspeed: u64, // Torrent upload speed (bytes/s)
+    /// Fields this version doesn't know about, kept as qBittorrent sent them.
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
 pub struct TorrentInfoPartial {
-    pub added_on: Option<f64>, // Time (Unix Epoch) when the torrent was added to the client
-    pub amount_left: Option<f64>, // Amount of data left to download (bytes)
+    #[serde(default, with = "adapters::epoch::option")]
+    pub added_on: Option<Option<DateTime<Utc>>>, // Time (Unix Epoch) when the torrent was added to the client
+    #[serde(default, with = "adapters::bytes::option")]
+    pub amount_left: Option<u64>, // Amount of data left to download (bytes)
     pub auto_tmm: Option<bool>, // Whether this torrent is managed by Automatic Torrent Management
     pub availability: Option<f64>, // Percentage of file pieces currently available
     pub category: Option<String>, // Category of the torrent
-    pub completed: Option<f64>, // Amount of transfer data completed (bytes)
-    pub completion_on: Option<f64>, // Time (Unix Epoch) when the torrent completed
+    #[serde(default, with = "adapters::bytes::option")]
+    pub completed: Option<u64>, // Amount of transfer data completed (bytes)
+    #[serde(default, with = "adapters::epoch::option")]
+    pub completion_on: Option<Option<DateTime<Utc>>>, // Time (Unix Epoch) when the torrent completed
     pub content_path: Option<String>, // Absolute path of torrent content (root path for multifile torrents, absolute file path for singlefile torrents)
-    pub dl_limit: Option<f64>,        // Torrent download speed limit (bytes/s). -1 if unlimited.
-    pub dlspeed: Option<f64>,         // Torrent download speed (bytes/s)
-    pub downloaded: Option<f64>,      // Amount of data downloaded
-    pub downloaded_session: Option<f64>, // Amount of data downloaded this session
-    pub eta: Option<f64>,             // Torrent ETA (seconds)
+    #[serde(default, with = "adapters::rate_limit::option")]
+    pub dl_limit: Option<Option<Limit<u64>>>, // Torrent download speed limit (bytes/s), None if unlimited
+    #[serde(default, with = "adapters::bytes::option")]
+    pub dlspeed: Option<u64>, // Torrent download speed (bytes/s)
+    #[serde(default, with = "adapters::bytes::option")]
+    pub downloaded: Option<u64>, // Amount of data downloaded
+    #[serde(default, with = "adapters::bytes::option")]
+    pub downloaded_session: Option<u64>, // Amount of data downloaded this session
+    #[serde(default, with = "adapters::eta::option")]
+    pub eta: Option<Option<Duration>>, // Torrent ETA, None when it never completes
     pub infohash_v1: Option<String>,  // Torrent hash
     pub f_l_piece_prio: Option<bool>, // True if first last piece are prioritized
     pub force_start: Option<bool>,    // True if force start is enabled for this torrent
-    pub last_activity: Option<f64>,   // Last time (Unix Epoch) when a chunk was downloaded/uploaded
-    pub magnet_uri: Option<String>,   // Magnet URI corresponding to this torrent
-    pub max_ratio: Option<f64>, // Maximum share ratio until torrent is stopped from seeding/uploading
-    pub max_seeding_time: Option<f64>, // Maximum seeding time (seconds) until torrent is stopped from seeding
-    pub name: Option<String>,          // Torrent name
-    pub num_complete: Option<f64>,     // Number of seeds in the swarm
-    pub num_incomplete: Option<f64>,   // Number of leechers in the swarm
-    pub num_leechs: Option<f64>,       // Number of leechers connected to
-    pub num_seeds: Option<f64>,        // Number of seeds connected to
-    pub priority: Option<f64>, // Torrent priority. Returns -1 if queuing is disabled or torrent is in seed mode
+    #[serde(default, with = "adapters::epoch::option")]
+    pub last_activity: Option<Option<DateTime<Utc>>>, // Last time (Unix Epoch) when a chunk was downloaded/uploaded
+    pub magnet_uri: Option<String>, // Magnet URI corresponding to this torrent
+    #[serde(default, with = "adapters::ratio_limit::option")]
+    pub max_ratio: Option<Option<Limit<f64>>>, // Maximum share ratio until torrent is stopped from seeding/uploading
+    #[serde(default, with = "adapters::minutes_limit::option")]
+    pub max_seeding_time: Option<Option<Limit<Duration>>>, // Maximum seeding time until torrent is stopped from seeding
+    pub name: Option<String>, // Torrent name
+    #[serde(default, with = "adapters::bytes::option")]
+    pub num_complete: Option<u64>, // Number of seeds in the swarm
+    #[serde(default, with = "adapters::bytes::option")]
+    pub num_incomplete: Option<u64>, // Number of leechers in the swarm
+    #[serde(default, with = "adapters::bytes::option")]
+    pub num_leechs: Option<u64>, // Number of leechers connected to
+    #[serde(default, with = "adapters::bytes::option")]
+    pub num_seeds: Option<u64>, // Number of seeds connected to
+    #[serde(default, with = "adapters::queue_position::option")]
+    pub priority: Option<Option<u64>>, // Torrent priority. None if queuing is disabled or torrent is in seed mode
     pub progress: Option<f64>, // Torrent progress (percentage/100)
     pub ratio: Option<f64>,    // Torrent share ratio. Max ratio value: 9999.
-    pub ratio_limit: Option<f64>, // TODO (what is different from max_ratio?)
+    #[serde(default, with = "adapters::ratio_limit::option")]
+    pub ratio_limit: Option<Option<Limit<f64>>>, // TODO (what is different from max_ratio?)
     pub save_path: Option<String>, // Path where this torrent's data is stored
-    pub seeding_time: Option<f64>, // Torrent elapsed time while complete (seconds)
-    pub seeding_time_limit: Option<f64>, // TODO (what is different from max_seeding_time?) seeding_time_limit is a per torrent setting, when Automatic Torrent Management is disabled, furthermore then max_seeding_time is set to seeding_time_limit for this torrent. If Automatic Torrent Management is enabled, the value is -2. And if max_seeding_time is unset it has a default value -1.
-    pub seen_complete: Option<f64>, // Time (Unix Epoch) when this torrent was last seen complete
-    pub seq_dl: Option<bool>,       // True if sequential download is enabled
-    pub size: Option<f64>,          // Total size (bytes) of files selected for download
+    #[serde(default, with = "adapters::seconds::option")]
+    pub seeding_time: Option<Duration>, // Torrent elapsed time while complete (seconds)
+    #[serde(default, with = "adapters::minutes_limit::option")]
+    pub seeding_time_limit: Option<Option<Limit<Duration>>>, // TODO (what is different from max_seeding_time?) seeding_time_limit is a per torrent setting, when Automatic Torrent Management is disabled, furthermore then max_seeding_time is set to seeding_time_limit for this torrent. If Automatic Torrent Management is enabled, the value is -2. And if max_seeding_time is unset it has a default value -1.
+    #[serde(default, with = "adapters::epoch::option")]
+    pub seen_complete: Option<Option<DateTime<Utc>>>, // Time (Unix Epoch) when this torrent was last seen complete
+    pub seq_dl: Option<bool>, // True if sequential download is enabled
+    #[serde(default, with = "adapters::bytes::option")]
+    pub size: Option<u64>, // Total size (bytes) of files selected for download
     pub state: Option<TorrentStatus>, // Torrent state. See table here below for the possible values
     pub super_seeding: Option<bool>, // True if super seeding is enabled
-    pub tags: Option<String>,       // Comma-concatenated tag list of the torrent
-    pub time_active: Option<f64>,   // Total active time (seconds)
-    pub total_size: Option<f64>, // Total size (bytes) of all file in this torrent (including unselected ones)
+    pub tags: Option<String>, // Comma-concatenated tag list of the torrent
+    #[serde(default, with = "adapters::seconds::option")]
+    pub time_active: Option<Duration>, // Total active time (seconds)
+    #[serde(default, with = "adapters::bytes::option")]
+    pub total_size: Option<u64>, // Total size (bytes) of all file in this torrent (including unselected ones)
     pub tracker: Option<String>, // The first tracker with working status. Returns empty string if no tracker is working.
-    pub up_limit: Option<f64>,   // Torrent upload speed limit (bytes/s). -1 if unlimited.
-    pub uploaded: Option<f64>,   // Amount of data uploaded
-    pub uploaded_session: Option<f64>, // Amount of data uploaded this session
-    pub upspeed: Option<f64>,    // Torrent upload speed (bytes/s)
+    #[serde(default, with = "adapters::rate_limit::option")]
+    pub up_limit: Option<Option<Limit<u64>>>, // Torrent upload speed limit (bytes/s), None if unlimited
+    #[serde(default, with = "adapters::bytes::option")]
+    pub uploaded: Option<u64>, // Amount of data uploaded
+    #[serde(default, with = "adapters::bytes::option")]
+    pub uploaded_session: Option<u64>, // Amount of data uploaded this session
+    #[serde(default, with = "adapters::bytes::option")]
+    pub upspeed: Option<u64>, // Torrent upload speed (bytes/s)
+    /// Fields this version doesn't know about, kept as qBittorrent sent them.
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
 }
@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// One entry of the `/torrents/trackers` response. DHT, PeX and LSD are listed too.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct TrackerInfo {
+    pub url: String,         // Tracker url, or `** [DHT] **` and the like
+    pub status: u8, // 0 disabled, 1 not contacted yet, 2 working, 3 updating, 4 not working
+    pub tier: Value, // Tier, a number, but -1 or an empty string for DHT, PeX and LSD
+    pub num_peers: i64, // Peers reported by the tracker, -1 when unknown
+    pub num_seeds: i64, // Seeds reported by the tracker, -1 when unknown
+    pub num_leeches: i64, // Leechers reported by the tracker, -1 when unknown
+    pub num_downloaded: i64, // Completed downloads reported by the tracker, -1 when unknown
+    pub msg: String, // Tracker message
+}
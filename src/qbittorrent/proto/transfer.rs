@@ -1,6 +1,11 @@
 use core::fmt;
+use std::collections::HashMap;
+use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::adapters::{self, Limit};
 
 #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -21,27 +26,58 @@ impl fmt::Display for ConnectionStatus {
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ServerStateFull {
-    pub dl_info_speed: f64,                  // Global download rate (bytes/s)
-    pub dl_info_data: f64,                   // Data downloaded this session (bytes)
-    pub up_info_speed: f64,                  // Global upload rate (bytes/s)
-    pub up_info_data: f64,                   // Data uploaded this session (bytes)
-    pub dl_rate_limit: f64,                  // Download rate limit (bytes/s)
-    pub up_rate_limit: f64,                  // Upload rate limit (bytes/s)
-    pub dht_nodes: f64,                      // DHT nodes connected to
+    #[serde(with = "adapters::bytes")]
+    pub dl_info_speed: u64, // Global download rate (bytes/s)
+    #[serde(with = "adapters::bytes")]
+    pub dl_info_data: u64, // Data downloaded this session (bytes)
+    #[serde(with = "adapters::bytes")]
+    pub up_info_speed: u64, // Global upload rate (bytes/s)
+    #[serde(with = "adapters::bytes")]
+    pub up_info_data: u64, // Data uploaded this session (bytes)
+    #[serde(with = "adapters::rate_limit")]
+    pub dl_rate_limit: Option<Limit<u64>>, // Download rate limit (bytes/s), None if unlimited
+    #[serde(with = "adapters::rate_limit")]
+    pub up_rate_limit: Option<Limit<u64>>, // Upload rate limit (bytes/s), None if unlimited
+    #[serde(with = "adapters::bytes")]
+    pub dht_nodes: u64, // DHT nodes connected to
     pub connection_status: ConnectionStatus, // Connection status
+    pub use_alt_speed_limits: bool,          // Whether the alternative speed limits apply
+    pub free_space_on_disk: Option<u64>,     // Free space in the default save path (bytes)
+    #[serde(with = "adapters::millis")]
+    pub refresh_interval: Duration, // Web UI refresh interval
+    /// Fields this version doesn't know about, kept as qBittorrent sent them.
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ServerStatePartial {
-    pub dl_info_speed: Option<f64>, // Global download rate (bytes/s)
-    pub dl_info_data: Option<f64>,  // Data downloaded this session (bytes)
-    pub up_info_speed: Option<f64>, // Global upload rate (bytes/s)
-    pub up_info_data: Option<f64>,  // Data uploaded this session (bytes)
-    pub dl_rate_limit: Option<f64>, // Download rate limit (bytes/s)
-    pub up_rate_limit: Option<f64>, // Upload rate limit (bytes/s)
-    pub dht_nodes: Option<f64>,     // DHT nodes connected to
+    #[serde(default, with = "adapters::bytes::option")]
+    pub dl_info_speed: Option<u64>, // Global download rate (bytes/s)
+    #[serde(default, with = "adapters::bytes::option")]
+    pub dl_info_data: Option<u64>, // Data downloaded this session (bytes)
+    #[serde(default, with = "adapters::bytes::option")]
+    pub up_info_speed: Option<u64>, // Global upload rate (bytes/s)
+    #[serde(default, with = "adapters::bytes::option")]
+    pub up_info_data: Option<u64>, // Data uploaded this session (bytes)
+    #[serde(default, with = "adapters::rate_limit::option")]
+    pub dl_rate_limit: Option<Option<Limit<u64>>>, // Download rate limit (bytes/s)
+    #[serde(default, with = "adapters::rate_limit::option")]
+    pub up_rate_limit: Option<Option<Limit<u64>>>, // Upload rate limit (bytes/s)
+    #[serde(default, with = "adapters::bytes::option")]
+    pub dht_nodes: Option<u64>, // DHT nodes connected to
     pub connection_status: Option<ConnectionStatus>, // Connection status
+    #[serde(default)]
+    pub use_alt_speed_limits: Option<bool>, // Whether the alternative speed limits apply
+    #[serde(default)]
+    pub free_space_on_disk: Option<u64>, // Free space in the default save path (bytes)
+    #[serde(default, with = "adapters::millis::option")]
+    pub refresh_interval: Option<Duration>, // Web UI refresh interval
+    /// Fields this version doesn't know about, kept as qBittorrent sent them.
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
 }
 
 impl ServerStateFull {
@@ -70,5 +106,15 @@ impl ServerStateFull {
         if let Some(v) = partial.dht_nodes {
             self.dht_nodes = v;
         }
+        if let Some(v) = partial.use_alt_speed_limits {
+            self.use_alt_speed_limits = v;
+        }
+        if let Some(v) = partial.free_space_on_disk {
+            self.free_space_on_disk = Some(v);
+        }
+        if let Some(v) = partial.refresh_interval {
+            self.refresh_interval = v;
+        }
+        self.extra.extend(partial.extra);
     }
 }
@@ -0,0 +1,242 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::Context;
+use bittower::app::auth::ssr::{AllowedOrigins, LimiterOptions, SessionOptions, TrustedProxies};
+use bittower::app::BasePath;
+use bittower::qbittorrent::client::{BasicAuth, QbtClientOptions, BASE_QBT_URL};
+use bittower::tls::TlsOptions;
+use bittower::webhooks::{EventKind, WebhookOptions};
+
+/// Prefix of the URLs that point to Transmission's RPC rather than to qBittorrent, e.g.
+/// `transmission+http://localhost:9091/transmission/rpc`.
+pub const TRANSMISSION_PREFIX: &str = "transmission+";
+
+/// Settings read from `BITTOWER_*` environment variables, next to the `LEPTOS_*` ones.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// `BITTOWER_QBT_URL`, qBittorrent's Web API, e.g. `http://localhost:8080/api/v2`, or
+    /// Transmission's RPC behind [`TRANSMISSION_PREFIX`].
+    pub qbt_url: String,
+    /// `BITTOWER_INSTANCES`, named clients to manage instead of the one at `qbt_url`, e.g.
+    /// `seedbox1=https://one.example.com/api/v2,seedbox2=transmission+https://two.example.com/transmission/rpc`.
+    pub instances: Vec<(String, String)>,
+    pub qbt_options: QbtClientOptions,
+    /// `BITTOWER_QBT_RECORD`, a fixture file to record every qBittorrent exchange to.
+    pub qbt_record: Option<PathBuf>,
+    pub session_options: SessionOptions,
+    /// `BITTOWER_USERS_FILE`, bit-tower's own users and the credentials it logs in to the
+    /// torrent clients with for them, see [`bittower::app::auth::ssr::Users`].
+    pub users_file: Option<PathBuf>,
+    pub limiter_options: LimiterOptions,
+    /// `BITTOWER_TRUSTED_PROXIES` and `BITTOWER_CLIENT_IP_HEADER`, who may tell the address of
+    /// the client.
+    pub trusted_proxies: TrustedProxies,
+    /// `BITTOWER_ALLOWED_ORIGINS`, origins besides our own that may open websockets.
+    pub allowed_origins: AllowedOrigins,
+    /// `BITTOWER_BASE_PATH`, the path bit-tower is served under, e.g. `/torrents`.
+    pub base_path: BasePath,
+    /// Serves HTTPS rather than plain HTTP when set.
+    pub tls: Option<TlsOptions>,
+    /// `BITTOWER_SERVICE_USER` and `BITTOWER_SERVICE_PASSWORD`, what `/readyz` checks the
+    /// torrent clients with when there is no users file.
+    pub service_account: Option<BasicAuth>,
+    /// `BITTOWER_API_TOKEN`, the bearer token of the `/api/v1` REST API, which is off without
+    /// one.
+    pub api_token: Option<String>,
+    /// `BITTOWER_TOKENS_FILE`, where the users' personal API tokens are kept across restarts.
+    pub tokens_file: Option<PathBuf>,
+    /// `BITTOWER_WEBHOOKS` and the other `BITTOWER_WEBHOOK_*`, where events are POSTed.
+    pub webhooks: WebhookOptions,
+    /// `BITTOWER_PUSH_FILE`, where the VAPID key and the push subscriptions are kept across
+    /// restarts.
+    pub push_file: Option<PathBuf>,
+    /// `BITTOWER_PUSH_SUBJECT`, a `mailto:` or `https:` URL push services can reach the admin at.
+    pub push_subject: Option<String>,
+}
+
+impl Config {
+    /// Reads the configuration, see the README for every variable.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let mut options = QbtClientOptions::default();
+        if let Some(timeout) = var_duration("BITTOWER_QBT_CONNECT_TIMEOUT")? {
+            options = options.connect_timeout(timeout);
+        }
+        if let Some(timeout) = var_duration("BITTOWER_QBT_TIMEOUT")? {
+            options = options.timeout(timeout);
+        }
+        if let Some(retries) = var("BITTOWER_QBT_RETRIES") {
+            options = options.retries(
+                retries
+                    .parse()
+                    .context("BITTOWER_QBT_RETRIES must be a number")?,
+            );
+        }
+        if let Some(backoff) = var_duration("BITTOWER_QBT_RETRY_BACKOFF")? {
+            options = options.retry_backoff(backoff);
+        }
+        if let Some(path) = var("BITTOWER_QBT_CA_CERT") {
+            options = options.ca_cert(PathBuf::from(path));
+        }
+        if let Some(insecure) = var("BITTOWER_QBT_INSECURE") {
+            options = options.accept_invalid_certs(
+                insecure
+                    .parse()
+                    .context("BITTOWER_QBT_INSECURE must be `true` or `false`")?,
+            );
+        }
+        if let Some(username) = var("BITTOWER_QBT_BASIC_AUTH_USER") {
+            let password = var("BITTOWER_QBT_BASIC_AUTH_PASSWORD").unwrap_or_default();
+            options = options.basic_auth(BasicAuth { username, password });
+        }
+
+        let mut session_options = SessionOptions::default();
+        if let Some(timeout) = var_duration("BITTOWER_SESSION_IDLE_TIMEOUT")? {
+            session_options = session_options.idle_timeout(timeout);
+        }
+        if let Some(max_age) = var_duration("BITTOWER_SESSION_MAX_AGE")? {
+            session_options = session_options.max_age(max_age);
+        }
+        if let Some(secure) = var("BITTOWER_SECURE_COOKIE") {
+            session_options = session_options.secure(
+                secure
+                    .parse()
+                    .context("BITTOWER_SECURE_COOKIE must be `true` or `false`")?,
+            );
+        }
+        if let Some(path) = var("BITTOWER_SESSION_FILE") {
+            session_options = session_options.file(PathBuf::from(path));
+        }
+
+        let mut limiter_options = LimiterOptions::default();
+        if let Some(attempts) = var("BITTOWER_LOGIN_MAX_ATTEMPTS") {
+            limiter_options = limiter_options.max_attempts(
+                attempts
+                    .parse()
+                    .context("BITTOWER_LOGIN_MAX_ATTEMPTS must be a number")?,
+            );
+        }
+        if let Some(lockout) = var_duration("BITTOWER_LOGIN_LOCKOUT")? {
+            limiter_options = limiter_options.lockout(lockout);
+        }
+        if let Some(lockout) = var_duration("BITTOWER_LOGIN_MAX_LOCKOUT")? {
+            limiter_options = limiter_options.max_lockout(lockout);
+        }
+        let trusted_proxies = TrustedProxies::new(
+            &var("BITTOWER_TRUSTED_PROXIES").unwrap_or_default(),
+            var("BITTOWER_CLIENT_IP_HEADER").as_deref(),
+        )
+        .context("BITTOWER_TRUSTED_PROXIES")?;
+        let tls = TlsOptions::new(
+            var("BITTOWER_TLS_CERT").as_deref(),
+            var("BITTOWER_TLS_KEY").as_deref(),
+            var("BITTOWER_HTTP_REDIRECT_ADDR").as_deref(),
+            var("BITTOWER_HTTPS_PUBLIC_PORT").as_deref(),
+        )?;
+
+        let mut webhooks = WebhookOptions::default();
+        if let Some(urls) = var("BITTOWER_WEBHOOKS") {
+            let urls = urls
+                .split(',')
+                .map(str::trim)
+                .filter(|url| !url.is_empty())
+                .map(|url| {
+                    reqwest::Url::parse(url)
+                        .ok()
+                        .filter(|parsed| matches!(parsed.scheme(), "http" | "https"))
+                        .with_context(|| format!("BITTOWER_WEBHOOKS: `{url}` isn't an HTTP URL"))?;
+                    Ok(url.to_owned())
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let secret = var("BITTOWER_WEBHOOK_SECRET")
+                .context("BITTOWER_WEBHOOK_SECRET is required to sign the webhooks")?;
+            webhooks = webhooks.urls(urls).secret(secret);
+        }
+        if let Some(stalled_after) = var_duration("BITTOWER_WEBHOOK_STALLED_AFTER")? {
+            webhooks = webhooks.stalled_after(stalled_after);
+        }
+        if let Some(ratio) = var("BITTOWER_WEBHOOK_RATIO") {
+            webhooks = webhooks.ratio_target(
+                ratio
+                    .parse::<f64>()
+                    .context("BITTOWER_WEBHOOK_RATIO must be a number such as `2.0`")?,
+            );
+        }
+        if let Some(events) = var("BITTOWER_WEBHOOK_EVENTS") {
+            webhooks = webhooks.events(
+                events
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|event| !event.is_empty())
+                    .map(|event| event.parse().context("BITTOWER_WEBHOOK_EVENTS"))
+                    .collect::<anyhow::Result<Vec<EventKind>>>()?,
+            );
+        }
+
+        Ok(Self {
+            qbt_url: var("BITTOWER_QBT_URL").unwrap_or_else(|| BASE_QBT_URL.to_owned()),
+            instances: var("BITTOWER_INSTANCES")
+                .map(|instances| parse_instances(&instances))
+                .transpose()?
+                .unwrap_or_default(),
+            qbt_options: options,
+            qbt_record: var("BITTOWER_QBT_RECORD").map(PathBuf::from),
+            session_options,
+            users_file: var("BITTOWER_USERS_FILE").map(PathBuf::from),
+            limiter_options,
+            trusted_proxies,
+            allowed_origins: AllowedOrigins::new(
+                &var("BITTOWER_ALLOWED_ORIGINS").unwrap_or_default(),
+            ),
+            base_path: BasePath::new(&var("BITTOWER_BASE_PATH").unwrap_or_default()),
+            tls,
+            service_account: var("BITTOWER_SERVICE_USER").map(|username| BasicAuth {
+                username,
+                password: var("BITTOWER_SERVICE_PASSWORD").unwrap_or_default(),
+            }),
+            api_token: var("BITTOWER_API_TOKEN"),
+            tokens_file: var("BITTOWER_TOKENS_FILE").map(PathBuf::from),
+            webhooks,
+            push_file: var("BITTOWER_PUSH_FILE").map(PathBuf::from),
+            push_subject: var("BITTOWER_PUSH_SUBJECT"),
+        })
+    }
+}
+
+/// Parses `name=url` pairs separated by commas.
+fn parse_instances(instances: &str) -> anyhow::Result<Vec<(String, String)>> {
+    instances
+        .split(',')
+        .map(str::trim)
+        .filter(|instance| !instance.is_empty())
+        .map(|instance| {
+            let (name, url) = instance.split_once('=').with_context(|| {
+                format!("BITTOWER_INSTANCES: `{instance}` should be `name=url`")
+            })?;
+            let name = name.trim();
+            if name.is_empty()
+                || !name
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || "-_.".contains(c))
+            {
+                anyhow::bail!("BITTOWER_INSTANCES: `{name}` isn't a valid instance name");
+            }
+            Ok((name.to_owned(), url.trim().to_owned()))
+        })
+        .collect()
+}
+
+/// Reads a variable, treating an empty one as unset.
+fn var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+/// Reads a duration such as `5s` or `1m 30s`.
+fn var_duration(name: &str) -> anyhow::Result<Option<Duration>> {
+    var(name)
+        .map(|value| {
+            humantime::parse_duration(&value)
+                .with_context(|| format!("{name} must be a duration such as `5s`"))
+        })
+        .transpose()
+}
@@ -0,0 +1,54 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::Extension;
+use bittower::app::auth::ssr::AuthSession;
+use bittower::protocol::{ServerMessage, SyncError};
+use futures::stream::StreamExt;
+use tokio::sync::watch;
+
+use super::poll::{poll_instances, PollSchedule};
+use super::AppState;
+
+/// Name of the SSE event carrying a JSON encoded `ServerMessage`.
+pub static SYNC_EVENT: &str = "sync";
+
+/// Streams the same sync updates as the websocket, for clients sitting behind proxies that
+/// don't let websockets through. Each event carries a JSON encoded
+/// [`ServerMessage::Sync`] or [`ServerMessage::SyncError`], for any of the instances the
+/// user is logged in to.
+#[tracing::instrument(skip_all)]
+pub async fn sse_handler(
+    State(app_state): State<AppState>,
+    Extension(auth_session): Extension<AuthSession>,
+) -> Response {
+    let (Some(id), Some(session)) = (auth_session.id, auth_session.session) else {
+        return (StatusCode::UNAUTHORIZED, "Unauthorized").into_response();
+    };
+
+    tracing::info!("Got an SSE connection");
+
+    let (_, schedule) = watch::channel(PollSchedule::default());
+    // The stream ends when the user logs out, the browser then finds out with a 401.
+    let sessions = app_state.sessions.clone();
+    let updates = poll_instances(&app_state.backends, &session, schedule)
+        .take_while(move |_| std::future::ready(sessions.contains(&id)));
+    let metrics = app_state.metrics.clone();
+    let events = updates.map(move |(instance, res)| {
+        let msg = match res {
+            Ok(update) => ServerMessage::Sync { instance, update },
+            Err(err) => ServerMessage::SyncError {
+                instance,
+                error: SyncError::from(&err),
+            },
+        };
+        let data = serde_json::to_string(&msg)?;
+        metrics.frame_sent("sse", data.len());
+        Ok::<_, serde_json::Error>(Event::default().event(SYNC_EVENT).data(data))
+    });
+
+    Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
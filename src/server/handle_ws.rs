@@ -1,22 +1,56 @@
 use core::panic;
+use std::collections::{HashMap, HashSet};
 use std::net::SocketAddr;
 use std::ops::ControlFlow;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use axum::extract::ws::{Message, WebSocket};
-use bittower::app::auth::ssr::Session;
+use bittower::app::auth::ssr::{Session, SessionStore};
+use bittower::app::auth::{owner_tag, tag_owner};
 use bittower::app::App;
-use bittower::qbittorrent::proto::sync::MainData;
+use bittower::backend::model::SyncUpdate;
+use bittower::backend::{self, BackendRegistry, TorrentBackend};
+use bittower::protocol::{
+    is_torrent_hash, ClientMessage, ServerMessage, Subscription, SyncError, TorrentAction,
+    PROTOCOL_VERSION,
+};
 use futures::{sink::SinkExt, stream::StreamExt};
 use leptos::prelude::*;
+use tokio::sync::{mpsc, watch};
 
+use super::poll::{poll_instances, PollSchedule, MAX_POLL_INTERVAL, MIN_POLL_INTERVAL};
 use super::AppState;
 
+/// How long we keep the socket open to flush the last frames once the client is done.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Everything the tasks serving one websocket connection share.
+#[derive(Debug, Clone)]
+struct Connection {
+    who: SocketAddr,
+    backends: BackendRegistry,
+    sessions: SessionStore,
+    /// Id of `session` in `sessions`, the connection ends when the user logs out.
+    session_id: String,
+    session: Session,
+    /// Frames queued for the client.
+    outgoing: mpsc::Sender<ServerMessage>,
+    /// What the client told us about how often it wants updates.
+    schedule: Arc<watch::Sender<PollSchedule>>,
+    /// Active subscriptions.
+    subscriptions: Arc<Mutex<HashSet<Subscription>>>,
+    /// Hashes of the torrents the user added, by instance, as of the last update.
+    owned: Arc<Mutex<HashMap<String, HashSet<String>>>>,
+}
+
 /// Actual websocket statemachine (one will be spawned per connection)
-#[tracing::instrument(skip(socket))]
+#[tracing::instrument(skip(socket, session_id, session))]
 pub async fn handle_socket(
     mut socket: WebSocket,
     who: SocketAddr,
     app_state: AppState,
+    session_id: String,
     session: Session,
 ) {
     //send a ping (unsupported by some browsers) just to kick things off and get a response
@@ -29,67 +63,134 @@ pub async fn handle_socket(
         return;
     }
 
-    // By splitting socket we can send and receive at the same time. In this example we will send
-    // unsolicited messages to client based on some sort of server's internal event (i.e .timer).
+    let _open = app_state.metrics.websocket_opened();
+
+    // By splitting socket we can send and receive at the same time. Every frame for the client
+    // goes through the `outgoing` queue so that any task can send one.
     let (mut sender, mut receiver) = socket.split();
+    let (outgoing, mut outgoing_rx) = mpsc::channel::<ServerMessage>(32);
+    let (schedule, schedule_rx) = watch::channel(PollSchedule::default());
+
+    let conn = Connection {
+        who,
+        backends: app_state.backends.clone(),
+        sessions: app_state.sessions.clone(),
+        session_id,
+        session,
+        outgoing,
+        schedule: Arc::new(schedule),
+        subscriptions: Default::default(),
+        owned: Default::default(),
+    };
 
-    // Spawn a task that will push several messages to the client (does not matter what client does)
+    // Spawn a task that writes the queued frames to the socket
+    let metrics = app_state.metrics.clone();
     let mut send_task = tokio::spawn(async move {
-        let mut rid = 0_u64;
-        let sid = session.sid.as_str();
-        loop {
-            // handle_ws(app_state.qbt.clone(), session.sid).await
-            let res = app_state.qbt.sync_maindata(sid, rid).await;
-
-            let Ok(maindata) = res else {
-                tracing::error!("Oops, the thing went boom.");
-                break;
-                // return Err(axum::BoxError::from(anyhow!("Oops")));
-            };
-            rid = match &maindata {
-                MainData::Full(md) => md.rid,
-                MainData::Partial(md) => md.rid,
-            };
-            let res = sender
-                .send(Message::Binary(rmp_serde::to_vec(&maindata).unwrap()))
-                .await;
+        let mut cnt = 0;
+        while let Some(msg) = outgoing_rx.recv().await {
+            let frame = rmp_serde::to_vec(&msg).unwrap();
+            metrics.frame_sent("ws", frame.len());
+            let res = sender.send(Message::Binary(frame)).await;
             if let Err(err) = res {
                 tracing::error!(error = %err);
                 break;
             }
-            tokio::time::sleep(std::time::Duration::from_millis(1000)).await;
+            cnt += 1;
+        }
+        cnt
+    });
+
+    // Spawn a task that polls the torrent clients and queues the updates for the client
+    let mut poll_task = tokio::spawn({
+        let conn = conn.clone();
+        async move {
+            let mut cnt = 0;
+            let hello = ServerMessage::Hello {
+                version: PROTOCOL_VERSION,
+            };
+            if conn.send(hello).await.is_err() {
+                return cnt;
+            }
+            let mut updates = poll_instances(&conn.backends, &conn.session, schedule_rx);
+            while let Some((instance, res)) = updates.next().await {
+                if !conn.sessions.contains(&conn.session_id) {
+                    tracing::info!("The session of {} ended, closing", conn.who);
+                    conn.session_ended().await;
+                    break;
+                }
+                match res {
+                    Ok(update) => {
+                        cnt += 1;
+                        conn.track_owned(&instance, &update);
+                        let msg = ServerMessage::Sync {
+                            instance: instance.clone(),
+                            update,
+                        };
+                        if conn.send(msg).await.is_err() {
+                            break;
+                        }
+                        conn.poll_subscriptions(&instance).await;
+                    }
+                    // The poller logs errors and backs off by itself, we keep the client posted.
+                    Err(err) => {
+                        let msg = ServerMessage::SyncError {
+                            instance,
+                            error: SyncError::from(&err),
+                        };
+                        if conn.send(msg).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+            cnt
         }
-        rid
     });
 
-    // This second task will receive messages from client and print them on server console
+    // This third task receives the commands sent by the client
     let mut recv_task = tokio::spawn(async move {
         let mut cnt = 0;
         while let Some(Ok(msg)) = receiver.next().await {
             cnt += 1;
-            // print message and break if instructed to do so
-            if process_message(msg, who).is_break() {
+            // handle message and break if instructed to do so
+            if process_message(msg, &conn).await.is_break() {
                 break;
             }
         }
         cnt
     });
 
-    // If any one of the tasks exit, abort the other.
+    // If any one of the tasks exit, abort the others.
     tokio::select! {
         rv_a = (&mut send_task) => {
             match rv_a {
                 Ok(a) => tracing::info!("{a} messages sent to {who}"),
                 Err(a) => tracing::info!("Error sending messages {a:?}")
             }
+            poll_task.abort();
             recv_task.abort();
         },
-        rv_b = (&mut recv_task) => {
+        rv_b = (&mut poll_task) => {
             match rv_b {
-                Ok(b) => tracing::info!("Received {b} messages"),
-                Err(b) => tracing::info!("Error receiving messages {b:?}")
+                Ok(b) => tracing::info!("Stopped polling for {who} after {b} updates"),
+                Err(b) => tracing::info!("Error polling {b:?}")
+            }
+            recv_task.abort();
+            // Let the client know why we stopped, e.g. because the session expired.
+            if tokio::time::timeout(FLUSH_TIMEOUT, &mut send_task).await.is_err() {
+                send_task.abort();
+            }
+        },
+        rv_c = (&mut recv_task) => {
+            match rv_c {
+                Ok(c) => tracing::info!("Received {c} messages"),
+                Err(c) => tracing::info!("Error receiving messages {c:?}")
+            }
+            poll_task.abort();
+            // Give the frames still queued, e.g. a protocol error, a chance to reach the client.
+            if tokio::time::timeout(FLUSH_TIMEOUT, &mut send_task).await.is_err() {
+                send_task.abort();
             }
-            send_task.abort();
         }
     }
 
@@ -97,16 +198,226 @@ pub async fn handle_socket(
     tracing::info!("Websocket context {who} destroyed");
 }
 
-/// helper to print contents of messages to stdout. Has special treatment for Close.
-#[tracing::instrument]
-fn process_message(msg: Message, who: SocketAddr) -> ControlFlow<(), ()> {
+impl Connection {
+    /// Lets the client know that it has to log in again, to every instance.
+    async fn session_ended(&self) {
+        for instance in self.session.sids.keys() {
+            let msg = ServerMessage::SyncError {
+                instance: instance.clone(),
+                error: SyncError::SessionExpired,
+            };
+            if self.send(msg).await.is_err() {
+                break;
+            }
+        }
+    }
+
+    /// Keeps up with which torrents carry the user's tag.
+    fn track_owned(&self, instance: &str, update: &SyncUpdate) {
+        let tag = owner_tag(&self.session.username);
+        let mut owned = self.owned.lock().unwrap();
+        let owned = owned.entry(instance.to_owned()).or_default();
+        if update.full {
+            owned.clear();
+        }
+        for torrent in &update.torrents {
+            if torrent.tags.contains(&tag) {
+                owned.insert(torrent.hash.clone());
+            } else {
+                owned.remove(&torrent.hash);
+            }
+        }
+        for hash in &update.removed {
+            owned.remove(hash);
+        }
+    }
+
+    /// Whether the user added every one of these torrents.
+    fn owns(&self, instance: &str, hashes: &[String]) -> bool {
+        let owned = self.owned.lock().unwrap();
+        owned
+            .get(instance)
+            .is_some_and(|owned| hashes.iter().all(|hash| owned.contains(hash)))
+    }
+
+    async fn send(&self, msg: ServerMessage) -> Result<(), mpsc::error::SendError<ServerMessage>> {
+        self.outgoing.send(msg).await
+    }
+
+    async fn handle_client_message(&self, msg: ClientMessage) -> ControlFlow<(), ()> {
+        let who = self.who;
+        match msg {
+            ClientMessage::Hello { version } if version != PROTOCOL_VERSION => {
+                tracing::warn!(
+                    ">>> {who} speaks protocol version {version}, not {PROTOCOL_VERSION}"
+                );
+                let message = format!(
+                    "Unsupported protocol version {version}, the server speaks {PROTOCOL_VERSION}"
+                );
+                let _ = self.send(ServerMessage::Error { message }).await;
+                return ControlFlow::Break(());
+            }
+            ClientMessage::Hello { version } => {
+                tracing::info!(">>> {who} speaks protocol version {version}");
+            }
+            ClientMessage::Subscribe(subscription) if !is_torrent_hash(subscription.hash()) => {
+                tracing::warn!(
+                    ">>> {who} subscribed to a bad hash: {:?}",
+                    subscription.hash()
+                );
+                let message = format!("Not a torrent hash: {:?}", subscription.hash());
+                let _ = self.send(ServerMessage::Error { message }).await;
+            }
+            ClientMessage::Subscribe(subscription) => {
+                self.subscriptions.lock().unwrap().insert(subscription);
+            }
+            ClientMessage::Unsubscribe(subscription) => {
+                self.subscriptions.lock().unwrap().remove(&subscription);
+            }
+            ClientMessage::SetPollInterval { interval_ms } => {
+                let interval =
+                    Duration::from_millis(interval_ms).clamp(MIN_POLL_INTERVAL, MAX_POLL_INTERVAL);
+                self.schedule
+                    .send_modify(|schedule| schedule.requested = Some(interval));
+            }
+            ClientMessage::Visibility { visible, focused } => {
+                tracing::info!(">>> {who} is now visible: {visible}, focused: {focused}");
+                self.schedule.send_modify(|schedule| {
+                    schedule.visible = visible;
+                    schedule.focused = focused;
+                });
+            }
+            ClientMessage::Action {
+                request_id,
+                instance,
+                action,
+            } => {
+                // Don't hold up the frames coming after this one while the client is busy.
+                let conn = self.clone();
+                tokio::spawn(async move {
+                    let result = conn.run_action(&instance, action).await;
+                    let _ = conn.send(ServerMessage::Ack { request_id, result }).await;
+                });
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// The backend of `instance` and our session there, if the user is logged in to it.
+    fn instance(&self, instance: &str) -> Option<(&Arc<dyn TorrentBackend>, &str)> {
+        Some((self.backends.get(instance)?, self.session.sid(instance)?))
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn run_action(&self, instance: &str, action: TorrentAction) -> Result<(), String> {
+        if !self.sessions.contains(&self.session_id) {
+            return Err("Your session has ended, please log in again".to_owned());
+        }
+        let Some((backend, session)) = self.instance(instance) else {
+            return Err(format!("You are not logged in to {instance}"));
+        };
+        let role = self.session.role;
+        if !role.allows(&action, self.owns(instance, action.hashes())) {
+            tracing::warn!(username = %self.session.username, %role, "Action denied");
+            return Err(format!("A {role} may not do that"));
+        }
+        // Remember who added the torrent.
+        let action = match action {
+            TorrentAction::Add(mut torrent) => {
+                tag_owner(&mut torrent, &self.session.username);
+                TorrentAction::Add(torrent)
+            }
+            action => action,
+        };
+        backend::run_action(backend.as_ref(), session, action)
+            .await
+            .map_err(|err| err.to_string())
+    }
+
+    /// Queues an update for every subscription of this connection to torrents of `instance`.
+    async fn poll_subscriptions(&self, instance: &str) {
+        let Some((backend, session)) = self.instance(instance) else {
+            return;
+        };
+        let subscriptions: Vec<_> = self
+            .subscriptions
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|subscription| subscription.instance() == instance)
+            .cloned()
+            .collect();
+
+        for subscription in subscriptions {
+            let msg = match subscription {
+                Subscription::TorrentPeers { instance, hash } => {
+                    match backend.peers(session, &hash).await {
+                        Ok(peers) => ServerMessage::TorrentPeers {
+                            instance,
+                            hash,
+                            peers,
+                        },
+                        Err(err) => {
+                            tracing::warn!(error = %err, "Failed to get peers of {hash}");
+                            continue;
+                        }
+                    }
+                }
+                Subscription::TorrentFiles { instance, hash } => {
+                    match backend.files(session, &hash).await {
+                        Ok(files) => ServerMessage::TorrentFiles {
+                            instance,
+                            hash,
+                            files,
+                        },
+                        Err(err) => {
+                            tracing::warn!(error = %err, "Failed to get files of {hash}");
+                            continue;
+                        }
+                    }
+                }
+                Subscription::TorrentTrackers { instance, hash } => {
+                    match backend.trackers(session, &hash).await {
+                        Ok(trackers) => ServerMessage::TorrentTrackers {
+                            instance,
+                            hash,
+                            trackers,
+                        },
+                        Err(err) => {
+                            tracing::warn!(error = %err, "Failed to get trackers of {hash}");
+                            continue;
+                        }
+                    }
+                }
+            };
+            if self.send(msg).await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+/// helper to decode and handle the frames sent by the client. Has special treatment for Close.
+#[tracing::instrument(skip(conn))]
+async fn process_message(msg: Message, conn: &Connection) -> ControlFlow<(), ()> {
+    let who = conn.who;
     match msg {
         Message::Text(t) => {
             tracing::info!(">>> {who} sent str: {t:?}");
         }
-        Message::Binary(d) => {
-            tracing::info!(">>> {} sent {} bytes: {:?}", who, d.len(), d);
-        }
+        Message::Binary(d) => match rmp_serde::from_slice::<ClientMessage>(&d) {
+            Ok(msg) => return conn.handle_client_message(msg).await,
+            Err(err) => {
+                tracing::warn!(
+                    ">>> {} sent {} bytes we could not decode: {}",
+                    who,
+                    d.len(),
+                    err
+                );
+                let message = format!("Could not decode message: {err}");
+                let _ = conn.send(ServerMessage::Error { message }).await;
+            }
+        },
         Message::Close(c) => {
             if let Some(cf) = c {
                 tracing::info!(
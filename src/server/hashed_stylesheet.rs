@@ -1,4 +1,5 @@
 use super::fileserv::Assets;
+use bittower::app::use_base_path;
 use leptos::prelude::*;
 use leptos::{
     attr::global::GlobalAttributes, component, prelude::LeptosOptions, tachys::html::element::link,
@@ -40,7 +41,8 @@ pub fn HashedStylesheet(
     }
     css_file_name.push_str(".css");
     let pkg_path = &options.site_pkg_dir;
+    let root = use_base_path();
     view! {
-        <Stylesheet id={id.unwrap_or_default()} href=format!("/{pkg_path}/{css_file_name}") />
+        <Stylesheet id={id.unwrap_or_default()} href=root.join(&format!("/{pkg_path}/{css_file_name}")) />
     }
 }
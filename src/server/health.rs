@@ -0,0 +1,34 @@
+//! Endpoints for load balancers and Prometheus, which don't log in.
+
+use axum::extract::State;
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use bittower::metrics::Metrics;
+
+/// `/healthz`: the process is up and serving requests.
+pub async fn healthz() -> &'static str {
+    "ok"
+}
+
+/// `/readyz`: every torrent client could be reached and logged in to with the service account
+/// at the last check.
+pub async fn readyz(State(metrics): State<Metrics>) -> Response {
+    let not_ready = metrics.not_ready();
+    if not_ready.is_empty() {
+        return "ready".into_response();
+    }
+    let body: String = not_ready
+        .iter()
+        .map(|(instance, error)| format!("{instance}: {error}\n"))
+        .collect();
+    (StatusCode::SERVICE_UNAVAILABLE, body).into_response()
+}
+
+/// `/metrics`, in Prometheus' text format.
+pub async fn metrics(State(metrics): State<Metrics>) -> Response {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics.render(),
+    )
+        .into_response()
+}
@@ -1,7 +1,7 @@
-(function (pkg_path, output_name, wasm_output_name) {
-	import(`/${pkg_path}/${output_name}.js`)
+(function (root, pkg_path, output_name, wasm_output_name) {
+	import(`${root}/${pkg_path}/${output_name}.js`)
 		.then(mod => {
-			mod.default(`/${pkg_path}/${wasm_output_name}.wasm`).then(() => {
+			mod.default(`${root}/${pkg_path}/${wasm_output_name}.wasm`).then(() => {
 				mod.hydrate();
 			});
 		})
@@ -1,4 +1,5 @@
 use super::fileserv::Assets;
+use bittower::app::use_base_path;
 use leptos::prelude::*;
 
 #[component]
@@ -35,6 +36,7 @@ pub fn HydrationScripts(options: LeptosOptions, #[prop(optional)] islands: bool)
     }
 
     let pkg_path = &options.site_pkg_dir;
+    let root = use_base_path().as_str().to_owned();
     // #[cfg(feature = "nonce")]
     // let nonce = nonce::use_nonce();
     // #[cfg(not(feature = "nonce"))]
@@ -49,16 +51,16 @@ pub fn HydrationScripts(options: LeptosOptions, #[prop(optional)] islands: bool)
     };
 
     view! {
-        <link rel="modulepreload" href=format!("/{pkg_path}/{js_file_name}.js") nonce=nonce.clone()/>
+        <link rel="modulepreload" href=format!("{root}/{pkg_path}/{js_file_name}.js") nonce=nonce.clone()/>
         <link
             rel="preload"
-            href=format!("/{pkg_path}/{wasm_file_name}.wasm")
+            href=format!("{root}/{pkg_path}/{wasm_file_name}.wasm")
             r#as="fetch"
             r#type="application/wasm"
             crossorigin=nonce.clone().unwrap_or_default()
         />
         <script type="module" nonce=nonce>
-            {format!("{script}({pkg_path:?}, {js_file_name:?}, {wasm_file_name:?})")}
+            {format!("{script}({root:?}, {pkg_path:?}, {js_file_name:?}, {wasm_file_name:?})")}
         </script>
     }
 }
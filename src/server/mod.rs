@@ -2,11 +2,12 @@ use core::panic;
 use std::borrow::Cow;
 use std::net::SocketAddr;
 use std::ops::ControlFlow;
+use std::sync::Arc;
+use std::time::Duration;
 
 use axum::extract::ws::CloseFrame;
 use axum::extract::ConnectInfo;
-use axum::http::StatusCode;
-use axum::response::sse::{Event, Sse};
+use axum::http::{StatusCode, Uri};
 use axum::{
     body::Body,
     extract::{
@@ -21,10 +22,23 @@ use axum::{
 };
 use axum_extra::headers::UserAgent;
 use axum_extra::TypedHeader;
-use bittower::app::auth::ssr::{AuthSession, Session, AUTH_COOKIE};
-use bittower::app::App;
+use bittower::api::v1::ApiState;
+use bittower::api::{bearer, ApiAuth, SyncedState};
+use bittower::app::auth::ssr::{
+    AllowedOrigins, AuthSession, ClientIp, CsrfToken, Forwarded, LoginLimiter, SessionStore,
+    TokenStore, TrustedProxies, Users, AUTH_COOKIE,
+};
+use bittower::app::push::ssr::PushService;
+use bittower::app::{App, BasePath};
+use bittower::backend::{
+    BackendInstance, BackendRegistry, Measured, QbittorrentBackend, Relogin, TorrentBackend,
+    TransmissionBackend, DEFAULT_INSTANCE,
+};
+use bittower::metrics::Metrics;
 use bittower::qbittorrent::client::QbtClient;
-use bittower::qbittorrent::proto::sync::MainData;
+use bittower::tls;
+use bittower::transmission::client::TransmissionClient;
+use bittower::webhooks::Webhooks;
 use fileserv::file_and_error_handler;
 use futures::stream::Stream;
 use futures::{sink::SinkExt, stream::StreamExt};
@@ -33,6 +47,7 @@ use leptos_axum::{
     generate_route_list_with_exclusions_and_ssg_and_context, handle_server_fns_with_context,
     AxumRouteListing, LeptosRoutes,
 };
+use tower::Layer;
 use tower_http::compression::{
     predicate::{NotForContentType, SizeAbove},
     CompressionLayer, CompressionLevel, Predicate,
@@ -42,15 +57,35 @@ use tower_http::{
     trace::{DefaultMakeSpan, TraceLayer},
 };
 
+mod config;
 pub mod fileserv;
+mod handle_sse;
 mod handle_ws;
 mod hashed_stylesheet;
+mod health;
 mod hydration;
+mod monitor;
+mod poll;
+mod pwa;
 mod shell;
 
+/// How often expired sessions and old failed logins are dropped.
+const SESSION_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
 #[derive(Debug, axum::extract::FromRef, Clone)]
 pub struct AppState {
-    pub qbt: QbtClient,
+    pub backends: BackendRegistry,
+    pub sessions: SessionStore,
+    /// Personal API tokens of bit-tower's own users.
+    pub tokens: TokenStore,
+    /// Browsers to notify of completed and failed torrents.
+    pub push: PushService,
+    /// bit-tower's own users, when there is a users file.
+    pub users: Option<Users>,
+    pub limiter: LoginLimiter,
+    /// Origins besides our own that may open websockets.
+    pub allowed_origins: AllowedOrigins,
+    pub metrics: Metrics,
     pub leptos_options: LeptosOptions,
     pub routes: Vec<AxumRouteListing>,
 }
@@ -68,16 +103,100 @@ pub async fn serve() {
     }
     let addr = leptos_options.site_addr;
 
-    let qbt = QbtClient::new("http://localhost:9090/api/v2");
-    let qbt_routes = qbt.clone();
+    let config = config::Config::from_env().expect("invalid configuration");
+    let sessions =
+        SessionStore::new(config.session_options.clone()).expect("could not load the sessions");
+    let tokens =
+        TokenStore::new(config.tokens_file.clone()).expect("could not load the API tokens");
+    let limiter = LoginLimiter::new(config.limiter_options.clone());
+    let trusted_proxies = config.trusted_proxies.clone();
+    let allowed_origins = config.allowed_origins.clone();
+    let base_path = config.base_path.clone();
+    let tls = config.tls.clone();
+    let service_account = config.service_account.clone();
+    let config_api_token = config.api_token.clone();
+    let metrics = Metrics::default();
+    let synced = SyncedState::default();
+    let webhooks = Webhooks::new(config.webhooks.clone()).expect("could not set up the webhooks");
+    tokio::spawn({
+        let (sessions, tokens, limiter) = (sessions.clone(), tokens.clone(), limiter.clone());
+        async move {
+            let mut interval = tokio::time::interval(SESSION_SWEEP_INTERVAL);
+            loop {
+                interval.tick().await;
+                sessions.sweep();
+                tokens.sweep();
+                limiter.sweep();
+            }
+        }
+    });
+    let users = config
+        .users_file
+        .as_deref()
+        .map(Users::load)
+        .transpose()
+        .expect("could not load the users");
+    let push = PushService::new(
+        config.push_file.clone(),
+        config.push_subject.clone(),
+        users.clone(),
+        false,
+    )
+    .expect("could not load the push subscriptions");
+    let backends =
+        backend_registry(config, &metrics).expect("could not set up the torrent clients");
+    if let Some(users) = &users {
+        for instance in backends.names() {
+            if users.credentials(instance).is_none() {
+                tracing::warn!(%instance, "No credentials for the instance, nobody can use it");
+            }
+        }
+        for instance in users.instances_with_credentials() {
+            if backends.get(instance).is_none() {
+                tracing::warn!(%instance, "Credentials for an unknown instance");
+            }
+        }
+    }
+    monitor::spawn(
+        &backends,
+        |instance| match &users {
+            Some(users) => users.credentials(instance).cloned(),
+            None => service_account.clone(),
+        },
+        &metrics,
+        &synced,
+        &webhooks,
+        &push,
+    );
+    if webhooks.enabled() && users.is_none() && service_account.is_none() {
+        tracing::warn!("No service account nor users file to sync with, no webhook will be sent");
+    }
+    let api_auth = ApiAuth::new(config_api_token.as_deref(), tokens.clone(), users.clone());
+    if !api_auth.enabled() {
+        tracing::info!("No BITTOWER_API_TOKEN nor users file, the REST API is off");
+    }
+    let api_state = ApiState {
+        backends: backends.clone(),
+        synced,
+        auth: api_auth,
+        webhooks,
+    };
+    let backends_routes = backends.clone();
 
     let (routes, _static_data_map) =
         generate_route_list_with_exclusions_and_ssg_and_context(App, None, move || {
-            provide_context::<QbtClient>(qbt_routes.clone());
+            provide_context::<BackendRegistry>(backends_routes.clone());
         });
 
     let app_state = AppState {
-        qbt,
+        backends,
+        sessions: sessions.clone(),
+        tokens,
+        push,
+        users,
+        limiter,
+        allowed_origins,
+        metrics,
         leptos_options: leptos_options.clone(),
         routes: routes.clone(),
     };
@@ -92,11 +211,18 @@ pub async fn serve() {
 
     // build our application with a route
     let app = Router::new()
+        // One segment, so that the REST API can live next to the server functions.
         .route(
-            "/api/*fn_name",
+            "/api/:fn_name",
             get(server_fn_handler).post(server_fn_handler),
         )
+        .nest("/api/v1", bittower::api::v1::router(api_state))
         .route("/ws", get(ws_handler))
+        .route("/events", get(handle_sse::sse_handler))
+        .route("/healthz", get(health::healthz))
+        .route("/readyz", get(health::readyz))
+        .route("/metrics", get(health::metrics))
+        .route("/sw.js", get(pwa::service_worker))
         .leptos_routes_with_handler(routes, get(leptos_routes_handler))
         // .layer(
         //     CompressionLayer::new()
@@ -104,67 +230,249 @@ pub async fn serve() {
         //         .compress_when(predicate),
         // )
         .fallback(file_and_error_handler)
-        .layer(middleware::from_fn(session_middleware))
+        .layer(middleware::from_fn_with_state(
+            sessions.clone(),
+            csrf_middleware,
+        ))
+        .layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            session_middleware,
+        ))
         .layer(
             TraceLayer::new_for_http()
                 .make_span_with(DefaultMakeSpan::default().include_headers(true)),
         )
         .with_state(app_state);
+    // Wraps the router rather than being one of its layers, which only run once the route
+    // is picked, since it takes the base path off the URI.
+    let app =
+        middleware::from_fn_with_state((trusted_proxies, base_path), proxy_middleware).layer(app);
+
+    let app = axum::ServiceExt::<Request>::into_make_service_with_connect_info::<SocketAddr>(app);
+
+    if let Some(tls) = tls {
+        let rustls = tls::rustls_config(&tls)
+            .await
+            .expect("could not load the TLS certificate");
+        if let Some(redirect_addr) = tls.redirect_addr {
+            let https_port = tls.public_port.unwrap_or(addr.port());
+            tokio::spawn(tls::redirect_http(redirect_addr, https_port));
+        }
+        tokio::spawn(tls::reload_on_change(tls, rustls.clone()));
+        tracing::info!("listening on https://{}", &addr);
+        axum_server::bind_rustls(addr, rustls)
+            .serve(app)
+            .await
+            .unwrap();
+        return;
+    }
 
     // run our app with hyper
     // `axum::Server` is a re-export of `hyper::Server`
     tracing::info!("listening on http://{}", &addr);
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(
-        listener,
-        app.into_make_service_with_connect_info::<SocketAddr>(),
-    )
-    .await
-    .unwrap();
+    axum::serve(listener, app).await.unwrap();
+}
+
+/// `bittower hash-password`: reads a password from stdin and prints its hash for the users
+/// file.
+pub fn hash_password() {
+    let mut password = String::new();
+    std::io::stdin()
+        .read_line(&mut password)
+        .expect("could not read the password");
+    let password = password.trim_end_matches(['\r', '\n']);
+    if password.is_empty() {
+        eprintln!("Pipe the password in: echo \"$pw\" | bittower hash-password");
+        std::process::exit(1);
+    }
+    println!("{}", bittower::app::auth::ssr::hash_password(password));
+}
+
+/// One backend per configured instance, or just the one at `BITTOWER_QBT_URL`. With a users
+/// file, bit-tower holds the credentials and logs in again whenever a session expires. Every
+/// backend reports to `metrics`.
+fn backend_registry(config: config::Config, metrics: &Metrics) -> anyhow::Result<BackendRegistry> {
+    let backend = |url: &str,
+                   record: Option<std::path::PathBuf>|
+     -> anyhow::Result<Arc<dyn TorrentBackend>> {
+        if let Some(url) = url.strip_prefix(config::TRANSMISSION_PREFIX) {
+            if record.is_some() {
+                tracing::warn!("Only qBittorrent exchanges can be recorded, not Transmission ones");
+            }
+            let client = TransmissionClient::with_options(url, config.qbt_options.clone())?;
+            return Ok(Arc::new(TransmissionBackend::new(client)));
+        }
+        let qbt = QbtClient::with_options(url, config.qbt_options.clone())?;
+        let qbt = match record {
+            Some(path) => qbt.record_to(path),
+            None => qbt,
+        };
+        Ok(Arc::new(QbittorrentBackend::new(qbt)))
+    };
+    let backend = |name: &str, url: &str, record| -> anyhow::Result<Arc<dyn TorrentBackend>> {
+        let backend = backend(url, record)?;
+        let backend: Arc<dyn TorrentBackend> = match config.users_file {
+            Some(_) => Arc::new(Relogin::new(backend)),
+            None => backend,
+        };
+        Ok(Arc::new(Measured::new(name, backend, metrics.clone())))
+    };
+    let instances = if config.instances.is_empty() {
+        vec![BackendInstance {
+            name: DEFAULT_INSTANCE.to_owned(),
+            backend: backend(DEFAULT_INSTANCE, &config.qbt_url, config.qbt_record.clone())?,
+        }]
+    } else {
+        config
+            .instances
+            .iter()
+            .map(|(name, url)| {
+                // Each instance gets its own recording, e.g. `4.6.5.seedbox1.json`.
+                let record = config
+                    .qbt_record
+                    .as_ref()
+                    .map(|path| path.with_extension(format!("{name}.json")));
+                Ok(BackendInstance {
+                    name: name.clone(),
+                    backend: backend(name, url, record)?,
+                })
+            })
+            .collect::<anyhow::Result<_>>()?
+    };
+    BackendRegistry::new(instances)
 }
 
-async fn session_middleware(mut request: Request, next: Next) -> Response {
-    let res = request
+/// Adds the [`AuthSession`] of the `bt-session` cookie to the request, or that of the personal
+/// API token it sends as `Authorization: Bearer` instead.
+async fn session_middleware(
+    State(app_state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let sessions = &app_state.sessions;
+    let id = request
         .headers()
         .get_all(header::COOKIE)
         .into_iter()
-        .find_map(|x| {
-            let cs = cookie::Cookie::split_parse(x.to_str().unwrap());
-            let token = cs
-                .into_iter()
-                .find(|c| match c {
-                    Ok(c) => c.name() == AUTH_COOKIE,
-                    _ => false,
-                })
-                .map(|c| c.unwrap().value().to_string());
-            token
-        });
-    request.extensions_mut().insert(AuthSession::new(None));
-    if let Some(sealed_token) = res {
-        let session = bittower::app::auth::ssr::get_session(sealed_token).ok();
-        if let Some(session) = session {
-            request
-                .extensions_mut()
-                .insert(AuthSession::new(Some(session)));
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(cookie::Cookie::split_parse)
+        .filter_map(Result::ok)
+        .find(|cookie| cookie.name() == AUTH_COOKIE)
+        .map(|cookie| cookie.value().to_owned());
+    let auth_session = id.and_then(|id| {
+        let session = sessions.get(&id)?;
+        Some(AuthSession::new(id, session))
+    });
+    let auth_session = match (auth_session, bearer(request.headers()), &app_state.users) {
+        (Some(auth_session), _, _) => auth_session,
+        (None, Some(token), Some(users)) => {
+            let token = token.to_owned();
+            let session = app_state
+                .tokens
+                .session(&token, sessions, users, &app_state.backends)
+                .await;
+            session
+                .map(|session| AuthSession::new(token, session))
+                .unwrap_or_default()
+        }
+        _ => AuthSession::default(),
+    };
+    request.extensions_mut().insert(auth_session);
+    next.run(request).await
+}
+
+/// Adds the [`CsrfToken`] of the cookie to the request, handing a new one to browsers that
+/// have none yet.
+async fn csrf_middleware(
+    State(sessions): State<SessionStore>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let (token, new) = match CsrfToken::from_headers(request.headers()) {
+        Some(token) => (token, false),
+        None => (CsrfToken::generate(), true),
+    };
+    request.extensions_mut().insert(token.clone());
+    let mut response = next.run(request).await;
+    if new {
+        if let Ok(cookie) = header::HeaderValue::from_str(&token.cookie(sessions.secure())) {
+            response.headers_mut().append(header::SET_COOKIE, cookie);
         }
     }
+    response
+}
+
+/// Looks past the trusted proxies and the base path: adds the [`ClientIp`], what the proxies
+/// [`Forwarded`] and the [`BasePath`] the browser sees to the request, and takes the base path
+/// off its URI so that the routes don't have to know it.
+async fn proxy_middleware(
+    State((proxies, base_path)): State<(TrustedProxies, BasePath)>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let ip = proxies.client_ip(peer.ip(), request.headers());
+    let forwarded = proxies.forwarded(peer.ip(), request.headers());
+    if let Some(path) = base_path.strip(request.uri().path()) {
+        let path_and_query = match request.uri().query() {
+            Some(query) => format!("{path}?{query}"),
+            None => path.to_owned(),
+        };
+        if let Ok(uri) = path_and_query.parse::<Uri>() {
+            *request.uri_mut() = uri;
+        }
+    }
+    // A proxy that strips the prefix itself tells which one it was.
+    let base_path = forwarded.prefix.as_deref().map_or(base_path, BasePath::new);
+    request.extensions_mut().insert(ClientIp(ip));
+    request.extensions_mut().insert(forwarded);
+    request.extensions_mut().insert(base_path);
     next.run(request).await
 }
 
+/// Everything server functions and rendering get from the request.
+fn provide_request_context(
+    app_state: &AppState,
+    auth_session: &AuthSession,
+    client_ip: ClientIp,
+    csrf: CsrfToken,
+    base_path: BasePath,
+) {
+    provide_context::<BackendRegistry>(app_state.backends.clone());
+    provide_context::<SessionStore>(app_state.sessions.clone());
+    provide_context::<TokenStore>(app_state.tokens.clone());
+    provide_context::<PushService>(app_state.push.clone());
+    provide_context::<LoginLimiter>(app_state.limiter.clone());
+    provide_context::<ClientIp>(client_ip);
+    provide_context::<CsrfToken>(csrf);
+    provide_context::<BasePath>(base_path);
+    if let Some(users) = &app_state.users {
+        provide_context::<Users>(users.clone());
+    }
+    auth_session.provide();
+}
+
 /// Creates an axum handler to inject context into server functions.
 async fn server_fn_handler(
     State(app_state): State<AppState>,
     Extension(auth_session): Extension<AuthSession>,
+    Extension(client_ip): Extension<ClientIp>,
+    Extension(csrf): Extension<CsrfToken>,
+    Extension(base_path): Extension<BasePath>,
     path: Path<String>,
     request: Request<Body>,
 ) -> impl IntoResponse {
     tracing::info!("Handling server function request: {:?}", path);
     handle_server_fns_with_context(
         move || {
-            provide_context::<QbtClient>(app_state.qbt.clone());
-            if let Some(session) = &auth_session.session {
-                provide_context::<Session>(session.clone());
-            }
+            provide_request_context(
+                &app_state,
+                &auth_session,
+                client_ip,
+                csrf.clone(),
+                base_path.clone(),
+            )
         },
         request,
     )
@@ -174,13 +482,30 @@ async fn server_fn_handler(
 pub async fn leptos_routes_handler(
     State(app_state): State<AppState>,
     Extension(auth_session): Extension<AuthSession>,
-    request: Request<Body>,
+    Extension(client_ip): Extension<ClientIp>,
+    Extension(csrf): Extension<CsrfToken>,
+    Extension(base_path): Extension<BasePath>,
+    mut request: Request<Body>,
 ) -> axum::response::Response {
+    // The router of the page matches the URL the browser sees, base path included.
+    let path = request
+        .uri()
+        .path_and_query()
+        .map_or("/", |path| path.as_str());
+    if let Ok(uri) = base_path.join(path).parse::<Uri>() {
+        *request.uri_mut() = uri;
+    }
     let handler = leptos_axum::render_app_to_stream_with_context(
-        move || {
-            provide_context::<QbtClient>(app_state.qbt.clone());
-            if let Some(session) = &auth_session.session {
-                provide_context::<Session>(session.clone());
+        {
+            let app_state = app_state.clone();
+            move || {
+                provide_request_context(
+                    &app_state,
+                    &auth_session,
+                    client_ip,
+                    csrf.clone(),
+                    base_path.clone(),
+                )
             }
         },
         {
@@ -192,15 +517,25 @@ pub async fn leptos_routes_handler(
     handler(request).await.into_response()
 }
 
-#[tracing::instrument(skip(ws))]
+#[tracing::instrument(skip(ws, headers))]
 async fn ws_handler(
     ws: WebSocketUpgrade,
     user_agent: Option<TypedHeader<UserAgent>>,
     State(app_state): State<AppState>,
     Extension(auth_session): Extension<AuthSession>,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Extension(ClientIp(ip)): Extension<ClientIp>,
+    Extension(forwarded): Extension<Forwarded>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: header::HeaderMap,
 ) -> impl IntoResponse {
     tracing::info!("Got a WS connection");
+    // Browsers let any page open a websocket with our cookies, unlike a `fetch`.
+    if !app_state.allowed_origins.allows(&headers, &forwarded) {
+        tracing::warn!(origin = ?headers.get(header::ORIGIN), "Websocket from another origin");
+        return (StatusCode::FORBIDDEN, "Forbidden").into_response();
+    }
+    // The address of the browser rather than of the proxy, for the logs.
+    let addr = SocketAddr::new(ip, peer.port());
     let user_agent = if let Some(TypedHeader(user_agent)) = user_agent {
         user_agent.to_string()
     } else {
@@ -208,9 +543,9 @@ async fn ws_handler(
     };
     tracing::info!("`{user_agent}` at {addr} connected.");
 
-    let Some(session) = auth_session.session else {
+    let (Some(id), Some(session)) = (auth_session.id, auth_session.session) else {
         return (StatusCode::UNAUTHORIZED, "Unauthorized").into_response();
     };
 
-    ws.on_upgrade(move |socket| handle_ws::handle_socket(socket, addr, app_state, session))
+    ws.on_upgrade(move |socket| handle_ws::handle_socket(socket, addr, app_state, id, session))
 }
@@ -0,0 +1,94 @@
+//! Checks the torrent clients in the background with a service account, for `/readyz`, the
+//! state of the clients in `/metrics`, what the REST API answers with, the webhooks and the push
+//! notifications.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use bittower::api::SyncedState;
+use bittower::app::push::ssr::PushService;
+use bittower::backend::{BackendRegistry, SyncCursor, TorrentBackend};
+use bittower::metrics::Metrics;
+use bittower::qbittorrent::client::BasicAuth;
+use bittower::webhooks::Webhooks;
+
+/// How often every torrent client is checked.
+const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Checks every instance that `account` has credentials for, the others are never ready.
+pub fn spawn(
+    backends: &BackendRegistry,
+    account: impl Fn(&str) -> Option<BasicAuth>,
+    metrics: &Metrics,
+    synced: &SyncedState,
+    webhooks: &Webhooks,
+    push: &PushService,
+) {
+    for instance in backends.iter() {
+        match account(&instance.name) {
+            Some(account) => {
+                metrics.check_failed(&instance.name, "Not checked yet");
+                tokio::spawn(check(
+                    instance.name.clone(),
+                    instance.backend.clone(),
+                    account,
+                    metrics.clone(),
+                    synced.clone(),
+                    webhooks.clone(),
+                    push.clone(),
+                ));
+            }
+            None => metrics.check_failed(&instance.name, "No service account to check it with"),
+        }
+    }
+}
+
+/// Logs in once and syncs every [`CHECK_INTERVAL`], logging in again when the session expired.
+async fn check(
+    instance: String,
+    backend: Arc<dyn TorrentBackend>,
+    account: BasicAuth,
+    metrics: Metrics,
+    synced: SyncedState,
+    webhooks: Webhooks,
+    push: PushService,
+) {
+    let mut session = None;
+    let mut cursor = SyncCursor::default();
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        let current = match &session {
+            Some(current) => current.clone(),
+            None => {
+                let login = backend.login(account.username.clone(), account.password.clone());
+                match login.await {
+                    Ok(current) => {
+                        cursor = SyncCursor::default();
+                        session = Some(current.clone());
+                        current
+                    }
+                    Err(err) => {
+                        tracing::warn!(%instance, error = %err, "Health check could not log in");
+                        metrics.check_failed(&instance, format!("Could not log in: {err}"));
+                        continue;
+                    }
+                }
+            }
+        };
+        match backend.sync(&current, &mut cursor).await {
+            Ok(_) => {
+                metrics.checked(&instance, &cursor);
+                synced.update(&instance, &current, &cursor);
+                webhooks.synced(&instance, &cursor.torrents);
+                push.synced(&instance, &cursor.torrents);
+            }
+            Err(err) => {
+                if err.is_session_expired() {
+                    session = None;
+                }
+                metrics.check_failed(&instance, format!("Could not sync: {err}"));
+            }
+        }
+    }
+}
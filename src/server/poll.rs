@@ -0,0 +1,171 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use bittower::app::auth::ssr::Session;
+use bittower::backend::model::SyncUpdate;
+use bittower::backend::{BackendError, BackendRegistry, SyncCursor, TorrentBackend};
+use futures::stream::{self, Stream, StreamExt};
+use tokio::sync::watch;
+use tokio::time::Instant;
+
+/// How long to wait between two syncs when nobody told us otherwise.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(1000);
+/// Bounds for poll intervals requested by clients.
+pub const MIN_POLL_INTERVAL: Duration = Duration::from_millis(250);
+pub const MAX_POLL_INTERVAL: Duration = Duration::from_secs(60);
+/// Poll interval while the client's tab is hidden.
+pub const HIDDEN_POLL_INTERVAL: Duration = Duration::from_secs(30);
+/// Upper bound for the backoff after consecutive errors of a torrent client.
+pub const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// What the client told us about how often it wants updates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PollSchedule {
+    /// Interval asked for with `SetPollInterval`, if any.
+    pub requested: Option<Duration>,
+    /// Whether the page is visible at all.
+    pub visible: bool,
+    /// Whether the page has the focus.
+    pub focused: bool,
+}
+
+impl Default for PollSchedule {
+    fn default() -> Self {
+        Self {
+            requested: None,
+            visible: true,
+            focused: true,
+        }
+    }
+}
+
+impl PollSchedule {
+    /// The interval to wait after a successful poll.
+    ///
+    /// We never poll faster than the client's own refresh interval, slow down when the page
+    /// is in the background and poll rarely when it is hidden.
+    pub fn interval(&self, refresh_interval: Option<Duration>) -> Duration {
+        let base = self
+            .requested
+            .unwrap_or(POLL_INTERVAL)
+            .max(refresh_interval.unwrap_or_default())
+            .clamp(MIN_POLL_INTERVAL, MAX_POLL_INTERVAL);
+        if !self.visible {
+            base.max(HIDDEN_POLL_INTERVAL)
+        } else if !self.focused {
+            base * 2
+        } else {
+            base
+        }
+    }
+}
+
+/// Doubles `interval` for every consecutive failure, up to [`MAX_BACKOFF`].
+fn backoff(interval: Duration, failures: u32) -> Duration {
+    if failures == 0 {
+        return interval;
+    }
+    interval
+        .saturating_mul(1 << failures.min(16))
+        .min(MAX_BACKOFF.max(interval))
+}
+
+struct PollState {
+    backend: Arc<dyn TorrentBackend>,
+    session: String,
+    schedule: watch::Receiver<PollSchedule>,
+    cursor: SyncCursor,
+    failures: u32,
+    last_poll: Option<Instant>,
+    /// Set once the client told us the session is gone, there's no point in polling on.
+    expired: bool,
+}
+
+impl PollState {
+    /// Sleeps until the next poll is due, waking up early when the schedule changes so that
+    /// e.g. a tab coming back to the foreground gets its update right away.
+    async fn wait(&mut self) {
+        let Some(last_poll) = self.last_poll else {
+            return;
+        };
+        loop {
+            let interval = self
+                .schedule
+                .borrow()
+                .interval(self.cursor.refresh_interval);
+            let deadline = last_poll + backoff(interval, self.failures);
+            tokio::select! {
+                _ = tokio::time::sleep_until(deadline) => return,
+                changed = self.schedule.changed() => {
+                    if changed.is_err() {
+                        // Nobody is left to change the schedule, just sleep it out.
+                        tokio::time::sleep_until(deadline).await;
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Syncs with a torrent client in the given session, yielding every update.
+///
+/// The first update is full, the next ones only carry what changed, see
+/// [`TorrentBackend::sync`]. How long we wait between two syncs follows `schedule`, see
+/// [`PollSchedule::interval`]. Errors are yielded too, after which we keep polling with an
+/// exponential backoff. The stream ends after an expired session.
+pub fn poll_sync(
+    backend: Arc<dyn TorrentBackend>,
+    session: String,
+    schedule: watch::Receiver<PollSchedule>,
+) -> impl Stream<Item = Result<SyncUpdate, BackendError>> {
+    let state = PollState {
+        backend,
+        session,
+        schedule,
+        cursor: SyncCursor::default(),
+        failures: 0,
+        last_poll: None,
+        expired: false,
+    };
+
+    stream::unfold(state, |mut state| async move {
+        if state.expired {
+            return None;
+        }
+        state.wait().await;
+        state.last_poll = Some(Instant::now());
+
+        let res = state.backend.sync(&state.session, &mut state.cursor).await;
+        match &res {
+            Ok(_) => state.failures = 0,
+            Err(err) if err.is_session_expired() => {
+                tracing::info!("The session has expired, stopped polling");
+                state.expired = true;
+            }
+            Err(err) => {
+                state.failures = state.failures.saturating_add(1);
+                tracing::warn!(error = %err, failures = state.failures, "Failed to sync");
+            }
+        }
+        Some((res, state))
+    })
+}
+
+/// Polls every instance of `registry` that `session` is logged in to, see [`poll_sync`].
+/// Updates are tagged with the name of their instance, the stream ends once every session has
+/// expired.
+pub fn poll_instances(
+    registry: &BackendRegistry,
+    session: &Session,
+    schedule: watch::Receiver<PollSchedule>,
+) -> impl Stream<Item = (String, Result<SyncUpdate, BackendError>)> {
+    let streams = registry.iter().filter_map(|instance| {
+        let sid = session.sid(&instance.name)?.to_owned();
+        let name = instance.name.clone();
+        let updates = poll_sync(instance.backend.clone(), sid, schedule.clone())
+            .map(move |res| (name.clone(), res));
+        Some(Box::pin(updates))
+    });
+    stream::select_all(streams)
+}
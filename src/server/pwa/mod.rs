@@ -0,0 +1,78 @@
+//! What makes bit-tower an installable app: the service worker, generated from what
+//! [`Assets`] holds so that it knows the files of this build, and changes whenever they do,
+//! which is how browsers find out about an update.
+
+use std::sync::OnceLock;
+
+use axum::extract::State;
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use leptos::prelude::LeptosOptions;
+use sha2::{Digest, Sha256};
+
+use super::fileserv::Assets;
+
+const SERVICE_WORKER: &str = include_str!("./sw.js");
+
+/// The files of a build that are cached for offline use.
+struct Build {
+    /// Changes whenever one of the files does.
+    id: String,
+    /// Paths relative to the base path.
+    assets: Vec<String>,
+}
+
+impl Build {
+    fn get(options: &LeptosOptions) -> &'static Build {
+        static BUILD: OnceLock<Build> = OnceLock::new();
+        BUILD.get_or_init(|| {
+            let pkg = format!("{}/", options.site_pkg_dir);
+            let mut assets: Vec<String> = Assets::iter()
+                .filter(|path| cached(path, &pkg))
+                .map(|path| path.into_owned())
+                .collect();
+            assets.sort();
+            let mut hasher = Sha256::new();
+            for path in &assets {
+                hasher.update(path.as_bytes());
+                if let Some(file) = Assets::get(path) {
+                    hasher.update(file.metadata.sha256_hash());
+                }
+            }
+            let id = hasher.finalize()[..8]
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect();
+            Build { id, assets }
+        })
+    }
+}
+
+/// The JS, WASM and CSS, hashed in release builds, and what the app shows besides them. The
+/// precompressed copies are what the server sends for them, not files of their own.
+fn cached(path: &str, pkg: &str) -> bool {
+    if path.ends_with(".br") || path.ends_with(".gz") {
+        return false;
+    }
+    path.starts_with(pkg)
+        || path.starts_with("fonts/")
+        || path.starts_with("icons/")
+        || path == "favicon.ico"
+        || path == "manifest.webmanifest"
+}
+
+/// `/sw.js`, never taken from the HTTP cache so that browsers see a new build right away.
+pub async fn service_worker(State(options): State<LeptosOptions>) -> Response {
+    let build = Build::get(&options);
+    let script = SERVICE_WORKER
+        .replace("__BUILD__", &serde_json::to_string(&build.id).unwrap())
+        .replace("__ASSETS__", &serde_json::to_string(&build.assets).unwrap());
+    (
+        [
+            (header::CONTENT_TYPE, "text/javascript"),
+            (header::CACHE_CONTROL, "no-cache"),
+        ],
+        script,
+    )
+        .into_response()
+}
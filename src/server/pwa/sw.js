@@ -0,0 +1,118 @@
+// bit-tower's service worker, served by `src/server/pwa/mod.rs` which fills in `BUILD` and
+// `ASSETS` for the build it runs. It keeps the files of the build and the last page for
+// offline use, until the user logs out, shows the push notifications the server sends, see
+// `src/app/push.rs`, and brings bit-tower up when one is clicked.
+
+const BUILD = __BUILD__;
+const ASSETS = __ASSETS__;
+const CACHE_PREFIX = "bit-tower-";
+const CACHE = CACHE_PREFIX + BUILD;
+const scope = self.registration.scope;
+const assetUrls = new Set(ASSETS.map((path) => new URL(path, scope).href));
+
+self.addEventListener("install", (event) => {
+  event.waitUntil(
+    (async () => {
+      const cache = await caches.open(CACHE);
+      await cache.addAll([...assetUrls]);
+      // The page too, which may fail when offline or logged out, it's cached when loaded then.
+      await cache.add(scope).catch(() => {});
+      // The first worker takes over right away, newer ones wait for the user to reload, see
+      // `src/app/hooks/use_service_worker.rs`.
+      if (!self.registration.active) {
+        await self.skipWaiting();
+      }
+    })(),
+  );
+});
+
+self.addEventListener("activate", (event) => {
+  event.waitUntil(
+    (async () => {
+      for (const name of await caches.keys()) {
+        if (name.startsWith(CACHE_PREFIX) && name !== CACHE) {
+          await caches.delete(name);
+        }
+      }
+      await self.clients.claim();
+    })(),
+  );
+});
+
+self.addEventListener("message", (event) => {
+  if (event.data && event.data.type === "skip-waiting") {
+    self.skipWaiting();
+  } else if (event.data && event.data.type === "forget-pages") {
+    // Sent on logout, the pages were rendered for the user and show their torrents.
+    event.waitUntil(
+      (async () => {
+        const cache = await caches.open(CACHE);
+        for (const request of await cache.keys()) {
+          if (!assetUrls.has(request.url)) {
+            await cache.delete(request);
+          }
+        }
+      })(),
+    );
+  }
+});
+
+self.addEventListener("fetch", (event) => {
+  const request = event.request;
+  if (request.method !== "GET") {
+    return;
+  }
+  if (assetUrls.has(request.url)) {
+    // Named after their hash in release builds, so they never change.
+    event.respondWith(caches.match(request).then((cached) => cached || fetch(request)));
+  } else if (request.mode === "navigate" && request.url.startsWith(scope)) {
+    // Fresh when online, the last one seen otherwise.
+    event.respondWith(
+      (async () => {
+        const cache = await caches.open(CACHE);
+        try {
+          const response = await fetch(request);
+          if (response.ok) {
+            await cache.put(request, response.clone());
+          }
+          return response;
+        } catch (err) {
+          const cached = (await cache.match(request)) || (await cache.match(scope));
+          if (cached) {
+            return cached;
+          }
+          throw err;
+        }
+      })(),
+    );
+  }
+});
+
+self.addEventListener("push", (event) => {
+  let message = { title: "bit-tower" };
+  try {
+    message = event.data ? event.data.json() : message;
+  } catch (err) {
+    message.body = event.data.text();
+  }
+  event.waitUntil(
+    self.registration.showNotification(message.title, {
+      body: message.body,
+      tag: message.tag,
+      icon: new URL("icons/icon-192.png", scope).href,
+      data: message,
+    }),
+  );
+});
+
+self.addEventListener("notificationclick", (event) => {
+  event.notification.close();
+  event.waitUntil(
+    self.clients
+      .matchAll({ type: "window", includeUncontrolled: true })
+      .then((windows) => {
+        const open = windows.find((client) => client.url.startsWith(scope));
+        return open ? open.focus() : self.clients.openWindow(scope);
+      }),
+  );
+});
@@ -1,16 +1,21 @@
 use super::hashed_stylesheet::HashedStylesheet;
 use super::hydration::HydrationScripts;
-use bittower::app::App;
+use bittower::app::{use_base_path, App, BASE_PATH_META};
 use leptos::prelude::*;
 use leptos_meta::*;
 
 pub fn shell(options: LeptosOptions) -> impl IntoView {
+    let base_path = use_base_path();
     view! {
         <!DOCTYPE html>
         <html lang="en">
             <head>
                 <meta charset="utf-8"/>
                 <meta name="viewport" content="width=device-width, initial-scale=1"/>
+                <meta name=BASE_PATH_META content=base_path.as_str().to_owned()/>
+                <meta name="theme-color" content="#0891b2"/>
+                <link rel="manifest" href=base_path.join("/manifest.webmanifest")/>
+                <link rel="apple-touch-icon" href=base_path.join("/icons/apple-touch-icon.png")/>
                 <AutoReload options=options.clone()/>
                 <HydrationScripts options=options.clone()/>
                 <HashedStylesheet id="leptos" options />
@@ -0,0 +1,134 @@
+//! Serving HTTPS without a reverse proxy in front.
+
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::Context;
+use axum::extract::Host;
+use axum::http::{uri::Authority, StatusCode, Uri};
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+
+/// How often the certificate and key are checked for changes.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(30);
+
+/// `BITTOWER_TLS_CERT`, `BITTOWER_TLS_KEY`, `BITTOWER_HTTP_REDIRECT_ADDR` and
+/// `BITTOWER_HTTPS_PUBLIC_PORT`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsOptions {
+    /// PEM file with the certificate chain.
+    pub cert: PathBuf,
+    /// PEM file with the private key.
+    pub key: PathBuf,
+    /// Where to listen for plain HTTP, only to redirect to HTTPS.
+    pub redirect_addr: Option<SocketAddr>,
+    /// The port browsers reach HTTPS on, when it isn't the one bit-tower listens on, e.g.
+    /// behind port mapping.
+    pub public_port: Option<u16>,
+}
+
+impl TlsOptions {
+    /// From the values of the variables, `None` when neither a certificate nor a key is given.
+    pub fn new(
+        cert: Option<&str>,
+        key: Option<&str>,
+        redirect_addr: Option<&str>,
+        public_port: Option<&str>,
+    ) -> anyhow::Result<Option<Self>> {
+        let (cert, key) = match (cert, key) {
+            (Some(cert), Some(key)) => (cert, key),
+            (None, None) => return Ok(None),
+            _ => anyhow::bail!("BITTOWER_TLS_CERT and BITTOWER_TLS_KEY go together"),
+        };
+        Ok(Some(Self {
+            cert: PathBuf::from(cert),
+            key: PathBuf::from(key),
+            redirect_addr: redirect_addr
+                .map(|addr| {
+                    addr.parse().context(
+                        "BITTOWER_HTTP_REDIRECT_ADDR must be an address such as `0.0.0.0:80`",
+                    )
+                })
+                .transpose()?,
+            public_port: public_port
+                .map(|port| {
+                    port.parse()
+                        .context("BITTOWER_HTTPS_PUBLIC_PORT must be a port such as `443`")
+                })
+                .transpose()?,
+        }))
+    }
+}
+
+/// Loads the certificate and key. HTTP/2 is offered to clients along with HTTP/1.1.
+pub async fn rustls_config(options: &TlsOptions) -> anyhow::Result<RustlsConfig> {
+    Ok(RustlsConfig::from_pem_file(&options.cert, &options.key).await?)
+}
+
+/// Reloads the certificate and key whenever either file changes, e.g. after a renewal.
+/// Connections keep being served with the previous ones while the new ones don't load.
+pub async fn reload_on_change(options: TlsOptions, config: RustlsConfig) {
+    let mut loaded = modified(&options);
+    let mut interval = tokio::time::interval(RELOAD_INTERVAL);
+    loop {
+        interval.tick().await;
+        let current = modified(&options);
+        if current == loaded {
+            continue;
+        }
+        match config
+            .reload_from_pem_file(&options.cert, &options.key)
+            .await
+        {
+            Ok(()) => {
+                tracing::info!(cert = %options.cert.display(), "Reloaded the TLS certificate");
+                loaded = current;
+            }
+            // The certificate may be written before the key, try again on the next tick.
+            Err(err) => tracing::warn!(%err, "Could not reload the TLS certificate"),
+        }
+    }
+}
+
+fn modified(options: &TlsOptions) -> [Option<SystemTime>; 2] {
+    let modified = |path: &Path| {
+        std::fs::metadata(path)
+            .and_then(|meta| meta.modified())
+            .ok()
+    };
+    [modified(&options.cert), modified(&options.key)]
+}
+
+/// Answers plain HTTP at `addr` with a redirect to the same URL over HTTPS on `https_port`.
+pub async fn redirect_http(addr: SocketAddr, https_port: u16) {
+    let app = Router::new().fallback(move |Host(host): Host, uri: Uri| async move {
+        redirect(&host, https_port, &uri)
+    });
+    let listener = match tokio::net::TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            tracing::error!(%addr, %err, "Could not listen for HTTP to redirect");
+            return;
+        }
+    };
+    tracing::info!("redirecting http://{addr} to HTTPS");
+    if let Err(err) = axum::serve(listener, app).await {
+        tracing::error!(%err, "HTTP redirect stopped");
+    }
+}
+
+/// A permanent redirect from `uri`, asked for on `host`, to HTTPS on `https_port`.
+pub fn redirect(host: &str, https_port: u16, uri: &Uri) -> Response {
+    let Ok(authority) = host.parse::<Authority>() else {
+        return (StatusCode::BAD_REQUEST, "Bad host").into_response();
+    };
+    let host = authority.host();
+    let path = uri.path_and_query().map_or("/", |path| path.as_str());
+    let url = match https_port {
+        443 => format!("https://{host}{path}"),
+        port => format!("https://{host}:{port}{path}"),
+    };
+    Redirect::permanent(&url).into_response()
+}
@@ -0,0 +1,210 @@
+use std::sync::{Arc, Mutex};
+
+use http::StatusCode;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+use super::proto::{
+    RpcRequest, RpcResponse, SessionGet, SessionStats, TorrentGet, SESSION_ID_HEADER, SUCCESS,
+};
+use crate::qbittorrent::client::{BasicAuth, QbtClientOptions};
+
+pub static BASE_TRANSMISSION_URL: &str = "http://localhost:9091/transmission/rpc";
+
+/// Talks to Transmission's RPC endpoint. Transmission has no login, every request carries the
+/// user's credentials as basic auth.
+#[derive(Clone, Debug)]
+pub struct TransmissionClient {
+    url: String,
+    http: reqwest::Client,
+    /// The last `X-Transmission-Session-Id` Transmission handed out. It protects against CSRF
+    /// and is the same for every user.
+    session_id: Arc<Mutex<Option<String>>>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum TransmissionError {
+    /// Transmission answered 401 to the credentials.
+    #[error("Unauthenticated")]
+    Unauthenticated,
+    /// We don't have the credentials of this session anymore, e.g. because bit-tower restarted.
+    #[error("The Transmission session has expired")]
+    SessionExpired,
+    #[error("Transmission is unreachable: {0}")]
+    Unreachable(#[source] reqwest::Error),
+    #[error("Transmission answered with HTTP {status}")]
+    Http { status: StatusCode },
+    #[error("Could not decode the Transmission response: {0}")]
+    Decode(#[source] serde_json::Error),
+    /// The `result` of a call that failed.
+    #[error("Transmission refused: {0}")]
+    Rpc(String),
+    #[error(transparent)]
+    ReqwestError(reqwest::Error),
+}
+
+impl From<reqwest::Error> for TransmissionError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_connect() || err.is_timeout() {
+            return TransmissionError::Unreachable(err);
+        }
+        match err.status() {
+            Some(StatusCode::UNAUTHORIZED) => TransmissionError::Unauthenticated,
+            Some(status) => TransmissionError::Http { status },
+            None => TransmissionError::ReqwestError(err),
+        }
+    }
+}
+
+impl TransmissionClient {
+    pub fn new(url: &str) -> Self {
+        Self::with_options(url, QbtClientOptions::default())
+            .expect("the default options always build a client")
+    }
+
+    /// Timeouts and TLS settings apply as they do to qBittorrent. Basic auth doesn't, that's
+    /// how Transmission takes the user's credentials.
+    pub fn with_options(url: &str, options: QbtClientOptions) -> anyhow::Result<Self> {
+        Ok(Self {
+            url: url.to_owned(),
+            http: options.build_http_client()?,
+            session_id: Default::default(),
+        })
+    }
+
+    /// Calls `method`, going through the session id handshake when Transmission asks for it.
+    #[tracing::instrument(skip(self, auth, arguments))]
+    pub async fn rpc<T: DeserializeOwned>(
+        &self,
+        auth: &BasicAuth,
+        method: &str,
+        arguments: Value,
+    ) -> Result<T, TransmissionError> {
+        let request = RpcRequest {
+            method: method.to_owned(),
+            arguments,
+        };
+        // Transmission answers 409 with a new session id to the first request, and whenever
+        // the id changed, e.g. after a restart. Once is enough to get it.
+        for _ in 0..2 {
+            let mut builder = self
+                .http
+                .post(&self.url)
+                .basic_auth(&auth.username, Some(&auth.password))
+                .json(&request);
+            let session_id = self.session_id.lock().unwrap().clone();
+            if let Some(session_id) = session_id {
+                builder = builder.header(SESSION_ID_HEADER, session_id);
+            }
+
+            let response = builder.send().await?;
+            match response.status() {
+                StatusCode::CONFLICT => {
+                    let session_id = response
+                        .headers()
+                        .get(SESSION_ID_HEADER)
+                        .and_then(|value| value.to_str().ok())
+                        .map(str::to_owned);
+                    tracing::debug!("Got a new Transmission session id");
+                    *self.session_id.lock().unwrap() = session_id;
+                    continue;
+                }
+                StatusCode::UNAUTHORIZED => return Err(TransmissionError::Unauthenticated),
+                status if !status.is_success() => {
+                    return Err(TransmissionError::Http { status });
+                }
+                _ => {}
+            }
+
+            let body = response.bytes().await?;
+            let response: RpcResponse =
+                serde_json::from_slice(&body).map_err(TransmissionError::Decode)?;
+            if response.result != SUCCESS {
+                return Err(TransmissionError::Rpc(response.result));
+            }
+            return serde_json::from_value(response.arguments).map_err(TransmissionError::Decode);
+        }
+        Err(TransmissionError::Http {
+            status: StatusCode::CONFLICT,
+        })
+    }
+
+    /// `fields` of the torrents `ids` points to, e.g. a list of hashes or `"recently-active"`.
+    /// Every torrent when `ids` is `None`.
+    pub async fn torrent_get(
+        &self,
+        auth: &BasicAuth,
+        fields: &[&str],
+        ids: Option<Value>,
+    ) -> Result<TorrentGet, TransmissionError> {
+        let mut arguments = json!({ "fields": fields });
+        if let Some(ids) = ids {
+            arguments["ids"] = ids;
+        }
+        self.rpc(auth, "torrent-get", arguments).await
+    }
+
+    /// Runs one of the `torrent-*` methods that only take `ids`, e.g. `torrent-stop`.
+    pub async fn torrent_command(
+        &self,
+        auth: &BasicAuth,
+        method: &str,
+        hashes: &[String],
+    ) -> Result<(), TransmissionError> {
+        self.rpc::<Value>(auth, method, json!({ "ids": hashes }))
+            .await?;
+        Ok(())
+    }
+
+    /// Changes the torrents' settings, `arguments` being any of `torrent-set`'s.
+    pub async fn torrent_set(
+        &self,
+        auth: &BasicAuth,
+        hashes: &[String],
+        mut arguments: Value,
+    ) -> Result<(), TransmissionError> {
+        arguments["ids"] = json!(hashes);
+        self.rpc::<Value>(auth, "torrent-set", arguments).await?;
+        Ok(())
+    }
+
+    /// Adds a torrent, `arguments` holding either a `filename` or a base64 `metainfo`.
+    pub async fn torrent_add(
+        &self,
+        auth: &BasicAuth,
+        arguments: Value,
+    ) -> Result<(), TransmissionError> {
+        // Answers with either `torrent-added` or `torrent-duplicate`, both fine by us.
+        self.rpc::<Value>(auth, "torrent-add", arguments).await?;
+        Ok(())
+    }
+
+    pub async fn torrent_remove(
+        &self,
+        auth: &BasicAuth,
+        hashes: &[String],
+        delete_local_data: bool,
+    ) -> Result<(), TransmissionError> {
+        let arguments = json!({ "ids": hashes, "delete-local-data": delete_local_data });
+        self.rpc::<Value>(auth, "torrent-remove", arguments).await?;
+        Ok(())
+    }
+
+    pub async fn session_get(&self, auth: &BasicAuth) -> Result<SessionGet, TransmissionError> {
+        self.rpc(auth, "session-get", json!({})).await
+    }
+
+    /// Changes the session settings in `arguments`, e.g. `{"alt-speed-enabled": true}`.
+    pub async fn session_set(
+        &self,
+        auth: &BasicAuth,
+        arguments: Value,
+    ) -> Result<(), TransmissionError> {
+        self.rpc::<Value>(auth, "session-set", arguments).await?;
+        Ok(())
+    }
+
+    pub async fn session_stats(&self, auth: &BasicAuth) -> Result<SessionStats, TransmissionError> {
+        self.rpc(auth, "session-stats", json!({})).await
+    }
+}
@@ -0,0 +1,515 @@
+//! A fake Transmission RPC endpoint, to test against without a real Transmission.
+//!
+//! It checks basic auth, goes through the session id handshake and implements the `torrent-*`
+//! and `session-*` methods bit-tower uses. `torrent-get` only answers with the fields asked for
+//! and keeps track of recently active and removed torrents like Transmission does.
+//!
+//! ```ignore
+//! let server = MockTransmission::default().spawn().await?;
+//! let hash = server.transmission.add_torrent("debian.iso", 1 << 30);
+//! let client = TransmissionClient::new(&server.url());
+//! ```
+
+use std::collections::{BTreeMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use base64::Engine;
+use http::header::AUTHORIZATION;
+use http::{HeaderMap, StatusCode};
+use serde_json::{json, Map, Value};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+use super::proto::{
+    status, File, FileStats, PeerFields, RpcRequest, RpcResponse, SessionGet, SessionStats, Stats,
+    TorrentFields, TrackerStats, SESSION_ID_HEADER, SPEED_UNIT, SUCCESS,
+};
+
+/// Handle to the fake Transmission, cheap to clone. Every clone sees the same torrents.
+#[derive(Clone, Debug, Default)]
+pub struct MockTransmission {
+    state: Arc<Mutex<MockState>>,
+}
+
+#[derive(Debug)]
+struct MockState {
+    username: String,
+    password: String,
+    session_id: String,
+    next_id: i64,
+    torrents: BTreeMap<i64, TorrentFields>,
+    /// Ids changed since the last `recently-active` request.
+    recently_active: HashSet<i64>,
+    /// Ids removed since the last `recently-active` request.
+    removed: Vec<i64>,
+    session: SessionGet,
+}
+
+impl Default for MockState {
+    fn default() -> Self {
+        Self {
+            username: "transmission".to_owned(),
+            password: "transmission".to_owned(),
+            session_id: new_session_id(),
+            next_id: 1,
+            torrents: BTreeMap::new(),
+            recently_active: HashSet::new(),
+            removed: Vec::new(),
+            session: SessionGet {
+                version: "4.0.6 (38c164933e)".to_owned(),
+                rpc_version: 17,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl MockTransmission {
+    /// Accepts these credentials instead of `transmission`/`transmission`.
+    pub fn with_credentials(self, username: &str, password: &str) -> Self {
+        self.with_state(|state| {
+            state.username = username.to_owned();
+            state.password = password.to_owned();
+        });
+        self
+    }
+
+    /// Adds a stopped torrent of `size` bytes with a single file, returns its hash.
+    pub fn add_torrent(&self, name: &str, size: u64) -> String {
+        self.with_state(|state| state.add_torrent(name, None, size))
+    }
+
+    pub fn remove_torrent(&self, hash: &str) {
+        self.with_state(|state| state.remove(hash));
+    }
+
+    /// Starts the torrent, downloading at `rate` bytes/s.
+    pub fn set_download_rate(&self, hash: &str, rate: u64) {
+        self.with_torrent(hash, |torrent| {
+            torrent.status = status::DOWNLOAD;
+            torrent.rate_download = rate;
+        });
+    }
+
+    /// The torrent as Transmission would describe it with every field.
+    pub fn torrent(&self, hash: &str) -> Option<TorrentFields> {
+        self.with_state(|state| state.find(hash).map(|id| state.torrents[&id].clone()))
+    }
+
+    pub fn is_paused(&self, hash: &str) -> Option<bool> {
+        self.torrent(hash)
+            .map(|torrent| torrent.status == status::STOPPED)
+    }
+
+    pub fn torrent_hashes(&self) -> Vec<String> {
+        self.with_state(|state| {
+            state
+                .torrents
+                .values()
+                .map(|torrent| torrent.hash_string.clone())
+                .collect()
+        })
+    }
+
+    /// Moves every downloading torrent forward by `dt`.
+    pub fn tick(&self, dt: Duration) {
+        self.with_state(|state| {
+            for (id, torrent) in &mut state.torrents {
+                if torrent.status != status::DOWNLOAD {
+                    continue;
+                }
+                let downloaded = ((torrent.rate_download as f64 * dt.as_secs_f64()) as u64)
+                    .min(torrent.left_until_done);
+                torrent.left_until_done -= downloaded;
+                torrent.downloaded_ever += downloaded;
+                refresh(torrent);
+                state.recently_active.insert(*id);
+            }
+        });
+    }
+
+    /// Hands out a new session id, as if Transmission restarted.
+    pub fn rotate_session_id(&self) {
+        self.with_state(|state| state.session_id = new_session_id());
+    }
+
+    /// The RPC endpoint, at `/transmission/rpc` like Transmission's.
+    pub fn router(&self) -> Router {
+        Router::new()
+            .route("/transmission/rpc", post(rpc))
+            .with_state(self.clone())
+    }
+
+    /// Serves the fake on a random local port until the returned server is dropped.
+    pub async fn spawn(self) -> std::io::Result<MockTransmissionServer> {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+        let addr = listener.local_addr()?;
+        let router = self.router();
+        let task = tokio::spawn(async move {
+            if let Err(err) = axum::serve(listener, router).await {
+                tracing::error!(error = %err, "Mock Transmission stopped");
+            }
+        });
+        Ok(MockTransmissionServer {
+            addr,
+            transmission: self,
+            task,
+        })
+    }
+
+    fn with_torrent(&self, hash: &str, f: impl FnOnce(&mut TorrentFields)) {
+        self.with_state(|state| {
+            if let Some(id) = state.find(hash) {
+                f(state.torrents.get_mut(&id).unwrap());
+                refresh(state.torrents.get_mut(&id).unwrap());
+                state.recently_active.insert(id);
+            }
+        });
+    }
+
+    fn with_state<T>(&self, f: impl FnOnce(&mut MockState) -> T) -> T {
+        f(&mut self.state.lock().unwrap())
+    }
+}
+
+/// A [`MockTransmission`] listening on [`MockTransmissionServer::addr`].
+#[derive(Debug)]
+pub struct MockTransmissionServer {
+    pub addr: SocketAddr,
+    pub transmission: MockTransmission,
+    task: JoinHandle<()>,
+}
+
+impl MockTransmissionServer {
+    /// What to give [`super::client::TransmissionClient::new`].
+    pub fn url(&self) -> String {
+        format!("http://{}/transmission/rpc", self.addr)
+    }
+}
+
+impl Drop for MockTransmissionServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Transmission checks the credentials first, then the session id.
+async fn rpc(State(mock): State<MockTransmission>, headers: HeaderMap, body: Bytes) -> Response {
+    let (credentials, session_id) = mock.with_state(|state| {
+        let credentials = format!("{}:{}", state.username, state.password);
+        (
+            base64::prelude::BASE64_STANDARD.encode(credentials),
+            state.session_id.clone(),
+        )
+    });
+    let authorized = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "))
+        .is_some_and(|value| value == credentials);
+    if !authorized {
+        return (StatusCode::UNAUTHORIZED, "Unauthorized").into_response();
+    }
+    let sent_session_id = headers
+        .get(SESSION_ID_HEADER)
+        .and_then(|value| value.to_str().ok());
+    if sent_session_id != Some(session_id.as_str()) {
+        return (
+            StatusCode::CONFLICT,
+            [(SESSION_ID_HEADER, session_id)],
+            "Invalid session id",
+        )
+            .into_response();
+    }
+
+    let Ok(request) = serde_json::from_slice::<RpcRequest>(&body) else {
+        return (StatusCode::BAD_REQUEST, "Invalid request").into_response();
+    };
+    let response = match mock.with_state(|state| state.call(&request.method, request.arguments)) {
+        Ok(arguments) => RpcResponse {
+            result: SUCCESS.to_owned(),
+            arguments,
+        },
+        Err(result) => RpcResponse {
+            result,
+            arguments: json!({}),
+        },
+    };
+    Json(response).into_response()
+}
+
+impl MockState {
+    fn call(&mut self, method: &str, arguments: Value) -> Result<Value, String> {
+        let ids = arguments.get("ids");
+        match method {
+            "session-get" => Ok(json!(self.session)),
+            "session-stats" => Ok(json!(self.stats())),
+            "session-set" => {
+                if let Some(enabled) = arguments.get("alt-speed-enabled").and_then(Value::as_bool) {
+                    self.session.alt_speed_enabled = enabled;
+                }
+                Ok(json!({}))
+            }
+            "torrent-get" => Ok(self.torrent_get(&arguments)),
+            "torrent-start" | "torrent-stop" | "torrent-verify" | "torrent-reannounce" => {
+                for id in self.select(ids) {
+                    let torrent = self.torrents.get_mut(&id).unwrap();
+                    match method {
+                        "torrent-start" if torrent.left_until_done > 0 => {
+                            torrent.status = status::DOWNLOAD
+                        }
+                        "torrent-start" => torrent.status = status::SEED,
+                        "torrent-stop" => torrent.status = status::STOPPED,
+                        // Nothing to simulate.
+                        _ => {}
+                    }
+                    refresh(torrent);
+                    self.recently_active.insert(id);
+                }
+                Ok(json!({}))
+            }
+            "torrent-set" => {
+                for id in self.select(ids) {
+                    let torrent = self.torrents.get_mut(&id).unwrap();
+                    let mut fields = serde_json::to_value(&*torrent).unwrap();
+                    for (key, value) in arguments.as_object().into_iter().flatten() {
+                        if key != "ids" && fields.get(key).is_some() {
+                            fields[key] = value.clone();
+                        }
+                    }
+                    *torrent = serde_json::from_value(fields).map_err(|err| err.to_string())?;
+                    refresh(torrent);
+                    self.recently_active.insert(id);
+                }
+                Ok(json!({}))
+            }
+            "torrent-add" => self.torrent_add(&arguments),
+            "torrent-remove" => {
+                for id in self.select(ids) {
+                    let hash = self.torrents[&id].hash_string.clone();
+                    self.remove(&hash);
+                }
+                Ok(json!({}))
+            }
+            _ => Err("method name not recognized".to_owned()),
+        }
+    }
+
+    fn torrent_get(&mut self, arguments: &Value) -> Value {
+        let fields: HashSet<&str> = arguments["fields"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .collect();
+        let ids = arguments.get("ids");
+        let recently_active = ids.and_then(Value::as_str) == Some("recently-active");
+        let selected = if recently_active {
+            let mut ids: Vec<_> = self.recently_active.drain().collect();
+            ids.sort();
+            ids
+        } else {
+            self.select(ids)
+        };
+        let torrents: Vec<Value> = selected
+            .iter()
+            .filter_map(|id| self.torrents.get(id))
+            .map(|torrent| {
+                let Value::Object(all) = json!(torrent) else {
+                    unreachable!("torrents serialize to objects")
+                };
+                let asked: Map<String, Value> = all
+                    .into_iter()
+                    .filter(|(key, _)| fields.contains(key.as_str()))
+                    .collect();
+                Value::Object(asked)
+            })
+            .collect();
+        if recently_active {
+            json!({ "torrents": torrents, "removed": std::mem::take(&mut self.removed) })
+        } else {
+            json!({ "torrents": torrents })
+        }
+    }
+
+    fn torrent_add(&mut self, arguments: &Value) -> Result<Value, String> {
+        let (name, hash) = if let Some(filename) = arguments["filename"].as_str() {
+            let param = |name: &str| {
+                filename
+                    .split(['?', '&'])
+                    .find_map(|param| param.strip_prefix(name))
+                    .map(str::to_owned)
+            };
+            let hash = param("xt=urn:btih:").map(|hash| hash.to_lowercase());
+            (param("dn=").unwrap_or_else(|| filename.to_owned()), hash)
+        } else if arguments["metainfo"].is_string() {
+            ("metainfo.torrent".to_owned(), None)
+        } else {
+            return Err("no filename or metainfo specified".to_owned());
+        };
+
+        if let Some(id) = hash.as_deref().and_then(|hash| self.find(hash)) {
+            let torrent = &self.torrents[&id];
+            return Ok(json!({ "torrent-duplicate": {
+                "id": id, "hashString": torrent.hash_string, "name": torrent.name,
+            }}));
+        }
+        let hash = self.add_torrent(&name, hash, 0);
+        let id = self.find(&hash).unwrap();
+        let torrent = self.torrents.get_mut(&id).unwrap();
+        if let Some(dir) = arguments["download-dir"].as_str() {
+            torrent.download_dir = dir.to_owned();
+        }
+        if let Ok(labels) = serde_json::from_value(arguments["labels"].clone()) {
+            torrent.labels = labels;
+        }
+        if arguments["paused"].as_bool() != Some(true) {
+            torrent.status = status::DOWNLOAD;
+        }
+        Ok(json!({ "torrent-added": {
+            "id": id, "hashString": hash, "name": name,
+        }}))
+    }
+
+    fn add_torrent(&mut self, name: &str, hash: Option<String>, size: u64) -> String {
+        let id = self.next_id;
+        self.next_id += 1;
+        let hash = hash.unwrap_or_else(|| format!("{id:040x}"));
+        let mut torrent = TorrentFields {
+            id,
+            hash_string: hash.clone(),
+            name: name.to_owned(),
+            status: status::STOPPED,
+            size_when_done: size,
+            total_size: size,
+            left_until_done: size,
+            added_date: now(),
+            download_dir: "/downloads".to_owned(),
+            files: vec![File {
+                name: name.to_owned(),
+                length: size,
+                bytes_completed: 0,
+            }],
+            file_stats: vec![FileStats {
+                wanted: true,
+                priority: 0,
+            }],
+            tracker_stats: vec![TrackerStats {
+                announce: "udp://tracker.example.org:6969/announce".to_owned(),
+                has_announced: true,
+                last_announce_succeeded: true,
+                last_announce_result: "Success".to_owned(),
+                seeder_count: 12,
+                leecher_count: 3,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        refresh(&mut torrent);
+        self.torrents.insert(id, torrent);
+        self.recently_active.insert(id);
+        hash
+    }
+
+    fn remove(&mut self, hash: &str) {
+        if let Some(id) = self.find(hash) {
+            self.torrents.remove(&id);
+            self.recently_active.remove(&id);
+            self.removed.push(id);
+        }
+    }
+
+    fn find(&self, hash: &str) -> Option<i64> {
+        self.torrents
+            .iter()
+            .find(|(_, torrent)| torrent.hash_string.eq_ignore_ascii_case(hash))
+            .map(|(id, _)| *id)
+    }
+
+    /// The torrents `ids` points to: an id, a hash, a list of them, or every torrent.
+    fn select(&self, ids: Option<&Value>) -> Vec<i64> {
+        let one = |id: &Value| match id {
+            Value::Number(id) => id.as_i64().filter(|id| self.torrents.contains_key(id)),
+            Value::String(hash) => self.find(hash),
+            _ => None,
+        };
+        match ids {
+            None => self.torrents.keys().copied().collect(),
+            Some(Value::Array(ids)) => ids.iter().filter_map(one).collect(),
+            Some(id) => one(id).into_iter().collect(),
+        }
+    }
+
+    fn stats(&self) -> SessionStats {
+        let torrents = self.torrents.values();
+        SessionStats {
+            download_speed: torrents.clone().map(|torrent| torrent.rate_download).sum(),
+            upload_speed: torrents.clone().map(|torrent| torrent.rate_upload).sum(),
+            current_stats: Stats {
+                downloaded_bytes: torrents.clone().map(|t| t.downloaded_ever).sum(),
+                uploaded_bytes: torrents.map(|t| t.uploaded_ever).sum(),
+            },
+        }
+    }
+}
+
+/// Recomputes everything derived from what was downloaded and the torrent's status.
+fn refresh(torrent: &mut TorrentFields) {
+    let done = torrent.size_when_done - torrent.left_until_done;
+    torrent.percent_done = if torrent.size_when_done > 0 {
+        done as f64 / torrent.size_when_done as f64
+    } else {
+        1.0
+    };
+    if torrent.left_until_done == 0 && torrent.status == status::DOWNLOAD {
+        torrent.status = status::SEED;
+    }
+    if torrent.status != status::DOWNLOAD {
+        torrent.rate_download = 0;
+    }
+    // Limits are enforced, roughly.
+    if torrent.download_limited {
+        let limit = torrent.download_limit * SPEED_UNIT;
+        torrent.rate_download = torrent.rate_download.min(limit);
+    }
+    torrent.eta = match torrent.rate_download {
+        0 => -1,
+        rate => torrent.left_until_done.div_ceil(rate) as i64,
+    };
+    torrent.desired_available = torrent.left_until_done;
+    torrent.peers_sending_to_us = if torrent.rate_download > 0 { 3 } else { 0 };
+    torrent.peers = if torrent.rate_download > 0 {
+        vec![PeerFields {
+            address: "192.0.2.1".to_owned(),
+            port: 51413,
+            client_name: "Transmission 4.0.6".to_owned(),
+            progress: 1.0,
+            rate_to_client: torrent.rate_download,
+            flag_str: "TDE".to_owned(),
+            ..Default::default()
+        }]
+    } else {
+        Vec::new()
+    };
+    if let Some(file) = torrent.files.first_mut() {
+        file.bytes_completed = done;
+    }
+}
+
+fn new_session_id() -> String {
+    uuid::Uuid::new_v4().simple().to_string()
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
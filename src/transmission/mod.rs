@@ -0,0 +1,7 @@
+pub mod proto;
+
+#[cfg(feature = "ssr")]
+pub mod client;
+
+#[cfg(feature = "mock")]
+pub mod mock;
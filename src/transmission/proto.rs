@@ -0,0 +1,185 @@
+//! Transmission's RPC, as described in its `docs/rpc-spec.md`. Every call is a POST of a
+//! [`RpcRequest`] answered with an [`RpcResponse`], only the fields bit-tower reads are here.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Header carrying the CSRF token Transmission hands out with a 409.
+pub static SESSION_ID_HEADER: &str = "X-Transmission-Session-Id";
+
+/// What [`RpcResponse::result`] says when all went well, anything else is an error message.
+pub static SUCCESS: &str = "success";
+
+/// Transmission's speed limits are in kB/s.
+pub const SPEED_UNIT: u64 = 1000;
+
+/// Fields asked for with `torrent-get` to fill the torrent list.
+pub static TORRENT_FIELDS: &[&str] = &[
+    "id",
+    "hashString",
+    "name",
+    "status",
+    "error",
+    "errorString",
+    "percentDone",
+    "sizeWhenDone",
+    "totalSize",
+    "leftUntilDone",
+    "desiredAvailable",
+    "downloadedEver",
+    "uploadedEver",
+    "rateDownload",
+    "rateUpload",
+    "eta",
+    "peersSendingToUs",
+    "peersGettingFromUs",
+    "uploadRatio",
+    "addedDate",
+    "downloadDir",
+    "labels",
+    "downloadLimit",
+    "downloadLimited",
+    "uploadLimit",
+    "uploadLimited",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RpcRequest {
+    pub method: String,
+    #[serde(default)]
+    pub arguments: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RpcResponse {
+    pub result: String,
+    #[serde(default)]
+    pub arguments: Value,
+}
+
+/// A torrent's `status`.
+pub mod status {
+    pub const STOPPED: i64 = 0;
+    pub const CHECK_WAIT: i64 = 1;
+    pub const CHECK: i64 = 2;
+    pub const DOWNLOAD_WAIT: i64 = 3;
+    pub const DOWNLOAD: i64 = 4;
+    pub const SEED_WAIT: i64 = 5;
+    pub const SEED: i64 = 6;
+}
+
+/// Arguments of a `torrent-get` response.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct TorrentGet {
+    pub torrents: Vec<TorrentFields>,
+    /// Ids of the torrents removed lately, only when asking for `recently-active` ones.
+    pub removed: Vec<i64>,
+}
+
+/// A torrent in a `torrent-get` response, with whichever fields were asked for.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct TorrentFields {
+    pub id: i64,                          // Id, only valid until Transmission restarts
+    pub hash_string: String,              // Info hash
+    pub name: String,                     // Torrent name
+    pub status: i64,                      // See `status`
+    pub error: i64,                       // 0 if all is well, a warning or an error otherwise
+    pub error_string: String,             // What the error is about
+    pub percent_done: f64,                // Progress of the files selected for download (0 to 1)
+    pub size_when_done: u64,              // Size of the files selected for download (bytes)
+    pub total_size: u64,                  // Size of all the files (bytes)
+    pub left_until_done: u64,             // Bytes left to download
+    pub desired_available: u64,           // Bytes we still need that connected peers have
+    pub downloaded_ever: u64,             // Downloaded, including what was discarded (bytes)
+    pub uploaded_ever: u64,               // Uploaded (bytes)
+    pub rate_download: u64,               // Download speed (bytes/s)
+    pub rate_upload: u64,                 // Upload speed (bytes/s)
+    pub eta: i64,                         // Seconds left, -1 if not downloading, -2 if unknown
+    pub peers_sending_to_us: u64,         // Peers we download from
+    pub peers_getting_from_us: u64,       // Peers we upload to
+    pub upload_ratio: f64, // Share ratio, -1 if nothing was downloaded, -2 if infinite
+    pub added_date: i64,   // Time (Unix Epoch) the torrent was added
+    pub download_dir: String, // Where the torrent is saved
+    pub labels: Vec<String>, // Labels, Transmission 3 and later
+    pub download_limit: u64, // Download speed limit (kB/s)
+    pub download_limited: bool, // Whether `download_limit` applies
+    pub upload_limit: u64, // Upload speed limit (kB/s)
+    pub upload_limited: bool, // Whether `upload_limit` applies
+    pub files: Vec<File>,  // Files, in the same order as `file_stats`
+    pub file_stats: Vec<FileStats>, // What we do with each file
+    pub peers: Vec<PeerFields>, // Connected peers
+    pub tracker_stats: Vec<TrackerStats>, // Trackers and how they answered
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct File {
+    pub name: String,         // File name, including the torrent's directory
+    pub length: u64,          // File size (bytes)
+    pub bytes_completed: u64, // Downloaded and verified (bytes)
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct FileStats {
+    pub wanted: bool,  // Whether the file is downloaded at all
+    pub priority: i64, // -1 low, 0 normal, 1 high
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PeerFields {
+    pub address: String,     // Peer IP address
+    pub port: u16,           // Peer port
+    pub client_name: String, // Client used by the peer
+    pub progress: f64,       // How much of the torrent the peer has (0 to 1)
+    pub rate_to_client: u64, // Download speed from this peer (bytes/s)
+    pub rate_to_peer: u64,   // Upload speed to this peer (bytes/s)
+    pub flag_str: String,    // Peer flags, e.g. "TDEI"
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct TrackerStats {
+    pub announce: String,              // Announce URL
+    pub tier: u64,                     // Tier, starting at 0
+    pub has_announced: bool,           // Whether we announced to it at all
+    pub last_announce_succeeded: bool, // Whether the last announce went fine
+    pub last_announce_result: String,  // What the tracker answered last
+    pub announce_state: i64,           // 0 inactive, 1 waiting, 2 queued, 3 announcing
+    pub seeder_count: i64,             // Seeds the tracker knows about, -1 if unknown
+    pub leecher_count: i64,            // Leechers the tracker knows about, -1 if unknown
+}
+
+/// Arguments of a `session-get` response.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct SessionGet {
+    pub version: String,       // Transmission version, e.g. "4.0.6 (38c164933e)"
+    pub rpc_version: u64,      // RPC version
+    pub speed_limit_down: u64, // Global download speed limit (kB/s)
+    pub speed_limit_down_enabled: bool, // Whether `speed_limit_down` applies
+    pub speed_limit_up: u64,   // Global upload speed limit (kB/s)
+    pub speed_limit_up_enabled: bool, // Whether `speed_limit_up` applies
+    pub alt_speed_enabled: bool, // Whether the alternative ("turtle") limits apply
+    pub download_dir_free_space: Option<i64>, // Free space in `download-dir` (bytes), -1 if unknown
+}
+
+/// Arguments of a `session-stats` response.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SessionStats {
+    pub download_speed: u64, // Global download speed (bytes/s)
+    pub upload_speed: u64,   // Global upload speed (bytes/s)
+    #[serde(rename = "current-stats")]
+    pub current_stats: Stats, // Since Transmission started
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Stats {
+    pub downloaded_bytes: u64,
+    pub uploaded_bytes: u64,
+}
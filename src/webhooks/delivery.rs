@@ -0,0 +1,177 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use utoipa::ToSchema;
+
+use super::{Event, EventKind, WebhookOptions};
+
+/// Name of the header with the signature of the body.
+pub const SIGNATURE_HEADER: &str = "X-Bittower-Signature-256";
+/// Name of the header with the kind of event.
+pub const EVENT_HEADER: &str = "X-Bittower-Event";
+/// Name of the header with the id of the delivery, the same for every attempt.
+pub const DELIVERY_HEADER: &str = "X-Bittower-Delivery";
+
+/// One event sent to one URL, however many attempts it took.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Delivery {
+    pub id: String,
+    pub url: String,
+    /// Id of the event delivered.
+    pub event_id: String,
+    pub event: EventKind,
+    pub instance: String,
+    /// Hash of the torrent the event is about.
+    pub hash: String,
+    /// Attempts so far.
+    pub attempts: u32,
+    /// Status code of the last response, `null` when there was none.
+    pub status: Option<u16>,
+    /// Why the last attempt failed, `null` when it didn't.
+    pub error: Option<String>,
+    /// Whether the URL accepted it. `false` while it is still being retried.
+    pub delivered: bool,
+    pub started_at: DateTime<Utc>,
+    /// When it was delivered or given up on, `null` while it is still being retried.
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// The last deliveries, newest last. Cheap to clone.
+#[derive(Clone, Debug, Default)]
+pub(super) struct DeliveryLog {
+    size: usize,
+    deliveries: Arc<Mutex<VecDeque<Delivery>>>,
+}
+
+impl DeliveryLog {
+    pub(super) fn new(size: usize) -> Self {
+        Self {
+            size,
+            deliveries: Default::default(),
+        }
+    }
+
+    fn push(&self, delivery: Delivery) {
+        let mut deliveries = self.deliveries.lock().unwrap();
+        deliveries.push_back(delivery);
+        while deliveries.len() > self.size {
+            deliveries.pop_front();
+        }
+    }
+
+    /// Updates delivery `id`, unless it already made room for newer ones.
+    fn update(&self, id: &str, f: impl FnOnce(&mut Delivery)) {
+        let mut deliveries = self.deliveries.lock().unwrap();
+        if let Some(delivery) = deliveries
+            .iter_mut()
+            .rev()
+            .find(|delivery| delivery.id == id)
+        {
+            f(delivery);
+        }
+    }
+
+    /// Newest first.
+    pub(super) fn list(&self) -> Vec<Delivery> {
+        self.deliveries
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .cloned()
+            .collect()
+    }
+}
+
+/// `sha256=` and the hex HMAC-SHA256 of `body` keyed with `secret`, what
+/// [`SIGNATURE_HEADER`] holds. Receivers compute it again over the raw body to check it.
+pub fn signature(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(body);
+    let hex: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    format!("sha256={hex}")
+}
+
+/// POSTs `event` to `url` until it is accepted or [`WebhookOptions::attempts`] ran out,
+/// waiting twice as long after every failure. Only failures that may pass are retried: the
+/// URL not answering, 5xx and 429.
+pub(super) async fn deliver(
+    client: &reqwest::Client,
+    options: &WebhookOptions,
+    log: &DeliveryLog,
+    url: &str,
+    event: &Event,
+) {
+    let body = serde_json::to_vec(event).expect("events always serialize");
+    let signature = signature(&options.secret, &body);
+    let id = uuid::Uuid::new_v4().to_string();
+    log.push(Delivery {
+        id: id.clone(),
+        url: url.to_owned(),
+        event_id: event.id.clone(),
+        event: event.event,
+        instance: event.instance.clone(),
+        hash: event.torrent.hash.clone(),
+        attempts: 0,
+        status: None,
+        error: None,
+        delivered: false,
+        started_at: Utc::now(),
+        finished_at: None,
+    });
+
+    let mut delay = options.backoff;
+    for attempt in 1..=options.attempts.max(1) {
+        let res = client
+            .post(url)
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, event.event.as_str())
+            .header(DELIVERY_HEADER, &id)
+            .header(SIGNATURE_HEADER, &signature)
+            .body(body.clone())
+            .send()
+            .await;
+        let (status, error, retry) = match res {
+            Ok(response) if response.status().is_success() => {
+                (Some(response.status()), None, false)
+            }
+            Ok(response) => {
+                let status = response.status();
+                let retry = status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS;
+                (Some(status), Some(format!("Answered {status}")), retry)
+            }
+            Err(err) => (None, Some(err.to_string()), true),
+        };
+        let last = error.is_none() || !retry || attempt == options.attempts.max(1);
+        log.update(&id, |delivery| {
+            delivery.attempts = attempt;
+            delivery.status = status.map(|status| status.as_u16());
+            delivery.delivered = error.is_none();
+            delivery.error.clone_from(&error);
+            if last {
+                delivery.finished_at = Some(Utc::now());
+            }
+        });
+        if last {
+            if let Some(error) = error {
+                tracing::warn!(%url, event = event.event.as_str(), attempt, %error, "Webhook not delivered");
+            }
+            return;
+        }
+        tracing::debug!(%url, attempt, ?delay, "Retrying webhook");
+        tokio::time::sleep(delay).await;
+        delay = delay.saturating_mul(2).min(Duration::from_secs(60 * 60));
+    }
+}
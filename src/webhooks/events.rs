@@ -0,0 +1,167 @@
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::api::v1;
+use crate::backend::model::{Torrent, TorrentState};
+
+/// What happened to a torrent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Added,
+    /// Every selected file downloaded.
+    Completed,
+    Errored,
+    /// Stalled for as long as the stall threshold, reported once per stall.
+    Stalled,
+    Removed,
+    /// Its ratio went past the ratio target.
+    RatioReached,
+}
+
+impl EventKind {
+    pub const ALL: [EventKind; 6] = [
+        EventKind::Added,
+        EventKind::Completed,
+        EventKind::Errored,
+        EventKind::Stalled,
+        EventKind::Removed,
+        EventKind::RatioReached,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EventKind::Added => "added",
+            EventKind::Completed => "completed",
+            EventKind::Errored => "errored",
+            EventKind::Stalled => "stalled",
+            EventKind::Removed => "removed",
+            EventKind::RatioReached => "ratio_reached",
+        }
+    }
+}
+
+impl FromStr for EventKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        EventKind::ALL
+            .into_iter()
+            .find(|kind| kind.as_str() == s)
+            .ok_or_else(|| anyhow::anyhow!("`{s}` isn't an event"))
+    }
+}
+
+/// What webhooks are sent, the torrent as it was when it happened.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Event {
+    /// Unique, for receivers to tell retries apart from new events.
+    pub id: String,
+    pub event: EventKind,
+    pub instance: String,
+    pub occurred_at: DateTime<Utc>,
+    pub torrent: v1::Torrent,
+}
+
+/// When torrents count as stalled or seeded enough.
+#[derive(Clone, Debug)]
+pub struct Thresholds {
+    pub stalled_after: Duration,
+    /// Ratio that fires [`EventKind::RatioReached`], `None` for no such event.
+    pub ratio_target: Option<f64>,
+}
+
+/// Finds the events between successive syncs of every instance.
+#[derive(Debug)]
+pub struct EventDetector {
+    thresholds: Thresholds,
+    instances: HashMap<String, Tracked>,
+}
+
+#[derive(Debug, Default)]
+struct Tracked {
+    torrents: HashMap<String, Torrent>,
+    /// When the stalled torrents were first seen stalled.
+    stalled_since: HashMap<String, DateTime<Utc>>,
+    /// Stalled torrents already reported, until they get going again.
+    stalled_reported: HashSet<String>,
+}
+
+impl EventDetector {
+    pub fn new(thresholds: Thresholds) -> Self {
+        Self {
+            thresholds,
+            instances: HashMap::new(),
+        }
+    }
+
+    /// What changed on `instance` since its last sync, now that it has `torrents`. The first
+    /// sync of an instance only sets where it is at, bit-tower starting isn't news.
+    pub fn diff(
+        &mut self,
+        instance: &str,
+        torrents: &HashMap<String, Torrent>,
+        now: DateTime<Utc>,
+    ) -> Vec<Event> {
+        let first = !self.instances.contains_key(instance);
+        let tracked = self.instances.entry(instance.to_owned()).or_default();
+        let mut events = Vec::new();
+        let mut event = |kind, torrent: &Torrent| {
+            events.push(Event {
+                id: uuid::Uuid::new_v4().to_string(),
+                event: kind,
+                instance: instance.to_owned(),
+                occurred_at: now,
+                torrent: v1::Torrent::new(instance, torrent),
+            });
+        };
+
+        for (hash, torrent) in torrents {
+            if torrent.state == TorrentState::Stalled {
+                let since = *tracked.stalled_since.entry(hash.clone()).or_insert(now);
+                let stalled_for = (now - since).to_std().unwrap_or_default();
+                if stalled_for >= self.thresholds.stalled_after
+                    && tracked.stalled_reported.insert(hash.clone())
+                    && !first
+                {
+                    event(EventKind::Stalled, torrent);
+                }
+            } else {
+                tracked.stalled_since.remove(hash);
+                tracked.stalled_reported.remove(hash);
+            }
+            if first {
+                continue;
+            }
+            let Some(before) = tracked.torrents.get(hash) else {
+                event(EventKind::Added, torrent);
+                continue;
+            };
+            if before.progress < 1.0 && torrent.progress >= 1.0 {
+                event(EventKind::Completed, torrent);
+            }
+            if before.state != TorrentState::Error && torrent.state == TorrentState::Error {
+                event(EventKind::Errored, torrent);
+            }
+            if let Some(target) = self.thresholds.ratio_target {
+                if before.ratio < target && torrent.ratio >= target {
+                    event(EventKind::RatioReached, torrent);
+                }
+            }
+        }
+        for (hash, torrent) in &tracked.torrents {
+            if !torrents.contains_key(hash) {
+                event(EventKind::Removed, torrent);
+                tracked.stalled_since.remove(hash);
+                tracked.stalled_reported.remove(hash);
+            }
+        }
+        tracked.torrents = torrents.clone();
+        events
+    }
+}
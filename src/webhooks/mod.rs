@@ -0,0 +1,203 @@
+//! Outgoing webhooks, for chat bots and scripts that want to hear about torrents as they
+//! change rather than polling the REST API.
+//!
+//! Events are found by comparing what the background checks sync from every instance with
+//! what they synced the time before, so that there are no more requests to the torrent clients
+//! for them. Every event is POSTed as JSON to every URL, signed with an HMAC of the shared
+//! secret, and retried for a while when the URL doesn't take it. Every URL has a queue of its
+//! own, so that it gets the events in the order they happened. The last deliveries are kept for
+//! the REST API to show.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::Utc;
+use default_struct_builder::DefaultBuilder;
+use tokio::sync::mpsc;
+
+use crate::backend::model::Torrent;
+
+mod delivery;
+mod events;
+
+pub use delivery::{signature, Delivery, DELIVERY_HEADER, EVENT_HEADER, SIGNATURE_HEADER};
+pub use events::*;
+
+/// Where webhooks are sent and which ones.
+#[derive(DefaultBuilder, Clone, Debug)]
+pub struct WebhookOptions {
+    /// URLs every event is POSTed to.
+    urls: Vec<String>,
+    /// Key of the HMAC in [`SIGNATURE_HEADER`].
+    secret: String,
+    /// How long a torrent is stalled before it is reported.
+    stalled_after: Duration,
+    /// Ratio that is reported when a torrent reaches it.
+    #[builder(into)]
+    ratio_target: Option<f64>,
+    /// The kinds of events sent, the others are dropped.
+    events: Vec<EventKind>,
+    /// Attempts per delivery, the first one included.
+    attempts: u32,
+    /// Wait after the first failed attempt, doubled after every further one.
+    backoff: Duration,
+    /// How long the URL has to answer.
+    timeout: Duration,
+    /// Deliveries kept in the log.
+    log_size: usize,
+}
+
+impl Default for WebhookOptions {
+    fn default() -> Self {
+        Self {
+            urls: Vec::new(),
+            secret: String::new(),
+            stalled_after: Duration::from_secs(30 * 60),
+            ratio_target: None,
+            events: EventKind::ALL.to_vec(),
+            attempts: 5,
+            backoff: Duration::from_secs(1),
+            timeout: Duration::from_secs(10),
+            log_size: 100,
+        }
+    }
+}
+
+/// Sends the webhooks, does nothing when there are no URLs. Cheap to clone.
+#[derive(Clone, Debug, Default)]
+pub struct Webhooks {
+    inner: Option<Arc<Inner>>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    deliverer: Arc<Deliverer>,
+    detector: Mutex<EventDetector>,
+    /// One queue per URL, each emptied in order by a worker of its own.
+    queues: Vec<mpsc::UnboundedSender<Event>>,
+}
+
+/// What the workers deliver with.
+#[derive(Debug)]
+struct Deliverer {
+    options: WebhookOptions,
+    client: reqwest::Client,
+    log: delivery::DeliveryLog,
+}
+
+impl Webhooks {
+    /// Starts a worker per URL, so it has to be called within a Tokio runtime. They stop once
+    /// every clone is dropped.
+    pub fn new(options: WebhookOptions) -> anyhow::Result<Self> {
+        if options.urls.is_empty() {
+            return Ok(Self::default());
+        }
+        let client = reqwest::Client::builder()
+            .timeout(options.timeout)
+            .user_agent(concat!("bit-tower/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+        let detector = EventDetector::new(Thresholds {
+            stalled_after: options.stalled_after,
+            ratio_target: options.ratio_target,
+        });
+        let deliverer = Arc::new(Deliverer {
+            log: delivery::DeliveryLog::new(options.log_size),
+            options,
+            client,
+        });
+        let queues = deliverer
+            .options
+            .urls
+            .iter()
+            .map(|url| {
+                let (queue, events) = mpsc::unbounded_channel();
+                tokio::spawn(deliver_in_order(deliverer.clone(), url.clone(), events));
+                queue
+            })
+            .collect();
+        Ok(Self {
+            inner: Some(Arc::new(Inner {
+                deliverer,
+                detector: Mutex::new(detector),
+                queues,
+            })),
+        })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    /// To be called after every sync of `instance`, with its torrents as of the sync. Queues
+    /// the wanted events since the previous one for every URL, which gets them in the order
+    /// they happened: an event only once the one before was delivered or ran out of attempts,
+    /// whichever sync it came from.
+    pub fn synced(&self, instance: &str, torrents: &HashMap<String, Torrent>) {
+        let Some(inner) = &self.inner else {
+            return;
+        };
+        let events = inner
+            .detector
+            .lock()
+            .unwrap()
+            .diff(instance, torrents, Utc::now());
+        for event in events {
+            if !inner.deliverer.options.events.contains(&event.event) {
+                continue;
+            }
+            for queue in &inner.queues {
+                // Only fails once the worker is gone, with the runtime.
+                let _ = queue.send(event.clone());
+            }
+        }
+    }
+
+    /// Sends `event` to every URL right away, past the queues, unless its kind isn't wanted.
+    /// Done once every URL took it or ran out of attempts.
+    pub async fn send(&self, event: &Event) {
+        let Some(inner) = &self.inner else {
+            return;
+        };
+        let deliverer = &inner.deliverer;
+        if !deliverer.options.events.contains(&event.event) {
+            return;
+        }
+        let deliveries = deliverer.options.urls.iter().map(|url| {
+            delivery::deliver(
+                &deliverer.client,
+                &deliverer.options,
+                &deliverer.log,
+                url,
+                event,
+            )
+        });
+        futures::future::join_all(deliveries).await;
+    }
+
+    /// The last deliveries, newest first.
+    pub fn deliveries(&self) -> Vec<Delivery> {
+        self.inner
+            .as_ref()
+            .map(|inner| inner.deliverer.log.list())
+            .unwrap_or_default()
+    }
+}
+
+/// Delivers the events of `url`'s queue one after the other, until the queue is dropped.
+async fn deliver_in_order(
+    deliverer: Arc<Deliverer>,
+    url: String,
+    mut events: mpsc::UnboundedReceiver<Event>,
+) {
+    while let Some(event) = events.recv().await {
+        delivery::deliver(
+            &deliverer.client,
+            &deliverer.options,
+            &deliverer.log,
+            &url,
+            &event,
+        )
+        .await;
+    }
+}
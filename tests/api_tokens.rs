@@ -0,0 +1,175 @@
+//! Personal API tokens, run with `cargo test --features mock`.
+#![cfg(feature = "mock")]
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use bittower::api::{ApiAuth, ApiCaller};
+use bittower::app::auth::ssr::{
+    auth, hash_password, AuthSession, Session, SessionId, SessionStore, TokenStore, User, Users,
+    UsersFile,
+};
+use bittower::app::auth::{Role, TokenScope};
+use bittower::backend::{BackendRegistry, QbittorrentBackend, Relogin};
+use bittower::qbittorrent::client::{BasicAuth, QbtClient};
+use bittower::qbittorrent::mock::MockQbittorrent;
+use http::{header, HeaderMap, HeaderValue};
+use leptos::prelude::*;
+
+fn users() -> Users {
+    let user = |role, instances: Option<&[&str]>| User {
+        password: hash_password("correct horse"),
+        role,
+        instances: instances.map(|names| names.iter().map(|name| name.to_string()).collect()),
+    };
+    Users::new(UsersFile {
+        users: HashMap::from([
+            ("alice".to_owned(), user(Role::Admin, None)),
+            ("bob".to_owned(), user(Role::Viewer, Some(&["seedbox1"]))),
+        ]),
+        credentials: HashMap::from([(
+            "default".to_owned(),
+            BasicAuth {
+                username: "admin".to_owned(),
+                password: "adminadmin".to_owned(),
+            },
+        )]),
+    })
+    .unwrap()
+}
+
+fn bearer(token: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    let value = HeaderValue::from_str(&format!("Bearer {token}")).unwrap();
+    headers.insert(header::AUTHORIZATION, value);
+    headers
+}
+
+#[test]
+fn tokens_are_listed_and_revoked_by_their_user() {
+    let tokens = TokenStore::default();
+    let (token, info) = tokens.create("alice", "backup script", TokenScope::ReadOnly);
+    tokens.create("bob", "dashboard", TokenScope::Full);
+
+    assert!(token.starts_with("btt_"));
+    assert_eq!(tokens.list("alice"), [info.clone()]);
+    assert_eq!(tokens.verify(&token).unwrap().username, "alice");
+    assert!(tokens.list("alice")[0].last_used.is_some());
+
+    assert_eq!(
+        tokens.revoke("bob", &info.id),
+        None,
+        "only alice may revoke it"
+    );
+    assert!(tokens.revoke("alice", &info.id).is_some());
+    assert_eq!(tokens.verify(&token), None);
+    assert!(tokens.list("alice").is_empty());
+}
+
+#[test]
+fn tokens_are_kept_hashed_across_restarts() {
+    let dir = std::env::temp_dir().join(format!("bittower-tokens-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("tokens.json");
+    let _ = std::fs::remove_file(&path);
+
+    let (token, _) = TokenStore::new(Some(path.clone())).unwrap().create(
+        "alice",
+        "backup script",
+        TokenScope::Full,
+    );
+
+    assert!(!std::fs::read_to_string(&path).unwrap().contains(&token));
+    let tokens = TokenStore::new(Some(path.clone())).unwrap();
+    assert_eq!(tokens.verify(&token).unwrap().scope, TokenScope::Full);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn the_rest_api_takes_personal_tokens_within_their_scope() {
+    let tokens = TokenStore::default();
+    let auth = ApiAuth::new(Some("shared"), tokens.clone(), Some(users()));
+    let (read_only, _) = tokens.create("alice", "dashboard", TokenScope::ReadOnly);
+    let (full, _) = tokens.create("alice", "cron", TokenScope::Full);
+    let (bobs, _) = tokens.create("bob", "phone", TokenScope::Full);
+
+    assert_eq!(
+        auth.caller(&bearer("shared")).map(|caller| caller.role),
+        Some(Role::Admin)
+    );
+    assert_eq!(auth.caller(&bearer(&read_only)).unwrap().role, Role::Viewer);
+    assert_eq!(auth.caller(&bearer(&full)).unwrap().role, Role::Admin);
+    let bob: ApiCaller = auth.caller(&bearer(&bobs)).unwrap();
+    assert_eq!(
+        bob.role,
+        Role::Viewer,
+        "tokens can't do more than their user"
+    );
+    assert!(bob.may_use("seedbox1") && !bob.may_use("seedbox2"));
+    assert_eq!(auth.caller(&bearer("btt_guess")), None);
+    assert_eq!(auth.caller(&HeaderMap::new()), None);
+}
+
+#[tokio::test]
+async fn tokens_get_a_session_of_their_own() {
+    let server = MockQbittorrent::default().spawn().await.unwrap();
+    let backend = QbittorrentBackend::new(QbtClient::new(&server.base_url()));
+    let registry = BackendRegistry::single(Relogin::new(Arc::new(backend)));
+    let sessions = SessionStore::default();
+    let tokens = TokenStore::default();
+    let users = users();
+    let (token, info) = tokens.create("alice", "cron", TokenScope::ReadOnly);
+
+    let session = tokens
+        .session(&token, &sessions, &users, &registry)
+        .await
+        .unwrap();
+    assert_eq!(session.username, "alice");
+    assert_eq!(session.role, Role::Viewer);
+    assert!(session.sid("default").is_some());
+    // The next request finds it rather than logging in again.
+    assert_eq!(sessions.get(&token), Some(session));
+
+    tokens.revoke("alice", &info.id);
+    assert!(tokens
+        .session(&token, &sessions, &users, &registry)
+        .await
+        .is_none());
+}
+
+#[tokio::test]
+async fn a_read_only_token_cannot_log_its_user_out() {
+    let server = MockQbittorrent::default().spawn().await.unwrap();
+    let backend = QbittorrentBackend::new(QbtClient::new(&server.base_url()));
+    let registry = BackendRegistry::single(Relogin::new(Arc::new(backend)));
+    let sessions = SessionStore::default();
+    let tokens = TokenStore::default();
+    let users = users();
+    let browser = sessions.create(Session {
+        username: "alice".to_owned(),
+        role: Role::Admin,
+        ..Default::default()
+    });
+    let (token, _) = tokens.create("alice", "cron", TokenScope::ReadOnly);
+    let session = tokens
+        .session(&token, &sessions, &users, &registry)
+        .await
+        .unwrap();
+
+    // Server functions, `Logout` among them, see no session for the token.
+    let token_session = AuthSession::new(token, session);
+    assert!(token_session.is_token());
+    Owner::new().with(|| {
+        token_session.provide();
+        assert!(use_context::<SessionId>().is_none());
+        assert_eq!(auth().unwrap(), None);
+    });
+    assert!(sessions.contains(&browser));
+
+    let browser_session = AuthSession::new(browser.clone(), sessions.get(&browser).unwrap());
+    Owner::new().with(|| {
+        browser_session.provide();
+        assert_eq!(use_context::<SessionId>(), Some(SessionId(browser)));
+        assert_eq!(auth().unwrap().unwrap().username, "alice");
+    });
+}
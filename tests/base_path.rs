@@ -0,0 +1,63 @@
+//! Serving under a base path behind reverse proxies, run with `cargo test --features ssr`.
+#![cfg(feature = "ssr")]
+
+use std::net::IpAddr;
+
+use bittower::app::auth::ssr::{Forwarded, TrustedProxies};
+use bittower::app::BasePath;
+use http::{HeaderMap, HeaderValue};
+
+fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in pairs {
+        headers.append(*name, HeaderValue::from_str(value).unwrap());
+    }
+    headers
+}
+
+#[test]
+fn base_paths_are_normalized() {
+    for path in ["torrents", "/torrents", "/torrents/", " torrents/ "] {
+        assert_eq!(BasePath::new(path).as_str(), "/torrents");
+    }
+    assert_eq!(BasePath::new("/").as_str(), "");
+    assert_eq!(BasePath::new("").join("/ws"), "/ws");
+    assert_eq!(BasePath::new("/torrents").join("/ws"), "/torrents/ws");
+}
+
+#[test]
+fn only_paths_under_the_base_path_are_stripped() {
+    let base = BasePath::new("/torrents");
+
+    assert_eq!(base.strip("/torrents"), Some("/"));
+    assert_eq!(base.strip("/torrents/"), Some("/"));
+    assert_eq!(base.strip("/torrents/api/login"), Some("/api/login"));
+    assert_eq!(base.strip("/torrentsfoo"), None);
+    assert_eq!(base.strip("/ws"), None);
+    assert_eq!(BasePath::default().strip("/ws"), Some("/ws"));
+}
+
+#[test]
+fn forwarded_headers_are_only_taken_from_trusted_proxies() {
+    let proxies = TrustedProxies::new("10.0.0.0/8", None).unwrap();
+    let headers = headers(&[
+        ("x-forwarded-proto", "HTTPS"),
+        ("x-forwarded-host", "home.example, proxy.lan"),
+        ("x-forwarded-prefix", "/torrents"),
+    ]);
+
+    let proxy: IpAddr = "10.0.0.2".parse().unwrap();
+    let forwarded = proxies.forwarded(proxy, &headers);
+    assert_eq!(
+        forwarded,
+        Forwarded {
+            proto: Some("https".to_owned()),
+            host: Some("home.example".to_owned()),
+            prefix: Some("/torrents".to_owned()),
+        }
+    );
+    assert_eq!(forwarded.origin().as_deref(), Some("https://home.example"));
+
+    let client: IpAddr = "192.0.2.1".parse().unwrap();
+    assert_eq!(proxies.forwarded(client, &headers), Forwarded::default());
+}
@@ -0,0 +1,83 @@
+//! CSRF tokens and websocket origins, run with `cargo test --features ssr`.
+#![cfg(feature = "ssr")]
+
+use bittower::app::auth::ssr::{AllowedOrigins, CsrfToken, Forwarded};
+use http::{HeaderMap, HeaderValue};
+
+fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in pairs {
+        headers.append(*name, HeaderValue::from_str(value).unwrap());
+    }
+    headers
+}
+
+#[test]
+fn tokens_come_back_from_the_cookie() {
+    let token = CsrfToken::generate();
+    assert_ne!(token, CsrfToken::generate());
+
+    let cookie = token.cookie(true);
+    for attribute in ["HttpOnly", "Secure", "SameSite=Strict", "Path=/"] {
+        assert!(cookie.contains(attribute), "{cookie} lacks {attribute}");
+    }
+
+    let sent = format!("bt-session=abc; bt-csrf={}", token.0);
+    assert_eq!(
+        CsrfToken::from_headers(&headers(&[("cookie", &sent)])),
+        Some(token)
+    );
+    assert_eq!(
+        CsrfToken::from_headers(&headers(&[("cookie", "bt-csrf=short")])),
+        None
+    );
+}
+
+#[test]
+fn only_the_same_token_matches() {
+    let token = CsrfToken::generate();
+
+    assert!(token.matches(&token.0));
+    assert!(!token.matches(&CsrfToken::generate().0));
+    assert!(!token.matches(""));
+}
+
+#[test]
+fn websockets_only_come_from_our_origin_or_allowed_ones() {
+    let origins = AllowedOrigins::new("https://torrents.example.com/, http://localhost:3000");
+    let from = |origin: &str, host: &str| {
+        origins.allows(
+            &headers(&[("origin", origin), ("host", host)]),
+            &Forwarded::default(),
+        )
+    };
+
+    assert!(from("https://bittower.lan:3000", "bittower.lan:3000"));
+    assert!(from("https://torrents.example.com", "127.0.0.1:3000"));
+    assert!(from("http://localhost:3000", "127.0.0.1:3000"));
+    assert!(!from("https://evil.example.org", "bittower.lan:3000"));
+    assert!(
+        origins.allows(&headers(&[("host", "bittower.lan")]), &Forwarded::default()),
+        "clients other than browsers send no origin"
+    );
+}
+
+#[test]
+fn behind_a_proxy_our_origin_is_the_forwarded_one() {
+    let origins = AllowedOrigins::default();
+    let forwarded = Forwarded {
+        proto: Some("https".to_owned()),
+        host: Some("home.example".to_owned()),
+        prefix: None,
+    };
+    let from = |origin: &str| {
+        origins.allows(
+            &headers(&[("origin", origin), ("host", "127.0.0.1:3010")]),
+            &forwarded,
+        )
+    };
+
+    assert!(from("https://home.example"));
+    assert!(!from("http://home.example"), "the scheme has to match too");
+    assert!(!from("http://127.0.0.1:3010"));
+}
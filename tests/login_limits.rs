@@ -0,0 +1,117 @@
+//! Login throttling and client addresses behind proxies, run with `cargo test --features ssr`.
+#![cfg(feature = "ssr")]
+
+use std::net::IpAddr;
+use std::time::Duration;
+
+use bittower::app::auth::ssr::{LimiterOptions, LoginLimiter, TrustedProxies};
+use http::{HeaderMap, HeaderValue};
+
+fn ip(ip: &str) -> IpAddr {
+    ip.parse().unwrap()
+}
+
+fn limiter() -> LoginLimiter {
+    LoginLimiter::new(
+        LimiterOptions::default()
+            .max_attempts(2)
+            .lockout(Duration::from_secs(10))
+            .max_lockout(Duration::from_secs(35)),
+    )
+}
+
+#[test]
+fn lockouts_double_after_a_few_failures() {
+    let limiter = limiter();
+    let attacker = ip("192.0.2.1");
+
+    assert_eq!(limiter.failed(attacker, "alice"), None);
+    assert_eq!(limiter.locked(attacker, "alice"), None);
+    assert_eq!(
+        limiter.failed(attacker, "alice"),
+        Some(Duration::from_secs(10))
+    );
+    assert_eq!(
+        limiter.failed(attacker, "alice"),
+        Some(Duration::from_secs(20))
+    );
+    assert_eq!(
+        limiter.failed(attacker, "alice"),
+        Some(Duration::from_secs(35)),
+        "lockouts are capped"
+    );
+    assert!(limiter.locked(attacker, "alice").is_some());
+}
+
+#[test]
+fn both_the_address_and_the_username_are_locked() {
+    let limiter = limiter();
+    let attacker = ip("192.0.2.1");
+    for _ in 0..2 {
+        limiter.failed(attacker, "alice");
+    }
+
+    assert!(
+        limiter.locked(attacker, "bob").is_some(),
+        "the address tries other usernames"
+    );
+    assert!(
+        limiter.locked(ip("198.51.100.7"), "alice").is_some(),
+        "the username is tried from other addresses"
+    );
+    assert!(limiter.locked(ip("198.51.100.7"), "bob").is_none());
+}
+
+#[test]
+fn logging_in_forgets_the_failures_of_the_username() {
+    let limiter = limiter();
+    let home = ip("192.0.2.1");
+    limiter.failed(home, "alice");
+
+    limiter.succeeded("alice");
+
+    assert_eq!(limiter.failed(ip("198.51.100.7"), "alice"), None);
+}
+
+fn forwarded_for(value: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert("x-forwarded-for", HeaderValue::from_str(value).unwrap());
+    headers
+}
+
+#[test]
+fn only_trusted_proxies_tell_the_client_address() {
+    let proxies = TrustedProxies::new("127.0.0.1, 10.0.0.0/8", None).unwrap();
+    let headers = forwarded_for("203.0.113.9, 192.0.2.1, 10.1.2.3");
+
+    assert_eq!(
+        proxies.client_ip(ip("127.0.0.1"), &headers),
+        ip("192.0.2.1"),
+        "addresses before the first untrusted one could be made up"
+    );
+    assert_eq!(
+        proxies.client_ip(ip("192.0.2.50"), &headers),
+        ip("192.0.2.50"),
+        "anybody else is the client"
+    );
+    assert_eq!(
+        proxies.client_ip(ip("::ffff:10.0.0.1"), &forwarded_for("192.0.2.1:51234")),
+        ip("192.0.2.1")
+    );
+    assert_eq!(
+        proxies.client_ip(ip("10.0.0.1"), &HeaderMap::new()),
+        ip("10.0.0.1")
+    );
+}
+
+#[test]
+fn other_headers_and_invalid_proxies() {
+    let proxies = TrustedProxies::new("::1", Some("x-real-ip")).unwrap();
+    let mut headers = HeaderMap::new();
+    headers.insert("x-real-ip", HeaderValue::from_static("2001:db8::7"));
+
+    assert_eq!(proxies.client_ip(ip("::1"), &headers), ip("2001:db8::7"));
+
+    assert!(TrustedProxies::new("10.0.0.0/33", None).is_err());
+    assert!(TrustedProxies::new("proxy.example.com", None).is_err());
+}
@@ -0,0 +1,115 @@
+//! Metrics and readiness, run with `cargo test --features mock`.
+#![cfg(feature = "mock")]
+
+use std::sync::Arc;
+
+use bittower::backend::{Measured, QbittorrentBackend, SyncCursor, TorrentBackend};
+use bittower::metrics::Metrics;
+use bittower::qbittorrent::client::{QbtClient, QbtClientOptions};
+use bittower::qbittorrent::mock::{MockQbittorrent, MockTorrent};
+use http::StatusCode;
+
+#[tokio::test]
+async fn client_state_comes_from_the_checks() {
+    let server = MockQbittorrent::default().spawn().await.unwrap();
+    server.qbt.add_torrent(
+        MockTorrent::default()
+            .name("debian.iso")
+            .size(1000)
+            .category("linux")
+            .paused(true),
+    );
+    server.qbt.add_torrent(
+        MockTorrent::default()
+            .name("arch.iso")
+            .size(500)
+            .category("linux")
+            .paused(true),
+    );
+    let backend = QbittorrentBackend::new(QbtClient::new(&server.base_url()));
+    let session = backend
+        .login("admin".to_owned(), "adminadmin".to_owned())
+        .await
+        .unwrap();
+    let mut cursor = SyncCursor::default();
+    backend.sync(&session, &mut cursor).await.unwrap();
+
+    let metrics = Metrics::default();
+    metrics.checked("seedbox1", &cursor);
+    let text = metrics.render();
+
+    for line in [
+        "bittower_instance_up{instance=\"seedbox1\"} 1",
+        "bittower_torrents{instance=\"seedbox1\",state=\"paused\"} 2",
+        "bittower_torrents{instance=\"seedbox1\",state=\"seeding\"} 0",
+        "bittower_category_torrents{instance=\"seedbox1\",category=\"linux\"} 2",
+        "bittower_category_size_bytes{instance=\"seedbox1\",category=\"linux\"} 1500",
+    ] {
+        assert!(
+            text.lines().any(|l| l == line),
+            "missing `{line}` in:\n{text}"
+        );
+    }
+}
+
+#[test]
+fn failed_checks_are_not_ready() {
+    let metrics = Metrics::default();
+    assert!(metrics.not_ready().is_empty());
+
+    metrics.check_failed("seedbox1", "Could not log in");
+    assert_eq!(
+        metrics.not_ready(),
+        vec![("seedbox1".to_owned(), "Could not log in".to_owned())]
+    );
+    assert!(metrics
+        .render()
+        .contains("bittower_instance_up{instance=\"seedbox1\"} 0"));
+
+    metrics.checked("seedbox1", &SyncCursor::default());
+    assert!(metrics.not_ready().is_empty());
+}
+
+#[test]
+fn open_websockets_and_frames_are_counted() {
+    let metrics = Metrics::default();
+    let first = metrics.websocket_opened();
+    let _second = metrics.websocket_opened();
+    drop(first);
+    metrics.frame_sent("ws", 300);
+    metrics.frame_sent("ws", 2000);
+
+    let text = metrics.render();
+    assert!(text.contains("bittower_websockets 1\n"));
+    assert!(text.contains("bittower_frame_bytes_bucket{transport=\"ws\",le=\"512\"} 1\n"));
+    assert!(text.contains("bittower_frame_bytes_sum{transport=\"ws\"} 2300\n"));
+    assert!(text.contains("bittower_frame_bytes_count{transport=\"ws\"} 2\n"));
+}
+
+#[tokio::test]
+async fn backend_calls_are_measured() {
+    let server = MockQbittorrent::default().spawn().await.unwrap();
+    let qbt = QbtClient::with_options(&server.base_url(), QbtClientOptions::default().retries(0))
+        .unwrap();
+    let metrics = Metrics::default();
+    let backend = Measured::new(
+        "seedbox1",
+        Arc::new(QbittorrentBackend::new(qbt)),
+        metrics.clone(),
+    );
+    let session = backend
+        .login("admin".to_owned(), "adminadmin".to_owned())
+        .await
+        .unwrap();
+    let mut cursor = SyncCursor::default();
+    backend.sync(&session, &mut cursor).await.unwrap();
+    server.qbt.fail_next(1, StatusCode::INTERNAL_SERVER_ERROR);
+    backend.sync(&session, &mut cursor).await.unwrap_err();
+    // Expired sessions aren't failures of the client.
+    server.qbt.expire_sessions();
+    backend.sync(&session, &mut cursor).await.unwrap_err();
+
+    let text = metrics.render();
+    assert!(text.contains("bittower_sync_duration_seconds_count{instance=\"seedbox1\"} 3\n"));
+    assert!(text.contains("bittower_backend_errors_total{instance=\"seedbox1\",call=\"sync\"} 1\n"));
+}
@@ -0,0 +1,155 @@
+//! `QbtClient` and its backend against the fake qBittorrent, run with
+//! `cargo test --features mock`. `tests/websocket.rs` goes through the server on top.
+#![cfg(feature = "mock")]
+
+use std::time::Duration;
+
+use bittower::backend::{QbittorrentBackend, SyncCursor, TorrentBackend};
+use bittower::qbittorrent::client::{QbtClient, QbtError};
+use bittower::qbittorrent::mock::{MockQbittorrent, MockTorrent, Step};
+use bittower::qbittorrent::proto::sync::MainData;
+use http::StatusCode;
+
+async fn logged_in(server: &bittower::qbittorrent::mock::MockServer) -> (QbtClient, String) {
+    let qbt = QbtClient::new(&server.base_url());
+    let sid = qbt
+        .auth_login("admin".to_owned(), "adminadmin".to_owned())
+        .await
+        .expect("login with the default credentials");
+    (qbt, sid)
+}
+
+#[tokio::test]
+async fn login_with_wrong_password_fails() {
+    let server = MockQbittorrent::default().spawn().await.unwrap();
+    let qbt = QbtClient::new(&server.base_url());
+
+    let res = qbt.auth_login("admin".to_owned(), "nope".to_owned()).await;
+
+    assert!(matches!(res, Err(QbtError::Unauthenticated)));
+}
+
+#[tokio::test]
+async fn maindata_is_full_then_partial() {
+    let server = MockQbittorrent::default().spawn().await.unwrap();
+    let hash = server.qbt.add_torrent(
+        MockTorrent::default()
+            .name("debian.iso")
+            .size(1000)
+            .download_rate(100),
+    );
+    let (qbt, sid) = logged_in(&server).await;
+
+    let MainData::Full(full) = qbt.sync_maindata(&sid, 0).await.unwrap() else {
+        panic!("the first update should be full");
+    };
+    assert_eq!(full.torrents[&hash].name, "debian.iso");
+
+    server.qbt.tick(Duration::from_secs(2));
+    let MainData::Partial(partial) = qbt.sync_maindata(&sid, full.rid).await.unwrap() else {
+        panic!("a known rid should get a partial update");
+    };
+    let torrent = &partial.torrents.unwrap()[&hash];
+    assert_eq!(torrent.completed, Some(200));
+    assert_eq!(torrent.name, None, "unchanged fields are left out");
+
+    let unknown_rid = partial.rid + 1000;
+    let update = qbt.sync_maindata(&sid, unknown_rid).await.unwrap();
+    assert!(matches!(update, MainData::Full(_)));
+}
+
+#[tokio::test]
+async fn backend_sync_only_carries_what_changed() {
+    let server = MockQbittorrent::default().spawn().await.unwrap();
+    let debian = server.qbt.add_torrent(
+        MockTorrent::default()
+            .name("debian.iso")
+            .size(1000)
+            .download_rate(100),
+    );
+    let arch = server
+        .qbt
+        .add_torrent(MockTorrent::default().name("arch.iso").size(10));
+    let (qbt, sid) = logged_in(&server).await;
+    let backend = QbittorrentBackend::new(qbt);
+    let mut cursor = SyncCursor::default();
+
+    let full = backend.sync(&sid, &mut cursor).await.unwrap();
+    assert!(full.full);
+    assert_eq!(full.torrents.len(), 2);
+
+    server.qbt.tick(Duration::from_secs(2));
+    server.qbt.remove_torrent(&arch);
+    let update = backend.sync(&sid, &mut cursor).await.unwrap();
+    assert!(!update.full);
+    assert_eq!(update.torrents.len(), 1);
+    assert_eq!(update.torrents[0].hash, debian);
+    assert_eq!(
+        update.torrents[0].name, "debian.iso",
+        "changes apply to the whole torrent"
+    );
+    assert_eq!(update.torrents[0].progress, 0.2);
+    assert_eq!(update.removed, vec![arch]);
+}
+
+#[tokio::test]
+async fn actions_change_the_torrents() {
+    let server = MockQbittorrent::default().spawn().await.unwrap();
+    let hash = server
+        .qbt
+        .add_torrent(MockTorrent::default().name("a").size(10));
+    let (qbt, sid) = logged_in(&server).await;
+
+    qbt.torrents_pause(&sid, &[hash.clone()]).await.unwrap();
+    assert_eq!(server.qbt.is_paused(&hash), Some(true));
+
+    qbt.torrents_resume(&sid, &[hash.clone()]).await.unwrap();
+    assert_eq!(server.qbt.is_paused(&hash), Some(false));
+
+    qbt.torrents_delete(&sid, &[hash], false).await.unwrap();
+    assert!(server.qbt.torrent_hashes().is_empty());
+}
+
+#[tokio::test]
+async fn qbittorrent_5_is_stopped_and_started() {
+    let server = MockQbittorrent::default()
+        .with_version("v5.0.0", "2.11.2")
+        .spawn()
+        .await
+        .unwrap();
+    let hash = server
+        .qbt
+        .add_torrent(MockTorrent::default().name("a").size(10));
+    let (qbt, sid) = logged_in(&server).await;
+
+    qbt.torrents_pause(&sid, &[hash.clone()]).await.unwrap();
+    assert_eq!(server.qbt.is_paused(&hash), Some(true));
+
+    qbt.torrents_resume(&sid, &[hash.clone()]).await.unwrap();
+    assert_eq!(server.qbt.is_paused(&hash), Some(false));
+}
+
+#[tokio::test]
+async fn expired_sessions_and_failures_are_reported() {
+    let server = MockQbittorrent::default().spawn().await.unwrap();
+    let (qbt, sid) = logged_in(&server).await;
+
+    server
+        .qbt
+        .play([Step::Fail {
+            count: 1,
+            status: StatusCode::BAD_REQUEST,
+        }])
+        .await;
+    let res = qbt.sync_maindata(&sid, 0).await;
+    assert!(matches!(
+        res,
+        Err(QbtError::Http {
+            status: StatusCode::BAD_REQUEST
+        })
+    ));
+
+    server.qbt.play([Step::ExpireSessions]).await;
+    let res = qbt.sync_maindata(&sid, 0).await;
+    assert!(matches!(res, Err(QbtError::SessionExpired)));
+}
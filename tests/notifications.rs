@@ -0,0 +1,51 @@
+//! The notices the UI tells the user about, run with `cargo test --features ssr`.
+#![cfg(feature = "ssr")]
+
+use std::collections::HashSet;
+
+use bittower::app::signals::notifications::{provide_notifications, Level, Notice};
+use chrono::Utc;
+use leptos::prelude::*;
+
+fn saved(id: u64, title: &str) -> Notice {
+    Notice {
+        id,
+        level: Level::Info,
+        title: title.to_owned(),
+        body: None,
+        at: Utc::now(),
+        read: true,
+    }
+}
+
+#[test]
+fn notices_told_before_the_history_loads_keep_their_own_ids() {
+    Owner::new().with(|| {
+        let notifications = provide_notifications();
+        // Errors stay until dismissed, no timer is set for them.
+        notifications.notify(Level::Error, "Can't connect to the server", None);
+        notifications.restore(vec![saved(1, "Download finished"), saved(0, "Paused")]);
+        notifications.notify(Level::Error, "Login failed", None);
+
+        let history = notifications.history().get_untracked();
+        let titles: Vec<&str> = history.iter().map(|notice| notice.title.as_str()).collect();
+        assert_eq!(
+            titles,
+            [
+                "Login failed",
+                "Can't connect to the server",
+                "Download finished",
+                "Paused"
+            ]
+        );
+        let ids: HashSet<u64> = history.iter().map(|notice| notice.id).collect();
+        assert_eq!(ids.len(), history.len(), "{history:?}");
+
+        // Dismissing the first toast leaves the other one alone.
+        let first = history[1].id;
+        notifications.dismiss(first);
+        let toasts = notifications.toasts().get_untracked();
+        assert_eq!(toasts.len(), 1);
+        assert_eq!(toasts[0].title, "Login failed");
+    });
+}
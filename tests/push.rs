@@ -0,0 +1,266 @@
+//! Browser push notifications, run with `cargo test --features mock`.
+#![cfg(feature = "mock")]
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use axum::routing::post;
+use axum::Router;
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+use base64::Engine;
+use bittower::app::push::ssr::PushService;
+use bittower::app::push::{PushKeys, PushPrefs, PushSubscription};
+use bittower::backend::model::{Torrent, TorrentState};
+use bittower::webhooks::{Event, EventDetector, EventKind, Thresholds};
+use chrono::Utc;
+use http::{header, HeaderMap, StatusCode};
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+
+/// A push service: takes messages for `/ok/...` endpoints, and says those of `/gone/...` are.
+#[derive(Clone, Default)]
+struct Received {
+    messages: Arc<Mutex<Vec<(String, HeaderMap, Bytes)>>>,
+}
+
+async fn receive(
+    State(received): State<Received>,
+    Path((kind, id)): Path<(String, String)>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    received
+        .messages
+        .lock()
+        .unwrap()
+        .push((format!("{kind}/{id}"), headers, body));
+    if kind == "gone" {
+        StatusCode::GONE
+    } else {
+        StatusCode::CREATED
+    }
+}
+
+async fn push_service() -> (SocketAddr, Received) {
+    let received = Received::default();
+    let app = Router::new()
+        .route("/:kind/:id", post(receive))
+        .with_state(received.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (addr, received)
+}
+
+/// A subscription like a browser makes, with keys of its own.
+fn subscription(addr: SocketAddr, path: &str) -> PushSubscription {
+    let secret = p256::SecretKey::random(&mut rand::rngs::OsRng);
+    let p256dh = secret.public_key().to_encoded_point(false);
+    PushSubscription {
+        endpoint: format!("http://{addr}/{path}"),
+        keys: PushKeys {
+            p256dh: BASE64_URL_SAFE_NO_PAD.encode(p256dh.as_bytes()),
+            auth: BASE64_URL_SAFE_NO_PAD.encode(rand::random::<[u8; 16]>()),
+        },
+    }
+}
+
+fn event(kind: EventKind) -> Event {
+    let torrent = |progress| {
+        let torrent = Torrent {
+            hash: "aaaa".to_owned(),
+            name: "debian.iso".to_owned(),
+            state: TorrentState::Downloading,
+            progress,
+            ..Default::default()
+        };
+        HashMap::from([(torrent.hash.clone(), torrent)])
+    };
+    let mut detector = EventDetector::new(Thresholds {
+        stalled_after: Duration::MAX,
+        ratio_target: None,
+    });
+    detector.diff("default", &torrent(0.5), Utc::now());
+    let mut event = detector
+        .diff("default", &torrent(1.0), Utc::now())
+        .remove(0);
+    event.event = kind;
+    event
+}
+
+#[test]
+fn the_vapid_key_is_kept_across_restarts() {
+    let dir = std::env::temp_dir().join(format!("bittower-push-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("push.json");
+    let _ = std::fs::remove_file(&path);
+
+    let push = PushService::new(Some(path.clone()), None, None, false).unwrap();
+    let public_key = push.public_key().to_vec();
+    assert_eq!(public_key.len(), 65, "an uncompressed P-256 point");
+    assert_eq!(public_key[0], 4);
+    push.set_prefs(
+        "alice",
+        PushPrefs {
+            completed: false,
+            errored: true,
+        },
+    );
+
+    let push = PushService::new(Some(path), None, None, false).unwrap();
+    assert_eq!(push.public_key(), public_key);
+    assert!(!push.status("alice").prefs.completed);
+    assert_ne!(
+        PushService::new(None, None, None, false)
+            .unwrap()
+            .public_key(),
+        public_key,
+        "every server has a key of its own"
+    );
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn only_https_endpoints_are_taken() {
+    let addr = "127.0.0.1:9".parse().unwrap();
+    let push = PushService::new(None, None, None, false).unwrap();
+    assert!(push
+        .subscribe("alice", subscription(addr, "phone"))
+        .is_err());
+    assert!(push.status("alice").endpoints.is_empty());
+}
+
+#[tokio::test]
+async fn notifications_are_encrypted_and_signed() {
+    let (addr, received) = push_service().await;
+    let push = PushService::new(
+        None,
+        Some("mailto:admin@example.com".to_owned()),
+        None,
+        true,
+    )
+    .unwrap();
+    push.subscribe("alice", subscription(addr, "ok/phone"))
+        .unwrap();
+
+    assert_eq!(push.notify(&event(EventKind::Completed)).await, 1);
+
+    let messages = received.messages.lock().unwrap().clone();
+    assert_eq!(messages.len(), 1);
+    let (endpoint, headers, body) = &messages[0];
+    assert_eq!(endpoint, "ok/phone");
+    assert_eq!(headers[header::CONTENT_ENCODING], "aes128gcm");
+    assert!(headers[header::AUTHORIZATION]
+        .to_str()
+        .unwrap()
+        .starts_with("vapid t="));
+    assert!(headers.contains_key("ttl"));
+    assert!(
+        !String::from_utf8_lossy(body).contains("debian.iso"),
+        "only the browser can read it"
+    );
+}
+
+#[tokio::test]
+async fn only_wanted_events_are_sent_and_gone_subscriptions_dropped() {
+    let (addr, received) = push_service().await;
+    let push = PushService::new(None, None, None, true).unwrap();
+    push.subscribe("alice", subscription(addr, "ok/laptop"))
+        .unwrap();
+    push.subscribe("alice", subscription(addr, "gone/old-phone"))
+        .unwrap();
+    push.subscribe("bob", subscription(addr, "ok/bobs-phone"))
+        .unwrap();
+    push.set_prefs(
+        "bob",
+        PushPrefs {
+            completed: false,
+            errored: true,
+        },
+    );
+
+    assert_eq!(
+        push.notify(&event(EventKind::Added)).await,
+        0,
+        "not notified"
+    );
+    assert_eq!(push.notify(&event(EventKind::Completed)).await, 1);
+    assert_eq!(
+        push.status("alice").endpoints.len(),
+        1,
+        "the gone one is dropped"
+    );
+    assert_eq!(push.notify(&event(EventKind::Errored)).await, 2);
+    assert_eq!(received.messages.lock().unwrap().len(), 4);
+
+    push.unsubscribe("alice", &format!("http://{addr}/ok/laptop"));
+    assert!(push.status("alice").endpoints.is_empty());
+    assert!(push
+        .subscribe(
+            "alice",
+            PushSubscription {
+                endpoint: "not a url".to_owned(),
+                ..subscription(addr, "ok/x")
+            }
+        )
+        .is_err());
+}
+
+#[tokio::test]
+async fn changes_without_subscriptions_are_not_notified_later() {
+    let (addr, received) = push_service().await;
+    let push = PushService::new(None, None, None, true).unwrap();
+    let torrents = |progress| {
+        let torrent = Torrent {
+            hash: "aaaa".to_owned(),
+            name: "debian.iso".to_owned(),
+            state: TorrentState::Downloading,
+            progress,
+            ..Default::default()
+        };
+        HashMap::from([(torrent.hash.clone(), torrent)])
+    };
+
+    push.synced("default", &torrents(0.5));
+    // Completes while nobody is subscribed.
+    push.synced("default", &torrents(1.0));
+    push.subscribe("alice", subscription(addr, "ok/phone"))
+        .unwrap();
+    push.synced("default", &torrents(1.0));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    assert!(
+        received.messages.lock().unwrap().is_empty(),
+        "completed before alice subscribed"
+    );
+}
+
+#[tokio::test]
+async fn browsers_are_forgotten_when_somebody_else_logs_in() {
+    let (addr, received) = push_service().await;
+    let push = PushService::new(None, None, None, true).unwrap();
+    let shared = subscription(addr, "ok/shared-laptop");
+    push.subscribe("alice", shared.clone()).unwrap();
+    push.subscribe("alice", subscription(addr, "ok/phone"))
+        .unwrap();
+
+    push.logged_in(&shared.endpoint, "alice");
+    assert_eq!(push.status("alice").endpoints.len(), 2, "still alice");
+    push.logged_in(&shared.endpoint, "bob");
+    assert_eq!(
+        push.status("alice").endpoints,
+        [format!("http://{addr}/ok/phone")],
+        "bob uses the laptop now"
+    );
+
+    push.subscribe("bob", shared.clone()).unwrap();
+    push.unsubscribe("bob", &shared.endpoint);
+    assert!(push.status("bob").endpoints.is_empty());
+    assert_eq!(push.notify(&event(EventKind::Completed)).await, 1);
+    let messages = received.messages.lock().unwrap().clone();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].0, "ok/phone");
+}
@@ -0,0 +1,82 @@
+//! Replays what different qBittorrent versions answer, to catch parsing regressions. Add a
+//! version by recording a session with `BITTOWER_QBT_RECORD` and dropping the file in
+//! `tests/fixtures/qbittorrent`.
+#![cfg(feature = "ssr")]
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use bittower::qbittorrent::client::{QbtClient, WebApiVersion};
+use bittower::qbittorrent::proto::sync::MainData;
+
+fn fixture(version: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/qbittorrent")
+        .join(format!("{version}.json"))
+}
+
+/// Logs in, syncs a full then a partial update and lists the torrents.
+async fn replay_session(version: &str, webapi_version: WebApiVersion) {
+    let qbt = QbtClient::replay(&fixture(version)).unwrap();
+    let sid = qbt
+        .auth_login("admin".to_owned(), "adminadmin".to_owned())
+        .await
+        .unwrap();
+    assert_eq!(qbt.webapi_version(&sid).await.unwrap(), webapi_version);
+
+    let MainData::Full(full) = qbt.sync_maindata(&sid, 0).await.unwrap() else {
+        panic!("qBittorrent {version}: the first update should be full");
+    };
+    assert_eq!(full.torrents.len(), 3);
+    assert!(full
+        .torrents
+        .values()
+        .all(|torrent| !torrent.name.is_empty()));
+    assert_eq!(
+        full.server_state.refresh_interval,
+        Duration::from_millis(1500)
+    );
+    assert!(full
+        .torrents
+        .values()
+        .all(|torrent| torrent.added_on.is_some()));
+    // Complete or stopped torrents have qBittorrent's "infinite" ETA.
+    assert!(full.torrents.values().any(|torrent| torrent.eta.is_none()));
+
+    let MainData::Partial(partial) = qbt.sync_maindata(&sid, full.rid).await.unwrap() else {
+        panic!("qBittorrent {version}: the second update should be partial");
+    };
+    let torrents = partial.torrents.expect("the partial update has torrents");
+    let (hash, torrent) = torrents.iter().next().unwrap();
+    assert!(full.torrents.contains_key(hash));
+    assert!(torrent.name.is_none());
+    assert!(torrent.completed.is_some());
+
+    let torrents = qbt.torrents_info(&sid).await.unwrap();
+    assert_eq!(torrents.len(), 3);
+}
+
+#[tokio::test]
+async fn qbittorrent_4_3() {
+    replay_session("4.3.9", WebApiVersion::new(2, 8, 2)).await;
+}
+
+#[tokio::test]
+async fn qbittorrent_4_4() {
+    replay_session("4.4.5", WebApiVersion::new(2, 8, 5)).await;
+}
+
+#[tokio::test]
+async fn qbittorrent_4_5() {
+    replay_session("4.5.5", WebApiVersion::new(2, 8, 19)).await;
+}
+
+#[tokio::test]
+async fn qbittorrent_4_6() {
+    replay_session("4.6.5", WebApiVersion::new(2, 9, 3)).await;
+}
+
+#[tokio::test]
+async fn qbittorrent_5_0() {
+    replay_session("5.0.0", WebApiVersion::new(2, 11, 2)).await;
+}
@@ -0,0 +1,158 @@
+//! The `/api/v1` REST API, run with `cargo test --features mock`.
+#![cfg(feature = "mock")]
+
+use axum::body::Body;
+use axum::Router;
+use bittower::api::v1::{self, ActionResult, AltSpeedResult, ApiState, Stats, Torrent};
+use bittower::api::{ApiAuth, SyncedState};
+use bittower::app::auth::ssr::TokenStore;
+use bittower::backend::{BackendRegistry, QbittorrentBackend, SyncCursor, TorrentBackend};
+use bittower::qbittorrent::client::QbtClient;
+use bittower::qbittorrent::mock::{MockQbittorrent, MockServer, MockTorrent};
+use bittower::webhooks::Webhooks;
+use http::{header, Request, StatusCode};
+use serde::de::DeserializeOwned;
+use tower::ServiceExt;
+
+const TOKEN: &str = "s3cret";
+
+/// The API over `server`, synced once like the background checks do.
+async fn api(server: &MockServer) -> Router {
+    let backend = QbittorrentBackend::new(QbtClient::new(&server.base_url()));
+    let session = backend
+        .login("admin".to_owned(), "adminadmin".to_owned())
+        .await
+        .unwrap();
+    let mut cursor = SyncCursor::default();
+    backend.sync(&session, &mut cursor).await.unwrap();
+    let synced = SyncedState::default();
+    synced.update("default", &session, &cursor);
+    Router::new().nest(
+        "/api/v1",
+        v1::router(ApiState {
+            backends: BackendRegistry::single(backend),
+            synced,
+            auth: ApiAuth::new(Some(TOKEN), TokenStore::default(), None),
+            webhooks: Webhooks::default(),
+        }),
+    )
+}
+
+async fn call(api: &Router, method: &str, uri: &str, token: Option<&str>) -> (StatusCode, Vec<u8>) {
+    let mut request = Request::builder().method(method).uri(uri);
+    if let Some(token) = token {
+        request = request.header(header::AUTHORIZATION, format!("Bearer {token}"));
+    }
+    let response = api
+        .clone()
+        .oneshot(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, body.to_vec())
+}
+
+async fn json<T: DeserializeOwned>(api: &Router, method: &str, uri: &str) -> (StatusCode, T) {
+    let (status, body) = call(api, method, uri, Some(TOKEN)).await;
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+#[tokio::test]
+async fn a_token_is_required() {
+    let server = MockQbittorrent::default().spawn().await.unwrap();
+    let api = api(&server).await;
+
+    let (status, _) = call(&api, "GET", "/api/v1/torrents", None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = call(&api, "GET", "/api/v1/torrents", Some("guess")).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = call(&api, "POST", "/api/v1/pause-all", None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // The document is public, it's the same for everyone.
+    let (status, body) = call(&api, "GET", "/api/v1/openapi.json", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let doc: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(doc["paths"]["/api/v1/torrents/{hash}"]["get"].is_object());
+    assert!(doc["components"]["schemas"]["Torrent"].is_object());
+}
+
+#[tokio::test]
+async fn torrents_come_from_the_synced_state() {
+    let server = MockQbittorrent::default().spawn().await.unwrap();
+    let hash = server.qbt.add_torrent(
+        MockTorrent::default()
+            .name("debian.iso")
+            .category("linux")
+            .paused(true),
+    );
+    server
+        .qbt
+        .add_torrent(MockTorrent::default().name("big_buck_bunny.mkv"));
+    let api = api(&server).await;
+    // Not synced yet, so not known to the API.
+    server
+        .qbt
+        .add_torrent(MockTorrent::default().name("arch.iso"));
+
+    let (status, torrents): (_, Vec<Torrent>) = json(&api, "GET", "/api/v1/torrents").await;
+    assert_eq!(status, StatusCode::OK);
+    let names: Vec<_> = torrents
+        .iter()
+        .map(|torrent| torrent.name.as_str())
+        .collect();
+    assert_eq!(names, ["big_buck_bunny.mkv", "debian.iso"]);
+    assert!(torrents.iter().all(|torrent| torrent.instance == "default"));
+
+    let (_, torrents): (_, Vec<Torrent>) =
+        json(&api, "GET", "/api/v1/torrents?state=paused&category=linux").await;
+    assert_eq!(torrents.len(), 1);
+
+    let (status, torrent): (_, Torrent) =
+        json(&api, "GET", &format!("/api/v1/torrents/{hash}")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(torrent.name, "debian.iso");
+    let (status, _) = call(&api, "GET", "/api/v1/torrents/0000", Some(TOKEN)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = call(&api, "GET", "/api/v1/torrents?instance=nope", Some(TOKEN)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (_, stats): (_, Stats) = json(&api, "GET", "/api/v1/stats").await;
+    assert_eq!(stats.torrents, 2);
+    assert_eq!(stats.instances[0].states[&v1::TorrentState::Paused], 1);
+}
+
+#[tokio::test]
+async fn everything_can_be_paused_resumed_and_slowed_down() {
+    let server = MockQbittorrent::default().spawn().await.unwrap();
+    let hashes = [
+        server
+            .qbt
+            .add_torrent(MockTorrent::default().name("debian.iso")),
+        server
+            .qbt
+            .add_torrent(MockTorrent::default().name("arch.iso").paused(true)),
+    ];
+    let api = api(&server).await;
+
+    let (status, results): (_, Vec<ActionResult>) = json(&api, "POST", "/api/v1/pause-all").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(results[0].torrents, 1, "only the one that wasn't paused");
+    assert!(hashes
+        .iter()
+        .all(|hash| server.qbt.is_paused(hash) == Some(true)));
+
+    let (status, results): (_, Vec<AltSpeedResult>) =
+        json(&api, "POST", "/api/v1/toggle-alt-speed").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(results[0].alt_speed, Some(true));
+    assert!(server.qbt.alt_speed());
+
+    server.qbt.fail_next(1, StatusCode::INTERNAL_SERVER_ERROR);
+    let (status, results): (_, Vec<ActionResult>) = json(&api, "POST", "/api/v1/resume-all").await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert!(results[0].error.is_some());
+}
@@ -0,0 +1,92 @@
+//! What each role may do with the torrents.
+
+use bittower::app::auth::{owner_tag, tag_owner, Role};
+use bittower::backend::model::{AddTorrent, Limits, TorrentSource};
+use bittower::protocol::TorrentAction;
+
+fn actions() -> Vec<TorrentAction> {
+    let hashes = vec!["c9e15763f722f23e98a29decdfae341b98d53056".to_owned()];
+    vec![
+        TorrentAction::Pause {
+            hashes: hashes.clone(),
+        },
+        TorrentAction::Resume {
+            hashes: hashes.clone(),
+        },
+        TorrentAction::Delete {
+            hashes: hashes.clone(),
+            delete_files: false,
+        },
+        TorrentAction::Delete {
+            hashes: hashes.clone(),
+            delete_files: true,
+        },
+        TorrentAction::SetLimits {
+            hashes,
+            limits: Limits::default(),
+        },
+        TorrentAction::Add(AddTorrent {
+            source: TorrentSource::Url("magnet:?xt=urn:btih:c9e1".to_owned()),
+            save_path: None,
+            paused: false,
+            tags: Vec::new(),
+        }),
+    ]
+}
+
+fn allowed(role: Role, owned: bool) -> Vec<bool> {
+    actions()
+        .iter()
+        .map(|action| role.allows(action, owned))
+        .collect()
+}
+
+#[test]
+fn viewers_only_look() {
+    assert!(allowed(Role::Viewer, true).iter().all(|allowed| !allowed));
+}
+
+#[test]
+fn contributors_control_what_they_added() {
+    assert_eq!(
+        allowed(Role::Contributor, true),
+        [true, true, true, false, false, true]
+    );
+    assert_eq!(
+        allowed(Role::Contributor, false),
+        [false, false, false, false, false, true]
+    );
+}
+
+#[test]
+fn admins_do_anything() {
+    assert!(allowed(Role::Admin, false).iter().all(|allowed| *allowed));
+}
+
+#[test]
+fn the_owner_of_an_added_torrent_is_whoever_added_it() {
+    let mut torrent = AddTorrent {
+        source: TorrentSource::Url("magnet:?xt=urn:btih:c9e1".to_owned()),
+        save_path: None,
+        paused: false,
+        tags: vec![
+            "linux".to_owned(),
+            owner_tag("bob"),
+            owner_tag("alice"),
+            "bt-user:".to_owned(),
+        ],
+    };
+    tag_owner(&mut torrent, "alice");
+    assert_eq!(
+        torrent.tags,
+        ["linux".to_owned(), owner_tag("alice")],
+        "can't be handed to bob, nor claimed twice"
+    );
+}
+
+#[test]
+fn roles_are_lowercase_in_the_users_file() {
+    let role: Role = serde_json::from_str("\"contributor\"").unwrap();
+    assert_eq!(role, Role::Contributor);
+    assert_eq!(Role::default(), Role::Viewer);
+}
@@ -0,0 +1,125 @@
+//! The server-side session store, run with `cargo test --features ssr`.
+#![cfg(feature = "ssr")]
+
+use std::time::Duration;
+
+use bittower::app::auth::ssr::{Session, SessionOptions, SessionStore};
+
+fn session(username: &str) -> Session {
+    let mut session = Session {
+        username: username.to_owned(),
+        ..Default::default()
+    };
+    session
+        .sids
+        .insert("default".to_owned(), format!("sid-of-{username}"));
+    session
+}
+
+#[test]
+fn sessions_are_found_by_id_until_removed() {
+    let store = SessionStore::new(SessionOptions::default()).unwrap();
+    let id = store.create(session("alice"));
+
+    assert_eq!(store.get(&id), Some(session("alice")));
+    assert_eq!(store.get("not-an-id"), None);
+
+    assert_eq!(store.remove(&id), Some(session("alice")));
+    assert_eq!(store.get(&id), None);
+}
+
+#[test]
+fn another_user_logging_in_keeps_nothing_of_the_session() {
+    let mut session = session("alice");
+    assert_eq!(session.clone().hand_over("alice"), None, "still alice");
+
+    let previous = session.hand_over("bob").unwrap();
+    assert_eq!(previous.sid("default"), Some("sid-of-alice"));
+    assert_eq!(session.username, "bob");
+    assert!(session.is_empty(), "bob can't use alice's sessions");
+}
+
+#[test]
+fn logging_in_again_rotates_the_id() {
+    let store = SessionStore::new(SessionOptions::default()).unwrap();
+    let old = store.create(session("alice"));
+
+    let new = store.rotate(Some(&old), session("alice"));
+
+    assert_ne!(old, new);
+    assert!(!store.contains(&old));
+    assert!(store.contains(&new));
+}
+
+#[test]
+fn idle_and_old_sessions_expire() {
+    let idle = SessionStore::new(SessionOptions::default().idle_timeout(Duration::from_millis(50)))
+        .unwrap();
+    let id = idle.create(session("alice"));
+    std::thread::sleep(Duration::from_millis(30));
+    assert!(idle.get(&id).is_some(), "using the session keeps it going");
+    std::thread::sleep(Duration::from_millis(30));
+    assert!(idle.get(&id).is_some());
+    std::thread::sleep(Duration::from_millis(80));
+    assert!(idle.get(&id).is_none());
+
+    let old =
+        SessionStore::new(SessionOptions::default().max_age(Duration::from_millis(50))).unwrap();
+    let id = old.create(session("alice"));
+    for _ in 0..3 {
+        std::thread::sleep(Duration::from_millis(30));
+        old.get(&id);
+    }
+    assert!(
+        !old.contains(&id),
+        "sessions end after max_age however used"
+    );
+}
+
+#[test]
+fn everywhere_only_logs_out_the_same_user() {
+    let store = SessionStore::new(SessionOptions::default()).unwrap();
+    let phone = store.create(session("alice"));
+    let laptop = store.create(session("alice"));
+    let bob = store.create(session("bob"));
+
+    let removed = store.remove_user("alice");
+
+    assert_eq!(removed.len(), 2);
+    assert!(!store.contains(&phone) && !store.contains(&laptop));
+    assert!(store.contains(&bob));
+}
+
+#[test]
+fn sessions_survive_a_restart_with_a_file() {
+    let dir = std::env::temp_dir().join(format!("bittower-sessions-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("sessions.json");
+    let options = SessionOptions::default().file(file.clone());
+
+    let id = SessionStore::new(options.clone())
+        .unwrap()
+        .create(session("alice"));
+    let saved = std::fs::read_to_string(&file).unwrap();
+    assert!(!saved.contains(&id), "only hashed ids are written down");
+
+    let store = SessionStore::new(options).unwrap();
+    assert_eq!(store.get(&id), Some(session("alice")));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn cookies_are_http_only_secure_and_same_site() {
+    let store = SessionStore::new(SessionOptions::default()).unwrap();
+
+    let cookie = store.cookie("abc");
+    assert!(cookie.starts_with("bt-session=abc"));
+    for attribute in ["HttpOnly", "Secure", "SameSite=Lax", "Path=/", "Max-Age="] {
+        assert!(cookie.contains(attribute), "{cookie} lacks {attribute}");
+    }
+
+    let insecure = SessionStore::new(SessionOptions::default().secure(false)).unwrap();
+    assert!(!insecure.cookie("abc").contains("Secure"));
+    assert!(store.removal_cookie().contains("Max-Age=0"));
+}
@@ -0,0 +1,106 @@
+//! Serving HTTPS: the TLS settings and the redirect from plain HTTP, run with
+//! `cargo test --features ssr`.
+#![cfg(feature = "ssr")]
+
+use std::path::PathBuf;
+
+use axum::http::{header, StatusCode, Uri};
+use bittower::tls::{redirect, TlsOptions};
+
+/// Where [`redirect`] sends a request for `uri` on `host`, `None` when it refuses it.
+fn location(host: &str, https_port: u16, uri: &str) -> Option<String> {
+    let response = redirect(host, https_port, &uri.parse::<Uri>().unwrap());
+    if response.status() == StatusCode::BAD_REQUEST {
+        return None;
+    }
+    assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+    let location = response.headers().get(header::LOCATION).unwrap();
+    Some(location.to_str().unwrap().to_owned())
+}
+
+#[test]
+fn the_default_port_is_left_out() {
+    assert_eq!(
+        location("example.com", 443, "/torrents").as_deref(),
+        Some("https://example.com/torrents")
+    );
+    assert_eq!(
+        location("example.com", 8443, "/torrents").as_deref(),
+        Some("https://example.com:8443/torrents")
+    );
+}
+
+#[test]
+fn the_port_asked_for_over_http_is_replaced() {
+    assert_eq!(
+        location("example.com:80", 443, "/").as_deref(),
+        Some("https://example.com/")
+    );
+    assert_eq!(
+        location("192.168.1.2:8080", 8443, "/").as_deref(),
+        Some("https://192.168.1.2:8443/")
+    );
+    assert_eq!(
+        location("[::1]:8080", 443, "/").as_deref(),
+        Some("https://[::1]/")
+    );
+}
+
+#[test]
+fn the_path_and_query_are_kept() {
+    assert_eq!(
+        location("example.com", 443, "/torrents/abc?sort=name&desc=1").as_deref(),
+        Some("https://example.com/torrents/abc?sort=name&desc=1")
+    );
+    assert_eq!(
+        location("example.com", 443, "http://example.com").as_deref(),
+        Some("https://example.com/")
+    );
+}
+
+#[test]
+fn bad_hosts_are_refused() {
+    for host in ["", "exa mple.com", "example.com:port"] {
+        assert_eq!(location(host, 443, "/"), None, "{host}");
+    }
+}
+
+#[test]
+fn tls_needs_both_the_certificate_and_the_key() {
+    assert_eq!(TlsOptions::new(None, None, None, None).unwrap(), None);
+    assert!(TlsOptions::new(Some("cert.pem"), None, None, None).is_err());
+    assert!(TlsOptions::new(None, Some("key.pem"), None, None).is_err());
+
+    let options = TlsOptions::new(Some("cert.pem"), Some("key.pem"), None, None)
+        .unwrap()
+        .unwrap();
+    assert_eq!(options.cert, PathBuf::from("cert.pem"));
+    assert_eq!(options.key, PathBuf::from("key.pem"));
+    assert_eq!(options.redirect_addr, None);
+    assert_eq!(options.public_port, None);
+}
+
+#[test]
+fn the_redirect_address_and_public_port_are_parsed() {
+    let options = TlsOptions::new(
+        Some("cert.pem"),
+        Some("key.pem"),
+        Some("0.0.0.0:80"),
+        Some("8443"),
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(options.redirect_addr, Some("0.0.0.0:80".parse().unwrap()));
+    assert_eq!(options.public_port, Some(8443));
+
+    for (addr, port) in [
+        (Some("80"), None),
+        (None, Some("https")),
+        (None, Some("65536")),
+    ] {
+        assert!(
+            TlsOptions::new(Some("cert.pem"), Some("key.pem"), addr, port).is_err(),
+            "{addr:?} {port:?}"
+        );
+    }
+}
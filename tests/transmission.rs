@@ -0,0 +1,202 @@
+//! `TransmissionBackend` against the fake Transmission, run with `cargo test --features mock`.
+#![cfg(feature = "mock")]
+
+use std::time::Duration;
+
+use bittower::backend::model::{AddTorrent, Limits, TorrentSource, TorrentState, TrackerStatus};
+use bittower::backend::{BackendError, Command, SyncCursor, TorrentBackend, TransmissionBackend};
+use bittower::qbittorrent::client::BasicAuth;
+use bittower::transmission::client::{TransmissionClient, TransmissionError};
+use bittower::transmission::mock::{MockTransmission, MockTransmissionServer};
+
+async fn logged_in(server: &MockTransmissionServer) -> (TransmissionBackend, String) {
+    let backend = TransmissionBackend::new(TransmissionClient::new(&server.url()));
+    let session = backend
+        .login("transmission".to_owned(), "transmission".to_owned())
+        .await
+        .expect("login with the default credentials");
+    (backend, session)
+}
+
+#[tokio::test]
+async fn session_id_handshake_is_done_again_after_a_restart() {
+    let server = MockTransmission::default().spawn().await.unwrap();
+    let client = TransmissionClient::new(&server.url());
+    let auth = BasicAuth {
+        username: "transmission".to_owned(),
+        password: "transmission".to_owned(),
+    };
+
+    let session = client.session_get(&auth).await.unwrap();
+    assert_eq!(session.rpc_version, 17);
+
+    server.transmission.rotate_session_id();
+    client.session_stats(&auth).await.unwrap();
+}
+
+#[tokio::test]
+async fn login_with_wrong_password_fails() {
+    let server = MockTransmission::default().spawn().await.unwrap();
+    let backend = TransmissionBackend::new(TransmissionClient::new(&server.url()));
+
+    let res = backend
+        .login("transmission".to_owned(), "nope".to_owned())
+        .await;
+
+    assert!(matches!(
+        res,
+        Err(BackendError::Transmission(
+            TransmissionError::Unauthenticated
+        ))
+    ));
+}
+
+#[tokio::test]
+async fn sync_is_full_then_incremental() {
+    let server = MockTransmission::default().spawn().await.unwrap();
+    let debian = server.transmission.add_torrent("debian.iso", 1000);
+    let arch = server.transmission.add_torrent("arch.iso", 500);
+    server.transmission.set_download_rate(&debian, 100);
+    let (backend, session) = logged_in(&server).await;
+    let mut cursor = SyncCursor::default();
+
+    let full = backend.sync(&session, &mut cursor).await.unwrap();
+    assert!(full.full);
+    assert_eq!(full.torrents.len(), 2);
+    let torrent = full.torrents.iter().find(|t| t.hash == debian).unwrap();
+    assert_eq!(torrent.name, "debian.iso");
+    assert_eq!(torrent.state, TorrentState::Downloading);
+    assert_eq!(torrent.eta, Some(Duration::from_secs(10)));
+    assert!(full.transfer.is_some());
+
+    server.transmission.tick(Duration::from_secs(2));
+    server.transmission.remove_torrent(&arch);
+    let update = backend.sync(&session, &mut cursor).await.unwrap();
+    assert!(!update.full);
+    assert_eq!(update.torrents.len(), 1, "only the active torrent changed");
+    assert_eq!(update.torrents[0].downloaded, 200);
+    assert_eq!(update.removed, vec![arch]);
+
+    let update = backend.sync(&session, &mut cursor).await.unwrap();
+    assert!(update.torrents.is_empty() && update.removed.is_empty());
+}
+
+#[tokio::test]
+async fn commands_change_the_torrents() {
+    let server = MockTransmission::default().spawn().await.unwrap();
+    let hash = server.transmission.add_torrent("a", 10);
+    let (backend, session) = logged_in(&server).await;
+    let hashes = [hash.clone()];
+
+    backend
+        .command(&session, Command::Resume, &hashes)
+        .await
+        .unwrap();
+    assert_eq!(server.transmission.is_paused(&hash), Some(false));
+
+    backend
+        .command(&session, Command::Pause, &hashes)
+        .await
+        .unwrap();
+    assert_eq!(server.transmission.is_paused(&hash), Some(true));
+
+    let delete = Command::Delete {
+        delete_files: false,
+    };
+    backend.command(&session, delete, &hashes).await.unwrap();
+    assert!(server.transmission.torrent_hashes().is_empty());
+}
+
+#[tokio::test]
+async fn magnets_are_added() {
+    let server = MockTransmission::default().spawn().await.unwrap();
+    let (backend, session) = logged_in(&server).await;
+    let hash = "c9e15763f722f23e98a29decdfae341b98d53056";
+
+    let torrent = AddTorrent {
+        source: TorrentSource::Url(format!("magnet:?xt=urn:btih:{hash}&dn=ubuntu.iso")),
+        save_path: Some("/data/isos".to_owned()),
+        paused: true,
+        tags: vec!["bt-user:alice".to_owned()],
+    };
+    backend.add(&session, torrent).await.unwrap();
+
+    let added = server
+        .transmission
+        .torrent(hash)
+        .expect("the torrent was added");
+    assert_eq!(added.name, "ubuntu.iso");
+    assert_eq!(added.download_dir, "/data/isos");
+    assert_eq!(server.transmission.is_paused(hash), Some(true));
+    assert_eq!(added.labels, vec!["bt-user:alice"]);
+}
+
+#[tokio::test]
+async fn limits_are_set_in_kilobytes() {
+    let server = MockTransmission::default().spawn().await.unwrap();
+    let hash = server.transmission.add_torrent("a", 10_000_000);
+    let (backend, session) = logged_in(&server).await;
+
+    let limits = Limits {
+        dl_limit: Some(250_000),
+        up_limit: None,
+    };
+    backend
+        .set_limits(&session, &[hash.clone()], limits)
+        .await
+        .unwrap();
+
+    let torrent = server.transmission.torrent(&hash).unwrap();
+    assert!(torrent.download_limited);
+    assert_eq!(torrent.download_limit, 250);
+    assert!(!torrent.upload_limited);
+
+    let mut cursor = SyncCursor::default();
+    let full = backend.sync(&session, &mut cursor).await.unwrap();
+    assert_eq!(full.torrents[0].dl_limit, Some(250_000));
+    assert_eq!(full.torrents[0].up_limit, None);
+}
+
+#[tokio::test]
+async fn files_peers_and_trackers_are_listed() {
+    let server = MockTransmission::default().spawn().await.unwrap();
+    let hash = server.transmission.add_torrent("debian.iso", 1000);
+    server.transmission.set_download_rate(&hash, 100);
+    server.transmission.tick(Duration::from_secs(5));
+    let (backend, session) = logged_in(&server).await;
+
+    let files = backend.files(&session, &hash).await.unwrap();
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].name, "debian.iso");
+    assert_eq!(files[0].progress, 0.5);
+
+    let peers = backend.peers(&session, &hash).await.unwrap();
+    assert_eq!(peers.len(), 1);
+    assert_eq!(peers[0].address, "192.0.2.1:51413");
+    assert_eq!(peers[0].dl_speed, 100);
+
+    let trackers = backend.trackers(&session, &hash).await.unwrap();
+    assert_eq!(trackers.len(), 1);
+    assert_eq!(trackers[0].status, TrackerStatus::Working);
+    assert_eq!(trackers[0].seeds, Some(12));
+}
+
+#[tokio::test]
+async fn sessions_expire_when_the_credentials_stop_working() {
+    let server = MockTransmission::default().spawn().await.unwrap();
+    let (backend, session) = logged_in(&server).await;
+    let _ = server
+        .transmission
+        .clone()
+        .with_credentials("transmission", "changed");
+
+    let mut cursor = SyncCursor::default();
+    let err = backend.sync(&session, &mut cursor).await.unwrap_err();
+    assert!(err.is_session_expired());
+
+    // The credentials are forgotten, there's no point in trying them again.
+    let err = backend.sync(&session, &mut cursor).await.unwrap_err();
+    assert!(err.is_session_expired());
+    let err = backend.sync("unknown", &mut cursor).await.unwrap_err();
+    assert!(err.is_session_expired());
+}
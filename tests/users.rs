@@ -0,0 +1,129 @@
+//! bit-tower's own users and logging in again for them, run with `cargo test --features mock`.
+#![cfg(feature = "mock")]
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use bittower::app::auth::ssr::{hash_password, User, Users, UsersFile};
+use bittower::app::auth::Role;
+use bittower::backend::{Command, QbittorrentBackend, Relogin, SyncCursor, TorrentBackend};
+use bittower::qbittorrent::client::{BasicAuth, QbtClient};
+use bittower::qbittorrent::mock::{MockQbittorrent, MockServer, MockTorrent};
+
+fn users() -> Users {
+    let user = |password: &str, instances: Option<&[&str]>| User {
+        password: hash_password(password),
+        role: Role::Contributor,
+        instances: instances.map(|names| names.iter().map(|name| name.to_string()).collect()),
+    };
+    Users::new(UsersFile {
+        users: HashMap::from([
+            ("alice".to_owned(), user("correct horse", None)),
+            (
+                "bob".to_owned(),
+                user("battery staple", Some(&["seedbox1"])),
+            ),
+        ]),
+        credentials: HashMap::from([(
+            "seedbox1".to_owned(),
+            BasicAuth {
+                username: "admin".to_owned(),
+                password: "adminadmin".to_owned(),
+            },
+        )]),
+    })
+    .unwrap()
+}
+
+fn relogin(server: &MockServer) -> Relogin {
+    let qbt = QbtClient::new(&server.base_url());
+    Relogin::new(Arc::new(QbittorrentBackend::new(qbt)))
+}
+
+#[tokio::test]
+async fn only_the_right_password_logs_in() {
+    let users = users();
+
+    let alice = users.verify("alice", "correct horse").await;
+    assert_eq!(alice.map(|alice| alice.role), Some(Role::Contributor));
+    assert!(users.verify("alice", "battery staple").await.is_none());
+    assert!(users.verify("mallory", "correct horse").await.is_none());
+}
+
+#[tokio::test]
+async fn users_may_be_limited_to_some_instances() {
+    let users = users();
+
+    let alice = users.verify("alice", "correct horse").await.unwrap();
+    assert!(alice.may_use("seedbox1") && alice.may_use("seedbox2"));
+    let bob = users.verify("bob", "battery staple").await.unwrap();
+    assert!(bob.may_use("seedbox1") && !bob.may_use("seedbox2"));
+
+    assert_eq!(users.credentials("seedbox1").unwrap().username, "admin");
+    assert!(users.credentials("seedbox2").is_none());
+}
+
+#[test]
+fn passwords_must_be_hashed() {
+    let file = UsersFile {
+        users: HashMap::from([(
+            "alice".to_owned(),
+            User {
+                password: "correct horse".to_owned(),
+                role: Role::Admin,
+                instances: None,
+            },
+        )]),
+        credentials: HashMap::new(),
+    };
+
+    assert!(Users::new(file).is_err());
+}
+
+#[tokio::test]
+async fn expired_sessions_are_logged_in_again() {
+    let server = MockQbittorrent::default().spawn().await.unwrap();
+    let hash = server
+        .qbt
+        .add_torrent(MockTorrent::default().name("debian.iso"));
+    let backend = relogin(&server);
+    let session = backend
+        .login("admin".to_owned(), "adminadmin".to_owned())
+        .await
+        .unwrap();
+    let mut cursor = SyncCursor::default();
+    backend.sync(&session, &mut cursor).await.unwrap();
+
+    server.qbt.expire_sessions();
+    let update = backend.sync(&session, &mut cursor).await.unwrap();
+    assert!(update.full, "the new SID starts over");
+    assert_eq!(update.torrents.len(), 1);
+
+    server.qbt.expire_sessions();
+    backend
+        .command(&session, Command::Pause, &[hash.clone()])
+        .await
+        .unwrap();
+    assert_eq!(server.qbt.is_paused(&hash), Some(true));
+}
+
+#[tokio::test]
+async fn sessions_end_when_the_credentials_stop_working() {
+    let server = MockQbittorrent::default().spawn().await.unwrap();
+    let backend = relogin(&server);
+    let session = backend
+        .login("admin".to_owned(), "adminadmin".to_owned())
+        .await
+        .unwrap();
+
+    let _ = server.qbt.clone().with_credentials("admin", "changed");
+    server.qbt.expire_sessions();
+    let mut cursor = SyncCursor::default();
+    let err = backend.sync(&session, &mut cursor).await.unwrap_err();
+    assert!(err.is_session_expired());
+
+    // The session is forgotten, nothing is tried again with the old credentials.
+    let _ = server.qbt.clone().with_credentials("admin", "adminadmin");
+    let err = backend.sync(&session, &mut cursor).await.unwrap_err();
+    assert!(err.is_session_expired());
+}
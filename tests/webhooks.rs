@@ -0,0 +1,259 @@
+//! Outgoing webhooks, run with `cargo test --features ssr`.
+#![cfg(feature = "ssr")]
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::routing::post;
+use axum::Router;
+use bittower::backend::model::{Torrent, TorrentState};
+use bittower::webhooks::{
+    signature, Event, EventDetector, EventKind, Thresholds, WebhookOptions, Webhooks,
+    DELIVERY_HEADER, EVENT_HEADER, SIGNATURE_HEADER,
+};
+use chrono::{DateTime, TimeDelta, Utc};
+use http::{HeaderMap, StatusCode};
+
+const SECRET: &str = "shh";
+
+fn torrent(hash: &str, state: TorrentState, progress: f64, ratio: f64) -> Torrent {
+    Torrent {
+        hash: hash.to_owned(),
+        name: format!("{hash}.iso"),
+        state,
+        progress,
+        ratio,
+        ..Default::default()
+    }
+}
+
+fn torrents(torrents: impl IntoIterator<Item = Torrent>) -> HashMap<String, Torrent> {
+    torrents
+        .into_iter()
+        .map(|torrent| (torrent.hash.clone(), torrent))
+        .collect()
+}
+
+fn kinds(events: &[Event]) -> Vec<(EventKind, &str)> {
+    let mut kinds: Vec<_> = events
+        .iter()
+        .map(|event| (event.event, event.torrent.hash.as_str()))
+        .collect();
+    kinds.sort_by_key(|(kind, hash)| (kind.as_str(), *hash));
+    kinds
+}
+
+#[test]
+fn events_come_from_successive_syncs() {
+    let mut detector = EventDetector::new(Thresholds {
+        stalled_after: Duration::from_secs(30 * 60),
+        ratio_target: Some(2.0),
+    });
+    let start: DateTime<Utc> = "2024-06-01T12:00:00Z".parse().unwrap();
+    let at = |minutes| start + TimeDelta::minutes(minutes);
+
+    let first = torrents([
+        torrent("aaaa", TorrentState::Downloading, 0.5, 0.0),
+        torrent("bbbb", TorrentState::Seeding, 1.0, 1.5),
+        torrent("cccc", TorrentState::Stalled, 0.1, 0.0),
+    ]);
+    assert!(
+        detector.diff("default", &first, at(0)).is_empty(),
+        "the first sync is where things are at, not news"
+    );
+
+    let second = torrents([
+        torrent("aaaa", TorrentState::Seeding, 1.0, 0.0),
+        torrent("bbbb", TorrentState::Seeding, 1.0, 2.1),
+        torrent("cccc", TorrentState::Stalled, 0.1, 0.0),
+        torrent("dddd", TorrentState::Error, 0.0, 0.0),
+    ]);
+    let events = detector.diff("default", &second, at(10));
+    assert_eq!(
+        kinds(&events),
+        [
+            (EventKind::Added, "dddd"),
+            (EventKind::Completed, "aaaa"),
+            (EventKind::RatioReached, "bbbb"),
+        ]
+    );
+    assert!(events.iter().all(|event| event.instance == "default"));
+
+    let third = torrents([
+        torrent("aaaa", TorrentState::Error, 1.0, 0.0),
+        torrent("bbbb", TorrentState::Seeding, 1.0, 2.2),
+        torrent("cccc", TorrentState::Stalled, 0.1, 0.0),
+    ]);
+    let events = detector.diff("default", &third, at(30));
+    assert_eq!(
+        kinds(&events),
+        [
+            (EventKind::Errored, "aaaa"),
+            (EventKind::Removed, "dddd"),
+            (EventKind::Stalled, "cccc"),
+        ]
+    );
+    assert!(
+        detector.diff("default", &third, at(60)).is_empty(),
+        "a stall is reported once"
+    );
+
+    // Other instances are tracked on their own.
+    assert!(detector.diff("seedbox", &second, at(60)).is_empty());
+}
+
+#[derive(Clone, Default)]
+struct Received {
+    /// Requests so far, the first one is refused.
+    requests: Arc<Mutex<Vec<(HeaderMap, Bytes)>>>,
+}
+
+async fn receive(State(received): State<Received>, headers: HeaderMap, body: Bytes) -> StatusCode {
+    let mut requests = received.requests.lock().unwrap();
+    requests.push((headers, body));
+    if requests.len() == 1 {
+        StatusCode::INTERNAL_SERVER_ERROR
+    } else {
+        StatusCode::NO_CONTENT
+    }
+}
+
+async fn receiver() -> (SocketAddr, Received) {
+    let received = Received::default();
+    let app = Router::new()
+        .route("/hook", post(receive))
+        .with_state(received.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (addr, received)
+}
+
+fn completed() -> Event {
+    let mut detector = EventDetector::new(Thresholds {
+        stalled_after: Duration::from_secs(60),
+        ratio_target: None,
+    });
+    let now = Utc::now();
+    detector.diff(
+        "default",
+        &torrents([torrent("aaaa", TorrentState::Downloading, 0.9, 0.0)]),
+        now,
+    );
+    detector
+        .diff(
+            "default",
+            &torrents([torrent("aaaa", TorrentState::Seeding, 1.0, 0.0)]),
+            now,
+        )
+        .remove(0)
+}
+
+#[tokio::test]
+async fn deliveries_are_signed_and_retried() {
+    let (addr, received) = receiver().await;
+    let webhooks = Webhooks::new(
+        WebhookOptions::default()
+            .urls(vec![format!("http://{addr}/hook")])
+            .secret(SECRET.to_owned())
+            .backoff(Duration::from_millis(10)),
+    )
+    .unwrap();
+    let event = completed();
+
+    webhooks.send(&event).await;
+
+    let requests = received.requests.lock().unwrap().clone();
+    assert_eq!(requests.len(), 2, "retried after the 500");
+    let (headers, body) = &requests[1];
+    assert_eq!(headers[EVENT_HEADER], "completed");
+    assert_eq!(headers[SIGNATURE_HEADER], signature(SECRET, body).as_str());
+    assert_ne!(signature("guess", body), signature(SECRET, body));
+    assert_eq!(
+        headers[DELIVERY_HEADER], requests[0].0[DELIVERY_HEADER],
+        "retries are the same delivery"
+    );
+    let sent: Event = serde_json::from_slice(body).unwrap();
+    assert_eq!(sent, event);
+
+    let deliveries = webhooks.deliveries();
+    assert_eq!(deliveries.len(), 1);
+    assert!(deliveries[0].delivered);
+    assert_eq!(deliveries[0].attempts, 2);
+    assert_eq!(deliveries[0].status, Some(204));
+    assert!(deliveries[0].finished_at.is_some());
+}
+
+#[tokio::test]
+async fn unwanted_events_and_unreachable_urls() {
+    let webhooks = Webhooks::new(
+        WebhookOptions::default()
+            // Nothing listens on port 9 of localhost.
+            .urls(vec!["http://127.0.0.1:9/hook".to_owned()])
+            .secret(SECRET.to_owned())
+            .attempts(2)
+            .backoff(Duration::from_millis(10))
+            .events(vec![EventKind::Completed]),
+    )
+    .unwrap();
+    let mut event = completed();
+    event.event = EventKind::Added;
+    webhooks.send(&event).await;
+    assert!(webhooks.deliveries().is_empty(), "not wanted, not sent");
+
+    event.event = EventKind::Completed;
+    webhooks.send(&event).await;
+    let deliveries = webhooks.deliveries();
+    assert_eq!(deliveries.len(), 1);
+    assert!(!deliveries[0].delivered);
+    assert_eq!(deliveries[0].attempts, 2);
+    assert!(deliveries[0].error.is_some());
+
+    assert!(!Webhooks::default().enabled());
+}
+
+#[tokio::test]
+async fn events_arrive_in_order_across_syncs() {
+    let (addr, received) = receiver().await;
+    let webhooks = Webhooks::new(
+        WebhookOptions::default()
+            .urls(vec![format!("http://{addr}/hook")])
+            .secret(SECRET.to_owned())
+            .ratio_target(1.0)
+            .backoff(Duration::from_millis(200)),
+    )
+    .unwrap();
+
+    webhooks.synced(
+        "default",
+        &torrents([torrent("aaaa", TorrentState::Downloading, 0.9, 0.0)]),
+    );
+    // Completes and reaches the ratio at once, the completion is refused at first.
+    webhooks.synced(
+        "default",
+        &torrents([torrent("aaaa", TorrentState::Seeding, 1.0, 2.0)]),
+    );
+    // Removed by the next sync, while the completion waits to be retried.
+    webhooks.synced("default", &torrents([]));
+    for _ in 0..100 {
+        if received.requests.lock().unwrap().len() == 4 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    let requests = received.requests.lock().unwrap().clone();
+    let kinds: Vec<_> = requests
+        .iter()
+        .map(|(headers, _)| headers[EVENT_HEADER].to_str().unwrap())
+        .collect();
+    assert_eq!(
+        kinds,
+        ["completed", "completed", "ratio_reached", "removed"],
+        "waits for the retry"
+    );
+}
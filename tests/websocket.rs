@@ -0,0 +1,217 @@
+//! The server against the fake qBittorrent, talking over `/ws` like the UI does, run with
+//! `cargo test --features mock`. Starts the `bittower` binary, as the websocket lives there.
+
+#![cfg(feature = "mock")]
+
+use std::net::{SocketAddr, TcpListener};
+use std::process::Stdio;
+use std::time::Duration;
+
+use bittower::backend::model::{SyncUpdate, TorrentState};
+use bittower::protocol::{
+    ClientMessage, ServerMessage, Subscription, TorrentAction, PROTOCOL_VERSION,
+};
+use bittower::qbittorrent::mock::{MockQbittorrent, MockTorrent};
+use futures::{SinkExt, StreamExt};
+use http::header;
+use tokio::net::TcpStream;
+use tokio::process::{Child, Command};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+const MIB: u64 = 1024 * 1024;
+/// Any token of the right length, the server only checks that the form sends the cookie's.
+const CSRF: &str = "0123456789abcdefghijklmnopqrstuvwxyzABCDEFG";
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// bit-tower on a free local port, managing the fake at `qbt_url`. Killed when dropped.
+async fn bittower(qbt_url: &str) -> (SocketAddr, Child) {
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let child = Command::new(env!("CARGO_BIN_EXE_bittower"))
+        .env("LEPTOS_OUTPUT_NAME", "bittower")
+        .env("LEPTOS_SITE_ROOT", "target/site")
+        .env("LEPTOS_SITE_PKG_DIR", "pkg")
+        .env("LEPTOS_SITE_ADDR", addr.to_string())
+        .env("BITTOWER_QBT_URL", qbt_url)
+        .stdout(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .expect("the bittower binary, built with --features mock");
+    let client = reqwest::Client::new();
+    for _ in 0..100 {
+        if client
+            .get(format!("http://{addr}/healthz"))
+            .send()
+            .await
+            .is_ok_and(|response| response.status().is_success())
+        {
+            return (addr, child);
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("bit-tower didn't start");
+}
+
+/// Logs in through the `Login` server function like the login form, returns the session cookie.
+async fn login(addr: SocketAddr) -> String {
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+    let response = client
+        .post(format!("http://{addr}/api/login"))
+        .header(header::COOKIE, format!("bt-csrf={CSRF}"))
+        .form(&[
+            ("csrf", CSRF),
+            ("username", "admin"),
+            ("password", "adminadmin"),
+        ])
+        .send()
+        .await
+        .unwrap();
+    assert!(
+        response.status().is_success() || response.status().is_redirection(),
+        "login answered {}",
+        response.status()
+    );
+    response
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .find_map(|value| {
+            let cookie = value.split(';').next()?;
+            cookie.starts_with("bt-session=").then(|| cookie.to_owned())
+        })
+        .expect("a session cookie")
+}
+
+async fn send(socket: &mut Socket, msg: &ClientMessage) {
+    let frame = rmp_serde::to_vec(msg).unwrap();
+    socket.send(Message::Binary(frame)).await.unwrap();
+}
+
+/// The next frame `f` picks, skipping the others.
+async fn next<T>(socket: &mut Socket, mut f: impl FnMut(ServerMessage) -> Option<T>) -> T {
+    let wait = async {
+        while let Some(frame) = socket.next().await {
+            if let Message::Binary(frame) = frame.unwrap() {
+                if let Some(found) = f(rmp_serde::from_slice(&frame).unwrap()) {
+                    return found;
+                }
+            }
+        }
+        panic!("the server closed the websocket");
+    };
+    tokio::time::timeout(Duration::from_secs(10), wait)
+        .await
+        .expect("the server didn't send it in time")
+}
+
+#[tokio::test]
+async fn the_ui_protocol_runs_against_the_fake() {
+    let qbt = MockQbittorrent::default();
+    let hash = qbt.add_torrent(
+        MockTorrent::default()
+            .name("debian-12.7.0-amd64-netinst.iso")
+            .size(631 * MIB)
+            .download_rate(4 * MIB),
+    );
+    let server = qbt.spawn().await.unwrap();
+    let (addr, _bittower) = bittower(&server.base_url()).await;
+    let cookie = login(addr).await;
+
+    let mut request = format!("ws://{addr}/ws").into_client_request().unwrap();
+    request
+        .headers_mut()
+        .insert(header::COOKIE, cookie.parse().unwrap());
+    let (mut socket, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+    send(
+        &mut socket,
+        &ClientMessage::Hello {
+            version: PROTOCOL_VERSION,
+        },
+    )
+    .await;
+    let version = next(&mut socket, |msg| match msg {
+        ServerMessage::Hello { version } => Some(version),
+        _ => None,
+    })
+    .await;
+    assert_eq!(version, PROTOCOL_VERSION);
+
+    // The first sync has every torrent.
+    let update: SyncUpdate = next(&mut socket, |msg| match msg {
+        ServerMessage::Sync { instance, update } if instance == "default" => Some(update),
+        _ => None,
+    })
+    .await;
+    assert!(update.full);
+    assert_eq!(update.torrents.len(), 1);
+    assert_eq!(update.torrents[0].hash, hash);
+
+    // Only info hashes make it into the queries sent to qBittorrent.
+    send(
+        &mut socket,
+        &ClientMessage::Subscribe(Subscription::TorrentFiles {
+            instance: "default".to_owned(),
+            hash: format!("{hash}&rid=0"),
+        }),
+    )
+    .await;
+    let message = next(&mut socket, |msg| match msg {
+        ServerMessage::Error { message } => Some(message),
+        _ => None,
+    })
+    .await;
+    assert!(message.starts_with("Not a torrent hash"), "{message}");
+
+    send(
+        &mut socket,
+        &ClientMessage::Subscribe(Subscription::TorrentFiles {
+            instance: "default".to_owned(),
+            hash: hash.clone(),
+        }),
+    )
+    .await;
+    let files = next(&mut socket, |msg| match msg {
+        ServerMessage::TorrentFiles { files, .. } => Some(files),
+        _ => None,
+    })
+    .await;
+    assert!(!files.is_empty());
+
+    send(
+        &mut socket,
+        &ClientMessage::Action {
+            request_id: 7,
+            instance: "default".to_owned(),
+            action: TorrentAction::Pause {
+                hashes: vec![hash.clone()],
+            },
+        },
+    )
+    .await;
+    let ack = next(&mut socket, |msg| match msg {
+        ServerMessage::Ack { request_id, result } => Some((request_id, result)),
+        _ => None,
+    })
+    .await;
+    assert_eq!(ack, (7, Ok(())));
+    // The next syncs carry the change.
+    next(&mut socket, |msg| match msg {
+        ServerMessage::Sync { update, .. } => update
+            .torrents
+            .iter()
+            .any(|torrent| torrent.hash == hash && torrent.state == TorrentState::Paused)
+            .then_some(()),
+        _ => None,
+    })
+    .await;
+    assert_eq!(server.qbt.is_paused(&hash), Some(true));
+}